@@ -0,0 +1,74 @@
+//! How fast the lexer and parser are on synthetic programs of a few sizes.
+//!
+//! Run with `cargo bench` from this directory. Lexing is measured in tokens per second, so that changes to how
+//! programs are written don't skew comparisons between runs.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hematite_lang::{parser, TokenStream};
+
+/// A program of `functions` functions, each with a documented signature, bindings and nested arithmetic.
+fn synthetic_program(functions: usize) -> String {
+    let mut source = String::new();
+    for index in 0..functions {
+        source += &format!(
+            "/// Function number {index}.\n\
+             function function_{index}(first: i64, second: u32) -> i32 {{\n    \
+                 let mut total: i64 = ({index} + 1) * 2 - 3 % 4;\n    \
+                 let scaled: u32 = 8 * {index} / (7 + 8 * (9 - 10));\n    \
+                 ({index} - 1) * 5;\n    \
+                 {index}\n\
+             }}\n\n"
+        );
+    }
+    source
+}
+
+const SIZES: &[(&str, usize)] = &[("small", 10), ("medium", 100), ("large", 1000)];
+
+fn lexing(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("lexing");
+    for &(name, functions) in SIZES {
+        let source = synthetic_program(functions);
+        let token_count = hematite_lang::tokenize_str(&source).len();
+        group.throughput(Throughput::Elements(token_count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |bencher, source| {
+            bencher.iter(|| hematite_lang::tokenize_str(source))
+        });
+    }
+    group.finish();
+}
+
+fn parsing(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("parsing");
+    for &(name, functions) in SIZES {
+        let tokens = hematite_lang::tokenize_str(&synthetic_program(functions));
+        group.throughput(Throughput::Elements(tokens.len() as u64));
+        // Only parsing is timed; cloning the tokens to consume is part of the setup.
+        group.bench_with_input(BenchmarkId::from_parameter(name), &tokens, |bencher, tokens| {
+            bencher.iter_batched(
+                || tokens.clone(),
+                |tokens| {
+                    let mut tokens = tokens.into_iter();
+                    parser::parse(&mut TokenStream::new(&mut tokens))
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn lexing_and_parsing(criterion: &mut Criterion) {
+    let mut group = criterion.benchmark_group("lexing and parsing");
+    for &(name, functions) in SIZES {
+        let source = synthetic_program(functions);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |bencher, source| {
+            bencher.iter(|| hematite_lang::parse_str(source))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, lexing, parsing, lexing_and_parsing);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+#![no_main]
+
+use hematite_lang::{diagnostics::Renderer, SourceFile, SourceMap};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    if let Err(diagnostics) = hematite_lang::parse_str(&source) {
+        // Rendering slices the source at the diagnostics' spans, so it is fuzzed along with the parser.
+        let mut source_map = SourceMap::new();
+        source_map.add(SourceFile::new("fuzz".to_string(), source.into_owned()));
+        let renderer = Renderer::new(&source_map, false);
+        for diagnostic in &diagnostics {
+            renderer.render(diagnostic);
+        }
+    }
+});
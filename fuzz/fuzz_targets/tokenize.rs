@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let source = String::from_utf8_lossy(data);
+    let mut previous_end = 0;
+    for (_, span) in hematite_lang::tokenize_str(&source) {
+        // Spans must be in order and slice the source cleanly, as diagnostics rely on both.
+        assert!(previous_end <= span.start() && span.start() <= span.end());
+        assert!(source.is_char_boundary(span.start()) && source.is_char_boundary(span.end()));
+        previous_end = span.end();
+    }
+});
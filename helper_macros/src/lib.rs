@@ -1,62 +1,417 @@
-use proc_macro::TokenTree;
+use proc_macro2::TokenStream;
 use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Token,
+};
 
 extern crate proc_macro;
 
-/// Implements the `TokenParser` trait for a token which expects a fixed set of characters.
+/// One `EnumConstant: "exact match string" { metadata }` entry of `exact_match_tokens!`.
+struct ExactMatchToken {
+    enum_constant: syn::Ident,
+    exact_match_string: syn::LitStr,
+    /// The `Type` variant the token names, if it is a type keyword.
+    type_keyword: Option<syn::Ident>,
+    starts_expression: bool,
+}
+
+impl ExactMatchToken {
+    fn parse_metadata(&mut self, input: ParseStream) -> syn::Result<()> {
+        let metadata;
+        syn::braced!(metadata in input);
+        while !metadata.is_empty() {
+            let key: syn::Ident = metadata.parse()?;
+            let duplicate = match key.to_string().as_str() {
+                "type_keyword" => {
+                    metadata.parse::<Token![:]>()?;
+                    self.type_keyword.replace(metadata.parse()?).is_some()
+                }
+                "starts_expression" => std::mem::replace(&mut self.starts_expression, true),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "expected `type_keyword` or `starts_expression`",
+                    ))
+                }
+            };
+            if duplicate {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!("`{key}` is given twice"),
+                ));
+            }
+            if !metadata.is_empty() {
+                metadata.parse::<Token![,]>()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Parse for ExactMatchToken {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let enum_constant = input.parse()?;
+        input.parse::<Token![:]>()?;
+        let exact_match_string: syn::LitStr = input.parse()?;
+        if exact_match_string.value().is_empty() {
+            return Err(syn::Error::new(
+                exact_match_string.span(),
+                "a token can't be an empty string",
+            ));
+        }
+        let mut entry = Self {
+            enum_constant,
+            exact_match_string,
+            type_keyword: None,
+            starts_expression: false,
+        };
+        if input.peek(syn::token::Brace) {
+            entry.parse_metadata(input)?;
+        }
+        Ok(entry)
+    }
+}
+
+/// Defines every token which is written as a fixed string, from a single table.
+///
+/// For each entry, this generates a struct implementing the `TokenParser` trait which expects exactly that string.
+/// It also generates:
+/// - `exact_match_parsers()`, returning a fresh parser for every entry, in the order of the table,
+/// - `Token::EXACT_MATCH_TOKENS`, every token in the table, in order,
+/// - `Token::exact_match_text()`, returning the string for the tokens in the table and `None` for any others,
+/// - `Token::from_exact_match_text()`, the reverse of `exact_match_text()`,
+/// - `Token::type_keyword()`, returning the `Type` named by type keywords,
+/// - `Token::exact_match_starts_expression()`, which is true for tokens marked `starts_expression`.
+///
+/// Entries are tried in order, so keywords must come before anything else which could match them. The strings may
+/// contain escapes like any other string literal.
+///
+/// `TokenParser` and `Type` must be in scope where the macro is used.
 ///
 /// # Format
 ///
-/// ```
-/// EnumConstant: "exact match string"
+/// ```text
+/// exact_match_tokens! {
+///     EnumConstant: "exact match string",
+///     TypeKeyword: "i32" { type_keyword: I32 },
+///     OpeningToken: "(" { starts_expression },
+///     ...
+/// }
 /// ```
 #[proc_macro]
-pub fn exact_match_token(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let mut token_iterator = input.into_iter().peekable();
-    let raw_enum_constant = match token_iterator.next().expect("Missing enum constant") {
-        TokenTree::Ident(ident) => ident.to_string(),
-        _ => panic!("Expected identifier for enum constant"),
-    };
-    let enum_constant_ident = format_ident!("{}", raw_enum_constant);
-    let parser_struct_name = format_ident!("{}Parser", raw_enum_constant);
-    assert!(
-        token_iterator.next().expect("Missing ':'").to_string() == ":",
-        "Expected ':' after enum constant"
+pub fn exact_match_tokens(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let entries = syn::parse_macro_input!(
+        input with Punctuated::<ExactMatchToken, Token![,]>::parse_terminated
     );
-    let quoted_exact_match_string = match &token_iterator.next().expect("Missing string") {
-        TokenTree::Literal(literal) => literal.to_string(),
-        _ => panic!("Expected string literal"),
+    let mut enum_constants = Vec::new();
+    let mut exact_match_strings = Vec::new();
+    let mut type_keywords = Vec::new();
+    let mut expression_starts = Vec::new();
+    for entry in entries {
+        if let Some(type_keyword) = &entry.type_keyword {
+            let enum_constant = &entry.enum_constant;
+            type_keywords.push(quote! {
+                Token::#enum_constant => Some(Type::#type_keyword),
+            });
+        }
+        if entry.starts_expression {
+            expression_starts.push(entry.enum_constant.clone());
+        }
+        enum_constants.push(entry.enum_constant);
+        exact_match_strings.push(entry.exact_match_string.value());
+    }
+    let starts_expression = if expression_starts.is_empty() {
+        quote!(false)
+    } else {
+        quote!(matches!(self, #(Token::#expression_starts)|*))
     };
-    let exact_match_string = quoted_exact_match_string.trim_matches('"');
+    let parser_struct_names = enum_constants
+        .iter()
+        .map(|enum_constant| format_ident!("{}Parser", enum_constant))
+        .collect::<Vec<_>>();
     quote! {
-        struct #parser_struct_name {
-            offset: usize,
-        }
+        #(
+            struct #parser_struct_names {
+                offset: usize,
+            }
 
-        impl #parser_struct_name {
-            fn new() -> Self {
-                Self { offset: 0 }
+            impl #parser_struct_names {
+                fn new() -> Self {
+                    Self { offset: 0 }
+                }
             }
+
+            impl TokenParser for #parser_struct_names {
+                fn reset(&mut self) {
+                    self.offset = 0;
+                }
+                fn accept(&mut self, character: char) -> bool {
+                    let accepted = #exact_match_strings.chars().nth(self.offset) == Some(character);
+                    if accepted {
+                        self.offset += 1;
+                    }
+                    accepted
+                }
+                fn complete(&self, _text: &str) -> Option<Token> {
+                    if self.offset == #exact_match_strings.len() {
+                        Some(Token::#enum_constants)
+                    } else {
+                        None
+                    }
+                }
+            }
+        )*
+
+        fn exact_match_parsers() -> Vec<Box<dyn TokenParser>> {
+            vec![#(Box::new(#parser_struct_names::new())),*]
         }
 
-        impl TokenParser for #parser_struct_name {
-            fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
-                if #exact_match_string.chars().nth(self.offset) == Some(character) {
-                    Some(Box::new(#parser_struct_name {
-                        offset: self.offset + 1,
-                    }))
-                }else {
-                    None
+        impl Token {
+            /// Every token which is written as a fixed string, in the order they are tried.
+            pub const EXACT_MATCH_TOKENS: &'static [Token] = &[#(Token::#enum_constants),*];
+
+            /// The fixed string the token is written as, if it is one.
+            pub fn exact_match_text(&self) -> Option<&'static str> {
+                match self {
+                    #(Token::#enum_constants => Some(#exact_match_strings),)*
+                    _ => None,
                 }
             }
-            fn complete(&self) -> Option<Token> {
-                if self.offset == #exact_match_string.len() {
-                    Some(Token::#enum_constant_ident)
-                } else {
-                    None
+
+            /// The token written as exactly `text`, if there is one.
+            pub fn from_exact_match_text(text: &str) -> Option<Token> {
+                match text {
+                    #(#exact_match_strings => Some(Token::#enum_constants),)*
+                    _ => None,
                 }
             }
+
+            /// The type the token names if it is a type keyword.
+            pub fn type_keyword(&self) -> Option<Type> {
+                match self {
+                    #(#type_keywords)*
+                    _ => None,
+                }
+            }
+
+            /// Whether an expression can start with the token, for those in the table.
+            fn exact_match_starts_expression(&self) -> bool {
+                #starts_expression
+            }
         }
     }
     .into()
 }
+
+/// Whether a field holds child nodes, and so is visited by `walk`.
+enum ChildField {
+    /// `Box<dyn AstNode>`
+    Single,
+    /// `Vec<Box<dyn AstNode>>`
+    List,
+    /// `Option<Box<dyn AstNode>>`
+    Optional,
+}
+
+fn child_field(field_type: &syn::Type) -> Option<ChildField> {
+    let written = quote!(#field_type).to_string().replace(' ', "");
+    match written.as_str() {
+        "Box<dynAstNode>" => Some(ChildField::Single),
+        "Vec<Box<dynAstNode>>" => Some(ChildField::List),
+        "Option<Box<dynAstNode>>" => Some(ChildField::Optional),
+        _ => None,
+    }
+}
+
+/// Whether a field holds spans, and so is replaced by `map_spans`.
+enum SpanField {
+    /// `Span`
+    Single,
+    /// `Option<Span>`
+    Optional,
+    /// A list of pairs whose second element is a span, such as `Vec<(Token, Span)>`.
+    Paired,
+}
+
+fn span_field(field_type: &syn::Type) -> Option<SpanField> {
+    let written = quote!(#field_type).to_string().replace(' ', "");
+    if written == "Span" {
+        Some(SpanField::Single)
+    } else if written == "Option<Span>" {
+        Some(SpanField::Optional)
+    } else if written.starts_with("Vec<(") && written.ends_with(",Span)>") {
+        Some(SpanField::Paired)
+    } else {
+        None
+    }
+}
+
+/// Implements `AstNode` for a struct or enum, along with a `walk` method which visits its child nodes.
+///
+/// The visitor method `apply` calls is named with the `visit` attribute. Fields of type `Box<dyn AstNode>`,
+/// `Vec<Box<dyn AstNode>>` and `Option<Box<dyn AstNode>>` are children, which `walk` visits, `children` lists and
+/// `map_children` replaces in the order they are declared. Fields of type `Span` and `Option<Span>`, along with lists
+/// of pairs ending in a `Span`, hold the spans `map_spans` replaces.
+///
+/// # Format
+///
+/// ```text
+/// #[derive(AstNode)]
+/// #[visit(visit_method_name)]
+/// struct Node { ... }
+/// ```
+#[proc_macro_derive(AstNode, attributes(visit))]
+pub fn derive_ast_node(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive_ast_node_impl(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn derive_ast_node_impl(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let visit_method = input
+        .attrs
+        .iter()
+        .find(|attribute| attribute.path.is_ident("visit"))
+        .ok_or_else(|| {
+            syn::Error::new(
+                name.span(),
+                "missing #[visit(...)] attribute naming the visitor method",
+            )
+        })?
+        .parse_args::<syn::Ident>()?;
+    let fields = match &input.data {
+        syn::Data::Struct(data) => data
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(index, field)| {
+                let field_name = match &field.ident {
+                    Some(ident) => syn::Member::Named(ident.clone()),
+                    None => syn::Member::Unnamed(index.into()),
+                };
+                (field_name, &field.ty)
+            })
+            .collect::<Vec<_>>(),
+        // Enum nodes, such as types, are leaves.
+        _ => Vec::new(),
+    };
+    let child_fields = fields
+        .iter()
+        .filter_map(|(field_name, field_type)| Some((field_name, child_field(field_type)?)))
+        .collect::<Vec<_>>();
+    let visit_children = child_fields
+        .iter()
+        .map(|(field_name, kind)| match kind {
+            ChildField::Single => quote! {
+                self.#field_name.apply(visitor);
+            },
+            ChildField::List => quote! {
+                for child in &self.#field_name {
+                    child.apply(visitor);
+                }
+            },
+            ChildField::Optional => quote! {
+                if let Some(child) = &self.#field_name {
+                    child.apply(visitor);
+                }
+            },
+        })
+        .collect::<Vec<_>>();
+    let push_children = child_fields
+        .iter()
+        .map(|(field_name, kind)| match kind {
+            ChildField::Single => quote! {
+                children.push(self.#field_name.as_ref());
+            },
+            ChildField::List => quote! {
+                children.extend(self.#field_name.iter().map(|child| child.as_ref()));
+            },
+            ChildField::Optional => quote! {
+                children.extend(self.#field_name.as_deref());
+            },
+        })
+        .collect::<Vec<_>>();
+    let map_children = match &input.data {
+        syn::Data::Struct(_) => {
+            let mapped_fields = fields.iter().map(|(field_name, field_type)| {
+                match child_field(field_type) {
+                    Some(ChildField::Single) => quote! {
+                        #field_name: map(self.#field_name.as_ref()),
+                    },
+                    Some(ChildField::List) => quote! {
+                        #field_name: self.#field_name.iter().map(|child| map(child.as_ref())).collect(),
+                    },
+                    Some(ChildField::Optional) => quote! {
+                        #field_name: self.#field_name.as_deref().map(|child| map(child)),
+                    },
+                    None => quote! {
+                        #field_name: self.#field_name.clone(),
+                    },
+                }
+            });
+            quote! {
+                Box::new(Self {
+                    #(#mapped_fields)*
+                })
+            }
+        }
+        _ => quote! {
+            Box::new(self.clone())
+        },
+    };
+    let map_spans = fields
+        .iter()
+        .filter_map(|(field_name, field_type)| match span_field(field_type)? {
+            SpanField::Single => Some(quote! {
+                self.#field_name = map(self.#field_name);
+            }),
+            SpanField::Optional => Some(quote! {
+                self.#field_name = self.#field_name.map(&mut *map);
+            }),
+            SpanField::Paired => Some(quote! {
+                for (_, span) in &mut self.#field_name {
+                    *span = map(*span);
+                }
+            }),
+        })
+        .collect::<Vec<_>>();
+    Ok(quote! {
+        impl crate::ast::AstNode for #name {
+            fn apply(&self, visitor: &mut dyn crate::ast::AstVisitor) {
+                visitor.#visit_method(self);
+            }
+
+            fn children(&self) -> Vec<&dyn crate::ast::AstNode> {
+                #[allow(unused_mut)]
+                let mut children = Vec::new();
+                #(#push_children)*
+                children
+            }
+
+            #[allow(unused_variables)]
+            fn map_children(
+                &self,
+                map: &mut dyn FnMut(&dyn crate::ast::AstNode) -> Box<dyn crate::ast::AstNode>,
+            ) -> Box<dyn crate::ast::AstNode> {
+                #map_children
+            }
+
+            #[allow(unused_variables)]
+            fn map_spans(&mut self, map: &mut dyn FnMut(crate::lexer::Span) -> crate::lexer::Span) {
+                #(#map_spans)*
+            }
+        }
+
+        impl #name {
+            /// Visits each of this node's children.
+            #[allow(unused_variables)]
+            pub fn walk(&self, visitor: &mut dyn crate::ast::AstVisitor) {
+                #(#visit_children)*
+            }
+        }
+    })
+}
@@ -0,0 +1,65 @@
+//! The front end compiled to WebAssembly, so that the web playground can check and format code in the browser.
+//!
+//! Build with `wasm-pack build --target web` in this directory.
+
+use hematite_lang::{
+    diagnostics::JsonRenderer, formatter, lexer, parser, Features, SourceFile, SourceMap,
+    TokenStream,
+};
+use wasm_bindgen::prelude::*;
+
+/// The name diagnostics give the playground's code.
+const FILE_NAME: &str = "playground.hmt";
+
+/// Lexes and parses `source`, returning the diagnostics it produced as an array of the objects `--error-format json`
+/// prints.
+#[wasm_bindgen]
+pub fn compile_to_diagnostics(source: &str) -> JsValue {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(SourceFile::new(FILE_NAME.to_string(), source.to_string()));
+    let renderer = JsonRenderer::new(&source_map);
+    let tokens = hematite_lang::tokenize_str(source);
+    let mut diagnostics = lexer::invalid_token_diagnostics(file, &tokens)
+        .map(|diagnostic| renderer.render(&diagnostic))
+        .collect::<Vec<_>>();
+    // Like the compiler, the parser only runs on files which lexed cleanly.
+    if diagnostics.is_empty() {
+        let mut tokens = tokens.into_iter();
+        if let Err(error) = parser::parse(&mut TokenStream::new(&mut tokens)) {
+            diagnostics.push(renderer.render(&error.to_diagnostic(file)));
+        }
+    }
+    let json = format!(
+        "[{}]",
+        diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.trim_end())
+            .collect::<Vec<_>>()
+            .join(",")
+    );
+    js_sys::JSON::parse(&json).expect("The renderer produces valid JSON")
+}
+
+/// Formats `source` in the default style, or throws its first diagnostic if it doesn't parse.
+#[wasm_bindgen]
+pub fn format(source: &str) -> Result<String, JsValue> {
+    let mut source_map = SourceMap::new();
+    let file = source_map.add(SourceFile::new(FILE_NAME.to_string(), source.to_string()));
+    let mut tokens = hematite_lang::tokenize_str(source).into_iter();
+    // As with `hematite-lang format`, formatting doesn't depend on which features are enabled.
+    let program =
+        parser::parse(&mut TokenStream::new(&mut tokens).with_features(Features::all()))
+            .map_err(|error| {
+                let diagnostic = error.to_diagnostic(file);
+                js_sys::JSON::parse(
+                    JsonRenderer::new(&source_map)
+                        .render(&diagnostic)
+                        .trim_end(),
+                )
+                .expect("The renderer produces valid JSON")
+            })?;
+    Ok(formatter::format(
+        program.as_ref(),
+        &formatter::Style::default(),
+    ))
+}
@@ -0,0 +1,321 @@
+//! Lists and maps, which the compiler lowers `list<T>` and `map<K, V>` to.
+//!
+//! C has no generics, so every function is told how big the elements are and works with pointers to them. The
+//! elements themselves are copied byte by byte; anything they own, such as a string's bytes, is still the caller's
+//! to free.
+
+use std::{
+    alloc::{self, Layout},
+    collections::HashMap,
+    ptr, slice,
+};
+
+use crate::{hematite_panic, HematiteString};
+
+/// Panics with a message built by the runtime rather than the compiled program.
+pub(crate) fn panic_with(message: String) -> ! {
+    let message = HematiteString::from_boxed_bytes(message.into_bytes().into_boxed_slice());
+    // SAFETY: the string was just allocated, and is never freed as the program ends.
+    unsafe { hematite_panic(message) }
+}
+
+/// A growable array, whose elements are stored one after another at `data`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct HematiteList {
+    data: *mut u8,
+    length: usize,
+    capacity: usize,
+}
+
+/// The layout of a list's storage with room for `capacity` elements.
+fn elements_layout(capacity: usize, size: usize, alignment: usize) -> Layout {
+    size.checked_mul(capacity)
+        .and_then(|bytes| Layout::from_size_align(bytes, alignment).ok())
+        .unwrap_or_else(|| panic_with("capacity overflow".to_string()))
+}
+
+/// Returns an empty list, which doesn't allocate until an element is pushed.
+#[no_mangle]
+pub extern "C" fn hematite_list_new() -> HematiteList {
+    HematiteList {
+        data: ptr::null_mut(),
+        length: 0,
+        capacity: 0,
+    }
+}
+
+/// Copies the `size` bytes at `element` onto the end of the list, growing it if it is full.
+///
+/// # Safety
+/// `list` must be valid, and every element pushed onto it must have the same `size` and `alignment`. `element`
+/// must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_list_push(
+    list: *mut HematiteList,
+    element: *const u8,
+    size: usize,
+    alignment: usize,
+) {
+    let list = &mut *list;
+    if list.length == list.capacity && size > 0 {
+        let capacity = (list.capacity * 2).max(4);
+        let layout = elements_layout(capacity, size, alignment);
+        let data = if list.capacity == 0 {
+            alloc::alloc(layout)
+        } else {
+            let old_layout = elements_layout(list.capacity, size, alignment);
+            alloc::realloc(list.data, old_layout, layout.size())
+        };
+        if data.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        list.data = data;
+        list.capacity = capacity;
+    }
+    if size > 0 {
+        ptr::copy_nonoverlapping(element, list.data.add(list.length * size), size);
+    }
+    list.length += 1;
+}
+
+/// Returns a pointer to the element at `index`, panicking if there isn't one.
+///
+/// # Safety
+/// `list` must be valid, holding elements of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_list_get(
+    list: HematiteList,
+    index: usize,
+    size: usize,
+) -> *const u8 {
+    if let Err(message) = check_index(&list, index) {
+        panic_with(message);
+    }
+    if size == 0 {
+        // Empty elements take up no room, so any non-null pointer will do.
+        return ptr::NonNull::dangling().as_ptr();
+    }
+    list.data.add(index * size)
+}
+
+/// Fails with the message `hematite_list_get` panics with unless the list has an element at `index`.
+fn check_index(list: &HematiteList, index: usize) -> Result<(), String> {
+    if index < list.length {
+        Ok(())
+    } else {
+        Err(format!(
+            "index out of bounds: the length is {} but the index is {index}",
+            list.length
+        ))
+    }
+}
+
+/// The number of elements in the list.
+#[no_mangle]
+pub extern "C" fn hematite_list_length(list: HematiteList) -> usize {
+    list.length
+}
+
+/// Frees the list's storage, without freeing anything its elements own.
+///
+/// # Safety
+/// `list` must be valid, holding elements of `size` bytes aligned to `alignment`, and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_list_free(list: HematiteList, size: usize, alignment: usize) {
+    if list.capacity > 0 {
+        alloc::dealloc(list.data, elements_layout(list.capacity, size, alignment));
+    }
+}
+
+/// The entries of a map, which compiled programs only see a pointer to.
+///
+/// Keys are compared by their bytes, which is why the compiler only allows keys whose bytes are equal exactly when
+/// their values are. Values are kept in a list, in the order their keys were first inserted.
+pub struct HematiteMapTable {
+    key_size: usize,
+    value_size: usize,
+    value_alignment: usize,
+    indices: HashMap<Box<[u8]>, usize>,
+    values: HematiteList,
+}
+
+pub type HematiteMap = *mut HematiteMapTable;
+
+impl HematiteMapTable {
+    /// # Safety
+    /// `key` must point to `key_size` readable bytes.
+    unsafe fn key<'a>(&self, key: *const u8) -> &'a [u8] {
+        if self.key_size == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(key, self.key_size)
+        }
+    }
+}
+
+/// Returns an empty map from keys of `key_size` bytes to values of `value_size` bytes.
+#[no_mangle]
+pub extern "C" fn hematite_map_new(
+    key_size: usize,
+    value_size: usize,
+    value_alignment: usize,
+) -> HematiteMap {
+    Box::into_raw(Box::new(HematiteMapTable {
+        key_size,
+        value_size,
+        value_alignment,
+        indices: HashMap::new(),
+        values: hematite_list_new(),
+    }))
+}
+
+/// Copies `value` into the map under `key`, replacing the value already there if there is one.
+///
+/// # Safety
+/// `map` must be valid, and `key` and `value` must point to as many readable bytes as the map's keys and values
+/// have.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_map_insert(map: HematiteMap, key: *const u8, value: *const u8) {
+    let map = &mut *map;
+    let key = map.key(key);
+    match map.indices.get(key) {
+        Some(&index) => {
+            let existing = hematite_list_get(map.values, index, map.value_size);
+            ptr::copy_nonoverlapping(value, existing as *mut u8, map.value_size);
+        }
+        None => {
+            map.indices.insert(key.into(), map.values.length);
+            hematite_list_push(&mut map.values, value, map.value_size, map.value_alignment);
+        }
+    }
+}
+
+/// Returns a pointer to the value under `key`, panicking if there isn't one.
+///
+/// # Safety
+/// `map` must be valid, and `key` must point to as many readable bytes as the map's keys have.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_map_get(map: HematiteMap, key: *const u8) -> *const u8 {
+    let map = &*map;
+    match map.indices.get(map.key(key)) {
+        Some(&index) => hematite_list_get(map.values, index, map.value_size),
+        None => panic_with("the map has no entry for the key".to_string()),
+    }
+}
+
+/// The number of entries in the map.
+///
+/// # Safety
+/// `map` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_map_length(map: HematiteMap) -> usize {
+    (*map).values.length
+}
+
+/// Frees the map, without freeing anything its values own.
+///
+/// # Safety
+/// `map` must be valid and not already freed.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_map_free(map: HematiteMap) {
+    let map = Box::from_raw(map);
+    hematite_list_free(map.values, map.value_size, map.value_alignment);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pushes `values` onto a new list of `u64`s.
+    unsafe fn list_of(values: &[u64]) -> HematiteList {
+        let mut list = hematite_list_new();
+        for value in values {
+            hematite_list_push(
+                &mut list,
+                (value as *const u64).cast(),
+                size_of::<u64>(),
+                align_of::<u64>(),
+            );
+        }
+        list
+    }
+
+    unsafe fn get(list: HematiteList, index: usize) -> u64 {
+        *hematite_list_get(list, index, size_of::<u64>()).cast::<u64>()
+    }
+
+    #[test]
+    fn lists_keep_their_elements_as_they_grow() {
+        unsafe {
+            let empty = hematite_list_new();
+            assert_eq!(hematite_list_length(empty), 0);
+            hematite_list_free(empty, size_of::<u64>(), align_of::<u64>());
+
+            let values = (0..100).map(|value| value * value).collect::<Vec<u64>>();
+            let list = list_of(&values);
+            assert_eq!(hematite_list_length(list), 100);
+            assert!(list.capacity >= 100);
+            for (index, value) in values.iter().enumerate() {
+                assert_eq!(get(list, index), *value);
+            }
+            assert_eq!(
+                hematite_list_get(list, 1, 8) as usize % align_of::<u64>(),
+                0
+            );
+            hematite_list_free(list, size_of::<u64>(), align_of::<u64>());
+        }
+    }
+
+    #[test]
+    fn empty_elements_are_counted_without_allocating() {
+        unsafe {
+            let mut list = hematite_list_new();
+            for _ in 0..3 {
+                hematite_list_push(&mut list, ptr::NonNull::dangling().as_ptr(), 0, 1);
+            }
+            assert_eq!(hematite_list_length(list), 3);
+            assert_eq!(list.capacity, 0);
+            assert!(!hematite_list_get(list, 2, 0).is_null());
+            hematite_list_free(list, 0, 1);
+        }
+    }
+
+    // Panicking exits the process, so the bounds check is tested for the message it would panic with instead.
+    #[test]
+    fn getting_past_the_end_panics() {
+        unsafe {
+            let list = list_of(&[7, 8]);
+            assert_eq!(check_index(&list, 1), Ok(()));
+            assert_eq!(
+                check_index(&list, 2),
+                Err("index out of bounds: the length is 2 but the index is 2".to_string())
+            );
+            hematite_list_free(list, size_of::<u64>(), align_of::<u64>());
+        }
+    }
+
+    #[test]
+    fn maps_replace_values_under_equal_keys() {
+        unsafe {
+            let map = hematite_map_new(size_of::<u32>(), size_of::<u64>(), align_of::<u64>());
+            let insert = |key: u32, value: u64| {
+                hematite_map_insert(
+                    map,
+                    (&key as *const u32).cast(),
+                    (&value as *const u64).cast(),
+                )
+            };
+            let get = |key: u32| *hematite_map_get(map, (&key as *const u32).cast()).cast::<u64>();
+            insert(1, 10);
+            insert(2, 20);
+            insert(1, 11);
+            assert_eq!(hematite_map_length(map), 2);
+            assert_eq!(get(1), 11);
+            assert_eq!(get(2), 20);
+            // Values stay in the order their keys were first inserted.
+            assert_eq!(*hematite_list_get((*map).values, 0, 8).cast::<u64>(), 11);
+            hematite_map_free(map);
+        }
+    }
+}
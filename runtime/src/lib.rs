@@ -0,0 +1,502 @@
+//! Runtime support for compiled Hematite programs.
+//!
+//! A Hematite `string` is passed around by value as a pointer to UTF-8 bytes followed by a length (see `HematiteString`).
+//! Strings come from two places:
+//! - literals, which point into the program's read-only data and must never be freed,
+//! - the functions in this library, which return heap allocations owned by the caller.
+//!
+//! Owned strings are released with `hematite_string_free`. Lengths, indices and offsets into strings count bytes,
+//! and a `char` is passed as its Unicode code point in a `u32`.
+//!
+//! Lists and maps are in the `collections` module, and are freed with `hematite_list_free` and `hematite_map_free`.
+
+mod collections;
+
+pub use collections::{HematiteList, HematiteMap, HematiteMapTable};
+
+use collections::{hematite_list_new, hematite_list_push, panic_with};
+
+use std::{
+    backtrace::Backtrace,
+    env,
+    io::{self, Write},
+    process, ptr, slice, str,
+};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct HematiteString {
+    data: *const u8,
+    length: usize,
+}
+
+impl HematiteString {
+    /// # Safety
+    /// `data` must point to `length` bytes which stay valid for as long as the string is used.
+    unsafe fn as_bytes<'a>(&self) -> &'a [u8] {
+        if self.length == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(self.data, self.length)
+        }
+    }
+
+    fn from_boxed_bytes(bytes: Box<[u8]>) -> Self {
+        let length = bytes.len();
+        Self {
+            data: Box::into_raw(bytes) as *const u8,
+            length,
+        }
+    }
+}
+
+/// Allocates a zero-filled string of `length` bytes, to be filled in by the caller.
+#[no_mangle]
+pub extern "C" fn hematite_string_allocate(length: usize) -> HematiteString {
+    HematiteString::from_boxed_bytes(vec![0; length].into_boxed_slice())
+}
+
+/// Frees a string returned by one of the functions in this library.
+///
+/// # Safety
+/// `string` must have been allocated by this library and not already freed.
+/// Strings pointing at literals must not be passed here.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_free(string: HematiteString) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        string.data as *mut u8,
+        string.length,
+    )));
+}
+
+/// Returns a newly allocated string containing `left` followed by `right`.
+///
+/// # Safety
+/// Both strings must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_concat(
+    left: HematiteString,
+    right: HematiteString,
+) -> HematiteString {
+    let mut bytes = Vec::with_capacity(left.length + right.length);
+    bytes.extend_from_slice(left.as_bytes());
+    bytes.extend_from_slice(right.as_bytes());
+    HematiteString::from_boxed_bytes(bytes.into_boxed_slice())
+}
+
+/// Compares two strings byte by byte, returning a negative number, zero or a positive number if `left` is less than, equal to or greater than `right`.
+///
+/// # Safety
+/// Both strings must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_compare(
+    left: HematiteString,
+    right: HematiteString,
+) -> i32 {
+    left.as_bytes().cmp(right.as_bytes()) as i32
+}
+
+/// Returns the byte at `index`, for indexing a string. Panics if the index is out of bounds.
+///
+/// # Safety
+/// `string` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_byte(string: HematiteString, index: usize) -> u8 {
+    checked_byte(string.as_bytes(), index).unwrap_or_else(|message| panic_with(message))
+}
+
+/// The byte at `index`, or the message `hematite_string_byte` panics with if there isn't one.
+fn checked_byte(bytes: &[u8], index: usize) -> Result<u8, String> {
+    bytes.get(index).copied().ok_or_else(|| {
+        format!(
+            "Index {index} is out of bounds for a string of length {}",
+            bytes.len()
+        )
+    })
+}
+
+/// Returns the number of bytes in a string, for `len!`.
+#[no_mangle]
+pub extern "C" fn hematite_string_length(string: HematiteString) -> usize {
+    string.length
+}
+
+/// Returns a newly allocated copy of the bytes from `start` up to `end`, for `slice!`. Panics unless both offsets
+/// are within the string and at the start or end of a character.
+///
+/// # Safety
+/// `string` must be valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_slice(
+    string: HematiteString,
+    start: usize,
+    end: usize,
+) -> HematiteString {
+    let text = str::from_utf8_unchecked(string.as_bytes());
+    match checked_slice(text, start, end) {
+        Ok(slice) => HematiteString::from_boxed_bytes(slice.as_bytes().into()),
+        Err(message) => panic_with(message),
+    }
+}
+
+/// The bytes from `start` up to `end`, or the message `hematite_string_slice` panics with if they aren't a slice of
+/// whole characters within `text`.
+fn checked_slice(text: &str, start: usize, end: usize) -> Result<&str, String> {
+    if start > end || end > text.len() {
+        return Err(format!(
+            "Bytes {start}..{end} are out of bounds for a string of length {}",
+            text.len()
+        ));
+    }
+    text.get(start..end).ok_or_else(|| {
+        let offset = if text.is_char_boundary(start) {
+            end
+        } else {
+            start
+        };
+        format!("Byte {offset} of {text:?} is inside a character")
+    })
+}
+
+/// Returns a newly allocated list of a string's characters as Unicode scalar values, for `chars!`. The list is
+/// freed with `hematite_list_free`.
+///
+/// # Safety
+/// `string` must be valid UTF-8.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_chars(string: HematiteString) -> HematiteList {
+    let mut list = hematite_list_new();
+    for character in str::from_utf8_unchecked(string.as_bytes()).chars() {
+        let value = u32::from(character);
+        hematite_list_push(
+            &mut list,
+            (&value as *const u32).cast(),
+            size_of::<u32>(),
+            align_of::<u32>(),
+        );
+    }
+    list
+}
+
+/// Reads the decimal integer in a string, for `parse_int!`. On success the integer is written to `value` and this
+/// returns true; otherwise a newly allocated message saying why is written to `error` and this returns false.
+///
+/// # Safety
+/// `string` must be valid, and `value` and `error` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_parse_int(
+    string: HematiteString,
+    value: *mut i64,
+    error: *mut HematiteString,
+) -> bool {
+    let parsed = str::from_utf8(string.as_bytes())
+        .ok()
+        .and_then(|text| text.parse().ok());
+    match parsed {
+        Some(parsed) => {
+            *value = parsed;
+            true
+        }
+        None => {
+            let message = format!(
+                "`{}` isn't an integer",
+                String::from_utf8_lossy(string.as_bytes())
+            );
+            *error = HematiteString::from_boxed_bytes(message.into_bytes().into_boxed_slice());
+            false
+        }
+    }
+}
+
+/// Returns a newly allocated string holding just `character`, a Unicode scalar value, for `to_string!`.
+#[no_mangle]
+pub extern "C" fn hematite_char_to_string(character: u32) -> HematiteString {
+    let character = char::from_u32(character).unwrap_or(char::REPLACEMENT_CHARACTER);
+    HematiteString::from_boxed_bytes(character.to_string().into_bytes().into_boxed_slice())
+}
+
+/// Returns the code point of a char, for `char_code!`. Chars are stored as their code points, so this is the
+/// identity, but it keeps the conversion explicit in compiled code.
+#[no_mangle]
+pub extern "C" fn hematite_char_code(character: u32) -> u32 {
+    character
+}
+
+/// Checks that `code` is a Unicode scalar value, for `from_char_code!`. If it is, it is written to `character`
+/// and this returns true.
+///
+/// # Safety
+/// `character` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_char_from_code(code: u32, character: *mut u32) -> bool {
+    let valid = char::from_u32(code).is_some();
+    if valid {
+        *character = code;
+    }
+    valid
+}
+
+/// Returns a newly allocated list of the program's arguments, without its own name, for `args!`. Each is a newly
+/// allocated string, with any invalid UTF-8 replaced by U+FFFD.
+///
+/// The strings are freed with `hematite_string_free` before the list is freed with `hematite_list_free`.
+#[no_mangle]
+pub extern "C" fn hematite_args() -> HematiteList {
+    let mut list = hematite_list_new();
+    for argument in env::args_os().skip(1) {
+        let argument = HematiteString::from_boxed_bytes(
+            argument
+                .to_string_lossy()
+                .into_owned()
+                .into_bytes()
+                .into_boxed_slice(),
+        );
+        // SAFETY: `argument` is a `HematiteString`, which is the size and alignment given.
+        unsafe {
+            hematite_list_push(
+                &mut list,
+                (&argument as *const HematiteString).cast(),
+                size_of::<HematiteString>(),
+                align_of::<HematiteString>(),
+            );
+        }
+    }
+    list
+}
+
+/// Looks up an environment variable, for `env_var!`. If it is set to valid UTF-8, a newly allocated copy of its
+/// value is written to `value` and this returns true.
+///
+/// # Safety
+/// `name` must be valid, and `value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_env_var(
+    name: HematiteString,
+    value: *mut HematiteString,
+) -> bool {
+    let found = str::from_utf8(name.as_bytes())
+        .ok()
+        .and_then(|name| env::var(name).ok());
+    match found {
+        Some(found) => {
+            *value = HematiteString::from_boxed_bytes(found.into_bytes().into_boxed_slice());
+            true
+        }
+        None => false,
+    }
+}
+
+/// Writes a string to standard output without a trailing newline.
+///
+/// # Safety
+/// `string` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_string_print(string: HematiteString) {
+    let mut stdout = io::stdout().lock();
+    // There is nowhere to report a failure to write to stdout, so it is ignored like in C's printf.
+    let _ = stdout.write_all(string.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Returns a newly allocated string containing `value` written in the style `format!`'s `spec` selects:
+/// 0 for decimal, 1 for lowercase hexadecimal, 2 for uppercase hexadecimal, 3 for binary and 4 for octal.
+#[no_mangle]
+pub extern "C" fn hematite_format_integer(value: i64, spec: u8) -> HematiteString {
+    let formatted = match spec {
+        1 => format!("{value:x}"),
+        2 => format!("{value:X}"),
+        3 => format!("{value:b}"),
+        4 => format!("{value:o}"),
+        _ => value.to_string(),
+    };
+    HematiteString::from_boxed_bytes(formatted.into_bytes().into_boxed_slice())
+}
+
+/// The exit code of a program which panicked, which `hematite debug` also uses for programs it runs.
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Writes `message` to standard error and ends the program with `PANIC_EXIT_CODE`, for `panic!`, failed assertions
+/// and other errors such as dividing by zero.
+///
+/// The message already says where the program was, as the compiler puts the source location into it. Setting
+/// `HEMATITE_BACKTRACE=1` also prints the call stack, which names Hematite functions when the program was compiled
+/// with debug information.
+///
+/// # Safety
+/// `message` must be valid.
+#[no_mangle]
+pub unsafe extern "C" fn hematite_panic(message: HematiteString) -> ! {
+    // Whatever the program printed before it panicked should come before the message.
+    let _ = io::stdout().flush();
+    let mut stderr = io::stderr().lock();
+    let _ = stderr.write_all(message.as_bytes());
+    let _ = stderr.write_all(b"\n");
+    if env::var_os("HEMATITE_BACKTRACE").is_some_and(|value| value != "0") {
+        let _ = writeln!(stderr, "stack backtrace:\n{}", Backtrace::force_capture());
+    } else {
+        let _ =
+            stderr.write_all(b"note: run with `HEMATITE_BACKTRACE=1` to print the call stack\n");
+    }
+    let _ = stderr.flush();
+    process::exit(PANIC_EXIT_CODE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::{hematite_list_free, hematite_list_get, hematite_list_length};
+
+    /// A string pointing at a literal, as compiled programs pass them.
+    fn literal(text: &'static str) -> HematiteString {
+        HematiteString {
+            data: text.as_ptr(),
+            length: text.len(),
+        }
+    }
+
+    /// Copies an owned string's text out, then frees it.
+    unsafe fn take(string: HematiteString) -> String {
+        let text = str::from_utf8(string.as_bytes()).unwrap().to_string();
+        hematite_string_free(string);
+        text
+    }
+
+    #[test]
+    fn concat_joins_the_bytes() {
+        unsafe {
+            let joined = hematite_string_concat(literal("héllo, "), literal("world"));
+            assert_eq!(take(joined), "héllo, world");
+            assert_eq!(take(hematite_string_concat(literal(""), literal(""))), "");
+        }
+    }
+
+    #[test]
+    fn compare_orders_by_bytes() {
+        unsafe {
+            assert!(hematite_string_compare(literal("a"), literal("b")) < 0);
+            assert_eq!(hematite_string_compare(literal("ab"), literal("ab")), 0);
+            assert!(hematite_string_compare(literal("ab"), literal("a")) > 0);
+        }
+    }
+
+    #[test]
+    fn slice_copies_whole_characters() {
+        unsafe {
+            assert_eq!(take(hematite_string_slice(literal("héllo"), 0, 1)), "h");
+            assert_eq!(take(hematite_string_slice(literal("héllo"), 1, 3)), "é");
+            assert_eq!(take(hematite_string_slice(literal("héllo"), 6, 6)), "");
+        }
+    }
+
+    // Panicking exits the process, so the checks are tested for the messages they would panic with instead.
+    #[test]
+    fn slicing_inside_a_character_panics() {
+        let message = Err("Byte 2 of \"héllo\" is inside a character".to_string());
+        assert_eq!(checked_slice("héllo", 2, 4), message);
+        assert_eq!(checked_slice("héllo", 0, 2), message);
+    }
+
+    #[test]
+    fn slicing_out_of_bounds_panics() {
+        assert_eq!(
+            checked_slice("héllo", 3, 7),
+            Err("Bytes 3..7 are out of bounds for a string of length 6".to_string())
+        );
+        assert_eq!(
+            checked_slice("héllo", 3, 1),
+            Err("Bytes 3..1 are out of bounds for a string of length 6".to_string())
+        );
+    }
+
+    #[test]
+    fn indexing_past_the_end_panics() {
+        unsafe {
+            assert_eq!(hematite_string_byte(literal("hello"), 4), b'o');
+        }
+        assert_eq!(
+            checked_byte(b"hello", 5),
+            Err("Index 5 is out of bounds for a string of length 5".to_string())
+        );
+    }
+
+    #[test]
+    fn parse_int_reads_decimal_integers() {
+        unsafe {
+            let mut value = 0;
+            let mut error = literal("");
+            assert!(hematite_string_parse_int(
+                literal("-42"),
+                &mut value,
+                &mut error
+            ));
+            assert_eq!(value, -42);
+            assert!(!hematite_string_parse_int(
+                literal("4x"),
+                &mut value,
+                &mut error
+            ));
+            assert_eq!(take(error), "`4x` isn't an integer");
+        }
+    }
+
+    #[test]
+    fn chars_lists_code_points() {
+        unsafe {
+            let list = hematite_string_chars(literal("hé"));
+            assert_eq!(hematite_list_length(list), 2);
+            let code = |index| *hematite_list_get(list, index, size_of::<u32>()).cast::<u32>();
+            assert_eq!(code(0), u32::from('h'));
+            assert_eq!(code(1), u32::from('é'));
+            hematite_list_free(list, size_of::<u32>(), align_of::<u32>());
+        }
+    }
+
+    #[test]
+    fn char_codes_must_be_scalar_values() {
+        unsafe {
+            let mut character = 0;
+            assert!(hematite_char_from_code(0xe9, &mut character));
+            assert_eq!(take(hematite_char_to_string(character)), "é");
+            assert!(!hematite_char_from_code(0xd800, &mut character));
+            assert!(!hematite_char_from_code(0x110000, &mut character));
+            assert_eq!(character, 0xe9);
+        }
+    }
+
+    #[test]
+    fn format_integer_uses_the_spec() {
+        unsafe {
+            assert_eq!(take(hematite_format_integer(-255, 0)), "-255");
+            assert_eq!(take(hematite_format_integer(255, 1)), "ff");
+            assert_eq!(take(hematite_format_integer(255, 2)), "FF");
+            assert_eq!(take(hematite_format_integer(5, 3)), "101");
+            assert_eq!(take(hematite_format_integer(8, 4)), "10");
+        }
+    }
+
+    #[test]
+    fn args_leaves_out_the_program_name() {
+        unsafe {
+            let list = hematite_args();
+            let size = size_of::<HematiteString>();
+            let arguments = (0..hematite_list_length(list))
+                .map(|index| take(*hematite_list_get(list, index, size).cast::<HematiteString>()))
+                .collect::<Vec<_>>();
+            hematite_list_free(list, size, align_of::<HematiteString>());
+            assert_eq!(arguments, env::args().skip(1).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn env_var_is_only_found_when_set() {
+        unsafe {
+            let mut value = literal("");
+            let found = hematite_env_var(literal("PATH"), &mut value);
+            assert_eq!(found.then(|| take(value)), env::var("PATH").ok());
+            assert!(!hematite_env_var(
+                literal("HEMATITE_RUNTIME_TEST_UNSET"),
+                &mut value
+            ));
+        }
+    }
+}
@@ -1,19 +1,126 @@
-use core::fmt;
+use alloc::{boxed::Box, string::String, vec, vec::Vec};
+use core::{any::Any, fmt};
 
 use dyn_clone::DynClone;
+use helper_macros::AstNode;
 
-pub trait AstVisitor {
-    fn visit_list(&mut self, list: &[Box<dyn AstNode>]);
-    fn visit_variable_definition(&mut self, variable: &VariableDefinition);
-    fn visit_type(&mut self, type_value: &Type);
-    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration);
-    fn visit_function_definition(&mut self, function: &FunctionDefinition);
-    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue);
-    fn visit_integer_literal(&mut self, integer_literal: &i128);
+use crate::{
+    lexer::{Span, Token},
+    symbol::Symbol,
+};
+
+/// Lets the default visitor methods pass the visitor on to child nodes, whatever type it is.
+pub trait AsAstVisitor {
+    fn as_ast_visitor(&mut self) -> &mut dyn AstVisitor;
+}
+
+impl<T: AstVisitor> AsAstVisitor for T {
+    fn as_ast_visitor(&mut self) -> &mut dyn AstVisitor {
+        self
+    }
+}
+
+/// By default, visiting a node just visits its children.
+pub trait AstVisitor: AsAstVisitor {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        for item in list {
+            item.apply(self.as_ast_visitor());
+        }
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        variable.walk(self.as_ast_visitor());
+    }
+    fn visit_type(&mut self, type_value: &Type) {
+        type_value.walk(self.as_ast_visitor());
+    }
+    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration) {
+        parameter.walk(self.as_ast_visitor());
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        function.walk(self.as_ast_visitor());
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        ignore_value.walk(self.as_ast_visitor());
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {}
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        operation.walk(self.as_ast_visitor());
+    }
+    fn visit_type_intrinsic(&mut self, intrinsic: &TypeIntrinsic) {
+        intrinsic.walk(self.as_ast_visitor());
+    }
+    fn visit_module(&mut self, module: &Module) {
+        module.walk(self.as_ast_visitor());
+    }
+    fn visit_macro_invocation(&mut self, invocation: &MacroInvocation) {
+        invocation.walk(self.as_ast_visitor());
+    }
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        assertion.walk(self.as_ast_visitor());
+    }
+    fn visit_format(&mut self, format: &Format) {
+        format.walk(self.as_ast_visitor());
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        call.walk(self.as_ast_visitor());
+    }
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        assertion.walk(self.as_ast_visitor());
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        literal.walk(self.as_ast_visitor());
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        literal.walk(self.as_ast_visitor());
+    }
+    fn visit_index(&mut self, index: &Index) {
+        index.walk(self.as_ast_visitor());
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        literal.walk(self.as_ast_visitor());
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        unwrap.walk(self.as_ast_visitor());
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        literal.walk(self.as_ast_visitor());
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        propagate.walk(self.as_ast_visitor());
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        call.walk(self.as_ast_visitor());
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        definition.walk(self.as_ast_visitor());
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        variant.walk(self.as_ast_visitor());
+    }
 }
 
-pub trait AstNode: DynClone + fmt::Debug {
+/// Nodes are plain data so that files can be parsed on separate threads.
+pub trait AstNode: Any + DynClone + fmt::Debug + Send + Sync {
     fn apply(&self, visitor: &mut dyn AstVisitor);
+
+    /// The node's children, in the order `walk` visits them.
+    fn children(&self) -> Vec<&dyn AstNode>;
+
+    /// A copy of the node with each of its children replaced by what `map` gives for it.
+    fn map_children(
+        &self,
+        map: &mut dyn FnMut(&dyn AstNode) -> Box<dyn AstNode>,
+    ) -> Box<dyn AstNode>;
+
+    /// Replaces each span the node records with what `map` gives for it, leaving its children alone.
+    fn map_spans(&mut self, map: &mut dyn FnMut(Span) -> Span);
+}
+
+impl dyn AstNode {
+    /// The node as a `T`, if that is its type.
+    pub fn downcast_ref<T: AstNode>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
 }
 
 impl Clone for Box<dyn AstNode> {
@@ -22,22 +129,70 @@ impl Clone for Box<dyn AstNode> {
     }
 }
 
+/// Implements `AstNode` for types from other crates, which can't use the derive.
+///
+/// None of them record spans.
 macro_rules! impl_ast_node {
-    ($type:ty, $visit_method:ident) => {
+    ($type:ty, $visit_method:ident, |$node:ident| $children:expr, |$map:ident| $mapped:expr) => {
         impl AstNode for $type {
             fn apply(&self, visitor: &mut dyn AstVisitor) {
                 visitor.$visit_method(self);
             }
+
+            fn children(&self) -> Vec<&dyn AstNode> {
+                #[allow(unused_variables)]
+                let $node = self;
+                $children
+            }
+
+            fn map_children(
+                &self,
+                $map: &mut dyn FnMut(&dyn AstNode) -> Box<dyn AstNode>,
+            ) -> Box<dyn AstNode> {
+                let $node = self;
+                Box::new($mapped)
+            }
+
+            fn map_spans(&mut self, _map: &mut dyn FnMut(Span) -> Span) {}
         }
     };
 }
 
-impl_ast_node!(Vec<Box<dyn AstNode>>, visit_list);
+impl_ast_node!(
+    Vec<Box<dyn AstNode>>,
+    visit_list,
+    |list| list.iter().map(|item| item.as_ref()).collect(),
+    |map| list
+        .iter()
+        .map(|item| map(item.as_ref()))
+        .collect::<Vec<_>>()
+);
 
-#[derive(Clone, Debug)]
+/// A pass which rebuilds a tree, replacing only the nodes it is interested in.
+pub trait Folder {
+    /// What `node` is replaced with, or `None` to keep it and fold its children.
+    ///
+    /// Replacements built from the node's children should fold them with `fold` themselves.
+    fn fold_node(&mut self, node: &dyn AstNode) -> Option<Box<dyn AstNode>>;
+}
+
+/// Rebuilds `node` with `folder`, which is given each node before its children.
+pub fn fold(folder: &mut dyn Folder, node: &dyn AstNode) -> Box<dyn AstNode> {
+    folder
+        .fold_node(node)
+        .unwrap_or_else(|| node.map_children(&mut |child| fold(folder, child)))
+}
+
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_variable_definition)]
 pub struct VariableDefinition {
     mutable: bool,
-    name: String,
+    name: Symbol,
+    /// The span of the whole statement, from `let` to the semicolon.
+    span: Span,
+    /// The span of the type annotation, which errors about the value point back to.
+    type_span: Span,
     variable_type: Box<dyn AstNode>,
     value: Box<dyn AstNode>,
 }
@@ -45,22 +200,50 @@ pub struct VariableDefinition {
 impl VariableDefinition {
     pub fn new(
         mutable: bool,
-        name: String,
+        name: Symbol,
+        span: Span,
+        type_span: Span,
         variable_type: Box<dyn AstNode>,
         value: Box<dyn AstNode>,
     ) -> Self {
         Self {
             mutable,
             name,
+            span,
+            type_span,
             variable_type,
             value,
         }
     }
-}
 
-impl_ast_node!(VariableDefinition, visit_variable_definition);
+    pub fn mutable(&self) -> bool {
+        self.mutable
+    }
 
-#[derive(Clone, Debug)]
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn type_span(&self) -> Span {
+        self.type_span
+    }
+
+    pub fn variable_type(&self) -> &dyn AstNode {
+        self.variable_type.as_ref()
+    }
+
+    pub fn value(&self) -> &dyn AstNode {
+        self.value.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_type)]
 pub enum Type {
     I8,
     I16,
@@ -75,64 +258,1096 @@ pub enum Type {
     F32,
     F64,
     Bool,
+    /// A Unicode scalar value, which is never a surrogate. Chars aren't integers: `char_code!` and
+    /// `from_char_code!` convert between the two.
     Char,
+    /// UTF-8 text. Strings are indexed, sliced and measured in bytes, and `chars!` splits them into characters.
     String,
+    /// `list<T>`, a growable array.
+    List(Box<Type>),
+    /// `map<K, V>`, from keys to values.
+    Map(Box<Type>, Box<Type>),
+    /// `option<T>`, either a `T` or nothing.
+    Option(Box<Type>),
+    /// `result<T, E>`, either a `T` or an error of type `E`.
+    Result(Box<Type>, Box<Type>),
 }
 
-impl_ast_node!(Type, visit_type);
+impl Type {
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Type::I8
+                | Type::I16
+                | Type::I32
+                | Type::I64
+                | Type::Iptr
+                | Type::U8
+                | Type::U16
+                | Type::U32
+                | Type::U64
+                | Type::Uptr
+        )
+    }
 
-#[derive(Clone, Debug)]
+    /// Whether a map can use the type as its key. The runtime compares keys byte by byte, so only types whose
+    /// values are equal exactly when their bytes are can be keys.
+    pub fn is_map_key(&self) -> bool {
+        self.is_integer() || matches!(self, Type::Bool | Type::Char)
+    }
+}
+
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_parameter_declaration)]
 pub struct ParameterDeclaration {
-    name: String,
+    name: Symbol,
     parameter_type: Box<dyn AstNode>,
 }
 
 impl ParameterDeclaration {
-    pub fn new(name: String, parameter_type: Box<dyn AstNode>) -> Self {
+    pub fn new(name: Symbol, parameter_type: Box<dyn AstNode>) -> Self {
         Self {
             name,
             parameter_type,
         }
     }
-}
 
-impl_ast_node!(ParameterDeclaration, visit_parameter_declaration);
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
 
-#[derive(Clone, Debug)]
+    pub fn parameter_type(&self) -> &dyn AstNode {
+        self.parameter_type.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_function_definition)]
 pub struct FunctionDefinition {
-    name: String,
+    name: Symbol,
+    name_span: Span,
     parameters: Vec<Box<dyn AstNode>>,
     return_type: Box<dyn AstNode>,
     body: Box<dyn AstNode>,
+    /// Where the body's last expression is, if the body ends with one rather than a statement. Literals don't record
+    /// where they are, so this is the only span some of them have.
+    tail_span: Option<Span>,
+    /// The lines of the function's doc comment, without their leading `///`.
+    documentation: Vec<String>,
+    /// The names of the function's attributes, such as `test` for `@test`, along with where each name is.
+    attributes: Vec<(Symbol, Span)>,
 }
 
 impl FunctionDefinition {
     pub fn new(
-        name: String,
+        name: Symbol,
+        name_span: Span,
         parameters: Vec<Box<dyn AstNode>>,
         return_type: Box<dyn AstNode>,
         body: Box<dyn AstNode>,
     ) -> Self {
         Self {
             name,
+            name_span,
             parameters,
             return_type,
             body,
+            tail_span: None,
+            documentation: Vec::new(),
+            attributes: Vec::new(),
         }
     }
+
+    pub fn with_attributes(mut self, attributes: Vec<(Symbol, Span)>) -> Self {
+        self.attributes = attributes;
+        self
+    }
+
+    pub fn with_tail_span(mut self, tail_span: Option<Span>) -> Self {
+        self.tail_span = tail_span;
+        self
+    }
+
+    pub fn with_documentation(mut self, documentation: Vec<String>) -> Self {
+        self.documentation = documentation;
+        self
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn name_span(&self) -> Span {
+        self.name_span
+    }
+
+    pub fn parameters(&self) -> &[Box<dyn AstNode>] {
+        &self.parameters
+    }
+
+    pub fn return_type(&self) -> &dyn AstNode {
+        self.return_type.as_ref()
+    }
+
+    pub fn body(&self) -> &dyn AstNode {
+        self.body.as_ref()
+    }
+
+    pub fn tail_span(&self) -> Option<Span> {
+        self.tail_span
+    }
+
+    pub fn documentation(&self) -> &[String] {
+        &self.documentation
+    }
+
+    pub fn attributes(&self) -> &[(Symbol, Span)] {
+        &self.attributes
+    }
+
+    pub fn has_attribute(&self, name: &str) -> bool {
+        self.attributes
+            .iter()
+            .any(|(attribute, _)| attribute == name)
+    }
 }
 
-impl_ast_node!(FunctionDefinition, visit_function_definition);
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_ignore_value)]
+pub struct IgnoreValue {
+    /// The span of the whole statement, including the semicolon.
+    span: Span,
+    value: Box<dyn AstNode>,
+}
+
+impl IgnoreValue {
+    pub fn new(span: Span, value: Box<dyn AstNode>) -> Self {
+        Self { span, value }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn value(&self) -> &dyn AstNode {
+        self.value.as_ref()
+    }
+}
+
+impl_ast_node!(
+    i128,
+    visit_integer_literal,
+    |integer_literal| Vec::new(),
+    |_map| *integer_literal
+);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+}
+
+impl BinaryOperator {
+    /// The operator for a token, if it is a binary operator.
+    pub fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Plus => Some(BinaryOperator::Add),
+            Token::Minus => Some(BinaryOperator::Subtract),
+            Token::Star => Some(BinaryOperator::Multiply),
+            Token::Slash => Some(BinaryOperator::Divide),
+            Token::Percent => Some(BinaryOperator::Remainder),
+            _ => None,
+        }
+    }
+
+    /// The token the operator is written as.
+    pub fn token(&self) -> Token {
+        match self {
+            BinaryOperator::Add => Token::Plus,
+            BinaryOperator::Subtract => Token::Minus,
+            BinaryOperator::Multiply => Token::Star,
+            BinaryOperator::Divide => Token::Slash,
+            BinaryOperator::Remainder => Token::Percent,
+        }
+    }
+}
+
+/// What an integer operation does when its result doesn't fit in its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Overflow {
+    /// Panic, as operators do when overflow checks are on and `checked_add!` and friends always do.
+    Checked,
+    /// Wrap around to the other end of the type's range, as operators do when overflow checks are off.
+    Wrapping,
+    /// Stop at the end of the type's range.
+    Saturating,
+}
+
+impl Overflow {
+    /// The behaviour named by the start of an arithmetic intrinsic, such as `wrapping` in `wrapping_add!`.
+    pub fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            "checked" => Some(Overflow::Checked),
+            "wrapping" => Some(Overflow::Wrapping),
+            "saturating" => Some(Overflow::Saturating),
+            _ => None,
+        }
+    }
+}
+
+/// An operation on two integers, such as `1 + 2`, or `+` joining two strings.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_binary_operation)]
+pub struct BinaryOperation {
+    operator: BinaryOperator,
+    /// The span of the operator token.
+    span: Span,
+    left: Box<dyn AstNode>,
+    right: Box<dyn AstNode>,
+    /// Set by the arithmetic intrinsics, or during macro expansion from the build's overflow checks for operators.
+    overflow: Option<Overflow>,
+}
+
+impl BinaryOperation {
+    pub fn new(
+        operator: BinaryOperator,
+        span: Span,
+        left: Box<dyn AstNode>,
+        right: Box<dyn AstNode>,
+    ) -> Self {
+        Self {
+            operator,
+            span,
+            left,
+            right,
+            overflow: None,
+        }
+    }
+
+    pub fn with_overflow(mut self, overflow: Option<Overflow>) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn operator(&self) -> BinaryOperator {
+        self.operator
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn left(&self) -> &dyn AstNode {
+        self.left.as_ref()
+    }
+
+    pub fn right(&self) -> &dyn AstNode {
+        self.right.as_ref()
+    }
+
+    pub fn overflow(&self) -> Option<Overflow> {
+        self.overflow
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TypeIntrinsicKind {
+    /// `sizeof!(T)`
+    Size,
+    /// `alignof!(T)`
+    Alignment,
+}
+
+/// A compile-time query about the layout of a type, such as `sizeof!(i32)`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_type_intrinsic)]
+pub struct TypeIntrinsic {
+    kind: TypeIntrinsicKind,
+    type_value: Box<dyn AstNode>,
+}
+
+impl TypeIntrinsic {
+    pub fn new(kind: TypeIntrinsicKind, type_value: Box<dyn AstNode>) -> Self {
+        Self { kind, type_value }
+    }
+
+    pub fn kind(&self) -> TypeIntrinsicKind {
+        self.kind
+    }
+
+    pub fn type_value(&self) -> &dyn AstNode {
+        self.type_value.as_ref()
+    }
+}
+
+/// The items parsed from a single source file.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_module)]
+pub struct Module {
+    name: String,
+    /// The index of the source file this module was parsed from.
+    file: usize,
+    items: Box<dyn AstNode>,
+}
+
+impl Module {
+    pub fn new(name: String, file: usize, items: Box<dyn AstNode>) -> Self {
+        Self { name, file, items }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn file(&self) -> usize {
+        self.file
+    }
+
+    pub fn items(&self) -> &dyn AstNode {
+        self.items.as_ref()
+    }
+}
+
+/// A use of a macro, `name!(arguments)`, which is replaced by its expansion before semantic analysis.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_macro_invocation)]
+pub struct MacroInvocation {
+    name: Symbol,
+    /// The span of the whole invocation, from the name to the closing parenthesis.
+    span: Span,
+    /// The tokens between the parentheses, which each macro interprets in its own way.
+    arguments: Vec<(Token, Span)>,
+}
+
+impl MacroInvocation {
+    pub fn new(name: Symbol, span: Span, arguments: Vec<(Token, Span)>) -> Self {
+        Self {
+            name,
+            span,
+            arguments,
+        }
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn arguments(&self) -> &[(Token, Span)] {
+        &self.arguments
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AssertionKind {
+    /// `assert!(condition)`, which fails if the condition is zero.
+    True,
+    /// `assert_eq!(left, right)`, which fails if the operands differ.
+    Equal,
+}
+
+/// A check which aborts the program with `message` if it fails, expanded from `assert!` or `assert_eq!`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_assertion)]
+pub struct Assertion {
+    kind: AssertionKind,
+    /// One operand for `AssertionKind::True`, two for `AssertionKind::Equal`.
+    operands: Vec<Box<dyn AstNode>>,
+    /// What failed and where, such as ``assertion failed: `x` at src/main.hematite:3``.
+    message: String,
+}
+
+impl Assertion {
+    pub fn new(kind: AssertionKind, operands: Vec<Box<dyn AstNode>>, message: String) -> Self {
+        Self {
+            kind,
+            operands,
+            message,
+        }
+    }
+
+    pub fn kind(&self) -> AssertionKind {
+        self.kind
+    }
+
+    pub fn operands(&self) -> &[Box<dyn AstNode>] {
+        &self.operands
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// How a `format!` placeholder presents its argument, such as the `x` in `{:x}`.
+///
+/// The discriminants are those the runtime's `hematite_format_integer` expects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(u8)]
+pub enum FormatSpec {
+    /// `{}`
+    Display = 0,
+    /// `{:x}`
+    LowerHex = 1,
+    /// `{:X}`
+    UpperHex = 2,
+    /// `{:b}`
+    Binary = 3,
+    /// `{:o}`
+    Octal = 4,
+}
+
+impl FormatSpec {
+    /// The text after the `:` in a placeholder.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            FormatSpec::Display => "",
+            FormatSpec::LowerHex => "x",
+            FormatSpec::UpperHex => "X",
+            FormatSpec::Binary => "b",
+            FormatSpec::Octal => "o",
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
-pub struct IgnoreValue(Box<dyn AstNode>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormatSegment {
+    Text(String),
+    /// A placeholder, replaced by the argument at `index` when the string is built.
+    Argument {
+        index: usize,
+        spec: FormatSpec,
+    },
+}
 
-impl IgnoreValue {
-    pub fn new(value: Box<dyn AstNode>) -> Self {
-        Self(value)
+/// A string built from a template and arguments at runtime, expanded from `format!`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_format)]
+pub struct Format {
+    /// The span of the invocation, for reporting mismatched arguments.
+    span: Span,
+    segments: Vec<FormatSegment>,
+    arguments: Vec<Box<dyn AstNode>>,
+}
+
+impl Format {
+    pub fn new(span: Span, segments: Vec<FormatSegment>, arguments: Vec<Box<dyn AstNode>>) -> Self {
+        Self {
+            span,
+            segments,
+            arguments,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn segments(&self) -> &[FormatSegment] {
+        &self.segments
+    }
+
+    pub fn arguments(&self) -> &[Box<dyn AstNode>] {
+        &self.arguments
+    }
+}
+
+/// A function in the runtime library which macros lower to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RuntimeFunction {
+    /// `print!` and `println!`
+    Print,
+    /// `panic!`
+    Panic,
+    /// `len!(string)`, the number of bytes in a string.
+    StringLength,
+    /// `slice!(string, start, end)`, the bytes from `start` up to `end`, which must both be at character boundaries.
+    StringSlice,
+    /// `chars!(string)`, a list of a string's characters.
+    StringChars,
+    /// `compare!(left, right)`, which is negative, zero or positive as `left` sorts before, with or after `right`.
+    StringCompare,
+    /// `parse_int!(string)`, the decimal integer a string holds, or an `err` saying why it doesn't hold one.
+    StringParseInt,
+    /// `to_string!(char)`, a string holding just the character.
+    CharToString,
+    /// `char_code!(char)`, the character's Unicode code point as a `u32`.
+    CharCode,
+    /// `from_char_code!(code)`, the character with a code point, or `none` if it isn't a Unicode scalar value.
+    CharFromCode,
+    /// `args!()`, the arguments the program was run with, not including the program's own name.
+    Args,
+    /// `env_var!(name)`, the value of an environment variable when the program is run, or `none` if it isn't set or
+    /// isn't valid UTF-8. `env!` is the compile-time equivalent.
+    EnvVar,
+}
+
+impl RuntimeFunction {
+    /// The name the runtime library exports the function under.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            RuntimeFunction::Print => "hematite_string_print",
+            RuntimeFunction::Panic => "hematite_panic",
+            RuntimeFunction::StringLength => "hematite_string_length",
+            RuntimeFunction::StringSlice => "hematite_string_slice",
+            RuntimeFunction::StringChars => "hematite_string_chars",
+            RuntimeFunction::StringCompare => "hematite_string_compare",
+            RuntimeFunction::StringParseInt => "hematite_string_parse_int",
+            RuntimeFunction::CharToString => "hematite_char_to_string",
+            RuntimeFunction::CharCode => "hematite_char_code",
+            RuntimeFunction::CharFromCode => "hematite_char_from_code",
+            RuntimeFunction::Args => "hematite_args",
+            RuntimeFunction::EnvVar => "hematite_env_var",
+        }
+    }
+
+    /// The macro which lowers to the function.
+    pub fn macro_name(&self) -> &'static str {
+        match self {
+            // `println!` is `print!` with a newline added to its string.
+            RuntimeFunction::Print => "print",
+            RuntimeFunction::Panic => "panic",
+            RuntimeFunction::StringLength => "len",
+            RuntimeFunction::StringSlice => "slice",
+            RuntimeFunction::StringChars => "chars",
+            RuntimeFunction::StringCompare => "compare",
+            RuntimeFunction::StringParseInt => "parse_int",
+            RuntimeFunction::CharToString => "to_string",
+            RuntimeFunction::CharCode => "char_code",
+            RuntimeFunction::CharFromCode => "from_char_code",
+            RuntimeFunction::Args => "args",
+            RuntimeFunction::EnvVar => "env_var",
+        }
+    }
+
+    /// The types of the function's arguments. Offsets are `uptr` and codes `u32`, but any integer type is accepted
+    /// for them.
+    pub fn parameter_types(&self) -> Vec<Type> {
+        match self {
+            RuntimeFunction::Print
+            | RuntimeFunction::Panic
+            | RuntimeFunction::StringLength
+            | RuntimeFunction::StringChars
+            | RuntimeFunction::StringParseInt
+            | RuntimeFunction::EnvVar => vec![Type::String],
+            RuntimeFunction::StringSlice => vec![Type::String, Type::Uptr, Type::Uptr],
+            RuntimeFunction::StringCompare => vec![Type::String, Type::String],
+            RuntimeFunction::CharToString | RuntimeFunction::CharCode => vec![Type::Char],
+            RuntimeFunction::CharFromCode => vec![Type::U32],
+            RuntimeFunction::Args => Vec::new(),
+        }
+    }
+
+    /// The type of the function's value, or `None` if it doesn't produce one.
+    pub fn return_type(&self) -> Option<Type> {
+        match self {
+            RuntimeFunction::Print | RuntimeFunction::Panic => None,
+            RuntimeFunction::StringLength => Some(Type::Uptr),
+            RuntimeFunction::StringSlice | RuntimeFunction::CharToString => Some(Type::String),
+            RuntimeFunction::StringChars => Some(Type::List(Box::new(Type::Char))),
+            RuntimeFunction::StringCompare => Some(Type::I32),
+            RuntimeFunction::StringParseInt => {
+                Some(Type::Result(Box::new(Type::I64), Box::new(Type::String)))
+            }
+            RuntimeFunction::CharCode => Some(Type::U32),
+            RuntimeFunction::CharFromCode => Some(Type::Option(Box::new(Type::Char))),
+            RuntimeFunction::Args => Some(Type::List(Box::new(Type::String))),
+            RuntimeFunction::EnvVar => Some(Type::Option(Box::new(Type::String))),
+        }
+    }
+}
+
+/// A call to the runtime library, with the arguments `RuntimeFunction::parameter_types` says it takes.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_runtime_call)]
+pub struct RuntimeCall {
+    function: RuntimeFunction,
+    /// The span of the invocation, for reporting arguments of the wrong type.
+    span: Span,
+    arguments: Vec<Box<dyn AstNode>>,
+}
+
+impl RuntimeCall {
+    pub fn new(function: RuntimeFunction, span: Span, arguments: Vec<Box<dyn AstNode>>) -> Self {
+        Self {
+            function,
+            span,
+            arguments,
+        }
+    }
+
+    pub fn function(&self) -> RuntimeFunction {
+        self.function
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn arguments(&self) -> &[Box<dyn AstNode>] {
+        &self.arguments
+    }
+}
+
+/// A condition which must hold at compile time, expanded from `static_assert!`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_static_assertion)]
+pub struct StaticAssertion {
+    span: Span,
+    condition: Box<dyn AstNode>,
+    /// The error reported if the condition is zero.
+    message: String,
+}
+
+impl StaticAssertion {
+    pub fn new(span: Span, condition: Box<dyn AstNode>, message: String) -> Self {
+        Self {
+            span,
+            condition,
+            message,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn condition(&self) -> &dyn AstNode {
+        self.condition.as_ref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// A list written out element by element, such as `[1, 2, 3]`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_list_literal)]
+pub struct ListLiteral {
+    /// The span from the opening bracket to the closing one.
+    span: Span,
+    elements: Vec<Box<dyn AstNode>>,
+}
+
+impl ListLiteral {
+    pub fn new(span: Span, elements: Vec<Box<dyn AstNode>>) -> Self {
+        Self { span, elements }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn elements(&self) -> &[Box<dyn AstNode>] {
+        &self.elements
+    }
+}
+
+/// A map written out entry by entry, such as `[1: 10, 2: 20]`, or `[:]` when it is empty.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_map_literal)]
+pub struct MapLiteral {
+    /// The span from the opening bracket to the closing one.
+    span: Span,
+    /// The entries' keys, in the order they are written; `values` has the same length.
+    keys: Vec<Box<dyn AstNode>>,
+    values: Vec<Box<dyn AstNode>>,
+}
+
+impl MapLiteral {
+    pub fn new(span: Span, keys: Vec<Box<dyn AstNode>>, values: Vec<Box<dyn AstNode>>) -> Self {
+        debug_assert_eq!(keys.len(), values.len());
+        Self { span, keys, values }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn keys(&self) -> &[Box<dyn AstNode>] {
+        &self.keys
+    }
+
+    pub fn values(&self) -> &[Box<dyn AstNode>] {
+        &self.values
+    }
+}
+
+/// An element of a list or the value for a key in a map, such as `numbers[0]`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_index)]
+pub struct Index {
+    /// The span of the brackets and what is between them.
+    span: Span,
+    collection: Box<dyn AstNode>,
+    index: Box<dyn AstNode>,
+}
+
+impl Index {
+    pub fn new(span: Span, collection: Box<dyn AstNode>, index: Box<dyn AstNode>) -> Self {
+        Self {
+            span,
+            collection,
+            index,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn collection(&self) -> &dyn AstNode {
+        self.collection.as_ref()
+    }
+
+    pub fn index(&self) -> &dyn AstNode {
+        self.index.as_ref()
+    }
+}
+
+/// `some(value)`, or `none` when there is no value.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_option_literal)]
+pub struct OptionLiteral {
+    /// The span of `none`, or from `some` to the closing parenthesis.
+    span: Span,
+    value: Option<Box<dyn AstNode>>,
+}
+
+impl OptionLiteral {
+    pub fn new(span: Span, value: Option<Box<dyn AstNode>>) -> Self {
+        Self { span, value }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn value(&self) -> Option<&dyn AstNode> {
+        self.value.as_deref()
+    }
+}
+
+/// The value in an option or result, expanded from `unwrap!` or `unwrap_or!`.
+///
+/// Options and results can't be used where their value is expected, so this is how a program says what happens
+/// when there isn't one.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_unwrap)]
+pub struct Unwrap {
+    /// The span of the invocation.
+    span: Span,
+    option: Box<dyn AstNode>,
+    /// The value used for `none` or an `err`. Without one, the program panics, saying what it found and where.
+    default: Option<Box<dyn AstNode>>,
+    /// The unwrapped expression and where it is, such as `` `x` at src/main.hematite:3 ``.
+    message: String,
+}
+
+impl Unwrap {
+    pub fn new(
+        span: Span,
+        option: Box<dyn AstNode>,
+        default: Option<Box<dyn AstNode>>,
+        message: String,
+    ) -> Self {
+        Self {
+            span,
+            option,
+            default,
+            message,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn option(&self) -> &dyn AstNode {
+        self.option.as_ref()
+    }
+
+    pub fn default(&self) -> Option<&dyn AstNode> {
+        self.default.as_deref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Which of a result's variants a `ResultLiteral` is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ResultVariant {
+    /// `ok(value)`
+    Ok,
+    /// `err(error)`
+    Err,
+}
+
+/// `ok(value)` or `err(error)`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_result_literal)]
+pub struct ResultLiteral {
+    /// The span from `ok` or `err` to the closing parenthesis.
+    span: Span,
+    variant: ResultVariant,
+    value: Box<dyn AstNode>,
+}
+
+impl ResultLiteral {
+    pub fn new(span: Span, variant: ResultVariant, value: Box<dyn AstNode>) -> Self {
+        Self {
+            span,
+            variant,
+            value,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn variant(&self) -> ResultVariant {
+        self.variant
+    }
+
+    pub fn value(&self) -> &dyn AstNode {
+        self.value.as_ref()
+    }
+}
+
+/// `value?`, which returns an `err` from the function it is in, and is otherwise the value in an `ok`.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_propagate)]
+pub struct Propagate {
+    /// The span of the `?`.
+    span: Span,
+    value: Box<dyn AstNode>,
+}
+
+impl Propagate {
+    pub fn new(span: Span, value: Box<dyn AstNode>) -> Self {
+        Self { span, value }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn value(&self) -> &dyn AstNode {
+        self.value.as_ref()
+    }
+}
+
+/// A method the compiler provides for a builtin type, which method resolution picks for a `MethodCall`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BuiltinMethod {
+    /// A method which does the same as a runtime library macro, such as `string.length()` for `len!(string)`. The
+    /// receiver is the function's first argument.
+    Runtime(RuntimeFunction),
+    /// `list.length()`
+    ListLength,
+    /// `map.length()`, the number of entries.
+    MapLength,
+    /// `integer.abs()`, for signed integers, which overflows for the type's smallest value.
+    Abs,
+}
+
+impl BuiltinMethod {
+    /// The types of the method's arguments, besides the receiver.
+    pub fn parameter_types(&self) -> Vec<Type> {
+        match self {
+            BuiltinMethod::Runtime(function) => function.parameter_types().split_off(1),
+            BuiltinMethod::ListLength | BuiltinMethod::MapLength | BuiltinMethod::Abs => Vec::new(),
+        }
+    }
+
+    /// The type of the method's value when it is called on a receiver of type `receiver`, if that is known.
+    pub fn return_type(&self, receiver: Option<&Type>) -> Option<Type> {
+        match self {
+            BuiltinMethod::Runtime(function) => function.return_type(),
+            BuiltinMethod::ListLength | BuiltinMethod::MapLength => Some(Type::Uptr),
+            BuiltinMethod::Abs => receiver.cloned(),
+        }
+    }
+}
+
+/// A call such as `format!("hi").length()`, whose method is looked up from the receiver's type.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_method_call)]
+pub struct MethodCall {
+    /// The span of the method's name.
+    span: Span,
+    receiver: Box<dyn AstNode>,
+    name: Symbol,
+    arguments: Vec<Box<dyn AstNode>>,
+    /// Filled in by method resolution.
+    method: Option<BuiltinMethod>,
+}
+
+impl MethodCall {
+    pub fn new(
+        span: Span,
+        receiver: Box<dyn AstNode>,
+        name: Symbol,
+        arguments: Vec<Box<dyn AstNode>>,
+    ) -> Self {
+        Self {
+            span,
+            receiver,
+            name,
+            arguments,
+            method: None,
+        }
+    }
+
+    pub fn with_method(mut self, method: Option<BuiltinMethod>) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn receiver(&self) -> &dyn AstNode {
+        self.receiver.as_ref()
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn arguments(&self) -> &[Box<dyn AstNode>] {
+        &self.arguments
+    }
+
+    pub fn method(&self) -> Option<BuiltinMethod> {
+        self.method
     }
 }
 
-impl_ast_node!(IgnoreValue, visit_ignore_value);
+/// `enum Flags: u8 { A = 1, B = 2 }`, a set of named integer constants.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_enum_definition)]
+pub struct EnumDefinition {
+    name: Symbol,
+    name_span: Span,
+    /// The integer type the discriminants are stored as, which is `i32` if the enum doesn't say.
+    representation: Option<Box<dyn AstNode>>,
+    variants: Vec<Box<dyn AstNode>>,
+    /// The lines of the enum's doc comment, without their leading `///`.
+    documentation: Vec<String>,
+}
 
-impl_ast_node!(i128, visit_integer_literal);
+impl EnumDefinition {
+    pub fn new(
+        name: Symbol,
+        name_span: Span,
+        representation: Option<Box<dyn AstNode>>,
+        variants: Vec<Box<dyn AstNode>>,
+    ) -> Self {
+        Self {
+            name,
+            name_span,
+            representation,
+            variants,
+            documentation: Vec::new(),
+        }
+    }
+
+    pub fn with_documentation(mut self, documentation: Vec<String>) -> Self {
+        self.documentation = documentation;
+        self
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn name_span(&self) -> Span {
+        self.name_span
+    }
+
+    pub fn representation(&self) -> Option<&dyn AstNode> {
+        self.representation.as_deref()
+    }
+
+    pub fn variants(&self) -> &[Box<dyn AstNode>] {
+        &self.variants
+    }
+
+    pub fn documentation(&self) -> &[String] {
+        &self.documentation
+    }
+}
+
+/// One of an `EnumDefinition`'s variants. Without an explicit discriminant, it is one more than the variant before,
+/// or zero for the first.
+#[derive(Clone, Debug, AstNode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[visit(visit_enum_variant)]
+pub struct EnumVariant {
+    name: Symbol,
+    span: Span,
+    discriminant: Option<Box<dyn AstNode>>,
+}
+
+impl EnumVariant {
+    pub fn new(name: Symbol, span: Span, discriminant: Option<Box<dyn AstNode>>) -> Self {
+        Self {
+            name,
+            span,
+            discriminant,
+        }
+    }
+
+    pub fn name(&self) -> Symbol {
+        self.name
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn discriminant(&self) -> Option<&dyn AstNode> {
+        self.discriminant.as_deref()
+    }
+}
@@ -0,0 +1,157 @@
+//! Code generators, which turn an analysed program into output files.
+//!
+//! The driver only talks to a `Backend`, so adding a code generator means implementing the trait here and giving it
+//! a `BackendKind`.
+
+use std::io::{self, Write};
+
+use hematite_lang::{ast::AstNode, diagnostics::Diagnostic, session::Session, target::Target};
+
+use crate::{c_header, EmitKind};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum BackendKind {
+    /// Generates C, which is only declarations of the exported functions so far
+    C,
+    /// Runs programs directly, with `test` and `debug`, rather than writing output
+    Interpreter,
+    /// Generates code with LLVM
+    Llvm,
+    /// Generates code with Cranelift, for fast unoptimized builds
+    Cranelift,
+}
+
+impl BackendKind {
+    pub fn backend(self) -> Box<dyn Backend> {
+        match self {
+            BackendKind::C => Box::new(CBackend),
+            BackendKind::Interpreter => Box::new(InterpreterBackend),
+            BackendKind::Llvm => Box::new(UnavailableBackend { name: "llvm" }),
+            BackendKind::Cranelift => Box::new(UnavailableBackend { name: "cranelift" }),
+        }
+    }
+}
+
+/// A code generator, chosen with `--backend`.
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    /// What the backend produces when compiling for `target`, for `--verbose`.
+    fn describe(&self, target: &Target) -> String;
+
+    /// Whether the backend can write `kind`, which is never one of the front-end's own outputs.
+    fn supports(&self, kind: EmitKind) -> bool;
+
+    /// Turns the analysed program into the backend's own representation, ready for each output to be emitted from.
+    fn lower<'program>(
+        &self,
+        program: &'program dyn AstNode,
+        session: &Session,
+    ) -> Result<Box<dyn Lowered + 'program>, Diagnostic>;
+}
+
+/// A program which a backend has lowered.
+pub trait Lowered {
+    /// Writes the program as `kind`, which the backend supports. `name` names the output, such as for an include guard.
+    fn emit(&self, kind: EmitKind, name: &str, output: &mut dyn Write) -> io::Result<()>;
+}
+
+struct CBackend;
+
+impl Backend for CBackend {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn describe(&self, target: &Target) -> String {
+        format!(
+            "c: C declarations for {}, with {}-bit pointers",
+            target.triple(),
+            target.pointer_width()
+        )
+    }
+
+    fn supports(&self, kind: EmitKind) -> bool {
+        kind == EmitKind::CHeader
+    }
+
+    fn lower<'program>(
+        &self,
+        program: &'program dyn AstNode,
+        _session: &Session,
+    ) -> Result<Box<dyn Lowered + 'program>, Diagnostic> {
+        // C is generated straight from the tree, so there is nothing to lower it into.
+        Ok(Box::new(CProgram(program)))
+    }
+}
+
+struct CProgram<'program>(&'program dyn AstNode);
+
+impl Lowered for CProgram<'_> {
+    fn emit(&self, kind: EmitKind, name: &str, output: &mut dyn Write) -> io::Result<()> {
+        debug_assert_eq!(kind, EmitKind::CHeader);
+        output.write_all(c_header::generate(self.0, name).as_bytes())
+    }
+}
+
+struct InterpreterBackend;
+
+impl Backend for InterpreterBackend {
+    fn name(&self) -> &'static str {
+        "interpreter"
+    }
+
+    fn describe(&self, _target: &Target) -> String {
+        // The interpreter always runs on the host, whichever target the program was checked for.
+        "interpreter: runs programs on the host with `test` and `debug`, writing no output"
+            .to_string()
+    }
+
+    fn supports(&self, _kind: EmitKind) -> bool {
+        false
+    }
+
+    fn lower<'program>(
+        &self,
+        _program: &'program dyn AstNode,
+        _session: &Session,
+    ) -> Result<Box<dyn Lowered + 'program>, Diagnostic> {
+        Err(Diagnostic::error(
+            "The interpreter runs programs rather than writing output",
+        ))
+    }
+}
+
+/// A backend which can be selected, but which doesn't generate code yet.
+struct UnavailableBackend {
+    name: &'static str,
+}
+
+impl Backend for UnavailableBackend {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn describe(&self, target: &Target) -> String {
+        format!(
+            "{}: can't generate code for {} yet",
+            self.name,
+            target.triple()
+        )
+    }
+
+    fn supports(&self, _kind: EmitKind) -> bool {
+        false
+    }
+
+    fn lower<'program>(
+        &self,
+        _program: &'program dyn AstNode,
+        _session: &Session,
+    ) -> Result<Box<dyn Lowered + 'program>, Diagnostic> {
+        Err(Diagnostic::error(format!(
+            "The {} backend can't generate code yet",
+            self.name
+        )))
+    }
+}
@@ -0,0 +1,213 @@
+//! C declarations of a program's `@export`ed functions, for `--emit c-header`.
+
+use std::fmt::Write;
+
+use hematite_lang::{
+    ast::{AstNode, FunctionDefinition, ParameterDeclaration, Type},
+    semantic,
+};
+
+/// Matches `HematiteString` in the runtime library; guarded so that several headers can be included together.
+const STRING_DEFINITION: &str = "#ifndef HEMATITE_STRING_DEFINED
+#define HEMATITE_STRING_DEFINED
+/* A UTF-8 string, which isn't null-terminated. */
+typedef struct HematiteString {
+    const uint8_t *data;
+    size_t length;
+} HematiteString;
+#endif
+";
+
+/// Matches `HematiteList` and `HematiteMap` in the runtime library. C has no generics, so every list and map has
+/// the same type whatever it holds.
+const COLLECTION_DEFINITIONS: &str = "#ifndef HEMATITE_COLLECTIONS_DEFINED
+#define HEMATITE_COLLECTIONS_DEFINED
+/* A growable array, whose elements are stored one after another. */
+typedef struct HematiteList {
+    uint8_t *data;
+    size_t length;
+    size_t capacity;
+} HematiteList;
+/* A map, which is only used through the runtime library's functions. */
+typedef struct HematiteMapTable *HematiteMap;
+#endif
+";
+
+/// `c_type` as part of an identifier, such as the `uint8_t` in `HematiteOption_uint8_t`.
+fn identifier_part(c_type: &str) -> String {
+    c_type
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn c_type(type_value: &Type) -> String {
+    let name = match type_value {
+        Type::I8 => "int8_t",
+        Type::I16 => "int16_t",
+        Type::I32 => "int32_t",
+        Type::I64 => "int64_t",
+        Type::Iptr => "intptr_t",
+        Type::U8 => "uint8_t",
+        Type::U16 => "uint16_t",
+        Type::U32 => "uint32_t",
+        Type::U64 => "uint64_t",
+        Type::Uptr => "uintptr_t",
+        Type::F32 => "float",
+        Type::F64 => "double",
+        Type::Bool => "bool",
+        // A Unicode scalar value.
+        Type::Char => "uint32_t",
+        Type::String => "HematiteString",
+        Type::List(_) => "HematiteList",
+        Type::Map(_, _) => "HematiteMap",
+        // Each option and result type is its own struct, named after the types inside it.
+        Type::Option(value_type) => {
+            return format!("HematiteOption_{}", identifier_part(&c_type(value_type)))
+        }
+        Type::Result(value_type, error_type) => {
+            return format!(
+                "HematiteResult_{}_{}",
+                identifier_part(&c_type(value_type)),
+                identifier_part(&c_type(error_type))
+            )
+        }
+    };
+    name.to_string()
+}
+
+/// The type a type node holds, which the parser only ever fills with a `Type`.
+fn type_of(node: &dyn AstNode) -> &Type {
+    node.downcast_ref::<Type>()
+        .expect("Types are written as type keywords")
+}
+
+/// The C type for a type node.
+fn c_type_of(node: &dyn AstNode) -> String {
+    c_type(type_of(node))
+}
+
+/// Defines the struct for `type_value` if it is an option or a result, after the structs for any options and
+/// results inside it. The definitions are guarded so that headers using the same types can be included together.
+fn define_generated_types(type_value: &Type, output: &mut String) {
+    let name = c_type(type_value);
+    // Both are laid out like `Layout::of_enum`, with a one byte flag before the payload.
+    let definition = match type_value {
+        Type::Option(value_type) => {
+            define_generated_types(value_type, output);
+            format!("    bool is_some;\n    {} value;\n", c_type(value_type))
+        }
+        Type::Result(value_type, error_type) => {
+            define_generated_types(value_type, output);
+            define_generated_types(error_type, output);
+            format!(
+                "    bool is_ok;\n    union {{\n        {} ok;\n        {} err;\n    }} value;\n",
+                c_type(value_type),
+                c_type(error_type)
+            )
+        }
+        _ => return,
+    };
+    let guard = format!(
+        "HEMATITE_{}_DEFINED",
+        identifier_part(&name["Hematite".len()..]).to_ascii_uppercase()
+    );
+    if output.contains(&guard) {
+        return;
+    }
+    writeln!(
+        output,
+        "#ifndef {guard}\n#define {guard}\ntypedef struct {name} {{\n{definition}}} {name};\n#endif"
+    )
+    .unwrap();
+}
+
+fn declaration(function: &FunctionDefinition) -> String {
+    let parameters = function
+        .parameters()
+        .iter()
+        .filter_map(|parameter| parameter.downcast_ref::<ParameterDeclaration>())
+        .map(|parameter| {
+            format!(
+                "{} {}",
+                c_type_of(parameter.parameter_type()),
+                parameter.name()
+            )
+        })
+        .collect::<Vec<_>>();
+    let parameters = if parameters.is_empty() {
+        "void".to_string()
+    } else {
+        parameters.join(", ")
+    };
+    format!(
+        "{} {}({parameters});",
+        c_type_of(function.return_type()),
+        function.name()
+    )
+}
+
+/// The include guard for a header, made from its name.
+fn include_guard(name: &str) -> String {
+    let identifier = name
+        .chars()
+        .map(|character| {
+            if character.is_ascii_alphanumeric() {
+                character.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    format!("HEMATITE_{identifier}_H")
+}
+
+/// Declares every function marked `@export` in `program`, in a header called `name`.
+pub fn generate(program: &dyn AstNode, name: &str) -> String {
+    let guard = include_guard(name);
+    let mut output = format!(
+        "/* Generated by hematite-lang. Do not edit. */\n\n#ifndef {guard}\n#define {guard}\n\n\
+         #include <stdbool.h>\n#include <stddef.h>\n#include <stdint.h>\n\n{STRING_DEFINITION}\n{COLLECTION_DEFINITIONS}\n\
+         "
+    );
+    let exported = semantic::collect_module_functions(program)
+        .into_iter()
+        .filter(|collected| collected.function().has_attribute("export"))
+        .collect::<Vec<_>>();
+    let mut generated_types = String::new();
+    for collected in &exported {
+        let function = collected.function();
+        for parameter in function
+            .parameters()
+            .iter()
+            .filter_map(|parameter| parameter.downcast_ref::<ParameterDeclaration>())
+        {
+            define_generated_types(type_of(parameter.parameter_type()), &mut generated_types);
+        }
+        define_generated_types(type_of(function.return_type()), &mut generated_types);
+    }
+    if !generated_types.is_empty() {
+        writeln!(output, "{generated_types}").unwrap();
+    }
+    output.push_str("#ifdef __cplusplus\nextern \"C\" {\n#endif\n");
+    for collected in exported {
+        let function = collected.function();
+        writeln!(output).unwrap();
+        for line in function.documentation() {
+            writeln!(output, "// {line}").unwrap();
+        }
+        writeln!(output, "{}", declaration(function)).unwrap();
+    }
+    writeln!(
+        output,
+        "\n#ifdef __cplusplus\n}}\n#endif\n\n#endif /* {guard} */"
+    )
+    .unwrap();
+    output
+}
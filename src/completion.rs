@@ -0,0 +1,206 @@
+//! Works out what could be typed at a point in a file, for editors to offer as completions.
+//!
+//! Completion has to work in files which are half written, and so which usually don't parse. The context is found
+//! from the tokens before the cursor instead: the token just before it decides what kind of thing comes next, and
+//! how deeply nested the braces are decides whether the cursor is among items or in a function's body.
+
+use crate::{
+    lexer::{Span, Token},
+    macros, methods, semantic,
+};
+
+/// The types a type annotation can name, including the collections which take parameters.
+const TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "iptr", "u8", "u16", "u32", "u64", "uptr", "f32", "f64", "bool",
+    "char", "string", "list", "map", "option", "result",
+];
+
+/// The keywords which start an expression.
+const EXPRESSION_KEYWORDS: &[&str] = &["none", "some", "ok", "err"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Type,
+    Macro,
+    Method,
+    Attribute,
+}
+
+/// Something which could be typed at the cursor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    label: String,
+    kind: CompletionKind,
+    detail: Option<String>,
+}
+
+impl Completion {
+    fn new(label: &str, kind: CompletionKind) -> Self {
+        Self {
+            label: label.to_string(),
+            kind,
+            detail: None,
+        }
+    }
+
+    /// The name of what is offered, which for macros doesn't include the `!`.
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn kind(&self) -> CompletionKind {
+        self.kind
+    }
+
+    /// More about the completion, such as what a method can be called on.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+}
+
+/// What the tokens before the cursor say can come next.
+#[derive(Debug, PartialEq, Eq)]
+enum Context {
+    Item,
+    Statement,
+    Expression,
+    Type,
+    Method,
+    Attribute,
+    Nothing,
+}
+
+/// Works out the context from the tokens before the cursor, which doesn't include a word being typed.
+fn context(tokens: &[(Token, Span)]) -> Context {
+    // Each open brace, and whether it is an enum's body.
+    let mut braces = Vec::new();
+    // How many `<`s are open in the type being written, if the last tokens are a type.
+    let mut open_type_parameters = 0;
+    for (index, (token, _)) in tokens.iter().enumerate() {
+        match token {
+            Token::LeftBrace => {
+                let is_enum = tokens[..index]
+                    .iter()
+                    .rev()
+                    .take_while(|(token, _)| !matches!(token, Token::LeftBrace | Token::RightBrace))
+                    .any(|(token, _)| *token == Token::Enum);
+                braces.push(is_enum);
+            }
+            Token::RightBrace => {
+                braces.pop();
+            }
+            Token::Less if index > 0 && is_generic_type(&tokens[index - 1].0) => {
+                open_type_parameters += 1;
+            }
+            Token::Greater if open_type_parameters > 0 => open_type_parameters -= 1,
+            Token::Colon | Token::Arrow => open_type_parameters = 0,
+            _ => {}
+        }
+    }
+    if braces.last() == Some(&true) {
+        return Context::Nothing;
+    }
+    let in_function = !braces.is_empty();
+    let previous = |back: usize| {
+        tokens
+            .len()
+            .checked_sub(back + 1)
+            .map(|index| &tokens[index].0)
+    };
+    match previous(0) {
+        Some(Token::Dot) => Context::Method,
+        Some(Token::At) if !in_function => Context::Attribute,
+        // A colon after a name annotates it, but one in a map literal comes after a key.
+        Some(Token::Colon) if matches!(previous(1), Some(Token::Identifier(_))) => Context::Type,
+        Some(Token::Arrow) => Context::Type,
+        Some(Token::Less | Token::Comma) if open_type_parameters > 0 => Context::Type,
+        _ if !in_function => match previous(0) {
+            None | Some(Token::RightBrace | Token::DocComment(_)) => Context::Item,
+            Some(Token::Identifier(_)) if previous(1) == Some(&Token::At) => Context::Item,
+            _ => Context::Nothing,
+        },
+        Some(Token::LeftBrace | Token::RightBrace | Token::Semicolon) => Context::Statement,
+        Some(Token::Let | Token::Function | Token::Enum) => Context::Nothing,
+        Some(token) if is_operator(token) => Context::Expression,
+        Some(
+            Token::Equals | Token::Comma | Token::Colon | Token::LeftParen | Token::LeftBracket,
+        ) => Context::Expression,
+        _ => Context::Nothing,
+    }
+}
+
+fn is_generic_type(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::List | Token::Map | Token::OptionType | Token::ResultType
+    )
+}
+
+/// Whether the token is a binary operator, which has an operand after it.
+fn is_operator(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent
+    )
+}
+
+fn words(words: &'static [&'static str], kind: CompletionKind) -> impl Iterator<Item = Completion> {
+    words.iter().map(move |word| Completion::new(word, kind))
+}
+
+/// What could be typed at byte `offset` in a file with these tokens, given whether the prelude is in scope.
+///
+/// A word which the cursor is at the end of is taken to be half typed, so completions for it are given even though
+/// it is already a token; editors filter them by what has been typed so far.
+pub fn completions(tokens: &[(Token, Span)], offset: usize, has_prelude: bool) -> Vec<Completion> {
+    let mut before = tokens.partition_point(|(_, span)| span.end() <= offset);
+    if let Some((token, span)) = before.checked_sub(1).map(|index| &tokens[index]) {
+        let is_word = matches!(token, Token::Identifier(_) | Token::MacroCall(_))
+            || token
+                .exact_match_text()
+                .is_some_and(|text| text.chars().all(char::is_alphanumeric));
+        if is_word && span.end() == offset {
+            before -= 1;
+        }
+    }
+    let expressions = || {
+        words(EXPRESSION_KEYWORDS, CompletionKind::Keyword).chain(
+            macros::macro_names(has_prelude)
+                .map(|name| Completion::new(name, CompletionKind::Macro)),
+        )
+    };
+    match context(&tokens[..before]) {
+        Context::Item => words(&["function", "enum"], CompletionKind::Keyword).collect(),
+        Context::Statement => words(&["let"], CompletionKind::Keyword)
+            .chain(expressions())
+            .collect(),
+        Context::Expression => expressions().collect(),
+        Context::Type => words(TYPES, CompletionKind::Type).collect(),
+        Context::Attribute => {
+            words(semantic::KNOWN_ATTRIBUTES, CompletionKind::Attribute).collect()
+        }
+        Context::Method => {
+            // Without knowing the receiver's type, every method is offered, saying what each can be called on.
+            let mut completions: Vec<Completion> = Vec::new();
+            for (name, receiver) in methods::all_methods() {
+                match completions
+                    .iter_mut()
+                    .find(|completion| completion.label == name)
+                {
+                    Some(completion) => {
+                        let detail = completion.detail.get_or_insert_with(String::new);
+                        detail.push_str(", ");
+                        detail.push_str(receiver);
+                    }
+                    None => completions.push(Completion {
+                        detail: Some(receiver.to_string()),
+                        ..Completion::new(name, CompletionKind::Method)
+                    }),
+                }
+            }
+            completions
+        }
+        Context::Nothing => Vec::new(),
+    }
+}
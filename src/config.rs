@@ -0,0 +1,100 @@
+//! Defaults for command line options, from the user's config file and the `HEMATITE_FLAGS` environment variable.
+//!
+//! Both are turned into ordinary arguments which come before those actually given,
+//! so the command line always has the final say.
+
+use std::{env, error::Error, fmt::Display, fs, io, path::PathBuf};
+
+pub const FLAGS_VARIABLE: &str = "HEMATITE_FLAGS";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io { path: PathBuf, error: io::Error },
+    Invalid { path: PathBuf, message: String },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            ConfigError::Invalid { path, message } => {
+                write!(f, "Invalid config {}: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// `$XDG_CONFIG_HOME/hematite/config.toml`, falling back to `~/.config` if it isn't set.
+fn config_path() -> Option<PathBuf> {
+    let config_directory = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_directory.join("hematite").join("config.toml"))
+}
+
+fn flag(key: &str) -> String {
+    if key.len() == 1 {
+        format!("-{key}")
+    } else {
+        format!("--{key}")
+    }
+}
+
+/// Turns `key = value` into `--key value`; `true` is just `--key` and arrays repeat the flag.
+fn arguments_for(
+    key: &str,
+    value: &toml::Value,
+    arguments: &mut Vec<String>,
+) -> Result<(), String> {
+    match value {
+        toml::Value::Boolean(true) => arguments.push(flag(key)),
+        toml::Value::Boolean(false) => {}
+        toml::Value::String(string) => arguments.extend([flag(key), string.clone()]),
+        toml::Value::Integer(integer) => arguments.extend([flag(key), integer.to_string()]),
+        toml::Value::Array(values) => {
+            for value in values {
+                arguments_for(key, value, arguments)?;
+            }
+        }
+        _ => {
+            return Err(format!(
+                "`{key}` must be a string, integer, boolean or array"
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn config_file_arguments() -> Result<Vec<String>, ConfigError> {
+    let Some(path) = config_path() else {
+        return Ok(Vec::new());
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(ConfigError::Io { path, error }),
+    };
+    let invalid = |message: String| ConfigError::Invalid {
+        path: path.clone(),
+        message,
+    };
+    let table = text
+        .parse::<toml::Table>()
+        .map_err(|error| invalid(error.to_string().trim_end().to_string()))?;
+    let mut arguments = Vec::new();
+    for (key, value) in &table {
+        arguments_for(key, value, &mut arguments).map_err(invalid)?;
+    }
+    Ok(arguments)
+}
+
+/// The arguments to insert before those given on the command line.
+pub fn default_arguments() -> Result<Vec<String>, ConfigError> {
+    let mut arguments = config_file_arguments()?;
+    if let Ok(flags) = env::var(FLAGS_VARIABLE) {
+        arguments.extend(flags.split_whitespace().map(str::to_string));
+    }
+    Ok(arguments)
+}
@@ -0,0 +1,199 @@
+//! Which lines of a program its tests ran, for `test --coverage`.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use hematite_lang::{
+    interpreter::{self, EvaluationHook, Frame, RuntimeError},
+    semantic::CollectedFunction,
+    source::SourceMap,
+    symbol::Symbol,
+};
+
+use crate::docs::escape_html;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum CoverageFormat {
+    /// The tracefile format read by lcov, genhtml and most editors' coverage plugins
+    Lcov,
+    /// A page showing each file with the lines which ran highlighted
+    Html,
+}
+
+impl CoverageFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            CoverageFormat::Lcov => "info",
+            CoverageFormat::Html => "html",
+        }
+    }
+}
+
+/// A function, and how many times it was called.
+struct FunctionRecord {
+    name: Symbol,
+    file: usize,
+    /// The zero-based line its name is on.
+    line: usize,
+    calls: usize,
+}
+
+/// Counts how many times each line with a statement on it runs.
+///
+/// There is no branching yet, so only lines and functions are counted.
+pub struct Coverage<'files> {
+    source_map: &'files SourceMap,
+    /// Keyed by file index and zero-based line; every line which could run is present, so unrun lines count 0.
+    lines: BTreeMap<(usize, usize), usize>,
+    functions: Vec<FunctionRecord>,
+}
+
+impl<'files> Coverage<'files> {
+    /// Coverage of every statement in `functions`, none of which has run yet.
+    pub fn new(source_map: &'files SourceMap, functions: &[CollectedFunction]) -> Self {
+        let mut lines = BTreeMap::new();
+        let mut records = Vec::new();
+        for collected in functions {
+            let location = collected.location();
+            let source_file = &source_map[location.file()];
+            for span in interpreter::statement_spans(collected.function()) {
+                lines.insert((location.file(), source_file.line_index(span.start())), 0);
+            }
+            records.push(FunctionRecord {
+                name: collected.function().name(),
+                file: location.file(),
+                line: source_file.line_index(location.span().start()),
+                calls: 0,
+            });
+        }
+        Self {
+            source_map,
+            lines,
+            functions: records,
+        }
+    }
+
+    /// The lines of one file which could run, with how many times each did.
+    fn file_lines(&self, file: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.lines
+            .range((file, 0)..(file + 1, 0))
+            .map(|(&(_, line), &count)| (line, count))
+    }
+
+    /// The files containing something which could run, by index.
+    fn files(&self) -> Vec<usize> {
+        let mut files = self
+            .functions
+            .iter()
+            .map(|function| function.file)
+            .collect::<Vec<_>>();
+        files.sort_unstable();
+        files.dedup();
+        files
+    }
+
+    pub fn render(&self, format: CoverageFormat) -> String {
+        match format {
+            CoverageFormat::Lcov => self.render_lcov(),
+            CoverageFormat::Html => self.render_html(),
+        }
+    }
+
+    fn render_lcov(&self) -> String {
+        let mut output = String::new();
+        for file in self.files() {
+            writeln!(output, "TN:\nSF:{}", self.source_map[file].name()).unwrap();
+            let functions = self
+                .functions
+                .iter()
+                .filter(|function| function.file == file)
+                .collect::<Vec<_>>();
+            for function in &functions {
+                writeln!(output, "FN:{},{}", function.line + 1, function.name).unwrap();
+            }
+            for function in &functions {
+                writeln!(output, "FNDA:{},{}", function.calls, function.name).unwrap();
+            }
+            writeln!(
+                output,
+                "FNF:{}\nFNH:{}",
+                functions.len(),
+                functions
+                    .iter()
+                    .filter(|function| function.calls > 0)
+                    .count()
+            )
+            .unwrap();
+            let (mut found, mut hit) = (0, 0);
+            for (line, count) in self.file_lines(file) {
+                writeln!(output, "DA:{},{count}", line + 1).unwrap();
+                found += 1;
+                if count > 0 {
+                    hit += 1;
+                }
+            }
+            writeln!(output, "LF:{found}\nLH:{hit}\nend_of_record").unwrap();
+        }
+        output
+    }
+
+    fn render_html(&self) -> String {
+        let mut output = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Coverage</title>\n<style>\n\
+             .hit {\n  background: #dfd;\n}\n.missed {\n  background: #fdd;\n}\n</style>\n</head>\n<body>\n",
+        );
+        for file in self.files() {
+            let source_file = &self.source_map[file];
+            let counts = self.file_lines(file).collect::<BTreeMap<_, _>>();
+            let hit = counts.values().filter(|&&count| count > 0).count();
+            writeln!(
+                output,
+                "<h1>{}</h1>\n<p>{hit} of {} lines ran</p>\n<pre>",
+                escape_html(source_file.name()),
+                counts.len()
+            )
+            .unwrap();
+            for line in 0..source_file.line_count() {
+                let text = escape_html(source_file.line(line));
+                match counts.get(&line) {
+                    Some(0) => writeln!(
+                        output,
+                        "<span class=\"missed\">{:>5}      0 | {text}</span>",
+                        line + 1
+                    ),
+                    Some(count) => writeln!(
+                        output,
+                        "<span class=\"hit\">{:>5} {count:>6} | {text}</span>",
+                        line + 1
+                    ),
+                    None => writeln!(output, "{:>5}        | {text}", line + 1),
+                }
+                .unwrap();
+            }
+            output.push_str("</pre>\n");
+        }
+        output.push_str("</body>\n</html>\n");
+        output
+    }
+}
+
+impl EvaluationHook for Coverage<'_> {
+    fn before_statement(&mut self, stack: &[Frame]) -> Result<(), RuntimeError> {
+        if let Some(location) = stack.last().and_then(Frame::location) {
+            let line = self.source_map[location.file()].line_index(location.span().start());
+            *self.lines.entry((location.file(), line)).or_default() += 1;
+        }
+        Ok(())
+    }
+
+    fn enter_function(&mut self, function: Symbol) {
+        // Hooks are only told a function's name, so calls to functions which share one are all counted
+        // against the first.
+        if let Some(record) = self
+            .functions
+            .iter_mut()
+            .find(|record| record.name == function)
+        {
+            record.calls += 1;
+        }
+    }
+}
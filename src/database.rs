@@ -0,0 +1,188 @@
+//! Memoised queries over a program's files, so that tools which check a program again after every change (such as
+//! watch mode) only redo the work which the change affects.
+//!
+//! The text of each file is the database's input. Everything else is the result of a query, which is kept along
+//! with the revision it was last known to be up to date in and the revision its result last changed in. When asked
+//! for again, a query is only run if something it depends on has changed since, and if it gives the same result as
+//! before, the queries depending on it don't have to run either.
+//!
+//! So far only lexing and parsing are queries; the passes after them still run over the whole program. Only watch
+//! mode uses the database: the language server still keeps each open document in an `IncrementalParse`, which
+//! reparses just the items an edit touches, and hasn't been moved onto these queries yet.
+
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+
+use crate::{
+    ast::AstNode,
+    features::Features,
+    lexer::{Span, Token},
+    parser::{self, SyntaxError, TokenStream},
+};
+
+/// A point in the database's history, which moves on whenever a file's text changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct Revision(u64);
+
+/// The queries the database can answer, to count how often each is run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Query {
+    Tokens,
+    Syntax,
+}
+
+type Tokens = Vec<(Token, Span)>;
+type Syntax = Result<Box<dyn AstNode>, SyntaxError>;
+
+/// A query's result, with when it was last checked to be up to date and when it last changed.
+struct Memo<T> {
+    value: Arc<T>,
+    verified_at: Revision,
+    changed_at: Revision,
+}
+
+impl<T> Memo<T> {
+    /// The result from `memo`, after running `query` again if what it depends on has changed since it was last
+    /// checked.
+    ///
+    /// When the new result is `same` as the old, the memo keeps the revision it last changed in, so that whatever
+    /// depends on it doesn't have to be run again.
+    fn fetch(
+        memo: &mut Option<Self>,
+        revision: Revision,
+        dependency_changed_at: Revision,
+        query: impl FnOnce() -> T,
+        same: impl FnOnce(&T, &T) -> bool,
+    ) -> Arc<T> {
+        match memo {
+            Some(memo) if memo.verified_at >= dependency_changed_at => {
+                memo.verified_at = revision;
+            }
+            Some(memo) => {
+                let value = query();
+                if !same(&memo.value, &value) {
+                    memo.value = Arc::new(value);
+                    memo.changed_at = revision;
+                }
+                memo.verified_at = revision;
+            }
+            None => {
+                *memo = Some(Self {
+                    value: Arc::new(query()),
+                    verified_at: revision,
+                    changed_at: revision,
+                });
+            }
+        }
+        let memo = memo.as_ref().expect("The memo was just filled in");
+        Arc::clone(&memo.value)
+    }
+}
+
+/// A file's text and the results of the queries about it.
+struct File {
+    text: String,
+    text_changed_at: Revision,
+    tokens: Option<Memo<Tokens>>,
+    syntax: Option<Memo<Syntax>>,
+}
+
+/// The files making up a program, and everything which has been worked out about them.
+#[derive(Default)]
+pub struct Database {
+    revision: Revision,
+    features: Features,
+    files: Vec<File>,
+    token_runs: usize,
+    syntax_runs: usize,
+}
+
+impl Database {
+    /// A database parsing with `features` enabled.
+    pub fn new(features: Features) -> Self {
+        Self {
+            features,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a file, returning its index.
+    pub fn add_file(&mut self, text: String) -> usize {
+        self.revision.0 += 1;
+        self.files.push(File {
+            text,
+            text_changed_at: self.revision,
+            tokens: None,
+            syntax: None,
+        });
+        self.files.len() - 1
+    }
+
+    /// Replaces a file's text. Nothing is invalidated if it is the same as before.
+    pub fn set_text(&mut self, file: usize, text: String) {
+        let file = &mut self.files[file];
+        if file.text != text {
+            self.revision.0 += 1;
+            file.text = text;
+            file.text_changed_at = self.revision;
+        }
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn text(&self, file: usize) -> &str {
+        &self.files[file].text
+    }
+
+    /// The file's tokens, as `lexer::tokenize` gives them.
+    pub fn tokens(&mut self, file: usize) -> Arc<Tokens> {
+        let revision = self.revision;
+        let runs = &mut self.token_runs;
+        let file = &mut self.files[file];
+        let text = &file.text;
+        Memo::fetch(
+            &mut file.tokens,
+            revision,
+            file.text_changed_at,
+            || {
+                *runs += 1;
+                crate::tokenize_str(text)
+            },
+            |old, new| old == new,
+        )
+    }
+
+    /// The items in the file, or why it doesn't parse.
+    pub fn syntax(&mut self, file: usize) -> Arc<Syntax> {
+        let tokens = self.tokens(file);
+        let revision = self.revision;
+        let features = &self.features;
+        let runs = &mut self.syntax_runs;
+        let file = &mut self.files[file];
+        let tokens_changed_at = file
+            .tokens
+            .as_ref()
+            .map_or(revision, |memo| memo.changed_at);
+        Memo::fetch(
+            &mut file.syntax,
+            revision,
+            tokens_changed_at,
+            || {
+                *runs += 1;
+                let mut tokens = tokens.iter().cloned();
+                parser::parse(&mut TokenStream::new(&mut tokens).with_features(features.clone()))
+            },
+            // Trees can't be compared, so a new tree is always a change.
+            |_, _| false,
+        )
+    }
+
+    /// How many times a query has been run rather than answered from what was already known.
+    pub fn runs(&self, query: Query) -> usize {
+        match query {
+            Query::Tokens => self.token_runs,
+            Query::Syntax => self.syntax_runs,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! An interactive debugger for `hematite debug`, which runs a program in the interpreter and stops it at
+//! breakpoints.
+
+use std::{
+    fmt::Display,
+    io::{self, BufRead, Write},
+};
+
+use hematite_lang::{
+    interpreter::{EvaluationHook, Frame, RuntimeError},
+    source::SourceMap,
+    symbol::Symbol,
+    Location,
+};
+
+const HELP: &str = "Commands:
+  break FILE:LINE, b     stop when a statement on the line is reached
+  delete FILE:LINE, d    remove a breakpoint
+  step, s                run until the next statement, entering calls
+  next, n                run until the next statement in this function
+  continue, c            run until a breakpoint is reached
+  locals                 show the current function's variables
+  print NAME, p          show a variable
+  backtrace, bt          show the functions being run
+  quit, q                stop the program";
+
+/// A line of a source file to stop at.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Breakpoint {
+    file: String,
+    line: usize,
+}
+
+impl Breakpoint {
+    /// Parses `file:line`, where lines count from 1.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (file, line) = text
+            .rsplit_once(':')
+            .ok_or_else(|| format!("Expected a breakpoint like 'main.hematite:3', not '{text}'"))?;
+        match line.parse() {
+            Ok(line) if line > 0 && !file.is_empty() => Ok(Self {
+                file: file.to_string(),
+                line,
+            }),
+            _ => Err(format!("'{line}' isn't a line number")),
+        }
+    }
+
+    /// Whether the breakpoint names `file`, which may be given by its full path or just its end.
+    fn is_in(&self, file: &str) -> bool {
+        file == self.file || file.ends_with(&format!("/{}", self.file))
+    }
+}
+
+impl Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+/// When to next stop the program.
+#[derive(Clone, Copy, Debug)]
+enum Resume {
+    /// At the next statement anywhere.
+    Step,
+    /// At the next statement in a frame no deeper than this many.
+    Next(usize),
+    /// At the next breakpoint.
+    Continue,
+}
+
+pub struct Debugger<'files> {
+    source_map: &'files SourceMap,
+    breakpoints: Vec<Breakpoint>,
+    resume: Resume,
+}
+
+impl<'files> Debugger<'files> {
+    /// A debugger which stops at the first statement when there are no breakpoints, so that some can be set.
+    pub fn new(source_map: &'files SourceMap, breakpoints: Vec<Breakpoint>) -> Self {
+        let resume = if breakpoints.is_empty() {
+            Resume::Step
+        } else {
+            Resume::Continue
+        };
+        Self {
+            source_map,
+            breakpoints,
+            resume,
+        }
+    }
+
+    /// The file name and one-based line of a location.
+    fn position(&self, location: Location) -> (&'files str, usize) {
+        let source_file = &self.source_map[location.file()];
+        let (line, _) = source_file.line_and_column(location.span().start());
+        (source_file.name(), line)
+    }
+
+    fn describe(&self, frame: &Frame) -> String {
+        match frame.location() {
+            Some(location) => {
+                let (file, line) = self.position(location);
+                format!("{} at {file}:{line}", frame.function())
+            }
+            None => format!("{} at an unknown location", frame.function()),
+        }
+    }
+
+    fn should_stop(&self, stack: &[Frame]) -> bool {
+        let at_breakpoint = stack
+            .last()
+            .and_then(Frame::location)
+            .is_some_and(|location| {
+                let (file, line) = self.position(location);
+                self.breakpoints
+                    .iter()
+                    .any(|breakpoint| breakpoint.line == line && breakpoint.is_in(file))
+            });
+        match self.resume {
+            Resume::Step => true,
+            Resume::Next(depth) => stack.len() <= depth || at_breakpoint,
+            Resume::Continue => at_breakpoint,
+        }
+    }
+
+    fn show_stop(&self, frame: &Frame) {
+        println!("Stopped in {}", self.describe(frame));
+        if let Some(location) = frame.location() {
+            let source_file = &self.source_map[location.file()];
+            let line_index = source_file.line_index(location.span().start());
+            println!("{:>5} | {}", line_index + 1, source_file.line(line_index));
+        }
+    }
+
+    /// Runs commands until one resumes the program.
+    fn prompt(&mut self, stack: &[Frame]) -> Result<(), RuntimeError> {
+        let frame = stack.last().expect("A statement is always in a function");
+        let mut input = String::new();
+        loop {
+            print!("(hdb) ");
+            let _ = io::stdout().flush();
+            input.clear();
+            // The end of the input leaves nobody to give commands, so the program is stopped.
+            if io::stdin().lock().read_line(&mut input).unwrap_or(0) == 0 {
+                println!();
+                return Err(RuntimeError::new("Stopped by the debugger"));
+            }
+            let mut words = input.split_whitespace();
+            let command = words.next().unwrap_or("");
+            let argument = words.next();
+            match (command, argument) {
+                ("", _) => {}
+                ("break" | "b", Some(text)) => match Breakpoint::parse(text) {
+                    Ok(breakpoint) => {
+                        println!("Breakpoint set at {breakpoint}");
+                        self.breakpoints.push(breakpoint);
+                    }
+                    Err(error) => println!("{error}"),
+                },
+                ("delete" | "d", Some(text)) => match Breakpoint::parse(text) {
+                    Ok(breakpoint) if self.breakpoints.contains(&breakpoint) => {
+                        self.breakpoints.retain(|existing| *existing != breakpoint);
+                        println!("Deleted the breakpoint at {breakpoint}");
+                    }
+                    Ok(breakpoint) => println!("There is no breakpoint at {breakpoint}"),
+                    Err(error) => println!("{error}"),
+                },
+                ("step" | "s", None) => {
+                    self.resume = Resume::Step;
+                    return Ok(());
+                }
+                ("next" | "n", None) => {
+                    self.resume = Resume::Next(stack.len());
+                    return Ok(());
+                }
+                ("continue" | "c", None) => {
+                    self.resume = Resume::Continue;
+                    return Ok(());
+                }
+                ("locals", None) => {
+                    let variables = frame.variables();
+                    if variables.is_empty() {
+                        println!("No variables have been defined yet");
+                    }
+                    for (name, value) in variables {
+                        println!("{name} = {value}");
+                    }
+                }
+                ("print" | "p", Some(name)) => match frame.variable(Symbol::intern(name)) {
+                    Some(value) => println!("{name} = {value}"),
+                    None => println!("There is no variable called '{name}' here"),
+                },
+                ("backtrace" | "bt", None) => {
+                    for (index, frame) in stack.iter().rev().enumerate() {
+                        println!("#{index} {}", self.describe(frame));
+                    }
+                }
+                ("quit" | "q", None) => return Err(RuntimeError::new("Stopped by the debugger")),
+                ("help" | "h", None) => println!("{HELP}"),
+                _ => println!("Unknown command '{}'; 'help' lists them", input.trim()),
+            }
+        }
+    }
+}
+
+impl EvaluationHook for Debugger<'_> {
+    fn before_statement(&mut self, stack: &[Frame]) -> Result<(), RuntimeError> {
+        if !self.should_stop(stack) {
+            return Ok(());
+        }
+        self.show_stop(stack.last().expect("A statement is always in a function"));
+        self.prompt(stack)
+    }
+}
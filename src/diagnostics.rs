@@ -0,0 +1,728 @@
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::{
+    lexer::Span,
+    source::{SourceFile, SourceMap},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+
+    fn color(&self) -> &'static str {
+        match self {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        }
+    }
+}
+
+/// A span within one of the files being compiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Location {
+    file: usize,
+    span: Span,
+}
+
+impl Location {
+    pub fn new(file: usize, span: Span) -> Self {
+        Self { file, span }
+    }
+
+    pub fn file(&self) -> usize {
+        self.file
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// How sure the compiler is that applying a suggestion gives what the user meant.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Applicability {
+    /// The change is certainly right, so `fix` may apply it without asking.
+    MachineApplicable,
+    /// The change is a guess, such as the closest spelling of an unknown name.
+    MaybeIncorrect,
+}
+
+impl Applicability {
+    fn name(&self) -> &'static str {
+        match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+        }
+    }
+}
+
+/// A change to the source which would fix a diagnostic, in the same file as the diagnostic's location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suggestion {
+    message: String,
+    span: Span,
+    replacement: String,
+    applicability: Applicability,
+}
+
+impl Suggestion {
+    /// Suggests replacing `span` with `replacement`, which is an insertion when `span` is empty.
+    pub fn new(
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    pub fn applicability(&self) -> Applicability {
+        self.applicability
+    }
+}
+
+/// Another place in the source which explains a diagnostic, such as the annotation a value was expected to match.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    location: Location,
+    message: String,
+}
+
+impl Label {
+    pub fn new(location: Location, message: impl Into<String>) -> Self {
+        Self {
+            location,
+            message: message.into(),
+        }
+    }
+
+    pub fn location(&self) -> Location {
+        self.location
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+/// Applies suggestions to the text of the file they refer to.
+///
+/// Suggestions which overlap one applied before them are skipped, as their spans no longer mean the same thing.
+pub fn apply_suggestions<'suggestion>(
+    text: &str,
+    suggestions: impl IntoIterator<Item = &'suggestion Suggestion>,
+) -> String {
+    let mut applied: Vec<&Suggestion> = Vec::new();
+    for suggestion in suggestions {
+        let overlaps = applied.iter().any(|other| {
+            (suggestion.span.start() < other.span.end()
+                && other.span.start() < suggestion.span.end())
+                || suggestion.span == other.span
+        });
+        if !overlaps {
+            applied.push(suggestion);
+        }
+    }
+    // Working from the end keeps the spans of the earlier suggestions valid.
+    applied.sort_by_key(|suggestion| suggestion.span.start());
+    let mut text = text.to_string();
+    for suggestion in applied.iter().rev() {
+        text.replace_range(
+            suggestion.span.start()..suggestion.span.end(),
+            &suggestion.replacement,
+        );
+    }
+    text
+}
+
+/// A message for the user about their program.
+///
+/// The contents are boxed so that the many results which fail with a diagnostic stay small.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic(Box<DiagnosticContents>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct DiagnosticContents {
+    severity: Severity,
+    code: Option<&'static str>,
+    message: String,
+    location: Option<Location>,
+    labels: Vec<Label>,
+    notes: Vec<String>,
+    help: Vec<String>,
+    suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self(Box::new(DiagnosticContents {
+            severity,
+            code: None,
+            message: message.into(),
+            location: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }))
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.0.code = Some(code);
+        self
+    }
+
+    pub fn code(&self) -> Option<&'static str> {
+        self.0.code
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.0.severity
+    }
+
+    pub fn message(&self) -> &str {
+        &self.0.message
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.0.location
+    }
+
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.0.location = Some(location);
+        self
+    }
+
+    /// Points at another place in the source which explains the problem, alongside the main location.
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.0.labels.push(label);
+        self
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        &self.0.labels
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.0.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.0.help.push(help.into());
+        self
+    }
+
+    /// Adds a change to the source which would fix the problem, which is shown as help.
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.0.suggestions.push(suggestion);
+        self
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.0.suggestions
+    }
+}
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// A span to underline in a source excerpt.
+struct Annotation<'message> {
+    span: Span,
+    /// Whether this is the diagnostic's own location, rather than one of its labels.
+    primary: bool,
+    message: Option<&'message str>,
+}
+
+/// Formats diagnostics for a terminal, showing the source they refer to.
+pub struct Renderer<'files> {
+    files: &'files SourceMap,
+    color: bool,
+}
+
+impl<'files> Renderer<'files> {
+    pub fn new(files: &'files SourceMap, color: bool) -> Self {
+        Self { files, color }
+    }
+
+    fn paint(&self, color: &'static str, text: &str) -> String {
+        if self.color {
+            format!("{color}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Writes the lines of `file` which `annotations` are on, underlining each, after a header pointing at the first.
+    fn render_excerpt(
+        &self,
+        output: &mut String,
+        file: &SourceFile,
+        annotations: &[Annotation],
+        arrow: &str,
+        gutter_width: usize,
+        severity: Severity,
+    ) {
+        let gutter = " ".repeat(gutter_width);
+        let first = annotations[0].span.start();
+        let (line_number, column) = file.line_and_column(first);
+        writeln!(
+            output,
+            "{gutter}{} {}:{line_number}:{column}",
+            self.paint(BLUE, arrow),
+            file.name(),
+        )
+        .unwrap();
+        writeln!(output, "{gutter} {}", self.paint(BLUE, "|")).unwrap();
+        let mut line_indices = annotations
+            .iter()
+            .map(|annotation| file.line_index(annotation.span.start()))
+            .collect::<Vec<_>>();
+        line_indices.sort_unstable();
+        line_indices.dedup();
+        for line_index in line_indices {
+            let line = file.line(line_index);
+            let line_start = file.line_start(line_index);
+            writeln!(
+                output,
+                "{} {line}",
+                self.paint(BLUE, &format!("{:>gutter_width$} |", line_index + 1))
+            )
+            .unwrap();
+            for annotation in annotations
+                .iter()
+                .filter(|annotation| file.line_index(annotation.span.start()) == line_index)
+            {
+                let span = annotation.span;
+                let before_span = &line[..(span.start() - line_start).min(line.len())];
+                let span_end_in_line = (span.end() - line_start).min(line.len());
+                let underlined = &line[before_span.len()..span_end_in_line.max(before_span.len())];
+                // Keep tabs so the underline lines up with the source however the terminal renders them.
+                let padding = before_span
+                    .chars()
+                    .map(|character| if character == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                let (marker, color) = if annotation.primary {
+                    ("^", severity.color())
+                } else {
+                    ("-", BLUE)
+                };
+                let underline = marker.repeat(underlined.chars().count().max(1));
+                let underline = match annotation.message {
+                    Some(message) => format!("{underline} {message}"),
+                    None => underline,
+                };
+                writeln!(
+                    output,
+                    "{gutter} {} {padding}{}",
+                    self.paint(BLUE, "|"),
+                    self.paint(color, &underline)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let mut output = String::new();
+        let severity = diagnostic.0.severity;
+        let heading = match diagnostic.0.code {
+            Some(code) => format!("{}[{code}]", severity.name()),
+            None => severity.name().to_string(),
+        };
+        writeln!(
+            output,
+            "{}{}",
+            self.paint(severity.color(), &heading),
+            self.paint(BOLD, &format!(": {}", diagnostic.0.message))
+        )
+        .unwrap();
+        let mut gutter_width = 1;
+        if let Some(location) = diagnostic.0.location {
+            // Labels in the same file as the location are shown in its excerpt, and others in one per file.
+            let mut excerpts = vec![(
+                location.file,
+                vec![Annotation {
+                    span: location.span,
+                    primary: true,
+                    message: None,
+                }],
+            )];
+            for label in &diagnostic.0.labels {
+                let annotation = Annotation {
+                    span: label.location.span,
+                    primary: false,
+                    message: Some(&label.message),
+                };
+                match excerpts
+                    .iter_mut()
+                    .find(|(file, _)| *file == label.location.file)
+                {
+                    Some((_, annotations)) => annotations.push(annotation),
+                    None => excerpts.push((label.location.file, vec![annotation])),
+                }
+            }
+            gutter_width = excerpts
+                .iter()
+                .flat_map(|(file, annotations)| {
+                    let file = &self.files[*file];
+                    annotations
+                        .iter()
+                        .map(|annotation| file.line_index(annotation.span.start()) + 1)
+                })
+                .max()
+                .unwrap_or(1)
+                .to_string()
+                .len();
+            for (index, (file, annotations)) in excerpts.iter().enumerate() {
+                if index > 0 {
+                    writeln!(
+                        output,
+                        "{} {}",
+                        " ".repeat(gutter_width),
+                        self.paint(BLUE, "|")
+                    )
+                    .unwrap();
+                }
+                let arrow = if index == 0 { "-->" } else { ":::" };
+                self.render_excerpt(
+                    &mut output,
+                    &self.files[*file],
+                    annotations,
+                    arrow,
+                    gutter_width,
+                    diagnostic.0.severity,
+                );
+            }
+        }
+        let gutter = " ".repeat(gutter_width);
+        for note in &diagnostic.0.notes {
+            writeln!(output, "{gutter} {} note: {note}", self.paint(BLUE, "=")).unwrap();
+        }
+        for help in &diagnostic.0.help {
+            writeln!(output, "{gutter} {} help: {help}", self.paint(BLUE, "=")).unwrap();
+        }
+        for suggestion in &diagnostic.0.suggestions {
+            writeln!(
+                output,
+                "{gutter} {} help: {}",
+                self.paint(BLUE, "="),
+                suggestion.message
+            )
+            .unwrap();
+        }
+        output
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len() + 2);
+    escaped.push('"');
+    for character in text.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            character if character.is_control() => {
+                write!(escaped, "\\u{:04x}", character as u32).unwrap()
+            }
+            character => escaped.push(character),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+fn json_string_array(strings: &[String]) -> String {
+    let elements = strings
+        .iter()
+        .map(|string| escape_json(string))
+        .collect::<Vec<_>>();
+    format!("[{}]", elements.join(","))
+}
+
+/// Formats diagnostics as single-line JSON objects for tools to consume.
+pub struct JsonRenderer<'files> {
+    files: &'files SourceMap,
+}
+
+impl<'files> JsonRenderer<'files> {
+    pub fn new(files: &'files SourceMap) -> Self {
+        Self { files }
+    }
+
+    /// The fields describing where a location is, without the braces around them, so that a span can add its own.
+    fn render_location_fields(&self, location: &Location) -> String {
+        let file = &self.files[location.file];
+        let (line_start, column_start) = file.line_and_column(location.span.start());
+        let (line_end, column_end) = file.line_and_column(location.span.end());
+        format!(
+            "\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{line_start},\"column_start\":{column_start},\"line_end\":{line_end},\"column_end\":{column_end}",
+            escape_json(file.name()),
+            location.span.start(),
+            location.span.end(),
+        )
+    }
+
+    /// Renders one of a diagnostic's spans: its location, or a label's.
+    fn render_span(&self, location: &Location, primary: bool, label: Option<&str>) -> String {
+        format!(
+            "{{{},\"is_primary\":{primary},\"label\":{}}}",
+            self.render_location_fields(location),
+            label.map_or_else(|| "null".to_string(), escape_json),
+        )
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let code = diagnostic
+            .0
+            .code
+            .map_or_else(|| "null".to_string(), escape_json);
+        // The location comes first, followed by the labels, which say why they are there.
+        let spans =
+            diagnostic
+                .0
+                .location
+                .iter()
+                .map(|location| self.render_span(location, true, None))
+                .chain(
+                    diagnostic.0.labels.iter().map(|label| {
+                        self.render_span(&label.location, false, Some(&label.message))
+                    }),
+                )
+                .collect::<Vec<_>>();
+        // Suggestions are in the file of the diagnostic's location, so they can only be placed if it has one.
+        let suggestions = diagnostic
+            .0
+            .location
+            .iter()
+            .flat_map(|location| {
+                diagnostic.0.suggestions.iter().map(|suggestion| {
+                    format!(
+                        "{{\"message\":{},\"span\":{{{}}},\"replacement\":{},\"applicability\":\"{}\"}}",
+                        escape_json(&suggestion.message),
+                        self.render_location_fields(&Location::new(location.file, suggestion.span)),
+                        escape_json(&suggestion.replacement),
+                        suggestion.applicability.name(),
+                    )
+                })
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "{{\"severity\":\"{}\",\"code\":{code},\"message\":{},\"spans\":[{}],\"notes\":{},\"help\":{},\"suggestions\":[{}]}}\n",
+            diagnostic.0.severity.name(),
+            escape_json(&diagnostic.0.message),
+            spans.join(","),
+            json_string_array(&diagnostic.0.notes),
+            json_string_array(&diagnostic.0.help),
+            suggestions.join(","),
+        )
+    }
+}
+
+/// How many diagnostics a compilation reported, for emitters to summarise once it is over.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DiagnosticCounts {
+    errors: usize,
+    warnings: usize,
+    suppressed_errors: usize,
+}
+
+impl DiagnosticCounts {
+    /// The errors which were emitted, not counting those over the limit.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.warnings
+    }
+
+    /// The errors which weren't emitted because the limit had already been reached.
+    pub fn suppressed_errors(&self) -> usize {
+        self.suppressed_errors
+    }
+
+    pub fn record(&mut self, severity: Severity) {
+        match severity {
+            Severity::Error => self.errors += 1,
+            Severity::Warning => self.warnings += 1,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn suppress(&mut self) {
+        self.suppressed_errors += 1;
+    }
+}
+
+/// `count` followed by `noun`, made plural unless there is exactly one.
+#[cfg(feature = "std")]
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {noun}"),
+        count => format!("{count} {noun}s"),
+    }
+}
+
+/// Somewhere diagnostics are sent as the compiler finds them.
+pub trait DiagnosticEmitter {
+    /// Reports a diagnostic, whose location refers to one of `source_map`'s files.
+    fn emit(&mut self, diagnostic: Diagnostic, source_map: &SourceMap);
+
+    /// Called once every diagnostic has been emitted, for emitters which summarise them.
+    fn finish(&mut self, _counts: &DiagnosticCounts) {}
+}
+
+/// Prints diagnostics to standard error for a person to read.
+#[cfg(feature = "std")]
+pub struct TerminalEmitter {
+    color: bool,
+    emitted_any: bool,
+    /// The codes of the errors emitted so far, which `finish` points to `--explain` for.
+    codes: Vec<&'static str>,
+}
+
+#[cfg(feature = "std")]
+impl TerminalEmitter {
+    pub fn new(color: bool) -> Self {
+        Self {
+            color,
+            emitted_any: false,
+            codes: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl DiagnosticEmitter for TerminalEmitter {
+    fn emit(&mut self, diagnostic: Diagnostic, source_map: &SourceMap) {
+        // A blank line keeps each diagnostic's source excerpt apart from the next.
+        if core::mem::replace(&mut self.emitted_any, true) {
+            eprintln!();
+        }
+        eprint!(
+            "{}",
+            Renderer::new(source_map, self.color).render(&diagnostic)
+        );
+        if let Some(code) = diagnostic.0.code {
+            if !self.codes.contains(&code) {
+                self.codes.push(code);
+            }
+        }
+    }
+
+    fn finish(&mut self, counts: &DiagnosticCounts) {
+        match counts.suppressed_errors() {
+            0 => {}
+            1 => eprintln!("\nToo many errors: 1 more wasn't shown. Raise the limit with `--error-limit`."),
+            suppressed => eprintln!(
+                "\nToo many errors: {suppressed} more weren't shown. Raise the limit with `--error-limit`."
+            ),
+        }
+        match self.codes.as_slice() {
+            [] => {}
+            [code] => {
+                eprintln!("For more information about this error, run with `--explain {code}`.")
+            }
+            codes => eprintln!(
+                "Some errors have detailed explanations: {}. Run with `--explain` followed by a code for more information.",
+                codes.join(", ")
+            ),
+        }
+        // Commands which reported nothing, such as `--explain`, have nothing to count.
+        let errors = counts.errors() + counts.suppressed_errors();
+        if errors > 0 || counts.warnings() > 0 {
+            eprintln!(
+                "{} and {} found.",
+                plural(errors, "error"),
+                plural(counts.warnings(), "warning")
+            );
+        }
+    }
+}
+
+/// Prints diagnostics to standard error as JSON lines, for tools to consume.
+#[cfg(feature = "std")]
+pub struct JsonEmitter;
+
+#[cfg(feature = "std")]
+impl DiagnosticEmitter for JsonEmitter {
+    fn emit(&mut self, diagnostic: Diagnostic, source_map: &SourceMap) {
+        eprint!("{}", JsonRenderer::new(source_map).render(&diagnostic));
+    }
+}
+
+/// Keeps diagnostics in memory, for callers such as the language server which present them themselves.
+#[derive(Debug, Default)]
+pub struct DiagnosticCollector {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl DiagnosticEmitter for DiagnosticCollector {
+    fn emit(&mut self, diagnostic: Diagnostic, _source_map: &SourceMap) {
+        self.diagnostics.push(diagnostic);
+    }
+}
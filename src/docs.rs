@@ -0,0 +1,127 @@
+//! Documentation pages generated from a program's doc comments, for `hematite doc`.
+
+use std::fmt::Write;
+
+use hematite_lang::{
+    ast::AstNode,
+    formatter::{self, Style},
+    semantic::{self, CollectedFunction},
+    source::SourceMap,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocFormat {
+    Markdown,
+    Html,
+}
+
+impl DocFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocFormat::Markdown => "md",
+            DocFormat::Html => "html",
+        }
+    }
+}
+
+/// Where a function is defined, as `file:line`.
+fn definition_site(collected: &CollectedFunction, source_map: &SourceMap) -> String {
+    let location = collected.location();
+    let source_file = &source_map[location.file()];
+    let (line, _) = source_file.line_and_column(location.span().start());
+    format!("{}:{line}", source_file.name())
+}
+
+/// Doc comment lines grouped into paragraphs, which are separated by blank lines.
+fn paragraphs(documentation: &[String]) -> Vec<String> {
+    documentation
+        .split(|line| line.is_empty())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(|paragraph| paragraph.join(" "))
+        .collect()
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_markdown(functions: &[CollectedFunction], source_map: &SourceMap) -> String {
+    let mut output = String::new();
+    let mut current_module = None;
+    for collected in functions {
+        if current_module != Some(collected.module()) {
+            current_module = Some(collected.module());
+            writeln!(output, "# Module `{}`\n", collected.module()).unwrap();
+        }
+        let function = collected.function();
+        writeln!(output, "## `{}`\n", function.name()).unwrap();
+        writeln!(
+            output,
+            "```hematite\n{}\n```\n",
+            formatter::signature(function, &Style::default())
+        )
+        .unwrap();
+        for line in function.documentation() {
+            writeln!(output, "{line}").unwrap();
+        }
+        if !function.documentation().is_empty() {
+            writeln!(output).unwrap();
+        }
+        writeln!(
+            output,
+            "*Defined at {}*\n",
+            definition_site(collected, source_map)
+        )
+        .unwrap();
+    }
+    output
+}
+
+fn render_html(functions: &[CollectedFunction], source_map: &SourceMap) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Documentation</title>\n</head>\n<body>\n",
+    );
+    let mut current_module = None;
+    for collected in functions {
+        if current_module != Some(collected.module()) {
+            current_module = Some(collected.module());
+            writeln!(
+                output,
+                "<h1>Module <code>{}</code></h1>",
+                escape_html(collected.module())
+            )
+            .unwrap();
+        }
+        let function = collected.function();
+        let name = escape_html(function.name().as_str());
+        writeln!(
+            output,
+            "<h2 id=\"{name}\"><code>{name}</code></h2>\n<pre><code>{}</code></pre>",
+            escape_html(&formatter::signature(function, &Style::default()))
+        )
+        .unwrap();
+        for paragraph in paragraphs(function.documentation()) {
+            writeln!(output, "<p>{}</p>", escape_html(&paragraph)).unwrap();
+        }
+        writeln!(
+            output,
+            "<p><em>Defined at {}</em></p>",
+            escape_html(&definition_site(collected, source_map))
+        )
+        .unwrap();
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// Documents every top-level function of `program`, grouped by module.
+pub fn generate(program: &dyn AstNode, source_map: &SourceMap, format: DocFormat) -> String {
+    let functions = semantic::collect_module_functions(program);
+    match format {
+        DocFormat::Markdown => render_markdown(&functions, source_map),
+        DocFormat::Html => render_html(&functions, source_map),
+    }
+}
@@ -0,0 +1,684 @@
+//! Stable codes identifying each kind of diagnostic, with longer explanations for `--explain`.
+//!
+//! Codes are grouped by the stage which reports them:
+//! - E00xx: lexing and parsing
+//! - E01xx: types
+//! - E02xx: names
+//! - E03xx: the program's entry point
+//! - W00xx: warnings, which don't stop a program from compiling
+//!
+//! Codes must never be reused for a different error, even if the original is removed.
+
+pub const UNEXPECTED_TOKEN: &str = "E0001";
+pub const UNEXPECTED_END: &str = "E0002";
+pub const INVALID_TOKEN: &str = "E0003";
+pub const UNSTABLE_FEATURE: &str = "E0004";
+pub const NESTING_LIMIT: &str = "E0005";
+pub const FORMAT_ARGUMENT_TYPE: &str = "E0101";
+pub const NOT_CONSTANT: &str = "E0102";
+pub const COLLECTION_TYPE: &str = "E0103";
+pub const NOT_INDEXABLE: &str = "E0104";
+pub const MAP_KEY_TYPE: &str = "E0105";
+pub const OPTION_TYPE: &str = "E0106";
+pub const RESULT_TYPE: &str = "E0107";
+pub const ERROR_PROPAGATION: &str = "E0108";
+pub const STRING_OPERATION: &str = "E0109";
+pub const CHAR_CONVERSION: &str = "E0110";
+pub const UNKNOWN_METHOD: &str = "E0111";
+pub const METHOD_ARGUMENT_COUNT: &str = "E0112";
+pub const ENUM_REPRESENTATION: &str = "E0113";
+pub const DISCRIMINANT_RANGE: &str = "E0114";
+pub const DUPLICATE_DISCRIMINANT: &str = "E0115";
+pub const DUPLICATE_FUNCTION: &str = "E0201";
+pub const UNKNOWN_ATTRIBUTE: &str = "E0202";
+pub const UNKNOWN_MACRO: &str = "E0203";
+pub const MACRO_RECURSION_LIMIT: &str = "E0204";
+pub const MACRO_ARGUMENT_COUNT: &str = "E0205";
+pub const FORMAT_STRING: &str = "E0206";
+pub const INCLUDE_FAILED: &str = "E0207";
+pub const STATIC_ASSERTION_FAILED: &str = "E0208";
+pub const ENVIRONMENT_VARIABLE: &str = "E0209";
+pub const RUNTIME_UNAVAILABLE: &str = "E0210";
+pub const DUPLICATE_VARIANT: &str = "E0211";
+pub const MISSING_MAIN: &str = "E0301";
+pub const MAIN_HAS_PARAMETERS: &str = "E0302";
+pub const MAIN_RETURN_TYPE: &str = "E0303";
+pub const TEST_SIGNATURE: &str = "E0304";
+pub const MISSING_ENTRY_POINT: &str = "E0305";
+pub const ENTRY_POINT_HAS_PARAMETERS: &str = "E0306";
+pub const UNUSED_VALUE: &str = "W0001";
+pub const INVALID_UTF8: &str = "W0002";
+
+const EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        UNEXPECTED_TOKEN,
+        "A token appeared somewhere the grammar doesn't allow it.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let x: i32 = ;
+    }
+
+The initialiser of `x` is missing, so the parser found `;` where it expected
+an expression. Check the highlighted token and the one before it for typos or
+missing pieces.",
+    ),
+    (
+        UNEXPECTED_END,
+        "The file ended in the middle of an item.
+
+Erroneous example:
+
+    function main() -> i32 {
+        0
+
+The closing `}` of `main` is missing. This is usually caused by an unbalanced
+brace or parenthesis somewhere earlier in the file.",
+    ),
+    (
+        INVALID_TOKEN,
+        "The source contains characters which don't form any token.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let price: i32 = $5;
+    }
+
+`$` isn't part of any Hematite token. Remove it, or put it inside a string
+literal if it is meant to be text.",
+    ),
+    (
+        UNSTABLE_FEATURE,
+        "The program uses syntax which is still experimental.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let size: uptr = sizeof!(i64);
+        0
+    }
+
+Experimental features may change or be removed, so they have to be enabled
+explicitly. The error says which feature is needed; enable it with
+`--enable-feature`, for example `--enable-feature type-intrinsics`.",
+    ),
+    (
+        NESTING_LIMIT,
+        "An expression is nested inside too many parentheses.
+
+Erroneous example:
+
+    function main() -> i32 {
+        ((((((((((((((((((((0))))))))))))))))))))
+    }
+
+(with a few hundred more pairs of parentheses). The parser handles each level
+of nesting with its own stack frame, so it stops at a fixed depth rather than
+risk running out of stack. Remove the redundant parentheses, or break the
+expression up using variables.",
+    ),
+    (
+        FORMAT_ARGUMENT_TYPE,
+        "A format string asks for an argument to be presented in a way its type
+doesn't support.
+
+Erroneous example:
+
+    function main() -> i32 {
+        println!(\"{:x}\", format!(\"ten\"));
+        0
+    }
+
+`{:x}`, `{:X}`, `{:b}` and `{:o}` print integers in another base, so they
+can't be used for strings. Use `{}` instead.",
+    ),
+    (
+        NOT_CONSTANT,
+        "An expression which must be evaluated at compile time depends on
+something only known when the program runs.
+
+Erroneous example:
+
+    static_assert!(format!(\"{}\", 1), \"formatting is constant\");
+
+Conditions of `static_assert!` and enum discriminants are evaluated by the
+compiler, so they can only use integer literals and compile-time intrinsics
+such as `sizeof!`, and must produce an integer.",
+    ),
+    (
+        COLLECTION_TYPE,
+        "A list or map literal's entries don't all have the same type, or a value
+isn't the type it is stored or returned as.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let names: list<string> = [1, 2];
+        0
+    }
+
+Every element of a list has the list's element type, and every key and value
+of a map has the map's key and value types. Integer literals fit any integer
+type, so `[1, 2]` can be a `list<u8>` or a `list<i64>`, but not a
+`list<string>`. Other values don't convert implicitly either: an integer can't
+be stored as a `string`, nor a string as an `i32`.",
+    ),
+    (
+        NOT_INDEXABLE,
+        "Something other than a list, map or string was indexed, or the index has
+the wrong type.
+
+Erroneous example:
+
+    function main() -> i32 {
+        [1: 2][format!(\"one\")]
+    }
+
+Lists are indexed with an integer, counting from 0, and maps with a value of
+their key type. Strings are indexed by byte, giving the `u8` at that offset in
+their UTF-8 encoding; use `chars!` to get at their characters instead.",
+    ),
+    (
+        MAP_KEY_TYPE,
+        "A map's key type can't be compared.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let ages: map<string, u8> = [:];
+        0
+    }
+
+The runtime library finds keys by comparing their bytes, so only integers,
+`bool` and `char` can be keys for now.",
+    ),
+    (
+        OPTION_TYPE,
+        "An option was used where its value is expected, or something which isn't
+an option was used as one.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let count: i32 = some(3);
+        count
+    }
+
+An `option<T>` may be `none`, so it is never used as a `T` implicitly. Say
+what happens when there is no value by unwrapping it, either with `unwrap!`,
+which panics on `none`, or with `unwrap_or!`, which uses a default instead:
+
+    function main() -> i32 {
+        let count: i32 = unwrap_or!(some(3), 0);
+        count
+    }
+
+Likewise, a value is only an option once it is wrapped in `some(...)`.",
+    ),
+    (
+        RESULT_TYPE,
+        "A result was used where its value is expected, or something which isn't
+a result was used as one.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let count: i32 = ok(3);
+        count
+    }
+
+A `result<T, E>` may hold an error instead of a `T`, so it is never used as a
+`T` implicitly. Unwrap it with `unwrap!` or `unwrap_or!`, as with options, or
+pass the error on to the caller with `?`:
+
+    function main() -> i32 {
+        let count: i32 = unwrap_or!(ok(3), 0);
+        count
+    }
+
+The value in an `ok(...)` or `err(...)` must also match the result's types.",
+    ),
+    (
+        ERROR_PROPAGATION,
+        "`?` was used where the error it passes on can't be returned.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let count: i32 = ok(3)?;
+        count
+    }
+
+`?` returns the error in a result from the enclosing function, so that
+function must return a `result` whose error type can hold it. `main` returns
+an `i32`, so either handle the error with `unwrap!` or `unwrap_or!`, or move
+the code into a function which returns a result:
+
+    function count() -> result<i32, string> {
+        let count: i32 = ok(3)?;
+        ok(count)
+    }",
+    ),
+    (
+        STRING_OPERATION,
+        "A string was used with an operation which doesn't work on strings, or a
+string operation was given something else.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let name: string = format!(\"hematite\") - 1;
+        0
+    }
+
+The only operator strings have is `+`, which joins two strings together. The
+other operations are macros: `len!` counts a string's bytes, `slice!` takes
+the bytes between two offsets, `chars!` makes a list of its characters,
+`compare!` orders two strings and `parse_int!` reads a decimal integer:
+
+    function main() -> i32 {
+        let name: string = format!(\"hematite\") + format!(\"-lang\");
+        let length: uptr = len!(name);
+        0
+    }",
+    ),
+    (
+        CHAR_CONVERSION,
+        "A char was used as a string or an integer, or the other way around.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let letter: char = 104;
+        0
+    }
+
+A `char` is a Unicode scalar value rather than a number or a string, so it
+never converts implicitly. `from_char_code!` turns a code point into an
+`option<char>`, which is `none` for numbers which aren't scalar values, and
+`char_code!` goes back the other way. `to_string!` makes a string of one char,
+and `chars!` splits a string into its chars:
+
+    function main() -> i32 {
+        let letter: char = unwrap!(from_char_code!(104));
+        let code: u32 = char_code!(letter);
+        0
+    }",
+    ),
+    (
+        UNKNOWN_METHOD,
+        "A method was called which the receiver's type doesn't have.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let size: uptr = format!(\"hematite\").size();
+        0
+    }
+
+Methods are looked up by the type of the value they are called on. Strings
+have `length`, `slice`, `chars`, `compare` and `parse_int`, chars have `code`
+and `to_string`, lists and maps have `length`, and signed integers have `abs`.
+Options and results have no methods, so unwrap them first:
+
+    function main() -> i32 {
+        let size: uptr = format!(\"hematite\").length();
+        0
+    }",
+    ),
+    (
+        METHOD_ARGUMENT_COUNT,
+        "A method was given the wrong number of arguments.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let part: string = format!(\"hematite\").slice(0);
+        0
+    }
+
+The value a method is called on is passed automatically, so only the others
+are written in the parentheses. `slice` needs the start and end of the bytes
+to keep:
+
+    function main() -> i32 {
+        let part: string = format!(\"hematite\").slice(0, 4);
+        0
+    }",
+    ),
+    (
+        ENUM_REPRESENTATION,
+        "An enum is represented by a type which isn't an integer.
+
+Erroneous example:
+
+    enum Colour: string {
+        Red,
+        Green,
+    }
+
+Each variant of an enum is stored as its discriminant, which is an integer.
+The type after the colon says which integer type, and must be one of the
+integer types such as `u8` or `i64`. Without one, it is `i32`:
+
+    enum Colour: u8 {
+        Red,
+        Green,
+    }",
+    ),
+    (
+        DISCRIMINANT_RANGE,
+        "An enum variant's discriminant doesn't fit in the enum's representation.
+
+Erroneous example:
+
+    enum Flags: u8 {
+        Low = 1,
+        High = 256,
+    }
+
+Discriminants are stored as the enum's representation, so they must be in
+its range. A variant without a discriminant is one more than the variant
+before it, which can also overflow. Use a wider representation, or a
+smaller discriminant:
+
+    enum Flags: u16 {
+        Low = 1,
+        High = 256,
+    }",
+    ),
+    (
+        DUPLICATE_DISCRIMINANT,
+        "Two variants of an enum have the same discriminant.
+
+Erroneous example:
+
+    enum Flags: u8 {
+        Read = 1,
+        Write = 1,
+    }
+
+A discriminant identifies its variant, so each one must be different. Note
+that a variant without a discriminant is one more than the variant before
+it, which can collide with a later explicit discriminant:
+
+    enum Flags: u8 {
+        Read = 1,
+        Write = 2,
+    }",
+    ),
+    (
+        DUPLICATE_FUNCTION,
+        "Two functions have the same name.
+
+Erroneous example:
+
+    function add(a: i32, b: i32) -> i32 { 0 }
+    function add(a: i64, b: i64) -> i64 { 0 }
+
+Functions share a single namespace across every file in the program, so a
+name can only be defined once. Rename one of the functions.",
+    ),
+    (
+        UNKNOWN_ATTRIBUTE,
+        "A function has an attribute the compiler doesn't know.
+
+Erroneous example:
+
+    @tset
+    function adds_numbers() -> i32 {
+        0
+    }
+
+The attributes are `@test`, which marks a function to be run by
+`hematite test`, and `@export`, which declares a function in the C header
+written by `--emit c-header`. Check the attribute's spelling.",
+    ),
+    (
+        UNKNOWN_MACRO,
+        "A macro was used which doesn't exist.
+
+Erroneous example:
+
+    function main() -> i32 {
+        let size: uptr = sizof!(i32);
+        0
+    }
+
+Macros are expanded before the rest of the program is checked, so every
+`name!(...)` must refer to a macro the compiler knows. Check the macro's
+spelling.
+
+The prelude's macros (`assert!`, `assert_eq!`, `format!`, `print!`,
+`println!` and `panic!`) are only known when the prelude is included, which
+`--no-prelude` turns off.",
+    ),
+    (
+        MACRO_RECURSION_LIMIT,
+        "Macros expanded into other macros too many times.
+
+A macro's expansion can use further macros, but only up to a fixed depth, so
+that a macro which (directly or indirectly) expands to itself is reported
+rather than expanding forever. Look for a cycle in the macros used at the
+highlighted invocation.",
+    ),
+    (
+        MACRO_ARGUMENT_COUNT,
+        "A macro was given the wrong number of arguments.
+
+Erroneous example:
+
+    @test
+    function one_is_one() -> i32 {
+        assert_eq!(1);
+        0
+    }
+
+`assert_eq!` compares two values, so it needs both of them. Arguments are
+separated by commas which aren't inside parentheses.",
+    ),
+    (
+        FORMAT_STRING,
+        "A format string is malformed or doesn't match its arguments.
+
+Erroneous example:
+
+    function main() -> i32 {
+        println!(\"{} and {}\", 1);
+        0
+    }
+
+The string has two `{}` placeholders but there is only one argument. Each
+placeholder takes the next argument, or the one it names: `{0}` is the first
+argument and `{width}` is the argument written as `width = ...`. Every
+argument must be used, and `{{` and `}}` stand for literal braces.",
+    ),
+    (
+        INCLUDE_FAILED,
+        "A file given to `include!` or `include_str!` couldn't be read.
+
+Erroneous example:
+
+    include!(\"helpers.hematite\");
+
+Paths are relative to the directory of the file containing the macro, not
+the directory the compiler is run from. Check that the file exists at the
+path shown in the error and that it can be read.",
+    ),
+    (
+        STATIC_ASSERTION_FAILED,
+        "A `static_assert!` condition evaluated to zero.
+
+Erroneous example:
+
+    static_assert!(0, \"this configuration isn't supported\");
+
+The condition is evaluated for the target being compiled for, so conditions
+using intrinsics such as `sizeof!` can pass on one target and fail on another.
+The error shows the message given to the macro, or the condition if there
+isn't one.",
+    ),
+    (
+        ENVIRONMENT_VARIABLE,
+        "An environment variable used with `env!` isn't set.
+
+Erroneous example:
+
+    function main() -> i32 {
+        println!(\"built by {}\", env!(\"BUILDER\"));
+        0
+    }
+
+`env!` is evaluated while compiling, so the variable must be set in the
+environment the compiler runs in, not the one the program runs in. Set it
+before compiling, for example with `BUILDER=ci hematite build`. If the program
+can do without the variable, `option_env!(\"BUILDER\")` gives `none` when it
+isn't set.",
+    ),
+    (
+        RUNTIME_UNAVAILABLE,
+        "A macro which needs the runtime library was used with `--freestanding`.
+
+Erroneous example:
+
+    function start() -> i32 {
+        println!(\"booting\");
+        0
+    }
+
+Freestanding programs aren't linked with the runtime library, so there is
+nothing to print, allocate strings or panic with. `format!`, `print!`,
+`println!`, `panic!`, `assert!` and `assert_eq!` all rely on it. Write to
+the hardware directly instead, or build without `--freestanding`.",
+    ),
+    (
+        DUPLICATE_VARIANT,
+        "An enum has two variants with the same name.
+
+Erroneous example:
+
+    enum Direction {
+        Up,
+        Down,
+        Up,
+    }
+
+Variants are told apart by their names, so each name can only be used once
+in an enum. Rename or remove one of the variants.",
+    ),
+    (
+        MISSING_MAIN,
+        "The program has no `main` function.
+
+Every program starts by calling `main`, so one of the files being compiled
+must define it:
+
+    function main() -> i32 {
+        0
+    }",
+    ),
+    (
+        MAIN_HAS_PARAMETERS,
+        "The `main` function takes parameters.
+
+Erroneous example:
+
+    function main(count: i32) -> i32 {
+        0
+    }
+
+Nothing passes arguments to `main`, so it must not declare any parameters.",
+    ),
+    (
+        MAIN_RETURN_TYPE,
+        "The `main` function doesn't return `i32`.
+
+Erroneous example:
+
+    function main() -> u8 {
+        0
+    }
+
+The value returned from `main` becomes the program's exit code, so it must
+be an `i32`. Return 0 to indicate success.",
+    ),
+    (
+        TEST_SIGNATURE,
+        "A test function takes parameters or doesn't return `i32`.
+
+Erroneous example:
+
+    @test
+    function adds_numbers(a: i32) -> bool {
+        0
+    }
+
+`hematite test` runs each test like a program's `main`: with no arguments,
+treating a returned 0 as a pass and anything else as a failure. Test
+functions must have the same signature as `main`.",
+    ),
+    (
+        MISSING_ENTRY_POINT,
+        "A freestanding program doesn't define the function named by `--entry`.
+
+Freestanding programs have no `main`; execution starts at the function
+given with `--entry`, so one of the files being compiled must define it:
+
+    function start() -> i32 {
+        0
+    }",
+    ),
+    (
+        ENTRY_POINT_HAS_PARAMETERS,
+        "A freestanding program's entry point takes parameters.
+
+Erroneous example:
+
+    function start(magic: u32) -> i32 {
+        0
+    }
+
+Without an operating system, nothing passes arguments to the entry point, so
+it must not declare any parameters.",
+    ),
+    (
+        UNUSED_VALUE,
+        "A statement's value is thrown away by its semicolon.
+
+Example:
+
+    function main() -> i32 {
+        format!(\"text\").length();
+        0
+    }
+
+The length is worked out and then discarded, which usually means that the
+call was meant to be used for something. If the value really isn't needed,
+assign it to `_` to say so:
+
+    let _: uptr = format!(\"text\").length();",
+    ),
+    (
+        INVALID_UTF8,
+        "A source file contains bytes which aren't valid UTF-8.
+
+Source files must be encoded as UTF-8. Any bytes which aren't are replaced
+with the replacement character (U+FFFD) so that the rest of the file can
+still be compiled, but this usually means that the file was saved in another
+encoding such as Latin-1 or UTF-16. Replacement characters are only allowed
+in string literals and doc comments, so an error will follow if one ends up
+anywhere else.
+
+Re-save the file as UTF-8 to fix it.",
+    ),
+];
+
+/// The long-form explanation of an error code, if it exists.
+pub fn explain(code: &str) -> Option<&'static str> {
+    EXPLANATIONS
+        .iter()
+        .find(|(known_code, _)| known_code.eq_ignore_ascii_case(code))
+        .map(|(_, explanation)| *explanation)
+}
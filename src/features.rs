@@ -0,0 +1,66 @@
+//! Versions of the language, and the unstable features programs can opt into.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "std")]
+use clap::ValueEnum;
+
+/// A release of the language; programs written for one keep compiling under it even as the language changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "std", derive(ValueEnum))]
+pub enum LanguageVersion {
+    #[default]
+    #[cfg_attr(feature = "std", value(name = "2023"))]
+    V2023,
+}
+
+/// Syntax which is still experimental, and so is only accepted when explicitly enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(ValueEnum))]
+pub enum Feature {
+    /// `sizeof!` and `alignof!`, whose results depend on layouts which aren't settled yet.
+    TypeIntrinsics,
+}
+
+impl Feature {
+    pub const ALL: &'static [Feature] = &[Feature::TypeIntrinsics];
+
+    /// The name used for the feature on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::TypeIntrinsics => "type-intrinsics",
+        }
+    }
+
+    /// The first language version in which the feature is available without being enabled.
+    fn stable_since(&self) -> Option<LanguageVersion> {
+        match self {
+            Feature::TypeIntrinsics => None,
+        }
+    }
+}
+
+/// The features a program may use.
+#[derive(Clone, Debug, Default)]
+pub struct Features {
+    version: LanguageVersion,
+    enabled: Vec<Feature>,
+}
+
+impl Features {
+    pub fn new(version: LanguageVersion, enabled: Vec<Feature>) -> Self {
+        Self { version, enabled }
+    }
+
+    /// Every feature, for tools like the formatter which should accept anything that parses.
+    pub fn all() -> Self {
+        Self::new(LanguageVersion::default(), Feature::ALL.to_vec())
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.enabled.contains(&feature)
+            || feature
+                .stable_since()
+                .is_some_and(|version| version <= self.version)
+    }
+}
@@ -0,0 +1,120 @@
+//! The mini-language of `format!` templates: text with `{}` placeholders which arguments are substituted into.
+//!
+//! A placeholder names its argument and how to present it: `{}` takes the next positional argument, `{1}` a
+//! positional argument by index and `{name}` a named argument (`name = value`). Any of them may be followed by
+//! `:x`, `:X`, `:b` or `:o` to print an integer in hexadecimal, binary or octal. `{{` and `}}` are literal braces.
+
+use crate::ast::FormatSpec;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// Which argument a placeholder refers to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgumentReference {
+    /// `{}`, the positional argument after the one the previous `{}` used.
+    Next,
+    /// `{0}`
+    Index(usize),
+    /// `{name}`
+    Name(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Piece {
+    Text(String),
+    Placeholder {
+        argument: ArgumentReference,
+        spec: FormatSpec,
+    },
+}
+
+/// Resolves the escape sequences in a string literal as it appears in the source, such as `\n`.
+pub fn unescape(literal: &str) -> Result<String, String> {
+    let mut result = String::new();
+    let mut characters = literal.chars();
+    while let Some(character) = characters.next() {
+        if character != '\\' {
+            result.push(character);
+            continue;
+        }
+        result.push(match characters.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some(other) => return Err(format!("unknown escape sequence `\\{other}`")),
+            None => return Err("the string ends in the middle of an escape sequence".to_string()),
+        });
+    }
+    Ok(result)
+}
+
+fn parse_placeholder(contents: &str) -> Result<Piece, String> {
+    let (reference, spec) = contents.split_once(':').unwrap_or((contents, ""));
+    let argument = if reference.is_empty() {
+        ArgumentReference::Next
+    } else if let Ok(index) = reference.parse() {
+        ArgumentReference::Index(index)
+    } else if reference.starts_with(|character: char| character.is_alphabetic() || character == '_')
+        && reference
+            .chars()
+            .all(|character| character.is_alphanumeric() || character == '_')
+    {
+        ArgumentReference::Name(reference.to_string())
+    } else {
+        return Err(format!("`{reference}` isn't an argument index or name"));
+    };
+    let spec = match spec {
+        "" => FormatSpec::Display,
+        "x" => FormatSpec::LowerHex,
+        "X" => FormatSpec::UpperHex,
+        "b" => FormatSpec::Binary,
+        "o" => FormatSpec::Octal,
+        _ => return Err(format!("unknown format specifier `{spec}`")),
+    };
+    Ok(Piece::Placeholder { argument, spec })
+}
+
+/// Splits an unescaped template into its text and placeholders.
+pub fn parse(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut characters = template.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '{' if characters.peek() == Some(&'{') => {
+                characters.next();
+                text.push('{');
+            }
+            '}' if characters.peek() == Some(&'}') => {
+                characters.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut contents = String::new();
+                loop {
+                    match characters.next() {
+                        Some('}') => break,
+                        Some('{') | None => return Err("unterminated placeholder".to_string()),
+                        Some(character) => contents.push(character),
+                    }
+                }
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(core::mem::take(&mut text)));
+                }
+                pieces.push(parse_placeholder(&contents)?);
+            }
+            '}' => return Err("unmatched `}`; write `}}` for a literal brace".to_string()),
+            _ => text.push(character),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
@@ -0,0 +1,488 @@
+use crate::{
+    ast::{
+        Assertion, AssertionKind, AstNode, AstVisitor, BinaryOperation, BinaryOperator,
+        EnumDefinition, EnumVariant, Format, FormatSegment, FormatSpec, FunctionDefinition,
+        IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral, MethodCall, Module,
+        OptionLiteral, ParameterDeclaration, Propagate, ResultLiteral, ResultVariant, RuntimeCall,
+        RuntimeFunction, StaticAssertion, Type, TypeIntrinsic, TypeIntrinsicKind, Unwrap,
+        VariableDefinition,
+    },
+    lexer::Token,
+    parser::{binary_precedence, Associativity, BinaryPrecedence},
+    query::NodeKind,
+};
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+/// The choices `hematite fmt` makes about how code is laid out.
+#[derive(Clone, Debug)]
+pub struct Style {
+    /// The number of spaces to indent each level of nesting by.
+    indent_width: usize,
+    /// The length lines are kept within where the layout allows it.
+    max_width: usize,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self::new(4, 100)
+    }
+}
+
+impl Style {
+    pub fn new(indent_width: usize, max_width: usize) -> Self {
+        Self {
+            indent_width,
+            max_width,
+        }
+    }
+}
+
+/// Renders a tree back into source code in the canonical style.
+struct Printer<'style> {
+    style: &'style Style,
+    /// How many blocks deep the node being printed is, where 0 is the top level of a file.
+    depth: usize,
+    result: Option<String>,
+    /// The precedence of the operator at the root of `result`, if it is a binary operation.
+    result_precedence: Option<BinaryPrecedence>,
+}
+
+impl Printer<'_> {
+    fn render(&mut self, node: &dyn AstNode) -> String {
+        self.render_with_precedence(node).0
+    }
+
+    fn render_with_precedence(&mut self, node: &dyn AstNode) -> (String, Option<BinaryPrecedence>) {
+        node.apply(self);
+        let source = self
+            .result
+            .take()
+            .expect("Every node should produce some source code");
+        (source, self.result_precedence.take())
+    }
+
+    /// Renders an operand of a binary operator, in parentheses if it would otherwise be grouped differently.
+    fn render_operand(
+        &mut self,
+        operand: &dyn AstNode,
+        operator: BinaryPrecedence,
+        side: Associativity,
+    ) -> String {
+        let (source, precedence) = self.render_with_precedence(operand);
+        let needs_parentheses = precedence.is_some_and(|precedence| {
+            precedence.precedence < operator.precedence
+                || (precedence.precedence == operator.precedence && side != operator.associativity)
+        });
+        if needs_parentheses {
+            format!("({source})")
+        } else {
+            source
+        }
+    }
+
+    fn indentation(&self, depth: usize) -> String {
+        " ".repeat(depth * self.style.indent_width)
+    }
+
+    /// A doc comment, with each line followed by the indentation of the item it documents.
+    fn documentation(&self, lines: &[String]) -> String {
+        let indentation = self.indentation(self.depth);
+        lines
+            .iter()
+            .map(|line| {
+                if line.is_empty() {
+                    format!("///\n{indentation}")
+                } else {
+                    format!("/// {line}\n{indentation}")
+                }
+            })
+            .collect()
+    }
+
+    /// A function's name, parameters and return type, on one line if they fit.
+    fn signature(&mut self, function: &FunctionDefinition) -> String {
+        let parameters = function
+            .parameters()
+            .iter()
+            .map(|parameter| self.render(parameter.as_ref()))
+            .collect::<Vec<_>>();
+        let return_type = self.render(function.return_type());
+        let indentation = self.indentation(self.depth);
+        let signature = format!(
+            "function {}({}) -> {return_type}",
+            function.name(),
+            parameters.join(", ")
+        );
+        // Parameters which don't fit on one line (with the opening brace) get a line each.
+        if indentation.len() + signature.len() + 2 <= self.style.max_width || parameters.is_empty()
+        {
+            return signature;
+        }
+        let parameter_indentation = self.indentation(self.depth + 1);
+        let parameter_lines = parameters
+            .iter()
+            .map(|parameter| format!("{parameter_indentation}{parameter},\n"))
+            .collect::<String>();
+        format!(
+            "function {}(\n{parameter_lines}{indentation}) -> {return_type}",
+            function.name()
+        )
+    }
+}
+
+/// How a type is written in source code, such as `i32` or `map<u8, list<string>>`.
+pub fn type_name(type_value: &Type) -> String {
+    let keyword = match type_value {
+        Type::I8 => "i8",
+        Type::I16 => "i16",
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::Iptr => "iptr",
+        Type::U8 => "u8",
+        Type::U16 => "u16",
+        Type::U32 => "u32",
+        Type::U64 => "u64",
+        Type::Uptr => "uptr",
+        Type::F32 => "f32",
+        Type::F64 => "f64",
+        Type::Bool => "bool",
+        Type::Char => "char",
+        Type::String => "string",
+        Type::List(element_type) => return format!("list<{}>", type_name(element_type)),
+        Type::Map(key_type, value_type) => {
+            return format!("map<{}, {}>", type_name(key_type), type_name(value_type))
+        }
+        Type::Option(value_type) => return format!("option<{}>", type_name(value_type)),
+        Type::Result(value_type, error_type) => {
+            return format!(
+                "result<{}, {}>",
+                type_name(value_type),
+                type_name(error_type)
+            )
+        }
+    };
+    keyword.to_string()
+}
+
+impl AstVisitor for Printer<'_> {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        let items = list
+            .iter()
+            .map(|item| {
+                format!(
+                    "{}{}",
+                    self.indentation(self.depth),
+                    self.render(item.as_ref())
+                )
+            })
+            .collect::<Vec<_>>();
+        // Top-level items are separated by a blank line; statements just by a line break.
+        let separator = if self.depth == 0 { "\n\n" } else { "\n" };
+        self.result = Some(items.join(separator));
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        let mutable = if variable.mutable() { "mut " } else { "" };
+        self.result = Some(format!(
+            "let {mutable}{}: {} = {};",
+            variable.name(),
+            self.render(variable.variable_type()),
+            self.render(variable.value())
+        ));
+    }
+    fn visit_type(&mut self, type_value: &Type) {
+        self.result = Some(type_name(type_value));
+    }
+    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration) {
+        self.result = Some(format!(
+            "{}: {}",
+            parameter.name(),
+            self.render(parameter.parameter_type())
+        ));
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        let indentation = self.indentation(self.depth);
+        let documentation = self.documentation(function.documentation());
+        let attributes = function
+            .attributes()
+            .iter()
+            .map(|(attribute, _)| format!("@{attribute}\n{indentation}"))
+            .collect::<String>();
+        let header = format!("{documentation}{attributes}{} {{", self.signature(function));
+        self.depth += 1;
+        let body = self.render(function.body());
+        self.depth -= 1;
+        self.result = Some(if body.is_empty() {
+            format!("{header}}}")
+        } else {
+            format!("{header}\n{body}\n{indentation}}}")
+        });
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.result = Some(format!("{};", self.render(ignore_value.value())));
+    }
+    fn visit_integer_literal(&mut self, integer_literal: &i128) {
+        self.result = Some(integer_literal.to_string());
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        let token = operation.operator().token();
+        let precedence = binary_precedence(operation.operator());
+        let left = self.render_operand(operation.left(), precedence, Associativity::Left);
+        let right = self.render_operand(operation.right(), precedence, Associativity::Right);
+        self.result = Some(format!("{left} {} {right}", token.source_text()));
+        self.result_precedence = Some(precedence);
+    }
+    fn visit_type_intrinsic(&mut self, intrinsic: &TypeIntrinsic) {
+        let name = match intrinsic.kind() {
+            TypeIntrinsicKind::Size => "sizeof",
+            TypeIntrinsicKind::Alignment => "alignof",
+        };
+        self.result = Some(format!("{name}!({})", self.render(intrinsic.type_value())));
+    }
+    fn visit_module(&mut self, module: &Module) {
+        self.result = Some(self.render(module.items()));
+    }
+    fn visit_macro_invocation(&mut self, invocation: &MacroInvocation) {
+        let mut arguments = String::new();
+        let mut previous: Option<&Token> = None;
+        for (token, _) in invocation.arguments() {
+            let is_word = |token: &Token| token.source_text().starts_with(char::is_alphanumeric);
+            // Words next to each other need a space to stay separate tokens, and binary operators are spaced out.
+            if previous.is_some_and(|previous| {
+                *previous == Token::Comma
+                    || (is_word(previous) && is_word(token))
+                    || BinaryOperator::from_token(previous).is_some()
+                    || BinaryOperator::from_token(token).is_some()
+            }) {
+                arguments.push(' ');
+            }
+            arguments += &token.source_text();
+            previous = Some(token);
+        }
+        // Only items are printed at depth 0, and macros used as items end with a semicolon.
+        let terminator = if self.depth == 0 { ";" } else { "" };
+        self.result = Some(format!("{}!({arguments}){terminator}", invocation.name()));
+    }
+    fn visit_format(&mut self, format: &Format) {
+        let mut template = String::new();
+        for segment in format.segments() {
+            match segment {
+                FormatSegment::Text(text) => {
+                    for character in text.chars() {
+                        match character {
+                            '{' => template += "{{",
+                            '}' => template += "}}",
+                            '\\' => template += "\\\\",
+                            '"' => template += "\\\"",
+                            '\n' => template += "\\n",
+                            '\t' => template += "\\t",
+                            '\r' => template += "\\r",
+                            '\0' => template += "\\0",
+                            _ => template.push(character),
+                        }
+                    }
+                }
+                // Indices are always written, as named arguments aren't kept after expansion.
+                FormatSegment::Argument { index, spec } if *spec == FormatSpec::Display => {
+                    template += &format!("{{{index}}}")
+                }
+                FormatSegment::Argument { index, spec } => {
+                    template += &format!("{{{index}:{}}}", spec.suffix())
+                }
+            }
+        }
+        let arguments = format
+            .arguments()
+            .iter()
+            .map(|argument| format!(", {}", self.render(argument.as_ref())))
+            .collect::<String>();
+        self.result = Some(format!("format!(\"{template}\"{arguments})"));
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        let arguments = call
+            .arguments()
+            .iter()
+            .map(|argument| self.render(argument.as_ref()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let arguments = match call.function() {
+            // Printing and panicking take a template, which the string they were given is passed through whole.
+            RuntimeFunction::Print | RuntimeFunction::Panic => format!("\"{{}}\", {arguments}"),
+            _ => arguments,
+        };
+        self.result = Some(format!("{}!({arguments})", call.function().macro_name()));
+    }
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        let mut message = String::new();
+        for character in assertion.message().chars() {
+            match character {
+                '\\' => message += "\\\\",
+                '"' => message += "\\\"",
+                '\n' => message += "\\n",
+                _ => message.push(character),
+            }
+        }
+        let terminator = if self.depth == 0 { ";" } else { "" };
+        self.result = Some(format!(
+            "static_assert!({}, \"{message}\"){terminator}",
+            self.render(assertion.condition())
+        ));
+    }
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        let name = match assertion.kind() {
+            AssertionKind::True => "assert",
+            AssertionKind::Equal => "assert_eq",
+        };
+        let operands = assertion
+            .operands()
+            .iter()
+            .map(|operand| self.render(operand.as_ref()))
+            .collect::<Vec<_>>();
+        self.result = Some(format!("{name}!({})", operands.join(", ")));
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        let elements = literal
+            .elements()
+            .iter()
+            .map(|element| self.render(element.as_ref()))
+            .collect::<Vec<_>>();
+        self.result = Some(format!("[{}]", elements.join(", ")));
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        let entries = literal
+            .keys()
+            .iter()
+            .zip(literal.values())
+            .map(|(key, value)| {
+                format!(
+                    "{}: {}",
+                    self.render(key.as_ref()),
+                    self.render(value.as_ref())
+                )
+            })
+            .collect::<Vec<_>>();
+        self.result = Some(if entries.is_empty() {
+            "[:]".to_string()
+        } else {
+            format!("[{}]", entries.join(", "))
+        });
+    }
+    fn visit_index(&mut self, index: &Index) {
+        // Indexing binds more tightly than any binary operator.
+        let (collection, precedence) = self.render_with_precedence(index.collection());
+        let collection = if precedence.is_some() {
+            format!("({collection})")
+        } else {
+            collection
+        };
+        self.result = Some(format!("{collection}[{}]", self.render(index.index())));
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.result = Some(match literal.value() {
+            Some(value) => format!("some({})", self.render(value)),
+            None => "none".to_string(),
+        });
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        let option = self.render(unwrap.option());
+        self.result = Some(match unwrap.default() {
+            Some(default) => format!("unwrap_or!({option}, {})", self.render(default)),
+            None => format!("unwrap!({option})"),
+        });
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        let keyword = match literal.variant() {
+            ResultVariant::Ok => "ok",
+            ResultVariant::Err => "err",
+        };
+        self.result = Some(format!("{keyword}({})", self.render(literal.value())));
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        // Like indexing, `?` binds more tightly than any binary operator.
+        let (value, precedence) = self.render_with_precedence(propagate.value());
+        let value = if precedence.is_some() {
+            format!("({value})")
+        } else {
+            value
+        };
+        self.result = Some(format!("{value}?"));
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        let (receiver, precedence) = self.render_with_precedence(call.receiver());
+        // Without parentheses, `5.abs()` would start with the float `5.`.
+        let is_integer = NodeKind::of(call.receiver()) == NodeKind::IntegerLiteral;
+        let receiver = if precedence.is_some() || is_integer {
+            format!("({receiver})")
+        } else {
+            receiver
+        };
+        let arguments = call
+            .arguments()
+            .iter()
+            .map(|argument| self.render(argument.as_ref()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.result = Some(format!("{receiver}.{}({arguments})", call.name()));
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        let indentation = self.indentation(self.depth);
+        let documentation = self.documentation(definition.documentation());
+        let representation = definition
+            .representation()
+            .map(|representation| format!(": {}", self.render(representation)))
+            .unwrap_or_default();
+        let header = format!(
+            "{documentation}enum {}{representation} {{",
+            definition.name()
+        );
+        self.depth += 1;
+        let variant_indentation = self.indentation(self.depth);
+        let variants = definition
+            .variants()
+            .iter()
+            .map(|variant| format!("{variant_indentation}{},\n", self.render(variant.as_ref())))
+            .collect::<String>();
+        self.depth -= 1;
+        self.result = Some(if variants.is_empty() {
+            format!("{header}}}")
+        } else {
+            format!("{header}\n{variants}{indentation}}}")
+        });
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.result = Some(match variant.discriminant() {
+            Some(discriminant) => format!("{} = {}", variant.name(), self.render(discriminant)),
+            None => variant.name().to_string(),
+        });
+    }
+}
+
+/// A function's signature as it appears in formatted code.
+pub fn signature(function: &FunctionDefinition, style: &Style) -> String {
+    let mut printer = Printer {
+        style,
+        depth: 0,
+        result: None,
+        result_precedence: None,
+    };
+    printer.signature(function)
+}
+
+/// Prints a parsed file in the canonical style, ending with a single newline.
+pub fn format(program: &dyn AstNode, style: &Style) -> String {
+    let mut printer = Printer {
+        style,
+        depth: 0,
+        result: None,
+        result_precedence: None,
+    };
+    let mut source = printer.render(program);
+    if !source.is_empty() {
+        source.push('\n');
+    }
+    source
+}
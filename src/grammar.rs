@@ -0,0 +1,158 @@
+//! Hematite's grammar written out in EBNF, generated from the same tables the parser uses so that it can't drift.
+//!
+//! The parser is written by hand, so the rules for items, statements and the other expressions are written out
+//! here alongside it. The keywords, punctuation, primitive types and binary operators come from the lexer's and
+//! parser's tables.
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+use crate::{
+    lexer::Token,
+    parser::{Associativity, BINARY_OPERATORS},
+};
+
+/// The rule for the operand of the tightest binding binary operators.
+const OPERAND_RULE: &str = "postfix_expression";
+
+/// The rules which follow the structure of the parser's functions, in the order they are defined there.
+const SYNTAX_RULES: &str = r#"(* Items. *)
+program = { item } ;
+item = { doc_comment } , { attribute } , function
+     | { doc_comment } , enum
+     | macro_invocation , ";" ;
+attribute = "@" , identifier ;
+function = "function" , identifier , "(" , { parameter , [ "," ] } , ")" , "->" , type , block ;
+parameter = identifier , ":" , type ;
+enum = "enum" , identifier , [ ":" , type ] , "{" , [ enum_variant , { "," , enum_variant } , [ "," ] ] , "}" ;
+enum_variant = identifier , [ "=" , expression ] ;
+
+(* Statements. *)
+block = "{" , { statement } , "}" ;
+statement = variable_definition
+          | expression , [ ";" ] ;
+variable_definition = "let" , [ "mut" ] , identifier , ":" , type , "=" , expression , ";" ;
+
+(* Types. *)
+type = primitive_type
+     | "list" , "<" , type , ">"
+     | "map" , "<" , type , "," , type , ">"
+     | "option" , "<" , type , ">"
+     | "result" , "<" , type , "," , type , ">" ;
+
+(* Expressions other than binary operations. *)
+postfix_expression = primary_expression , { "[" , expression , "]"
+                                           | "?"
+                                           | "." , identifier , "(" , { expression , [ "," ] } , ")" } ;
+primary_expression = integer
+                   | macro_invocation
+                   | "(" , expression , ")"
+                   | collection_literal
+                   | "none"
+                   | ( "some" | "ok" | "err" ) , "(" , expression , ")" ;
+(* The first entry decides whether a collection literal is a list or a map. *)
+collection_literal = "[" , "]"
+                   | "[" , ":" , "]"
+                   | "[" , expression , { "," , expression } , [ "," ] , "]"
+                   | "[" , map_entry , { "," , map_entry } , [ "," ] , "]" ;
+map_entry = expression , ":" , expression ;
+(* The arguments are kept as tokens for the macro to interpret, so they only need balanced parentheses. *)
+macro_invocation = macro_name , "(" , balanced_tokens , ")" ;
+balanced_tokens = { token - ( "(" | ")" ) | "(" , balanced_tokens , ")" } ;
+"#;
+
+/// The rules for the tokens which aren't in the lexer's exact match table.
+const LEXICAL_RULES: &str = r#"(* Tokens. Whitespace may separate any two tokens. *)
+token = identifier | macro_name | integer | float | string | doc_comment | keyword | punctuation ;
+identifier = ( letter | "_" ) , { letter | "_" | digit } - keyword ;
+macro_name = { letter | "_" | digit } , "!" ;
+integer = digit , { digit } ;
+float = digit , { digit } , "." , { digit }
+      | "." , digit , { digit } ;
+string = '"' , { character - ( '"' | "\" ) | "\" , character } , '"' ;
+doc_comment = "///" , { character - newline } ;
+letter = ? any alphabetic character ? ;
+digit = ? any ASCII digit ? ;
+character = ? any character ? ;
+newline = ? a line feed ? ;
+"#;
+
+/// Renders the whole grammar, from a program down to its tokens.
+pub fn grammar() -> String {
+    let mut grammar = String::from(SYNTAX_RULES);
+    grammar += "\n";
+    grammar += &binary_expression_rules();
+    grammar += "\n";
+    grammar += &primitive_type_rule();
+    grammar += "\n";
+    grammar += LEXICAL_RULES;
+    grammar += "\n";
+    grammar += &exact_match_rules();
+    grammar
+}
+
+/// Renders the grammar of binary expressions, with one rule for each precedence level in
+/// `parser::BINARY_OPERATORS`, from the loosest binding to the tightest.
+fn binary_expression_rules() -> String {
+    let mut levels: Vec<(u8, Associativity, Vec<String>)> = Vec::new();
+    for (operator, precedence) in BINARY_OPERATORS {
+        let text = format!("\"{}\"", operator.token().source_text());
+        match levels.last_mut() {
+            Some((level, _, operators)) if *level == precedence.precedence => operators.push(text),
+            _ => levels.push((precedence.precedence, precedence.associativity, vec![text])),
+        }
+    }
+    let rule_name = |index: usize| {
+        if index < levels.len() {
+            format!("binary_expression_{}", levels[index].0)
+        } else {
+            OPERAND_RULE.into()
+        }
+    };
+    let mut grammar =
+        String::from("(* Binary operators, from the loosest binding to the tightest. *)\n");
+    grammar += &format!("expression = {} ;\n", rule_name(0));
+    for (index, (_, associativity, operators)) in levels.iter().enumerate() {
+        let name = rule_name(index);
+        let operand = rule_name(index + 1);
+        let operators = operators.join(" | ");
+        grammar += &match associativity {
+            Associativity::Left => {
+                format!("{name} = {operand} , {{ ( {operators} ) , {operand} }} ;\n")
+            }
+            Associativity::Right => {
+                format!("{name} = {operand} , [ ( {operators} ) , {name} ] ;\n")
+            }
+        };
+    }
+    grammar
+}
+
+/// Renders `name = "a" | "b" ... ;` for the tokens in the lexer's exact match table which satisfy `filter`.
+fn exact_match_rule(name: &str, filter: impl Fn(&Token) -> bool) -> String {
+    let alternatives = Token::EXACT_MATCH_TOKENS
+        .iter()
+        .filter(|token| filter(token))
+        .map(|token| format!("\"{}\"", token.source_text()))
+        .collect::<Vec<_>>();
+    format!("{name} = {} ;\n", alternatives.join(" | "))
+}
+
+/// Renders the rule for the types which are written as a single keyword, from the lexer's `type_keyword`s.
+fn primitive_type_rule() -> String {
+    let mut grammar = String::from("(* Types written as a single keyword. *)\n");
+    grammar += &exact_match_rule("primitive_type", |token| token.type_keyword().is_some());
+    grammar
+}
+
+/// Renders the rules for keywords and punctuation, from the lexer's exact match table.
+fn exact_match_rules() -> String {
+    let is_keyword = |token: &Token| {
+        token
+            .exact_match_text()
+            .is_some_and(|text| text.chars().all(char::is_alphanumeric))
+    };
+    let mut grammar = String::from("(* Tokens written as a fixed string. *)\n");
+    grammar += &exact_match_rule("keyword", is_keyword);
+    grammar += &exact_match_rule("punctuation", |token| !is_keyword(token));
+    grammar
+}
@@ -0,0 +1,178 @@
+//! Classifies a file's tokens for syntax highlighting, using the parsed tree to tell apart what each name declares.
+//!
+//! Grammars which highlight with regular expressions can only guess what an identifier is from the text around it.
+//! Here, the names of functions, enums, their variants, parameters and variables are found from the nodes which
+//! declare them, so they are classified however they are laid out.
+
+use alloc::{collections::BTreeMap, string::String, vec::Vec};
+
+use crate::{
+    ast::{
+        AstNode, EnumDefinition, EnumVariant, FunctionDefinition, ParameterDeclaration,
+        VariableDefinition,
+    },
+    lexer::{Span, Token},
+    query::{self, NodeKind},
+};
+
+/// What a highlighted token is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HighlightKind {
+    Keyword,
+    /// A type keyword, or the name of an enum.
+    Type,
+    Function,
+    Parameter,
+    /// A variable declared with `let`.
+    Local,
+    Variant,
+    Macro,
+    Attribute,
+    Number,
+    /// A string or character literal.
+    String,
+    Comment,
+}
+
+impl HighlightKind {
+    /// The kind's name, which is also its class in HTML.
+    pub fn name(&self) -> &'static str {
+        match self {
+            HighlightKind::Keyword => "keyword",
+            HighlightKind::Type => "type",
+            HighlightKind::Function => "function",
+            HighlightKind::Parameter => "parameter",
+            HighlightKind::Local => "local",
+            HighlightKind::Variant => "variant",
+            HighlightKind::Macro => "macro",
+            HighlightKind::Attribute => "attribute",
+            HighlightKind::Number => "number",
+            HighlightKind::String => "string",
+            HighlightKind::Comment => "comment",
+        }
+    }
+}
+
+/// The index of the first token starting at or after `offset`.
+fn token_at(tokens: &[(Token, Span)], offset: usize) -> usize {
+    tokens.partition_point(|(_, span)| span.start() < offset)
+}
+
+/// The kinds of the names declared in `program`, by where they start.
+fn declarations(tokens: &[(Token, Span)], program: &dyn AstNode) -> BTreeMap<usize, HighlightKind> {
+    let mut declarations = BTreeMap::new();
+    let nodes = |kind| query::items_of_kind(program, kind);
+    for node in nodes(NodeKind::FunctionDefinition) {
+        let Some(function) = node.downcast_ref::<FunctionDefinition>() else {
+            continue;
+        };
+        declarations.insert(function.name_span().start(), HighlightKind::Function);
+        // Parameters don't record where they are, but each is named before anything in the function's body.
+        let mut next = token_at(tokens, function.name_span().end());
+        for parameter in function.parameters() {
+            let Some(parameter) = parameter.downcast_ref::<ParameterDeclaration>() else {
+                continue;
+            };
+            let found = tokens[next..]
+                .iter()
+                .position(|(token, _)| *token == Token::Identifier(parameter.name()));
+            if let Some(position) = found {
+                next += position;
+                declarations.insert(tokens[next].1.start(), HighlightKind::Parameter);
+                next += 1;
+            }
+        }
+    }
+    for node in nodes(NodeKind::VariableDefinition) {
+        let Some(variable) = node.downcast_ref::<VariableDefinition>() else {
+            continue;
+        };
+        // The variable's span starts at `let`, and its name is the first identifier after it.
+        let name = tokens[token_at(tokens, variable.span().start())..]
+            .iter()
+            .find(|(token, _)| *token == Token::Identifier(variable.name()));
+        if let Some((_, span)) = name {
+            declarations.insert(span.start(), HighlightKind::Local);
+        }
+    }
+    for node in nodes(NodeKind::EnumDefinition) {
+        if let Some(definition) = node.downcast_ref::<EnumDefinition>() {
+            declarations.insert(definition.name_span().start(), HighlightKind::Type);
+        }
+    }
+    for node in nodes(NodeKind::EnumVariant) {
+        if let Some(variant) = node.downcast_ref::<EnumVariant>() {
+            declarations.insert(variant.span().start(), HighlightKind::Variant);
+        }
+    }
+    declarations
+}
+
+/// The kind of each token worth highlighting, in order, along with its span.
+///
+/// `program` is the tree parsed from `tokens`, if they parse. Without it, keywords, literals and macros are still
+/// highlighted, but identifiers other than attributes aren't.
+pub fn highlight(
+    tokens: &[(Token, Span)],
+    program: Option<&dyn AstNode>,
+) -> Vec<(Span, HighlightKind)> {
+    let declarations = program
+        .map(|program| declarations(tokens, program))
+        .unwrap_or_default();
+    let mut highlights = Vec::new();
+    let mut previous = None;
+    for (token, span) in tokens {
+        let kind = match token {
+            Token::Identifier(_) if previous == Some(&Token::At) => Some(HighlightKind::Attribute),
+            Token::Identifier(_) => declarations.get(&span.start()).copied(),
+            Token::MacroCall(_) => Some(HighlightKind::Macro),
+            Token::Integer(_) | Token::Float(_) => Some(HighlightKind::Number),
+            Token::StringLiteral(_) | Token::Char(_) => Some(HighlightKind::String),
+            Token::DocComment(_) => Some(HighlightKind::Comment),
+            Token::List | Token::Map | Token::OptionType | Token::ResultType => {
+                Some(HighlightKind::Type)
+            }
+            token if token.type_keyword().is_some() => Some(HighlightKind::Type),
+            token => token
+                .exact_match_text()
+                .filter(|text| text.chars().all(char::is_alphabetic))
+                .map(|_| HighlightKind::Keyword),
+        };
+        if let Some(kind) = kind {
+            highlights.push((*span, kind));
+        }
+        previous = Some(token);
+    }
+    highlights
+}
+
+fn escape_html(text: &str, output: &mut String) {
+    for character in text.chars() {
+        match character {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            character => output.push(character),
+        }
+    }
+}
+
+/// The text as HTML, with each highlighted span in a `<span>` whose class is its kind's name.
+///
+/// This is only the highlighted text, to be put in a `<pre>` of a page with styles for the classes.
+pub fn to_html(text: &str, highlights: &[(Span, HighlightKind)]) -> String {
+    let mut output = String::new();
+    let mut position = 0;
+    for (span, kind) in highlights {
+        escape_html(&text[position..span.start()], &mut output);
+        output.push_str("<span class=\"");
+        output.push_str(kind.name());
+        output.push_str("\">");
+        escape_html(&text[span.start()..span.end()], &mut output);
+        output.push_str("</span>");
+        position = span.end();
+    }
+    escape_html(&text[position..], &mut output);
+    output
+}
@@ -0,0 +1,75 @@
+//! What to show about the code under the cursor, for editors' hovers.
+
+use crate::{
+    ast::{AstNode, EnumDefinition, FunctionDefinition, Type, VariableDefinition},
+    formatter::{self, type_name, Style},
+    lexer::Span,
+    query, semantic,
+};
+
+/// A description of a node: code saying what it is, such as a signature or a type, along with its doc comment.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hover {
+    span: Span,
+    code: String,
+    documentation: Vec<String>,
+}
+
+impl Hover {
+    fn new(span: Span, code: String) -> Self {
+        Self {
+            span,
+            code,
+            documentation: Vec::new(),
+        }
+    }
+
+    /// The span of the node being described.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn code(&self) -> &str {
+        &self.code
+    }
+
+    /// The lines of the node's doc comment, without their leading `///`.
+    pub fn documentation(&self) -> &[String] {
+        &self.documentation
+    }
+}
+
+/// Describes the innermost node at `offset` which can be described: a function's signature, an enum's name, a
+/// variable's type or the type of an expression.
+///
+/// The types of macro invocations and method calls are only known once macros have been expanded and methods
+/// resolved, so `program` should have been through those passes.
+pub fn hover(program: &dyn AstNode, offset: usize) -> Option<Hover> {
+    let node = query::node_at_offset(program, offset)?;
+    let span = query::span_of(node)?;
+    if let Some(function) = node.downcast_ref::<FunctionDefinition>() {
+        let signature = formatter::signature(function, &Style::default());
+        return Some(Hover {
+            documentation: function.documentation().to_vec(),
+            ..Hover::new(span, signature)
+        });
+    }
+    if let Some(definition) = node.downcast_ref::<EnumDefinition>() {
+        return Some(Hover {
+            documentation: definition.documentation().to_vec(),
+            ..Hover::new(span, format!("enum {}", definition.name()))
+        });
+    }
+    if let Some(variable) = node.downcast_ref::<VariableDefinition>() {
+        let mutability = if variable.mutable() { "mut " } else { "" };
+        let variable_type = variable.variable_type().downcast_ref::<Type>()?;
+        let code = format!(
+            "let {mutability}{}: {}",
+            variable.name(),
+            type_name(variable_type)
+        );
+        return Some(Hover::new(span, code));
+    }
+    let expression_type = semantic::expression_type(node)?;
+    Some(Hover::new(span, type_name(&expression_type)))
+}
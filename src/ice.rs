@@ -0,0 +1,69 @@
+//! Reporting of internal compiler errors: panics, which are always bugs in the compiler rather than in the program.
+
+use std::{
+    backtrace::Backtrace,
+    panic::{self, PanicHookInfo},
+    sync::Mutex,
+};
+
+const ISSUE_TRACKER: &str = "https://github.com/Jett59/hematite-lang/issues";
+
+/// The pass the compiler is running, shared by every thread so that panics on worker threads can name it.
+static CURRENT_PASS: Mutex<Option<&'static str>> = Mutex::new(None);
+
+/// Records that a pass is running until it is dropped, restoring whichever pass was running before.
+pub struct PassGuard {
+    previous_pass: Option<&'static str>,
+}
+
+impl PassGuard {
+    pub fn enter(pass: &'static str) -> Self {
+        let mut current_pass = CURRENT_PASS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        Self {
+            previous_pass: current_pass.replace(pass),
+        }
+    }
+}
+
+impl Drop for PassGuard {
+    fn drop(&mut self) {
+        *CURRENT_PASS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) = self.previous_pass;
+    }
+}
+
+fn report(info: &PanicHookInfo) {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic");
+    eprintln!("error: internal compiler error: {message}");
+    if let Some(location) = info.location() {
+        eprintln!(" --> {location}");
+    }
+    // The lock may be held by the thread which panicked, so don't wait for it.
+    let current_pass = CURRENT_PASS.try_lock().ok().and_then(|pass| *pass);
+    match current_pass {
+        Some(pass) => eprintln!("note: the compiler unexpectedly panicked during {pass}"),
+        None => eprintln!("note: the compiler unexpectedly panicked"),
+    }
+    eprintln!("note: this is a bug in the compiler, not your program; please report it at {ISSUE_TRACKER} along with the input which caused it");
+    eprintln!(
+        "note: {} {} for {}-{}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    );
+    eprintln!("backtrace:\n{}", Backtrace::force_capture());
+}
+
+/// Replaces the default panic output with a bug report.
+pub fn install_handler() {
+    panic::set_hook(Box::new(report));
+}
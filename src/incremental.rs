@@ -0,0 +1,221 @@
+//! Reparsing a file after an edit without starting from scratch, so that an editor can be kept up to date as fast as
+//! its user types.
+//!
+//! Only the tokens from the start of the edit to the point where lexing the new text gives the same tokens as before
+//! are relexed, and only the top-level items those tokens belong to are reparsed. Items before the edit are kept as
+//! they are, and items after it are kept with their spans moved by however much the edit changed the length of the
+//! text. Top-level items are parsed without looking at their neighbours, so this gives the same tree as parsing the
+//! whole file again.
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+
+use crate::{
+    ast::{self, AstNode, Folder},
+    features::Features,
+    lexer::{self, Span, Token},
+    parser::{self, SyntaxError, TokenStream},
+};
+
+/// A file's text along with its tokens and parsed items, which can be edited and reparsed incrementally.
+pub struct IncrementalParse {
+    text: String,
+    features: Features,
+    tokens: Vec<(Token, Span)>,
+    items: Vec<Box<dyn AstNode>>,
+    /// The span each of `items` covers, from its first doc comment or attribute to its last token.
+    item_spans: Vec<Span>,
+    /// Why the text doesn't parse, in which case `items` is empty.
+    error: Option<SyntaxError>,
+}
+
+impl IncrementalParse {
+    pub fn new(text: String, features: Features) -> Self {
+        let mut parse = Self {
+            text,
+            features,
+            tokens: Vec::new(),
+            items: Vec::new(),
+            item_spans: Vec::new(),
+            error: None,
+        };
+        parse.reparse_everything();
+        parse
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn tokens(&self) -> &[(Token, Span)] {
+        &self.tokens
+    }
+
+    /// The list of the file's items, as `parser::parse` would give, or why the file doesn't parse.
+    pub fn program(&self) -> Result<&dyn AstNode, &SyntaxError> {
+        match &self.error {
+            Some(error) => Err(error),
+            None => Ok(&self.items),
+        }
+    }
+
+    /// Replaces the text in `range` with `replacement`, reparsing only the items which the edit changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` isn't within the text or doesn't start and end on character boundaries.
+    pub fn edit(&mut self, range: Span, replacement: &str) {
+        self.text
+            .replace_range(range.start()..range.end(), replacement);
+        // Invalid tokens stop the lexer, so the tokens after one were never found and have to be now.
+        let stopped_early = matches!(self.tokens.last(), Some((Token::Error(_), _)));
+        if self.error.is_some() || stopped_early {
+            self.reparse_everything();
+            return;
+        }
+        let growth = replacement.len() as isize - (range.end() - range.start()) as isize;
+        // A token which ends where the edit starts may continue into the new text, so it is relexed too, starting
+        // from the end of the token before it.
+        let first_changed = self
+            .tokens
+            .partition_point(|(_, span)| span.end() < range.start());
+        let relex_start = match first_changed {
+            0 => 0,
+            index => self.tokens[index - 1].1.end(),
+        };
+        let edit_end = range.start() + replacement.len();
+        let mut relexed = Vec::new();
+        // The index of the first old token which the new tokens have caught up with, after which nothing changes.
+        let mut unchanged_from = None;
+        let mut old_index = first_changed;
+        for (token, span) in lexer::tokenize(&mut self.text[relex_start..].chars()) {
+            let span = Span::new(relex_start + span.start(), relex_start + span.end());
+            if span.start() >= edit_end {
+                while self.tokens.get(old_index).is_some_and(|(_, old_span)| {
+                    old_span.start() < range.end()
+                        || shift(*old_span, growth).start() < span.start()
+                }) {
+                    old_index += 1;
+                }
+                if let Some((old_token, old_span)) = self.tokens.get(old_index) {
+                    if *old_token == token && shift(*old_span, growth) == span {
+                        unchanged_from = Some(old_index);
+                        break;
+                    }
+                }
+            }
+            relexed.push((token, span));
+        }
+        // The items which don't contain any changed tokens are kept; the ones in between are parsed again.
+        let kept_before = self
+            .item_spans
+            .partition_point(|item_span| item_span.end() <= relex_start);
+        let kept_after = match unchanged_from {
+            Some(index) => {
+                let unchanged_start = self.tokens[index].1.start();
+                self.item_spans
+                    .partition_point(|item_span| item_span.start() < unchanged_start)
+            }
+            None => self.item_spans.len(),
+        };
+        let relexed_count = relexed.len();
+        let unchanged = match unchanged_from {
+            Some(index) => self.tokens.split_off(index),
+            None => Vec::new(),
+        };
+        self.tokens.truncate(first_changed);
+        self.tokens.extend(relexed);
+        self.tokens.extend(
+            unchanged
+                .into_iter()
+                .map(|(token, span)| (token, shift(span, growth))),
+        );
+        let reparse_start = match kept_before {
+            0 => 0,
+            count => self.item_spans[count - 1].end(),
+        };
+        let reparse_end = self
+            .item_spans
+            .get(kept_after)
+            .map_or(usize::MAX, |item_span| shift(*item_span, growth).start());
+        let first_token = self
+            .tokens
+            .partition_point(|(_, span)| span.start() < reparse_start);
+        let last_token = self
+            .tokens
+            .partition_point(|(_, span)| span.start() < reparse_end)
+            .max(first_changed + relexed_count);
+        let mut tokens = self.tokens[first_token..last_token].iter().cloned();
+        let mut token_stream = TokenStream::new(&mut tokens).with_features(self.features.clone());
+        let reparsed = match parser::parse_items_with_spans(&mut token_stream) {
+            Ok(reparsed) => reparsed,
+            // An item which no longer ends where it did may run into the ones after it, which a full parse
+            // reports where it really goes wrong.
+            Err(_) => {
+                self.reparse_everything();
+                return;
+            }
+        };
+        let mut shifter = SpanShifter { growth };
+        let after = self
+            .items
+            .split_off(kept_after)
+            .into_iter()
+            .map(|item| ast::fold(&mut shifter, item.as_ref()))
+            .collect::<Vec<_>>();
+        let after_spans = self
+            .item_spans
+            .split_off(kept_after)
+            .into_iter()
+            .map(|item_span| shift(item_span, growth))
+            .collect::<Vec<_>>();
+        self.items.truncate(kept_before);
+        self.item_spans.truncate(kept_before);
+        for (item_span, item) in reparsed {
+            self.item_spans.push(item_span);
+            self.items.push(item);
+        }
+        self.items.extend(after);
+        self.item_spans.extend(after_spans);
+    }
+
+    fn reparse_everything(&mut self) {
+        self.tokens = crate::tokenize_str(&self.text);
+        let mut tokens = self.tokens.iter().cloned();
+        let mut token_stream = TokenStream::new(&mut tokens).with_features(self.features.clone());
+        match parser::parse_items_with_spans(&mut token_stream) {
+            Ok(items) => {
+                (self.item_spans, self.items) = items.into_iter().unzip();
+                self.error = None;
+            }
+            Err(error) => {
+                self.items.clear();
+                self.item_spans.clear();
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// `span` moved by `growth` bytes.
+fn shift(span: Span, growth: isize) -> Span {
+    let move_offset = |offset: usize| {
+        offset
+            .checked_add_signed(growth)
+            .expect("Only spans after an edit are moved, which can't move before its start")
+    };
+    Span::new(move_offset(span.start()), move_offset(span.end()))
+}
+
+/// Rebuilds a tree with every span moved by the same amount, for the items after an edit.
+struct SpanShifter {
+    growth: isize,
+}
+
+impl Folder for SpanShifter {
+    fn fold_node(&mut self, node: &dyn AstNode) -> Option<Box<dyn AstNode>> {
+        let growth = self.growth;
+        let mut shifted = node.map_children(&mut |child| ast::fold(self, child));
+        shifted.map_spans(&mut |span| shift(span, growth));
+        Some(shifted)
+    }
+}
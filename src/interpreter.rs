@@ -0,0 +1,886 @@
+//! Direct evaluation of a checked program, used to run tests before there is a code generator.
+
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fmt::Display,
+    io::{self, Write},
+    ptr,
+};
+
+use crate::{
+    ast::{
+        Assertion, AssertionKind, AstNode, AstVisitor, BinaryOperation, BinaryOperator,
+        BuiltinMethod, EnumDefinition, EnumVariant, Format, FormatSegment, FormatSpec,
+        FunctionDefinition, IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral,
+        MethodCall, Module, OptionLiteral, Overflow, ParameterDeclaration, Propagate,
+        ResultLiteral, ResultVariant, RuntimeCall, RuntimeFunction, StaticAssertion, Type,
+        TypeIntrinsic, Unwrap, VariableDefinition,
+    },
+    diagnostics::Location,
+    formatter,
+    lexer::Span,
+    query::{self, NodeKind},
+    symbol::Symbol,
+};
+
+/// Why a program stopped early, such as a failed assertion, `panic!` or dividing by zero.
+#[derive(Clone, Debug)]
+pub struct RuntimeError {
+    message: String,
+    /// The functions which were running, innermost first, filled in as the error leaves each one.
+    backtrace: Vec<BacktraceFrame>,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            backtrace: Vec::new(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Where each running function was when the error happened, innermost first.
+    pub fn backtrace(&self) -> &[BacktraceFrame] {
+        &self.backtrace
+    }
+}
+
+/// A function which was running when a `RuntimeError` happened.
+#[derive(Clone, Copy, Debug)]
+pub struct BacktraceFrame {
+    function: Symbol,
+    /// The statement the function was running, if it records where it is.
+    location: Option<Location>,
+}
+
+impl BacktraceFrame {
+    pub fn function(&self) -> Symbol {
+        self.function
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for RuntimeError {}
+
+/// What evaluating a node produced.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// Statements, such as `let`, which don't produce a value.
+    Unit,
+    Integer(i128),
+    Char(char),
+    String(String),
+    List(Vec<Value>),
+    /// The entries in the order their keys were first added.
+    Map(Vec<(Value, Value)>),
+    Option(Option<Box<Value>>),
+    Result(Result<Box<Value>, Box<Value>>),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Integer(value) => write!(f, "{value}"),
+            Value::Char(character) => write!(f, "{character:?}"),
+            Value::String(string) => write!(f, "{string:?}"),
+            Value::List(elements) => {
+                write!(f, "[")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{element}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) if entries.is_empty() => write!(f, "[:]"),
+            Value::Map(entries) => {
+                write!(f, "[")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Option(Some(value)) => write!(f, "some({value})"),
+            Value::Option(None) => write!(f, "none"),
+            Value::Result(Ok(value)) => write!(f, "ok({value})"),
+            Value::Result(Err(error)) => write!(f, "err({error})"),
+        }
+    }
+}
+
+/// A function being evaluated, as a debugger sees it.
+pub struct Frame<'evaluator> {
+    function: Symbol,
+    /// Where the function is stopped, if the statement being run records where it is.
+    location: Option<Location>,
+    variables: &'evaluator HashMap<Symbol, Value>,
+}
+
+impl Frame<'_> {
+    pub fn function(&self) -> Symbol {
+        self.function
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    /// The local variables defined so far, sorted by name.
+    pub fn variables(&self) -> Vec<(Symbol, &Value)> {
+        let mut variables = self
+            .variables
+            .iter()
+            .map(|(name, value)| (*name, value))
+            .collect::<Vec<_>>();
+        variables.sort_by_key(|(name, _)| name.as_str());
+        variables
+    }
+
+    pub fn variable(&self, name: Symbol) -> Option<&Value> {
+        self.variables.get(&name)
+    }
+}
+
+/// Watches evaluation from outside, as `hematite debug` does to stop at breakpoints and `--instrument profile`
+/// does to time functions.
+pub trait EvaluationHook {
+    /// Called before each statement runs, with the innermost frame last; failing stops the program.
+    fn before_statement(&mut self, _stack: &[Frame]) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    /// Called as `function` starts running.
+    fn enter_function(&mut self, _function: Symbol) {}
+
+    /// Called once `function` has finished running, whether or not it succeeded.
+    fn exit_function(&mut self, _function: Symbol) {}
+}
+
+/// Where a statement is, taken from the first part of it which records a span; literals, for example, don't.
+fn first_span(statement: &dyn AstNode) -> Option<Span> {
+    query::span_of(statement).or_else(|| statement.children().into_iter().find_map(first_span))
+}
+
+/// The address of the last expression in `function`'s body, which only identifies it, along with the span the parser
+/// recorded for it.
+fn tail_of(function: &FunctionDefinition) -> Option<(*const (), Span)> {
+    let tail = function
+        .body()
+        .downcast_ref::<Vec<Box<dyn AstNode>>>()?
+        .last()?;
+    Some((ptr::from_ref(tail.as_ref()).cast(), function.tail_span()?))
+}
+
+/// Where a statement of a function whose last expression is `tail` is, as a hook is told. Literals don't record
+/// where they are, but the parser does for one ending a function.
+fn statement_span(statement: &dyn AstNode, tail: Option<(*const (), Span)>) -> Option<Span> {
+    first_span(statement).or_else(|| {
+        tail.filter(|(tail, _)| ptr::addr_eq(*tail, statement))
+            .map(|(_, span)| span)
+    })
+}
+
+/// The spans a hook is given for `function`'s statements, for tools such as coverage which need to know every
+/// statement that could run. Statements which record no span at all are left out.
+pub fn statement_spans(function: &FunctionDefinition) -> Vec<Span> {
+    let tail = tail_of(function);
+    query::items_of_kind(function.body(), NodeKind::List)
+        .into_iter()
+        .flat_map(|list| list.children())
+        .filter_map(|statement| statement_span(statement, tail))
+        .collect()
+}
+
+/// The function being run, and where in it the evaluator is.
+struct Running {
+    function: Symbol,
+    /// The file the function is defined in, which statements' spans refer to.
+    file: usize,
+    /// The statement being run, if it records where it is.
+    statement: Option<Span>,
+    /// The function's last expression, as `tail_of` finds it.
+    tail: Option<(*const (), Span)>,
+}
+
+impl Running {
+    fn location(&self) -> Option<Location> {
+        self.statement.map(|span| Location::new(self.file, span))
+    }
+}
+
+/// The smallest and largest values of an integer type.
+///
+/// Pointer-sized integers are as wide as the host's pointers, as the interpreter always runs on the host.
+pub(crate) fn integer_range(type_value: &Type) -> (i128, i128) {
+    let (bits, signed) = match type_value {
+        Type::I8 => (8, true),
+        Type::I16 => (16, true),
+        Type::I32 => (32, true),
+        Type::I64 => (64, true),
+        Type::Iptr => (usize::BITS, true),
+        Type::U8 => (8, false),
+        Type::U16 => (16, false),
+        Type::U32 => (32, false),
+        Type::U64 => (64, false),
+        Type::Uptr => (usize::BITS, false),
+        _ => unreachable!("Arithmetic is only done on integers"),
+    };
+    if signed {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    } else {
+        (0, (1 << bits) - 1)
+    }
+}
+
+/// The part of `string` from byte `start` up to byte `end`, which must both be at the start or end of a character.
+fn slice(string: &str, start: i128, end: i128) -> Result<&str, RuntimeError> {
+    let offsets = usize::try_from(start).ok().zip(usize::try_from(end).ok());
+    match offsets {
+        Some((start, end)) if start <= end && end <= string.len() => {
+            string.get(start..end).ok_or_else(|| {
+                let offset = if string.is_char_boundary(start) {
+                    end
+                } else {
+                    start
+                };
+                RuntimeError::new(format!("Byte {offset} of {string:?} is inside a character"))
+            })
+        }
+        _ => Err(RuntimeError::new(format!(
+            "Bytes {start}..{end} are out of bounds for a string of length {}",
+            string.len()
+        ))),
+    }
+}
+
+/// Evaluates expressions and statements, keeping track of local variables.
+///
+/// Type intrinsics must already have been resolved, as the interpreter doesn't know the target.
+struct Evaluator<'hook> {
+    variables: HashMap<Symbol, Value>,
+    /// The type arithmetic is done in: that of the variable being defined, or else the function's return type.
+    /// There is no type inference yet, so this stands in for the type of each operation.
+    integer_type: Type,
+    /// Whether only constant expressions may be evaluated, which excludes calls to the runtime.
+    constant: bool,
+    /// Unset when evaluating a constant, which isn't in any function.
+    running: Option<Running>,
+    /// What `args!()` gives the program.
+    arguments: Vec<String>,
+    hook: Option<&'hook mut dyn EvaluationHook>,
+    /// The error a `?` is returning from the running function.
+    returning: Option<Value>,
+    result: Result<Value, RuntimeError>,
+}
+
+impl Evaluator<'_> {
+    fn new(constant: bool) -> Self {
+        Self {
+            variables: HashMap::new(),
+            // Like `main`'s return value, integers are `i32` unless something says otherwise.
+            integer_type: Type::I32,
+            constant,
+            running: None,
+            arguments: Vec::new(),
+            hook: None,
+            returning: None,
+            result: Ok(Value::Unit),
+        }
+    }
+
+    /// Records that `statement` is about to run, letting the hook look at the program first.
+    fn before_statement(&mut self, statement: &dyn AstNode) -> Result<(), RuntimeError> {
+        let Some(running) = &mut self.running else {
+            return Ok(());
+        };
+        running.statement = statement_span(statement, running.tail);
+        let Some(hook) = &mut self.hook else {
+            return Ok(());
+        };
+        let frame = Frame {
+            function: running.function,
+            location: running.location(),
+            variables: &self.variables,
+        };
+        hook.before_statement(&[frame])
+    }
+
+    fn evaluate(&mut self, node: &dyn AstNode) -> Result<Value, RuntimeError> {
+        node.apply(self);
+        std::mem::replace(&mut self.result, Ok(Value::Unit))
+    }
+
+    fn evaluate_integer(&mut self, node: &dyn AstNode) -> Result<i128, RuntimeError> {
+        match self.evaluate(node)? {
+            Value::Integer(value) => Ok(value),
+            Value::Unit
+            | Value::Char(_)
+            | Value::String(_)
+            | Value::List(_)
+            | Value::Map(_)
+            | Value::Option(_)
+            | Value::Result(_) => Err(RuntimeError::new("Expected an integer".to_string())),
+        }
+    }
+
+    fn format(&mut self, format: &Format) -> Result<String, RuntimeError> {
+        let arguments = format
+            .arguments()
+            .iter()
+            .map(|argument| self.evaluate(argument.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut result = String::new();
+        for segment in format.segments() {
+            match segment {
+                FormatSegment::Text(text) => result += text,
+                FormatSegment::Argument { index, spec } => {
+                    // Semantic analysis has already checked that the specs match the arguments' types.
+                    let formatted = match (&arguments[*index], spec) {
+                        (Value::String(string), _) => string.clone(),
+                        (Value::Char(character), _) => character.to_string(),
+                        (
+                            value @ (Value::List(_)
+                            | Value::Map(_)
+                            | Value::Option(_)
+                            | Value::Result(_)),
+                            _,
+                        ) => value.to_string(),
+                        (Value::Integer(value), FormatSpec::Display) => value.to_string(),
+                        (Value::Integer(value), FormatSpec::LowerHex) => format!("{value:x}"),
+                        (Value::Integer(value), FormatSpec::UpperHex) => format!("{value:X}"),
+                        (Value::Integer(value), FormatSpec::Binary) => format!("{value:b}"),
+                        (Value::Integer(value), FormatSpec::Octal) => format!("{value:o}"),
+                        (Value::Unit, _) => {
+                            return Err(RuntimeError::new(
+                                "Tried to format an expression without a value".to_string(),
+                            ))
+                        }
+                    };
+                    result += &formatted;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn call_runtime(&mut self, call: &RuntimeCall) -> Result<Value, RuntimeError> {
+        let arguments = call
+            .arguments()
+            .iter()
+            .map(|argument| self.evaluate(argument.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.call_runtime_function(call.function(), &arguments)
+    }
+
+    fn call_runtime_function(
+        &mut self,
+        function: RuntimeFunction,
+        arguments: &[Value],
+    ) -> Result<Value, RuntimeError> {
+        match (function, arguments) {
+            (RuntimeFunction::Print, [Value::String(message)]) => {
+                let mut stdout = io::stdout().lock();
+                // Like the runtime library, there is nowhere to report a failure to write.
+                let _ = stdout.write_all(message.as_bytes());
+                let _ = stdout.flush();
+                Ok(Value::Unit)
+            }
+            (RuntimeFunction::Panic, [Value::String(message)]) => {
+                Err(RuntimeError::new(message.clone()))
+            }
+            (RuntimeFunction::StringLength, [Value::String(string)]) => {
+                Ok(Value::Integer(string.len() as i128))
+            }
+            (
+                RuntimeFunction::StringSlice,
+                [Value::String(string), Value::Integer(start), Value::Integer(end)],
+            ) => slice(string, *start, *end).map(|slice| Value::String(slice.to_string())),
+            (RuntimeFunction::StringChars, [Value::String(string)]) => {
+                Ok(Value::List(string.chars().map(Value::Char).collect()))
+            }
+            (RuntimeFunction::StringCompare, [Value::String(left), Value::String(right)]) => {
+                Ok(Value::Integer(left.cmp(right) as i128))
+            }
+            (RuntimeFunction::StringParseInt, [Value::String(string)]) => {
+                Ok(Value::Result(match string.parse::<i64>() {
+                    Ok(value) => Ok(Box::new(Value::Integer(value.into()))),
+                    Err(_) => Err(Box::new(Value::String(format!(
+                        "`{string}` isn't an integer"
+                    )))),
+                }))
+            }
+            (RuntimeFunction::CharToString, [Value::Char(character)]) => {
+                Ok(Value::String(character.to_string()))
+            }
+            (RuntimeFunction::CharCode, [Value::Char(character)]) => {
+                Ok(Value::Integer(u32::from(*character).into()))
+            }
+            (RuntimeFunction::CharFromCode, [Value::Integer(code)]) => Ok(Value::Option(
+                u32::try_from(*code)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map(|character| Box::new(Value::Char(character))),
+            )),
+            (RuntimeFunction::Args, []) => Ok(Value::List(
+                self.arguments.iter().cloned().map(Value::String).collect(),
+            )),
+            // The interpreter runs in the compiler's process, so the program sees the compiler's environment.
+            (RuntimeFunction::EnvVar, [Value::String(name)]) => Ok(Value::Option(
+                env::var(name)
+                    .ok()
+                    .map(|value| Box::new(Value::String(value))),
+            )),
+            // Semantic analysis has already checked the arguments' types.
+            _ => Err(RuntimeError::new(format!(
+                "'{}' was given the wrong arguments",
+                function.symbol()
+            ))),
+        }
+    }
+
+    fn call_method(&mut self, call: &MethodCall) -> Result<Value, RuntimeError> {
+        let Some(method) = call.method() else {
+            return Err(RuntimeError::new(format!(
+                "The unresolved method '{}' can't be evaluated",
+                call.name()
+            )));
+        };
+        if self.constant && matches!(method, BuiltinMethod::Runtime(_)) {
+            return Err(RuntimeError::new(
+                "A call to the runtime library can't be evaluated".to_string(),
+            ));
+        }
+        let mut arguments = vec![self.evaluate(call.receiver())?];
+        for argument in call.arguments() {
+            arguments.push(self.evaluate(argument.as_ref())?);
+        }
+        match (method, arguments.as_slice()) {
+            (BuiltinMethod::Runtime(function), _) => {
+                self.call_runtime_function(function, &arguments)
+            }
+            (BuiltinMethod::ListLength, [Value::List(elements)]) => {
+                Ok(Value::Integer(elements.len() as i128))
+            }
+            (BuiltinMethod::MapLength, [Value::Map(entries)]) => {
+                Ok(Value::Integer(entries.len() as i128))
+            }
+            (BuiltinMethod::Abs, [Value::Integer(value)]) => {
+                let (_, max) = integer_range(&self.integer_type);
+                match value.checked_abs().filter(|result| *result <= max) {
+                    Some(result) => Ok(Value::Integer(result)),
+                    None => Err(RuntimeError::new(format!(
+                        "'({value}).abs()' overflowed {}",
+                        formatter::type_name(&self.integer_type)
+                    ))),
+                }
+            }
+            // Method resolution has already checked the receiver's type.
+            _ => Err(RuntimeError::new(format!(
+                "'{}' was called on the wrong type",
+                call.name()
+            ))),
+        }
+    }
+
+    fn check_assertion(&mut self, assertion: &Assertion) -> Result<(), RuntimeError> {
+        let operands = assertion
+            .operands()
+            .iter()
+            .map(|operand| self.evaluate_integer(operand.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let message = match (assertion.kind(), operands.as_slice()) {
+            (AssertionKind::True, [0]) => assertion.message().to_string(),
+            (AssertionKind::Equal, [left, right]) if left != right => {
+                format!("{} (left: {left}, right: {right})", assertion.message())
+            }
+            _ => return Ok(()),
+        };
+        Err(RuntimeError::new(message))
+    }
+
+    fn binary_operation(&mut self, operation: &BinaryOperation) -> Result<Value, RuntimeError> {
+        match (
+            self.evaluate(operation.left())?,
+            self.evaluate(operation.right())?,
+        ) {
+            (Value::Integer(left), Value::Integer(right)) => self
+                .integer_operation(operation, left, right)
+                .map(Value::Integer),
+            // Semantic analysis has already checked that strings are only joined to strings.
+            (Value::String(left), Value::String(right)) => Ok(Value::String(left + &right)),
+            _ => Err(RuntimeError::new("Expected an integer".to_string())),
+        }
+    }
+
+    fn integer_operation(
+        &self,
+        operation: &BinaryOperation,
+        left: i128,
+        right: i128,
+    ) -> Result<i128, RuntimeError> {
+        let operator = operation.operator();
+        if matches!(operator, BinaryOperator::Divide | BinaryOperator::Remainder) && right == 0 {
+            return Err(RuntimeError::new(format!("Division of {left} by zero")));
+        }
+        // Every integer type fits in an `i128`, so most results are exact and only need checking against the type.
+        let exact = match operator {
+            BinaryOperator::Add => left.checked_add(right),
+            BinaryOperator::Subtract => left.checked_sub(right),
+            BinaryOperator::Multiply => left.checked_mul(right),
+            BinaryOperator::Divide => left.checked_div(right),
+            BinaryOperator::Remainder => left.checked_rem(right),
+        };
+        let (min, max) = integer_range(&self.integer_type);
+        if let Some(result) = exact.filter(|result| (min..=max).contains(result)) {
+            return Ok(result);
+        }
+        match operation.overflow().unwrap_or(Overflow::Checked) {
+            Overflow::Checked => Err(RuntimeError::new(format!(
+                "'{left} {} {right}' overflowed {}",
+                operator.token().source_text(),
+                formatter::type_name(&self.integer_type)
+            ))),
+            Overflow::Wrapping => {
+                // Wrapping in `i128` keeps the low bits, which are all that wrapping in a narrower type keeps too.
+                let wrapped = match operator {
+                    BinaryOperator::Add => left.wrapping_add(right),
+                    BinaryOperator::Subtract => left.wrapping_sub(right),
+                    BinaryOperator::Multiply => left.wrapping_mul(right),
+                    BinaryOperator::Divide => left.wrapping_div(right),
+                    BinaryOperator::Remainder => left.wrapping_rem(right),
+                };
+                Ok(min + wrapped.wrapping_sub(min).rem_euclid(max - min + 1))
+            }
+            Overflow::Saturating => {
+                let saturated = match operator {
+                    BinaryOperator::Add => left.saturating_add(right),
+                    BinaryOperator::Subtract => left.saturating_sub(right),
+                    BinaryOperator::Multiply => left.saturating_mul(right),
+                    BinaryOperator::Divide => left.saturating_div(right),
+                    BinaryOperator::Remainder => left.wrapping_rem(right),
+                };
+                Ok(saturated.clamp(min, max))
+            }
+        }
+    }
+
+    fn map_literal(&mut self, literal: &MapLiteral) -> Result<Value, RuntimeError> {
+        let mut entries: Vec<(Value, Value)> = Vec::new();
+        for (key, value) in literal.keys().iter().zip(literal.values()) {
+            let key = self.evaluate(key.as_ref())?;
+            let value = self.evaluate(value.as_ref())?;
+            // A key written twice keeps its first position, but takes the last value.
+            match entries.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, existing)) => *existing = value,
+                None => entries.push((key, value)),
+            }
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn index(&mut self, index: &Index) -> Result<Value, RuntimeError> {
+        let collection = self.evaluate(index.collection())?;
+        let position = self.evaluate(index.index())?;
+        match (collection, position) {
+            (Value::List(elements), Value::Integer(position)) => usize::try_from(position)
+                .ok()
+                .and_then(|position| elements.get(position).cloned())
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "Index {position} is out of bounds for a list of length {}",
+                        elements.len()
+                    ))
+                }),
+            // Strings are indexed by byte, as they are stored.
+            (Value::String(string), Value::Integer(position)) => usize::try_from(position)
+                .ok()
+                .and_then(|position| string.as_bytes().get(position))
+                .map(|byte| Value::Integer((*byte).into()))
+                .ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "Index {position} is out of bounds for a string of length {}",
+                        string.len()
+                    ))
+                }),
+            (Value::Map(entries), key) => entries
+                .into_iter()
+                .find(|(existing, _)| *existing == key)
+                .map(|(_, value)| value)
+                .ok_or_else(|| RuntimeError::new(format!("The map has no entry for {key}"))),
+            // Semantic analysis has already checked what is indexed with what.
+            _ => Err(RuntimeError::new(
+                "Only lists, maps and strings can be indexed",
+            )),
+        }
+    }
+
+    fn unwrap(&mut self, unwrap: &Unwrap) -> Result<Value, RuntimeError> {
+        let missing = match self.evaluate(unwrap.option())? {
+            Value::Option(Some(value)) | Value::Result(Ok(value)) => return Ok(*value),
+            missing @ (Value::Option(None) | Value::Result(Err(_))) => missing,
+            // Semantic analysis has already checked that only options and results are unwrapped.
+            _ => {
+                return Err(RuntimeError::new(
+                    "Only options and results can be unwrapped",
+                ))
+            }
+        };
+        match unwrap.default() {
+            Some(default) => self.evaluate(default),
+            None => Err(RuntimeError::new(format!(
+                "unwrapped `{missing}`: {}",
+                unwrap.message()
+            ))),
+        }
+    }
+
+    /// Evaluates `value?`, which returns an `err` from the running function.
+    fn propagate(&mut self, propagate: &Propagate) -> Result<Value, RuntimeError> {
+        match self.evaluate(propagate.value())? {
+            Value::Result(Ok(value)) => Ok(*value),
+            error @ Value::Result(Err(_)) => {
+                // Nothing else can stop every statement between here and the function, so the return is passed up
+                // as an error which `run` recognises.
+                self.returning = Some(error);
+                Err(RuntimeError::new("'?' returned from the function"))
+            }
+            // Semantic analysis has already checked that only results are propagated.
+            _ => Err(RuntimeError::new("Only results can be propagated with '?'")),
+        }
+    }
+
+    fn unsupported(&mut self, what: &str) {
+        self.result = Err(RuntimeError::new(format!("{what} can't be evaluated")));
+    }
+}
+
+impl AstVisitor for Evaluator<'_> {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        // A block's value is that of its last statement.
+        let mut value = Value::Unit;
+        for statement in list {
+            match self
+                .before_statement(statement.as_ref())
+                .and_then(|()| self.evaluate(statement.as_ref()))
+            {
+                Ok(statement_value) => value = statement_value,
+                Err(error) => {
+                    self.result = Err(error);
+                    return;
+                }
+            }
+        }
+        self.result = Ok(value);
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        let integer_type = variable
+            .variable_type()
+            .downcast_ref::<Type>()
+            .cloned()
+            .filter(Type::is_integer)
+            .unwrap_or_else(|| self.integer_type.clone());
+        let outer_type = std::mem::replace(&mut self.integer_type, integer_type);
+        let value = self.evaluate(variable.value());
+        self.integer_type = outer_type;
+        self.result = value.map(|value| {
+            self.variables.insert(variable.name(), value);
+            Value::Unit
+        });
+    }
+    fn visit_type(&mut self, _type_value: &Type) {
+        self.unsupported("A type");
+    }
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {
+        self.unsupported("A parameter declaration");
+    }
+    fn visit_function_definition(&mut self, _function: &FunctionDefinition) {
+        self.unsupported("A nested function");
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.result = self.evaluate(ignore_value.value()).map(|_| Value::Unit);
+    }
+    fn visit_integer_literal(&mut self, integer_literal: &i128) {
+        self.result = Ok(Value::Integer(*integer_literal));
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.result = self.binary_operation(operation);
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {
+        self.unsupported("An unresolved type intrinsic");
+    }
+    fn visit_module(&mut self, _module: &Module) {
+        self.unsupported("A module");
+    }
+    fn visit_macro_invocation(&mut self, invocation: &MacroInvocation) {
+        self.unsupported(&format!("The unexpanded macro '{}!'", invocation.name()));
+    }
+    fn visit_format(&mut self, format: &Format) {
+        self.result = self.format(format).map(Value::String);
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        if self.constant {
+            self.unsupported("A call to the runtime library");
+        } else {
+            self.result = self.call_runtime(call);
+        }
+    }
+    fn visit_static_assertion(&mut self, _assertion: &StaticAssertion) {
+        // These were checked during compilation.
+        self.result = Ok(Value::Unit);
+    }
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        self.result = self.check_assertion(assertion).map(|()| Value::Unit);
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.result = literal
+            .elements()
+            .iter()
+            .map(|element| self.evaluate(element.as_ref()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::List);
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.result = self.map_literal(literal);
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.result = self.index(index);
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.result = match literal.value() {
+            Some(value) => self
+                .evaluate(value)
+                .map(|value| Value::Option(Some(Box::new(value)))),
+            None => Ok(Value::Option(None)),
+        };
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.result = self.unwrap(unwrap);
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.result = self.evaluate(literal.value()).map(|value| {
+            Value::Result(match literal.variant() {
+                ResultVariant::Ok => Ok(Box::new(value)),
+                ResultVariant::Err => Err(Box::new(value)),
+            })
+        });
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.result = self.propagate(propagate);
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.result = self.call_method(call);
+    }
+    fn visit_enum_definition(&mut self, _definition: &EnumDefinition) {
+        self.unsupported("A nested enum");
+    }
+    fn visit_enum_variant(&mut self, _variant: &EnumVariant) {
+        self.unsupported("An enum variant");
+    }
+}
+
+/// Calls a function which takes no parameters and returns an integer, such as `main` or a test.
+///
+/// `file` is the index of the file defining the function, which the locations in errors' backtraces refer to.
+pub fn call(function: &FunctionDefinition, file: usize) -> Result<i128, RuntimeError> {
+    run(function, file, &mut Evaluator::new(false))
+}
+
+/// Calls a function like `call`, telling `hook` as it enters the function and reaches each statement.
+///
+/// `arguments` are what `args!()` gives the program, which has none when it is called with `call`.
+pub fn call_with_hook(
+    function: &FunctionDefinition,
+    file: usize,
+    arguments: &[String],
+    hook: &mut dyn EvaluationHook,
+) -> Result<i128, RuntimeError> {
+    let mut evaluator = Evaluator::new(false);
+    evaluator.arguments = arguments.to_vec();
+    evaluator.hook = Some(hook);
+    run(function, file, &mut evaluator)
+}
+
+fn run(
+    function: &FunctionDefinition,
+    file: usize,
+    evaluator: &mut Evaluator,
+) -> Result<i128, RuntimeError> {
+    let outer_type = std::mem::replace(
+        &mut evaluator.integer_type,
+        function
+            .return_type()
+            .downcast_ref::<Type>()
+            .cloned()
+            .filter(Type::is_integer)
+            .unwrap_or(Type::I32),
+    );
+    let caller = evaluator.running.replace(Running {
+        function: function.name(),
+        file,
+        statement: None,
+        tail: tail_of(function),
+    });
+    if let Some(hook) = &mut evaluator.hook {
+        hook.enter_function(function.name());
+    }
+    let mut result = evaluator.evaluate(function.body());
+    if let Some(returned) = evaluator.returning.take() {
+        result = Ok(returned);
+    }
+    if let Some(hook) = &mut evaluator.hook {
+        hook.exit_function(function.name());
+    }
+    evaluator.integer_type = outer_type;
+    let running = std::mem::replace(&mut evaluator.running, caller)
+        .expect("The function was running until now");
+    let result = match result {
+        Ok(Value::Integer(value)) => Ok(value),
+        Ok(_) => Err(RuntimeError::new(format!(
+            "'{}' finished without returning an integer",
+            function.name()
+        ))),
+        Err(error) => Err(error),
+    };
+    result.map_err(|mut error| {
+        error.backtrace.push(BacktraceFrame {
+            function: running.function,
+            location: running.location(),
+        });
+        error
+    })
+}
+
+/// Evaluates an expression at compile time, failing if it isn't a constant integer.
+pub fn evaluate_constant(expression: &dyn AstNode) -> Result<i128, RuntimeError> {
+    evaluate_constant_as(expression, &Type::I32)
+}
+
+/// Evaluates a constant like `evaluate_constant`, but with its arithmetic done in `integer_type` rather than `i32`.
+pub fn evaluate_constant_as(
+    expression: &dyn AstNode,
+    integer_type: &Type,
+) -> Result<i128, RuntimeError> {
+    let mut evaluator = Evaluator::new(true);
+    evaluator.integer_type = integer_type.clone();
+    evaluator.evaluate_integer(expression)
+}
@@ -0,0 +1,36 @@
+use crate::{
+    ast::{self, AstNode, Folder, Type, TypeIntrinsic, TypeIntrinsicKind},
+    layout,
+    session::Session,
+    target::Target,
+};
+
+/// Rebuilds a tree with every `TypeIntrinsic` replaced by the integer it evaluates to on the target.
+struct IntrinsicResolver<'target> {
+    target: &'target Target,
+}
+
+impl Folder for IntrinsicResolver<'_> {
+    fn fold_node(&mut self, node: &dyn AstNode) -> Option<Box<dyn AstNode>> {
+        let intrinsic = node.downcast_ref::<TypeIntrinsic>()?;
+        let type_value = intrinsic
+            .type_value()
+            .downcast_ref::<Type>()
+            .cloned()
+            .expect("The parser only accepts types in intrinsics");
+        let layout = layout::layout_of(&type_value, self.target);
+        let value = match intrinsic.kind() {
+            TypeIntrinsicKind::Size => layout.size(),
+            TypeIntrinsicKind::Alignment => layout.alignment(),
+        };
+        Some(Box::new(value as i128))
+    }
+}
+
+/// Evaluates every `sizeof!` and `alignof!` in the program for the session's target.
+pub fn resolve_intrinsics(program: &dyn AstNode, session: &Session) -> Box<dyn AstNode> {
+    let mut resolver = IntrinsicResolver {
+        target: session.target(),
+    };
+    ast::fold(&mut resolver, program)
+}
@@ -0,0 +1,149 @@
+use crate::{ast::Type, target::Target};
+
+/// The size and alignment of a type, in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Layout {
+    size: u64,
+    alignment: u64,
+}
+
+/// The layout of a struct along with the offset of each of its fields.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+    layout: Layout,
+    field_offsets: Vec<u64>,
+}
+
+/// The layout of an enum: a discriminant followed by storage for the largest variant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EnumLayout {
+    layout: Layout,
+    discriminant: Layout,
+    payload_offset: u64,
+}
+
+fn align_to(offset: u64, alignment: u64) -> u64 {
+    offset.next_multiple_of(alignment)
+}
+
+impl Layout {
+    pub fn new(size: u64, alignment: u64) -> Self {
+        assert!(
+            alignment.is_power_of_two(),
+            "Alignment must be a power of two"
+        );
+        Self { size, alignment }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn alignment(&self) -> u64 {
+        self.alignment
+    }
+
+    fn pointer(target: &Target) -> Self {
+        let pointer_size = target.pointer_width() as u64 / 8;
+        Self::new(pointer_size, pointer_size)
+    }
+
+    /// Lays out `fields` in declaration order, inserting padding so each one is correctly aligned.
+    /// The size is rounded up to the alignment so that arrays of the struct stay aligned.
+    pub fn of_struct(fields: &[Layout]) -> StructLayout {
+        let mut offset = 0;
+        let mut alignment = 1;
+        let mut field_offsets = Vec::with_capacity(fields.len());
+        for field in fields {
+            offset = align_to(offset, field.alignment);
+            field_offsets.push(offset);
+            offset += field.size;
+            alignment = alignment.max(field.alignment);
+        }
+        StructLayout {
+            layout: Self::new(align_to(offset, alignment), alignment),
+            field_offsets,
+        }
+    }
+
+    pub fn of_array(element: Layout, length: u64) -> Self {
+        Self::new(
+            align_to(element.size, element.alignment) * length,
+            element.alignment,
+        )
+    }
+
+    /// Lays out an enum with the given discriminant type and variant payloads.
+    pub fn of_enum(discriminant: Layout, variants: &[Layout]) -> EnumLayout {
+        let payload_alignment = variants.iter().map(Layout::alignment).max().unwrap_or(1);
+        let payload_size = variants.iter().map(Layout::size).max().unwrap_or(0);
+        let payload_offset = align_to(discriminant.size, payload_alignment);
+        let alignment = discriminant.alignment.max(payload_alignment);
+        EnumLayout {
+            layout: Self::new(
+                align_to(payload_offset + payload_size, alignment),
+                alignment,
+            ),
+            discriminant,
+            payload_offset,
+        }
+    }
+}
+
+impl StructLayout {
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn field_offsets(&self) -> &[u64] {
+        &self.field_offsets
+    }
+}
+
+impl EnumLayout {
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn discriminant(&self) -> Layout {
+        self.discriminant
+    }
+
+    pub fn payload_offset(&self) -> u64 {
+        self.payload_offset
+    }
+}
+
+pub fn layout_of(type_value: &Type, target: &Target) -> Layout {
+    match type_value {
+        Type::I8 | Type::U8 | Type::Bool => Layout::new(1, 1),
+        Type::I16 | Type::U16 => Layout::new(2, 2),
+        Type::I32 | Type::U32 | Type::F32 => Layout::new(4, 4),
+        // Chars are Unicode scalar values, stored as their code points, which need 21 bits.
+        Type::Char => Layout::new(4, 4),
+        Type::I64 | Type::U64 | Type::F64 => Layout::new(8, 8),
+        Type::Iptr | Type::Uptr => Layout::pointer(target),
+        // Strings are a pointer to UTF-8 bytes followed by their length in bytes.
+        Type::String => {
+            let pointer = Layout::pointer(target);
+            Layout::of_struct(&[pointer, pointer]).layout()
+        }
+        // Lists are a pointer to the elements followed by a length and a capacity, like `HematiteList`.
+        Type::List(_) => {
+            let pointer = Layout::pointer(target);
+            Layout::of_struct(&[pointer, pointer, pointer]).layout()
+        }
+        // Maps are a pointer to a table the runtime library manages.
+        Type::Map(_, _) => Layout::pointer(target),
+        // Options are a one byte flag, which is 1 for `some`, followed by the value.
+        Type::Option(value_type) => {
+            Layout::of_enum(Layout::new(1, 1), &[layout_of(value_type, target)]).layout()
+        }
+        // Results are a one byte flag, which is 1 for `ok`, followed by the value or the error.
+        Type::Result(value_type, error_type) => Layout::of_enum(
+            Layout::new(1, 1),
+            &[layout_of(value_type, target), layout_of(error_type, target)],
+        )
+        .layout(),
+    }
+}
@@ -1,12 +1,61 @@
-use std::{
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
     fmt::{self, Display, Formatter},
     iter::Peekable,
 };
 
+use crate::{
+    ast::Type,
+    diagnostics::{Diagnostic, DiagnosticEmitter, Location},
+    error_codes,
+    source::SourceMap,
+    symbol::Symbol,
+};
+
+/// A range of bytes in a source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The empty span just after this one.
+    pub fn following(&self) -> Self {
+        Self::new(self.end, self.end)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
-    Identifier(String),
-    MacroCall(String),
+    Identifier(Symbol),
+    MacroCall(Symbol),
     Integer(i128),
     Float(f64),
     StringLiteral(String),
@@ -26,13 +75,22 @@ pub enum Token {
     Star,
     Slash,
     Percent,
+    Less,
+    Greater,
+    Question,
     Arrow,
     Equals,
+    At,
     Function,
+    Enum,
     Let,
     Mut,
     If,
     Else,
+    NoneKeyword,
+    SomeKeyword,
+    OkKeyword,
+    ErrKeyword,
     I8,
     I16,
     I32,
@@ -48,6 +106,12 @@ pub enum Token {
     Bool,
     CharType,
     StringType,
+    List,
+    Map,
+    OptionType,
+    ResultType,
+    /// A `///` comment, without the slashes.
+    DocComment(String),
 
     Error(String),
 }
@@ -61,186 +125,176 @@ impl Display for Token {
             Token::Float(value) => write!(f, "'{value}'"),
             Token::StringLiteral(s) => write!(f, "'\"{s}\"'"),
             Token::Char(c) => write!(f, "\"'{c}'\""),
-            Token::LeftParen => write!(f, "'('"),
-            Token::RightParen => write!(f, "')'"),
-            Token::LeftBrace => write!(f, "'{{'"),
-            Token::RightBrace => write!(f, "'}}'"),
-            Token::LeftBracket => write!(f, "'['"),
-            Token::RightBracket => write!(f, "']'"),
-            Token::Comma => write!(f, "','"),
-            Token::Dot => write!(f, "'.'"),
-            Token::Colon => write!(f, "':'"),
-            Token::Semicolon => write!(f, "';'"),
-            Token::Plus => write!(f, "'+'"),
-            Token::Minus => write!(f, "'-'"),
-            Token::Star => write!(f, "'*'"),
-            Token::Slash => write!(f, "'/'"),
-            Token::Percent => write!(f, "'%'"),
-            Token::Arrow => write!(f, "'->'"),
-            Token::Equals => write!(f, "'='"),
-            Token::Function => write!(f, "'function'"),
-            Token::Let => write!(f, "'let'"),
-            Token::Mut => write!(f, "'mut'"),
-            Token::If => write!(f, "'if'"),
-            Token::Else => write!(f, "'else'"),
-            Token::I8 => write!(f, "'i8'"),
-            Token::I16 => write!(f, "'i16'"),
-            Token::I32 => write!(f, "'i32'"),
-            Token::I64 => write!(f, "'i64'"),
-            Token::Iptr => write!(f, "'iptr'"),
-            Token::U8 => write!(f, "'u8'"),
-            Token::U16 => write!(f, "'u16'"),
-            Token::U32 => write!(f, "'u32'"),
-            Token::U64 => write!(f, "'u64'"),
-            Token::Uptr => write!(f, "'uptr'"),
-            Token::F32 => write!(f, "'f32'"),
-            Token::F64 => write!(f, "'f64'"),
-            Token::Bool => write!(f, "'bool'"),
-            Token::CharType => write!(f, "'char'"),
-            Token::StringType => write!(f, "'string'"),
+            Token::DocComment(_) => write!(f, "doc comment"),
             Token::Error(s) => write!(f, "'{s}'"),
+            token => write!(
+                f,
+                "'{}'",
+                token
+                    .exact_match_text()
+                    .expect("Every other token is in the exact match table")
+            ),
+        }
+    }
+}
+
+impl Token {
+    /// Whether an expression can start with this token.
+    pub fn can_start_expression(&self) -> bool {
+        match self {
+            Token::Identifier(_)
+            | Token::MacroCall(_)
+            | Token::Integer(_)
+            | Token::Float(_)
+            | Token::StringLiteral(_)
+            | Token::Char(_) => true,
+            _ => self.exact_match_starts_expression(),
+        }
+    }
+
+    /// How the token is written in source code.
+    pub fn source_text(&self) -> String {
+        match self {
+            Token::Identifier(name) => name.to_string(),
+            Token::Error(text) => text.clone(),
+            Token::MacroCall(name) => format!("{name}!"),
+            Token::Integer(value) => value.to_string(),
+            Token::Float(value) => format!("{value:?}"),
+            Token::StringLiteral(value) => format!("\"{value}\""),
+            Token::Char(value) => format!("'{value}'"),
+            Token::DocComment(text) => format!("/// {text}"),
+            _ => self
+                .exact_match_text()
+                .expect("Every other token is in the exact match table")
+                .to_string(),
         }
     }
 }
 
+/// Recognises one kind of token a character at a time.
+///
+/// Parsers only keep track of where they are in the token; the characters themselves are collected once by the
+/// `TokenIterator` and passed to `complete`. Each parser is created once per `TokenIterator` and reset at the start
+/// of every token, so lexing doesn't allocate anything for tokens which don't own any text.
 trait TokenParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>>;
+    /// Starts again from the beginning of a token.
+    fn reset(&mut self);
+    /// Moves past `character` and returns true if it can come next in the token.
+    /// Otherwise returns false and leaves the parser as it was, so that it can still be completed.
+    fn accept(&mut self, character: char) -> bool;
     /// Return `Some(Token)` if complete, otherwise None.
     /// This function will be called if this token parser returns false in the last round where there are any possibilities left.
-    fn complete(&self) -> Option<Token>;
+    /// `text` is every character this parser has accepted.
+    fn complete(&self, text: &str) -> Option<Token>;
 }
 
 struct IdentifierParser {
-    so_far: String,
+    started: bool,
 }
 
 impl IdentifierParser {
     fn new() -> Self {
-        Self {
-            so_far: String::new(),
-        }
+        Self { started: false }
     }
 }
 
 impl TokenParser for IdentifierParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
-        if character.is_alphabetic()
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+    fn accept(&mut self, character: char) -> bool {
+        let accepted = character.is_alphabetic()
             || character == '_'
-            || (!self.so_far.is_empty() && character.is_ascii_digit())
-        {
-            Some(Box::new(IdentifierParser {
-                so_far: format!("{}{}", self.so_far, character),
-            }))
-        } else {
-            None
-        }
+            || (self.started && character.is_ascii_digit());
+        self.started |= accepted;
+        accepted
     }
-    fn complete(&self) -> Option<Token> {
-        Some(Token::Identifier(self.so_far.clone()))
+    fn complete(&self, text: &str) -> Option<Token> {
+        Some(Token::Identifier(Symbol::intern(text)))
     }
 }
 
 struct MacroCallParser {
-    so_far: String,
     found_bang: bool,
 }
 
 impl MacroCallParser {
     fn new() -> Self {
-        Self {
-            so_far: String::new(),
-            found_bang: false,
-        }
+        Self { found_bang: false }
     }
 }
 
 impl TokenParser for MacroCallParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+    fn accept(&mut self, character: char) -> bool {
         if self.found_bang {
-            None
+            false
         } else if character.is_alphanumeric() || character == '_' {
-            Some(Box::new(MacroCallParser {
-                so_far: format!("{}{}", self.so_far, character),
-                found_bang: false,
-            }))
+            true
         } else if character == '!' {
-            Some(Box::new(MacroCallParser {
-                so_far: self.so_far.clone(),
-                found_bang: true,
-            }))
+            self.found_bang = true;
+            true
         } else {
-            None
+            false
         }
     }
-    fn complete(&self) -> Option<Token> {
+    fn complete(&self, text: &str) -> Option<Token> {
         if self.found_bang {
-            Some(Token::MacroCall(self.so_far.clone()))
+            Some(Token::MacroCall(Symbol::intern(
+                text.strip_suffix('!').unwrap_or(text),
+            )))
         } else {
             None
         }
     }
 }
 
-struct IntegerParser {
-    so_far: String,
-}
-
-impl IntegerParser {
-    fn new() -> Self {
-        Self {
-            so_far: String::new(),
-        }
-    }
-}
+struct IntegerParser;
 
 impl TokenParser for IntegerParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
-        if character.is_numeric() {
-            Some(Box::new(IntegerParser {
-                so_far: format!("{}{}", self.so_far, character),
-            }))
-        } else {
-            None
-        }
+    fn reset(&mut self) {}
+    fn accept(&mut self, character: char) -> bool {
+        character.is_ascii_digit()
     }
-    fn complete(&self) -> Option<Token> {
-        Some(Token::Integer(self.so_far.parse().unwrap()))
+    fn complete(&self, text: &str) -> Option<Token> {
+        // Only digits are accepted, so the literal can only fail to parse by being too large.
+        Some(text.parse().map_or_else(
+            |_| Token::Error(format!("Integer literal is too large: {text}")),
+            Token::Integer,
+        ))
     }
 }
 
 struct FloatParser {
-    so_far: String,
     found_dot: bool,
 }
 
 impl FloatParser {
     fn new() -> Self {
-        Self {
-            so_far: String::new(),
-            found_dot: false,
-        }
+        Self { found_dot: false }
     }
 }
 
 impl TokenParser for FloatParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
-        if character.is_numeric() {
-            Some(Box::new(FloatParser {
-                so_far: format!("{}{}", self.so_far, character),
-                found_dot: self.found_dot,
-            }))
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+    fn accept(&mut self, character: char) -> bool {
+        if character.is_ascii_digit() {
+            true
         } else if character == '.' && !self.found_dot {
-            Some(Box::new(FloatParser {
-                so_far: format!("{}{}", self.so_far, character),
-                found_dot: true,
-            }))
+            self.found_dot = true;
+            true
         } else {
-            None
+            false
         }
     }
-    fn complete(&self) -> Option<Token> {
+    fn complete(&self, text: &str) -> Option<Token> {
         if self.found_dot {
-            Some(Token::Float(self.so_far.parse().unwrap()))
+            // A lone `.` has no digits, so it isn't a float.
+            text.parse().ok().map(Token::Float)
         } else {
             None
         }
@@ -248,7 +302,6 @@ impl TokenParser for FloatParser {
 }
 
 struct StringParser {
-    so_far: String,
     found_initial_quote: bool,
     found_terminal_quote: bool,
     next_character_is_escaped: bool,
@@ -257,7 +310,6 @@ struct StringParser {
 impl StringParser {
     fn new() -> Self {
         Self {
-            so_far: String::new(),
             found_initial_quote: false,
             found_terminal_quote: false,
             next_character_is_escaped: false,
@@ -266,91 +318,202 @@ impl StringParser {
 }
 
 impl TokenParser for StringParser {
-    fn accept(&self, character: char) -> Option<Box<dyn TokenParser>> {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+    fn accept(&mut self, character: char) -> bool {
         if self.found_terminal_quote {
-            return None;
+            return false;
         }
         if !self.found_initial_quote {
-            if character == '"' {
-                Some(Box::new(StringParser {
-                    so_far: self.so_far.clone(),
-                    found_initial_quote: true,
-                    found_terminal_quote: false,
-                    next_character_is_escaped: false,
-                }))
-            } else {
-                None
-            }
-        } else if character == '"' && !self.next_character_is_escaped {
-            Some(Box::new(StringParser {
-                so_far: self.so_far.clone(),
-                found_initial_quote: true,
-                found_terminal_quote: true,
-                next_character_is_escaped: false,
-            }))
+            self.found_initial_quote = character == '"';
+            self.found_initial_quote
         } else {
-            Some(Box::new(StringParser {
-                so_far: format!("{}{}", self.so_far, character),
-                found_initial_quote: true,
-                found_terminal_quote: false,
-                next_character_is_escaped: character == '\\',
-            }))
+            if character == '"' && !self.next_character_is_escaped {
+                self.found_terminal_quote = true;
+            }
+            self.next_character_is_escaped = character == '\\';
+            true
         }
     }
-    fn complete(&self) -> Option<Token> {
+    fn complete(&self, text: &str) -> Option<Token> {
         if self.found_terminal_quote {
-            Some(Token::StringLiteral(self.so_far.clone()))
+            // Both quotes are one byte long.
+            Some(Token::StringLiteral(text[1..text.len() - 1].to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+struct DocCommentParser {
+    slashes: usize,
+}
+
+impl DocCommentParser {
+    fn new() -> Self {
+        Self { slashes: 0 }
+    }
+}
+
+impl TokenParser for DocCommentParser {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+    fn accept(&mut self, character: char) -> bool {
+        if self.slashes < 3 {
+            let accepted = character == '/';
+            if accepted {
+                self.slashes += 1;
+            }
+            accepted
+        } else {
+            character != '\n'
+        }
+    }
+    fn complete(&self, text: &str) -> Option<Token> {
+        if self.slashes == 3 {
+            // The space conventionally written after the slashes isn't part of the text.
+            let text = &text["///".len()..];
+            let text = text.strip_prefix(' ').unwrap_or(text);
+            Some(Token::DocComment(text.trim_end().to_string()))
         } else {
             None
         }
     }
 }
 
-helper_macros::exact_match_token! {LeftParen: "("}
-helper_macros::exact_match_token! {RightParen: ")"}
-helper_macros::exact_match_token! {LeftBrace: "{"}
-helper_macros::exact_match_token! {RightBrace: "}"}
-helper_macros::exact_match_token! {LeftBracket: "["}
-helper_macros::exact_match_token! {RightBracket: "]"}
-helper_macros::exact_match_token! {Comma: ","}
-helper_macros::exact_match_token! {Dot: "."}
-helper_macros::exact_match_token! {Colon: ":"}
-helper_macros::exact_match_token! {Semicolon: ";"}
-helper_macros::exact_match_token! {Plus: "+"}
-helper_macros::exact_match_token! {Minus: "-"}
-helper_macros::exact_match_token! {Star: "*"}
-helper_macros::exact_match_token! {Slash: "/"}
-helper_macros::exact_match_token! {Percent: "%"}
-helper_macros::exact_match_token! {Arrow: "->"}
-helper_macros::exact_match_token! {Equals: "="}
-helper_macros::exact_match_token! {Function: "function"}
-helper_macros::exact_match_token! {Let: "let"}
-helper_macros::exact_match_token! {Mut: "mut"}
-helper_macros::exact_match_token! {If: "if"}
-helper_macros::exact_match_token! {Else: "else"}
-helper_macros::exact_match_token! {I8: "i8"}
-helper_macros::exact_match_token! {I16: "i16"}
-helper_macros::exact_match_token! {I32: "i32"}
-helper_macros::exact_match_token! {I64: "i64"}
-helper_macros::exact_match_token! {Iptr: "iptr"}
-helper_macros::exact_match_token! {U8: "u8"}
-helper_macros::exact_match_token! {U16: "u16"}
-helper_macros::exact_match_token! {U32: "u32"}
-helper_macros::exact_match_token! {U64: "u64"}
-helper_macros::exact_match_token! {Uptr: "uptr"}
-helper_macros::exact_match_token! {F32: "f32"}
-helper_macros::exact_match_token! {F64: "f64"}
-helper_macros::exact_match_token! {Bool: "bool"}
-helper_macros::exact_match_token! {CharType: "char"}
-helper_macros::exact_match_token! {StringType: "string"}
+helper_macros::exact_match_tokens! {
+    // Keywords come first so that they are preferred over identifiers.
+    Function: "function",
+    Enum: "enum",
+    Let: "let",
+    Mut: "mut",
+    If: "if",
+    Else: "else",
+    // Named so that they don't hide `Option`'s variants where the tokens are imported.
+    NoneKeyword: "none" { starts_expression },
+    SomeKeyword: "some" { starts_expression },
+    OkKeyword: "ok" { starts_expression },
+    ErrKeyword: "err" { starts_expression },
+    I8: "i8" { type_keyword: I8 },
+    I16: "i16" { type_keyword: I16 },
+    I32: "i32" { type_keyword: I32 },
+    I64: "i64" { type_keyword: I64 },
+    Iptr: "iptr" { type_keyword: Iptr },
+    U8: "u8" { type_keyword: U8 },
+    U16: "u16" { type_keyword: U16 },
+    U32: "u32" { type_keyword: U32 },
+    U64: "u64" { type_keyword: U64 },
+    Uptr: "uptr" { type_keyword: Uptr },
+    F32: "f32" { type_keyword: F32 },
+    F64: "f64" { type_keyword: F64 },
+    Bool: "bool" { type_keyword: Bool },
+    CharType: "char" { type_keyword: Char },
+    StringType: "string" { type_keyword: String },
+    // Collection types take parameters, so the parser builds them rather than `type_keyword`.
+    List: "list",
+    Map: "map",
+    OptionType: "option",
+    ResultType: "result",
+    LeftParen: "(" { starts_expression },
+    RightParen: ")",
+    LeftBrace: "{",
+    RightBrace: "}",
+    LeftBracket: "[" { starts_expression },
+    RightBracket: "]",
+    Comma: ",",
+    Dot: ".",
+    Colon: ":",
+    Semicolon: ";",
+    Plus: "+",
+    Minus: "-",
+    Star: "*",
+    Slash: "/",
+    Percent: "%",
+    Arrow: "->",
+    Less: "<",
+    Greater: ">",
+    Question: "?",
+    Equals: "=",
+    At: "@",
+}
 
 pub struct TokenIterator<'base_iterator> {
     base_iterator: Peekable<&'base_iterator mut dyn Iterator<Item = char>>,
+    /// The byte offset of the next character in the input.
+    offset: usize,
     found_invalid_token: bool,
+    /// A parser for every kind of token, in the order they are preferred.
+    parsers: Vec<Box<dyn TokenParser>>,
+    /// The indices of the parsers which have accepted every character of the current token, and of the ones which
+    /// accept the next character as well. They are kept between tokens so that they don't have to be allocated again.
+    possibilities: Vec<usize>,
+    new_possibilities: Vec<usize>,
+    /// The characters of the current token.
+    characters_read_so_far: String,
+}
+
+impl TokenIterator<'_> {
+    fn next_character(&mut self) -> Option<char> {
+        let character = self.base_iterator.next()?;
+        self.offset += character.len_utf8();
+        Some(character)
+    }
+
+    /// Makes every parser possible again, having accepted the characters read so far.
+    fn start_parsers(&mut self) {
+        for parser in &mut self.parsers {
+            parser.reset();
+        }
+        self.possibilities.clear();
+        self.possibilities.extend(0..self.parsers.len());
+        for character in self.characters_read_so_far.chars() {
+            self.possibilities
+                .retain(|&possibility| self.parsers[possibility].accept(character));
+        }
+    }
+
+    /// Lexes most identifiers, keywords and macro calls with a single keyword lookup, instead of trying every parser
+    /// on each character. Anything else is left to the parsers, which are started on whatever has been read.
+    fn identifier_or_keyword(&mut self) -> Option<Token> {
+        if !self
+            .base_iterator
+            .peek()
+            .is_some_and(|&character| character.is_alphabetic() || character == '_')
+        {
+            self.start_parsers();
+            return None;
+        }
+        while let Some(&character) = self.base_iterator.peek().filter(|&&character| {
+            character.is_alphabetic() || character == '_' || character.is_ascii_digit()
+        }) {
+            self.characters_read_so_far.push(character);
+            self.next_character();
+        }
+        match self.base_iterator.peek() {
+            Some('!') => {
+                self.next_character();
+                Some(Token::MacroCall(Symbol::intern(
+                    &self.characters_read_so_far,
+                )))
+            }
+            // Only a macro call's name can have digits other than 0-9 in it, which is rare enough not to hurry.
+            Some(character) if character.is_alphanumeric() => {
+                self.start_parsers();
+                None
+            }
+            _ => Some(
+                Token::from_exact_match_text(&self.characters_read_so_far).unwrap_or_else(|| {
+                    Token::Identifier(Symbol::intern(&self.characters_read_so_far))
+                }),
+            ),
+        }
+    }
 }
 
 impl Iterator for TokenIterator<'_> {
-    type Item = Token;
+    type Item = (Token, Span);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.found_invalid_token {
@@ -362,92 +525,111 @@ impl Iterator for TokenIterator<'_> {
             .filter(|character| character.is_whitespace())
             .is_some()
         {
-            self.base_iterator.next();
+            self.next_character();
         }
-        let mut possibilities: Vec<Box<dyn TokenParser>> = vec![
-            Box::new(FunctionParser::new()),
-            Box::new(LetParser::new()),
-            Box::new(MutParser::new()),
-            Box::new(IfParser::new()),
-            Box::new(ElseParser::new()),
-            Box::new(I8Parser::new()),
-            Box::new(I16Parser::new()),
-            Box::new(I32Parser::new()),
-            Box::new(I64Parser::new()),
-            Box::new(IptrParser::new()),
-            Box::new(U8Parser::new()),
-            Box::new(U16Parser::new()),
-            Box::new(U32Parser::new()),
-            Box::new(U64Parser::new()),
-            Box::new(UptrParser::new()),
-            Box::new(F32Parser::new()),
-            Box::new(F64Parser::new()),
-            Box::new(BoolParser::new()),
-            Box::new(CharTypeParser::new()),
-            Box::new(StringTypeParser::new()),
-            Box::new(IdentifierParser::new()),
-            Box::new(MacroCallParser::new()),
-            Box::new(FloatParser::new()),
-            Box::new(IntegerParser::new()),
-            Box::new(StringParser::new()),
-            Box::new(LeftParenParser::new()),
-            Box::new(RightParenParser::new()),
-            Box::new(LeftBraceParser::new()),
-            Box::new(RightBraceParser::new()),
-            Box::new(LeftBracketParser::new()),
-            Box::new(RightBracketParser::new()),
-            Box::new(CommaParser::new()),
-            Box::new(DotParser::new()),
-            Box::new(ColonParser::new()),
-            Box::new(SemicolonParser::new()),
-            Box::new(PlusParser::new()),
-            Box::new(MinusParser::new()),
-            Box::new(StarParser::new()),
-            Box::new(SlashParser::new()),
-            Box::new(PercentParser::new()),
-            Box::new(ArrowParser::new()),
-            Box::new(EqualsParser::new()),
-        ];
-        let mut characters_read_so_far = String::new();
-        while let Some(next_character) = self.base_iterator.peek() {
-            let new_possibilities = possibilities
-                .iter()
-                .filter_map(|possibility| possibility.accept(*next_character))
-                .collect::<Vec<_>>();
-            if new_possibilities.is_empty() {
+        let start = self.offset;
+        self.characters_read_so_far.clear();
+        if let Some(token) = self.identifier_or_keyword() {
+            return Some((token, Span::new(start, self.offset)));
+        }
+        loop {
+            let next_character = self.base_iterator.peek().copied();
+            self.new_possibilities.clear();
+            if let Some(next_character) = next_character {
+                for &possibility in &self.possibilities {
+                    if self.parsers[possibility].accept(next_character) {
+                        self.new_possibilities.push(possibility);
+                    }
+                }
+            }
+            if self.new_possibilities.is_empty() {
                 // This means that we have read a complete token or the input is invalid.
-                if characters_read_so_far.is_empty() {
+                let error_span = Span::new(
+                    start,
+                    self.offset + next_character.map_or(0, char::len_utf8),
+                );
+                if self.characters_read_so_far.is_empty() {
+                    let next_character = next_character?;
                     self.found_invalid_token = true;
-                    return Some(Token::Error(
-                        format!("Invalid character: {next_character}",),
+                    return Some((
+                        Token::Error(format!("Invalid character: {next_character}",)),
+                        error_span,
                     ));
                 }
-                let mut completed_tokens = possibilities
-                    .iter()
-                    .filter_map(|possibility| possibility.complete());
+                // The parsers which didn't accept the next character are as they were before it.
+                let mut completed_tokens = self.possibilities.iter().filter_map(|&possibility| {
+                    self.parsers[possibility].complete(&self.characters_read_so_far)
+                });
                 // We just take the first one.
                 // This should mean (assuming I'm right that they keep their order) that placing keywords above identifier *should* work.
                 if let Some(completed_token) = completed_tokens.next() {
-                    return Some(completed_token);
+                    return Some((completed_token, Span::new(start, self.offset)));
                 } else {
                     self.found_invalid_token = true;
-                    return Some(Token::Error(format!(
-                        "Invalid token: {characters_read_so_far}{next_character}",
-                    )));
+                    if let Some(next_character) = next_character {
+                        self.characters_read_so_far.push(next_character);
+                    }
+                    return Some((
+                        Token::Error(format!("Invalid token: {}", self.characters_read_so_far)),
+                        error_span,
+                    ));
                 }
             } else {
-                possibilities = new_possibilities;
-                characters_read_so_far.push(*next_character);
-                self.base_iterator.next().unwrap();
+                core::mem::swap(&mut self.possibilities, &mut self.new_possibilities);
+                self.characters_read_so_far.push(next_character.unwrap());
+                self.next_character();
             }
         }
-        None
     }
 }
 
-pub fn tokenize(input: &mut dyn Iterator<Item = char>) -> TokenIterator {
+pub fn tokenize(input: &mut dyn Iterator<Item = char>) -> TokenIterator<'_> {
+    let mut parsers = exact_match_parsers();
+    parsers.extend([
+        Box::new(IdentifierParser::new()) as Box<dyn TokenParser>,
+        Box::new(MacroCallParser::new()),
+        Box::new(FloatParser::new()),
+        Box::new(IntegerParser),
+        Box::new(StringParser::new()),
+        Box::new(DocCommentParser::new()),
+    ]);
     TokenIterator {
         base_iterator: input.peekable(),
+        offset: 0,
         found_invalid_token: false,
+        possibilities: Vec::with_capacity(parsers.len()),
+        new_possibilities: Vec::with_capacity(parsers.len()),
+        parsers,
+        characters_read_so_far: String::new(),
+    }
+}
+
+/// A diagnostic for each invalid token in a file's tokens.
+pub fn invalid_token_diagnostics(
+    file: usize,
+    tokens: &[(Token, Span)],
+) -> impl Iterator<Item = Diagnostic> + '_ {
+    tokens.iter().filter_map(move |(token, span)| match token {
+        Token::Error(message) => Some(
+            Diagnostic::error(message.clone())
+                .with_code(error_codes::INVALID_TOKEN)
+                .with_location(Location::new(file, *span)),
+        ),
+        _ => None,
+    })
+}
+
+/// Reports every invalid token in a file's tokens, returning whether there were any.
+pub fn report_invalid_tokens(
+    file: usize,
+    tokens: &[(Token, Span)],
+    source_map: &SourceMap,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> bool {
+    let mut found_invalid_token = false;
+    for diagnostic in invalid_token_diagnostics(file, tokens) {
+        emitter.emit(diagnostic, source_map);
+        found_invalid_token = true;
     }
+    found_invalid_token
 }
@@ -0,0 +1,84 @@
+//! The Hematite compiler as a library, for tools which want to lex, parse or analyse Hematite programs themselves.
+//!
+//! The items re-exported here are the stable interface, which only changes in a new major version. The modules are
+//! public so that tools can reach the rest of the compiler, but their contents change along with it.
+//!
+//! Without the default `std` feature, only the front end is built: the lexer, the parser, the AST and the types
+//! they report diagnostics with. These need nothing beyond `alloc`, and never touch files or standard streams.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+pub mod ast;
+#[cfg(feature = "std")]
+pub mod completion;
+pub mod database;
+pub mod diagnostics;
+pub mod error_codes;
+pub mod features;
+pub mod format_string;
+pub mod formatter;
+pub mod grammar;
+pub mod highlight;
+#[cfg(feature = "std")]
+pub mod hover;
+#[cfg(feature = "std")]
+pub mod ice;
+pub mod incremental;
+#[cfg(feature = "std")]
+pub mod interpreter;
+#[cfg(feature = "std")]
+pub mod intrinsics;
+#[cfg(feature = "std")]
+pub mod layout;
+pub mod lexer;
+#[cfg(feature = "std")]
+pub mod macros;
+#[cfg(feature = "std")]
+pub mod methods;
+pub mod parser;
+pub mod query;
+#[cfg(feature = "std")]
+pub mod semantic;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+pub mod session;
+pub mod source;
+#[cfg(feature = "std")]
+pub mod statistics;
+pub mod suggestions;
+pub mod symbol;
+#[cfg(feature = "std")]
+pub mod target;
+
+pub use ast::{AstNode, AstVisitor};
+pub use diagnostics::{Diagnostic, Location, Severity};
+pub use features::{Feature, Features, LanguageVersion};
+pub use lexer::{Span, Token};
+pub use parser::{SyntaxError, TokenStream};
+pub use query::{find_function, items_of_kind, node_at_offset, NodeKind};
+#[cfg(feature = "std")]
+pub use session::Session;
+pub use source::{SourceFile, SourceMap};
+pub use symbol::Symbol;
+#[cfg(feature = "std")]
+pub use target::Target;
+
+/// Splits source code into tokens along with their spans.
+///
+/// Invalid input becomes `Token::Error` tokens rather than stopping the lexer, so this always succeeds.
+pub fn tokenize_str(source: &str) -> Vec<(Token, Span)> {
+    lexer::tokenize(&mut source.chars()).collect()
+}
+
+/// Parses source code into the list of items it contains, with only the stable language features enabled.
+///
+/// Diagnostics refer to the source as file 0.
+pub fn parse_str(source: &str) -> Result<Box<dyn AstNode>, Vec<Diagnostic>> {
+    let mut tokens = tokenize_str(source).into_iter();
+    parser::parse(&mut TokenStream::new(&mut tokens)).map_err(|error| vec![error.to_diagnostic(0)])
+}
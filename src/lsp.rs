@@ -0,0 +1,535 @@
+//! A language server speaking LSP over stdio, so that editors can show diagnostics and navigate programs.
+
+use std::{collections::HashMap, error::Error};
+
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as RequestTrait,
+        SemanticTokensFullRequest,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticSeverity, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, MarkupContent, MarkupKind, NumberOrString, OneOf, Position,
+    PublishDiagnosticsParams, Range, SemanticToken, SemanticTokenType, SemanticTokens,
+    SemanticTokensFullOptions, SemanticTokensLegend, SemanticTokensOptions, SemanticTokensParams,
+    SemanticTokensResult, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+
+use hematite_lang::{
+    ast::{AstNode, FunctionDefinition, Module},
+    completion::{self, CompletionKind},
+    diagnostics::{Diagnostic, DiagnosticCollector, DiagnosticEmitter, Severity},
+    highlight::{self, HighlightKind},
+    hover,
+    incremental::IncrementalParse,
+    intrinsics,
+    lexer::{self, Span, Token},
+    macros, methods, semantic,
+    source::{SourceFile, SourceMap},
+    Features, Session, Symbol, Target,
+};
+
+type LspResult<T> = Result<T, Box<dyn Error + Sync + Send>>;
+
+/// The semantic token types the server reports. Tokens refer to their type by its index here.
+const TOKEN_TYPES: &[SemanticTokenType] = &[
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::TYPE,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::PARAMETER,
+    SemanticTokenType::VARIABLE,
+    SemanticTokenType::ENUM_MEMBER,
+    SemanticTokenType::MACRO,
+    SemanticTokenType::DECORATOR,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::STRING,
+    SemanticTokenType::COMMENT,
+];
+
+/// An open file, as the editor currently has it.
+struct Document {
+    source: SourceFile,
+    /// Kept between edits so that only the parts of the document which change are parsed again. This is separate
+    /// from the query database watch mode uses, which the server hasn't been moved onto yet.
+    parse: IncrementalParse,
+    /// The functions defined by the document, or none if it doesn't parse.
+    functions: Vec<FunctionDefinition>,
+    /// The document's items with macros expanded and methods resolved, if that succeeds, for finding the types of
+    /// expressions.
+    analysed: Option<Box<dyn AstNode>>,
+}
+
+impl Document {
+    fn new(name: String, parse: IncrementalParse) -> (Self, Vec<Diagnostic>) {
+        let mut source_map = SourceMap::new();
+        // The document is analysed on its own, so it is always file 0.
+        source_map.add(SourceFile::new(name.clone(), parse.text().to_string()));
+        let mut collector = DiagnosticCollector::new();
+        let (functions, analysed) =
+            if lexer::report_invalid_tokens(0, parse.tokens(), &source_map, &mut collector) {
+                (Vec::new(), None)
+            } else {
+                match parse.program() {
+                    Ok(items) => {
+                        let module = Module::new(name, 0, dyn_clone::clone_box(items));
+                        if let Err(error) = semantic::resolve_names(&module) {
+                            collector.emit(error.to_diagnostic(), &source_map);
+                        }
+                        (
+                            semantic::collect_functions(&module),
+                            analyse(&module, &source_map),
+                        )
+                    }
+                    Err(error) => {
+                        collector.emit(error.to_diagnostic(0), &source_map);
+                        (Vec::new(), None)
+                    }
+                }
+            };
+        let source = source_map.files()[0].clone();
+        (
+            Self {
+                source,
+                parse,
+                functions,
+                analysed,
+            },
+            collector.into_diagnostics(),
+        )
+    }
+
+    /// Converts a byte offset to an LSP position, whose columns count UTF-16 code units.
+    fn position(&self, offset: usize) -> Position {
+        let text = self.source.text();
+        let offset = offset.min(text.len());
+        let line = self.source.line_index(offset);
+        let column = text[self.source.line_start(line)..offset]
+            .encode_utf16()
+            .count();
+        Position::new(line as u32, column as u32)
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        let line_start = self.source.line_start(position.line as usize);
+        offset_in_line(self.source.text(), line_start, position.character)
+    }
+
+    fn range(&self, span: Span) -> Range {
+        Range::new(self.position(span.start()), self.position(span.end()))
+    }
+
+    /// The name of the identifier at `position`, if there is one.
+    fn identifier_at(&self, position: Position) -> Option<Symbol> {
+        let offset = self.offset(position);
+        self.parse
+            .tokens()
+            .iter()
+            .find_map(|(token, span)| match token {
+                Token::Identifier(name) if span.start() <= offset && offset <= span.end() => {
+                    Some(*name)
+                }
+                _ => None,
+            })
+    }
+}
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn send_notification<N: NotificationTrait>(&self, params: N::Params) -> LspResult<()> {
+        let notification = Notification::new(N::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+        Ok(())
+    }
+
+    fn respond(&self, id: RequestId, result: serde_json::Value) -> LspResult<()> {
+        self.connection
+            .sender
+            .send(Message::Response(Response::new_ok(id, result)))?;
+        Ok(())
+    }
+
+    fn update_document(
+        &mut self,
+        uri: Url,
+        parse: IncrementalParse,
+        version: i32,
+    ) -> LspResult<()> {
+        let name = uri
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .and_then(|file_name| file_name.split('.').next())
+            .unwrap_or_default()
+            .to_string();
+        let (document, diagnostics) = Document::new(name, parse);
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| to_lsp_diagnostic(&document, diagnostic))
+            .collect();
+        self.documents.insert(uri.clone(), document);
+        self.send_notification::<PublishDiagnostics>(PublishDiagnosticsParams::new(
+            uri,
+            diagnostics,
+            Some(version),
+        ))
+    }
+
+    fn handle_notification(&mut self, notification: Notification) -> LspResult<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                let parse = IncrementalParse::new(document.text, Features::default());
+                self.update_document(document.uri, parse, document.version)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                let Some(document) = self.documents.remove(&uri) else {
+                    return Ok(());
+                };
+                let mut parse = document.parse;
+                // Each change's range refers to the text as the changes before it left it.
+                for change in params.content_changes {
+                    match change.range {
+                        Some(range) => {
+                            let start = offset(parse.text(), range.start);
+                            let end = offset(parse.text(), range.end);
+                            parse.edit(Span::new(start, end), &change.text);
+                        }
+                        None => parse = IncrementalParse::new(change.text, Features::default()),
+                    }
+                }
+                self.update_document(uri, parse, params.text_document.version)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                self.send_notification::<PublishDiagnostics>(PublishDiagnosticsParams::new(
+                    params.text_document.uri,
+                    Vec::new(),
+                    None,
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_request(&mut self, request: Request) -> LspResult<()> {
+        match request.method.as_str() {
+            DocumentSymbolRequest::METHOD => {
+                let params: DocumentSymbolParams = serde_json::from_value(request.params)?;
+                let symbols = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .map(document_symbols);
+                self.respond(request.id, serde_json::to_value(symbols)?)
+            }
+            GotoDefinition::METHOD => {
+                let params: GotoDefinitionParams = serde_json::from_value(request.params)?;
+                let location = self.definition(params);
+                self.respond(request.id, serde_json::to_value(location)?)
+            }
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(request.params)?;
+                let position = params.text_document_position_params;
+                let hover = self
+                    .documents
+                    .get(&position.text_document.uri)
+                    .and_then(|document| hover(document, position.position));
+                self.respond(request.id, serde_json::to_value(hover)?)
+            }
+            SemanticTokensFullRequest::METHOD => {
+                let params: SemanticTokensParams = serde_json::from_value(request.params)?;
+                let tokens = self
+                    .documents
+                    .get(&params.text_document.uri)
+                    .map(semantic_tokens);
+                self.respond(request.id, serde_json::to_value(tokens)?)
+            }
+            Completion::METHOD => {
+                let params: CompletionParams = serde_json::from_value(request.params)?;
+                let position = params.text_document_position;
+                let completions = self
+                    .documents
+                    .get(&position.text_document.uri)
+                    .map(|document| completions(document, position.position));
+                self.respond(request.id, serde_json::to_value(completions)?)
+            }
+            _ => {
+                let response = Response::new_err(
+                    request.id,
+                    lsp_server::ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request: {}", request.method),
+                );
+                self.connection.sender.send(Message::Response(response))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let name = self
+            .documents
+            .get(&position.text_document.uri)?
+            .identifier_at(position.position)?;
+        // Functions share one namespace across the program, so look in every open file.
+        self.documents.iter().find_map(|(uri, document)| {
+            let function = document
+                .functions
+                .iter()
+                .find(|function| function.name() == name)?;
+            Some(GotoDefinitionResponse::Scalar(lsp_types::Location::new(
+                uri.clone(),
+                document.range(function.name_span()),
+            )))
+        })
+    }
+
+    fn run(mut self) -> LspResult<()> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    self.handle_request(request)?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts an LSP position to a byte offset in `text`, for edits to a document whose line starts haven't been found
+/// yet.
+fn offset(text: &str, position: Position) -> usize {
+    let line_start = match position.line as usize {
+        0 => 0,
+        line => text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(text.len(), |(index, _)| index + 1),
+    };
+    offset_in_line(text, line_start, position.character)
+}
+
+/// The byte offset of the `column`th UTF-16 code unit of the line starting at `line_start`, or of the end of the line
+/// if it is shorter.
+fn offset_in_line(text: &str, line_start: usize, column: u32) -> usize {
+    let mut current_column = 0;
+    for (index, character) in text[line_start..].char_indices() {
+        if character == '\n' || current_column >= column as usize {
+            return line_start + index;
+        }
+        current_column += character.len_utf16();
+    }
+    text.len()
+}
+
+fn to_lsp_diagnostic(document: &Document, diagnostic: &Diagnostic) -> lsp_types::Diagnostic {
+    let range = diagnostic
+        .location()
+        .map_or_else(Range::default, |location| document.range(location.span()));
+    let severity = match diagnostic.severity() {
+        Severity::Error => DiagnosticSeverity::ERROR,
+        Severity::Warning => DiagnosticSeverity::WARNING,
+    };
+    lsp_types::Diagnostic {
+        range,
+        severity: Some(severity),
+        code: diagnostic
+            .code()
+            .map(|code| NumberOrString::String(code.to_string())),
+        source: Some("hematite".to_string()),
+        message: diagnostic.message().to_string(),
+        ..Default::default()
+    }
+}
+
+fn document_symbols(document: &Document) -> DocumentSymbolResponse {
+    let symbols = document
+        .functions
+        .iter()
+        .map(|function| {
+            let range = document.range(function.name_span());
+            // `deprecated` is itself deprecated, but still has to be given.
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: function.name().to_string(),
+                detail: None,
+                kind: SymbolKind::FUNCTION,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children: None,
+            }
+        })
+        .collect();
+    DocumentSymbolResponse::Nested(symbols)
+}
+
+/// Runs the passes which work out what macros expand to and which methods are called, so that the types of the
+/// expressions using them are known.
+fn analyse(module: &Module, source_map: &SourceMap) -> Option<Box<dyn AstNode>> {
+    // Only syntax and name errors are reported for now, so whatever the passes emit is dropped.
+    let emitter = Box::new(DiagnosticCollector::new());
+    let mut session = Session::new(Target::host(), Features::default(), emitter);
+    *session.source_map_mut() = source_map.clone();
+    let expanded = macros::expand_macros(module, &mut session).ok()?;
+    let resolved = intrinsics::resolve_intrinsics(expanded.as_ref(), &session);
+    methods::resolve_methods(resolved.as_ref()).ok()
+}
+
+fn hover(document: &Document, position: Position) -> Option<Hover> {
+    let hover = hover::hover(document.analysed.as_deref()?, document.offset(position))?;
+    let mut value = format!("```hematite\n{}\n```", hover.code());
+    if !hover.documentation().is_empty() {
+        value.push_str("\n\n");
+        value.push_str(&hover.documentation().join("\n"));
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(document.range(hover.span())),
+    })
+}
+
+/// The index in `TOKEN_TYPES` of the type of tokens of a kind.
+fn token_type(kind: HighlightKind) -> u32 {
+    let token_type = match kind {
+        HighlightKind::Keyword => SemanticTokenType::KEYWORD,
+        HighlightKind::Type => SemanticTokenType::TYPE,
+        HighlightKind::Function => SemanticTokenType::FUNCTION,
+        HighlightKind::Parameter => SemanticTokenType::PARAMETER,
+        HighlightKind::Local => SemanticTokenType::VARIABLE,
+        HighlightKind::Variant => SemanticTokenType::ENUM_MEMBER,
+        HighlightKind::Macro => SemanticTokenType::MACRO,
+        HighlightKind::Attribute => SemanticTokenType::DECORATOR,
+        HighlightKind::Number => SemanticTokenType::NUMBER,
+        HighlightKind::String => SemanticTokenType::STRING,
+        HighlightKind::Comment => SemanticTokenType::COMMENT,
+    };
+    TOKEN_TYPES
+        .iter()
+        .position(|known| *known == token_type)
+        .expect("Every token type is in the legend") as u32
+}
+
+fn semantic_tokens(document: &Document) -> SemanticTokensResult {
+    let tokens = document.parse.tokens();
+    let highlights = highlight::highlight(tokens, document.parse.program().ok());
+    let mut data = Vec::new();
+    let mut previous = Position::new(0, 0);
+    for (span, kind) in highlights {
+        let start = document.position(span.start());
+        let end = document.position(span.end());
+        // Clients don't have to support tokens spanning lines, which only strings can.
+        if start.line != end.line {
+            continue;
+        }
+        let delta_start = if start.line == previous.line {
+            start.character - previous.character
+        } else {
+            start.character
+        };
+        data.push(SemanticToken {
+            delta_line: start.line - previous.line,
+            delta_start,
+            length: end.character - start.character,
+            token_type: token_type(kind),
+            token_modifiers_bitset: 0,
+        });
+        previous = start;
+    }
+    SemanticTokensResult::Tokens(SemanticTokens {
+        result_id: None,
+        data,
+    })
+}
+
+fn completions(document: &Document, position: Position) -> CompletionResponse {
+    // Editors don't say which target a file is built for, so assume the prelude is there as it is by default.
+    let items = completion::completions(document.parse.tokens(), document.offset(position), true)
+        .into_iter()
+        .map(|completion| {
+            let (kind, insert_text) = match completion.kind() {
+                CompletionKind::Keyword => (CompletionItemKind::KEYWORD, None),
+                CompletionKind::Type => (CompletionItemKind::STRUCT, None),
+                CompletionKind::Macro => (
+                    CompletionItemKind::FUNCTION,
+                    Some(format!("{}!", completion.label())),
+                ),
+                CompletionKind::Method => (CompletionItemKind::METHOD, None),
+                CompletionKind::Attribute => (CompletionItemKind::PROPERTY, None),
+            };
+            CompletionItem {
+                label: completion.label().to_string(),
+                kind: Some(kind),
+                detail: completion.detail().map(str::to_string),
+                insert_text,
+                ..Default::default()
+            }
+        })
+        .collect();
+    CompletionResponse::Array(items)
+}
+
+/// Serves requests from an editor on stdin and stdout until it asks us to shut down.
+pub fn run() -> LspResult<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        semantic_tokens_provider: Some(
+            SemanticTokensOptions {
+                legend: SemanticTokensLegend {
+                    token_types: TOKEN_TYPES.to_vec(),
+                    token_modifiers: Vec::new(),
+                },
+                full: Some(SemanticTokensFullOptions::Bool(true)),
+                ..Default::default()
+            }
+            .into(),
+        ),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), ":".to_string(), "@".to_string()]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    Server {
+        connection,
+        documents: HashMap::new(),
+    }
+    .run()?;
+    io_threads.join()?;
+    Ok(())
+}
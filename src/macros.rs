@@ -0,0 +1,1004 @@
+//! Expansion of macro invocations into the code they stand for, between parsing and semantic analysis.
+
+use std::{
+    env::VarError,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::{
+        self, Assertion, AssertionKind, AstNode, BinaryOperation, BinaryOperator, Folder, Format,
+        FormatSegment, FormatSpec, MacroInvocation, Module, OptionLiteral, Overflow, RuntimeCall,
+        RuntimeFunction, StaticAssertion, TypeIntrinsic, TypeIntrinsicKind, Unwrap,
+    },
+    diagnostics::{Applicability, Diagnostic, Location, Suggestion},
+    error_codes,
+    format_string::{self, ArgumentReference, Piece},
+    formatter,
+    lexer::{Span, Token},
+    parser,
+    query::NodeKind,
+    semantic,
+    session::Session,
+    suggestions,
+};
+
+/// How deeply macros may expand into further macros, to stop runaway recursion.
+const RECURSION_LIMIT: usize = 64;
+
+/// What a macro's expansion may depend on besides its arguments.
+struct ExpansionContext<'session> {
+    /// The compilation, whose source map grows as files are included.
+    session: &'session mut Session,
+    /// The name of the module containing the invocation.
+    module: String,
+    /// The index of the source file containing the invocation.
+    file: usize,
+}
+
+impl ExpansionContext<'_> {
+    fn location(&self, invocation: &MacroInvocation) -> Location {
+        Location::new(self.file, invocation.span())
+    }
+
+    /// Where the invocation is, as `file:line`.
+    fn position(&self, invocation: &MacroInvocation) -> String {
+        let source_file = &self.session.source_map()[self.file];
+        let (line, _) = source_file.line_and_column(invocation.span().start());
+        format!("{}:{line}", source_file.name())
+    }
+
+    /// Resolves `path` relative to the directory of the file containing the invocation.
+    fn relative_path(&self, path: &str) -> PathBuf {
+        let including_file = Path::new(self.session.source_map()[self.file].name());
+        including_file.parent().unwrap_or(Path::new("")).join(path)
+    }
+
+    /// The source code of a non-empty argument, exactly as it was written.
+    fn source_text(&self, argument: &[(Token, Span)]) -> &str {
+        let start = argument[0].1.start();
+        let end = argument[argument.len() - 1].1.end();
+        &self.session.source_map()[self.file].text()[start..end]
+    }
+}
+
+type Expansion =
+    fn(&MacroInvocation, &mut ExpansionContext) -> Result<Box<dyn AstNode>, Diagnostic>;
+
+/// Expansions are hygienic because they never bind names of their own: every identifier in an expansion comes
+/// from the invocation's arguments, so it resolves exactly as it would at the call site. (`format!`'s named
+/// arguments are matched up during expansion and don't survive it.) A macro which introduces a binding would
+/// need its identifiers marked with the expansion they came from, so that they can't capture or shadow the
+/// caller's.
+const BUILTIN_MACROS: &[(&str, Expansion)] = &[
+    ("sizeof", expand_sizeof),
+    ("alignof", expand_alignof),
+    ("assert", expand_assert),
+    ("assert_eq", expand_assert_eq),
+    ("format", expand_format),
+    ("print", expand_print),
+    ("println", expand_println),
+    ("panic", expand_panic),
+    ("include", expand_include),
+    ("include_str", expand_include_str),
+    ("compile_error", expand_compile_error),
+    ("static_assert", expand_static_assert),
+    ("env", expand_env),
+    ("option_env", expand_option_env),
+    ("checked_add", expand_arithmetic),
+    ("checked_sub", expand_arithmetic),
+    ("checked_mul", expand_arithmetic),
+    ("wrapping_add", expand_arithmetic),
+    ("wrapping_sub", expand_arithmetic),
+    ("wrapping_mul", expand_arithmetic),
+    ("saturating_add", expand_arithmetic),
+    ("saturating_sub", expand_arithmetic),
+    ("saturating_mul", expand_arithmetic),
+    ("unwrap", expand_unwrap),
+    ("unwrap_or", expand_unwrap_or),
+    ("len", expand_runtime_call),
+    ("slice", expand_runtime_call),
+    ("chars", expand_runtime_call),
+    ("compare", expand_runtime_call),
+    ("parse_int", expand_runtime_call),
+    ("to_string", expand_runtime_call),
+    ("char_code", expand_runtime_call),
+    ("from_char_code", expand_runtime_call),
+    ("args", expand_runtime_call),
+    ("env_var", expand_runtime_call),
+];
+
+/// The macros every program can use without asking for them, unless the prelude is turned off with `--no-prelude`.
+///
+/// Types are keywords rather than names, so they are always in scope, and there are no traits yet; the prelude is
+/// otherwise what a program needs to print and check things, to get at the values in options and to work with
+/// strings.
+const PRELUDE: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "format",
+    "print",
+    "println",
+    "panic",
+    "unwrap",
+    "unwrap_or",
+    "len",
+    "slice",
+    "chars",
+    "compare",
+    "parse_int",
+    "to_string",
+    "char_code",
+    "from_char_code",
+];
+
+/// The names of the builtin macros a program can use, which leaves out the prelude's without `has_prelude`.
+pub fn macro_names(has_prelude: bool) -> impl Iterator<Item = &'static str> {
+    BUILTIN_MACROS
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(move |name| has_prelude || !PRELUDE.contains(name))
+}
+
+/// The macros which call into the runtime library, and so can't be used in freestanding programs.
+const RUNTIME_MACROS: &[&str] = &[
+    "assert",
+    "assert_eq",
+    "format",
+    "print",
+    "println",
+    "panic",
+    "checked_add",
+    "checked_sub",
+    "checked_mul",
+    "unwrap",
+    "len",
+    "slice",
+    "chars",
+    "compare",
+    "parse_int",
+    "to_string",
+    "char_code",
+    "from_char_code",
+    "args",
+    "env_var",
+];
+
+/// The invocation's arguments, split at the commas which aren't nested inside parentheses or brackets.
+fn split_arguments(invocation: &MacroInvocation) -> Vec<&[(Token, Span)]> {
+    let arguments = invocation.arguments();
+    if arguments.is_empty() {
+        return Vec::new();
+    }
+    let mut split = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    for (index, (token, _)) in arguments.iter().enumerate() {
+        match token {
+            Token::LeftParen | Token::LeftBracket => depth += 1,
+            Token::RightParen | Token::RightBracket => depth -= 1,
+            Token::Comma if depth == 0 => {
+                split.push(&arguments[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    // A trailing comma doesn't start another argument.
+    if start < arguments.len() {
+        split.push(&arguments[start..]);
+    }
+    split
+}
+
+/// Splits the invocation's arguments, failing unless there are exactly `count` of them.
+fn expect_arguments<'invocation>(
+    count: usize,
+    invocation: &'invocation MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<Vec<&'invocation [(Token, Span)]>, Diagnostic> {
+    let arguments = split_arguments(invocation);
+    if arguments.len() == count {
+        return Ok(arguments);
+    }
+    let plural = |count: usize| if count == 1 { "" } else { "s" };
+    Err(Diagnostic::error(format!(
+        "'{}!' takes {count} argument{} but {} {} given",
+        invocation.name(),
+        plural(count),
+        arguments.len(),
+        if arguments.len() == 1 { "was" } else { "were" }
+    ))
+    .with_code(error_codes::MACRO_ARGUMENT_COUNT)
+    .with_location(context.location(invocation)))
+}
+
+fn expand_type_intrinsic(
+    kind: TypeIntrinsicKind,
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let type_value = parser::parse_type_argument(invocation.arguments(), invocation.span())
+        .map_err(|error| error.to_diagnostic(context.file))?;
+    Ok(Box::new(TypeIntrinsic::new(kind, type_value)))
+}
+
+fn expand_sizeof(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_type_intrinsic(TypeIntrinsicKind::Size, invocation, context)
+}
+
+fn expand_alignof(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_type_intrinsic(TypeIntrinsicKind::Alignment, invocation, context)
+}
+
+fn expand_assertion(
+    kind: AssertionKind,
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let count = match kind {
+        AssertionKind::True => 1,
+        AssertionKind::Equal => 2,
+    };
+    let arguments = expect_arguments(count, invocation, context)?;
+    let operands = arguments
+        .iter()
+        .map(|argument| {
+            parser::parse_expression_argument(
+                argument,
+                invocation.span(),
+                context.session.features(),
+            )
+            .map_err(|error| error.to_diagnostic(context.file))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // The failure message quotes the source, as the operands' values alone rarely explain what went wrong.
+    let expression = arguments
+        .iter()
+        .map(|argument| context.source_text(argument))
+        .collect::<Vec<_>>()
+        .join(" == ");
+    let message = format!(
+        "assertion failed: `{expression}` at {}",
+        context.position(invocation)
+    );
+    Ok(Box::new(Assertion::new(kind, operands, message)))
+}
+
+/// Expands `wrapping_add!(a, b)` and the other arithmetic intrinsics, whose names give the operation and what it
+/// does on overflow.
+fn expand_arithmetic(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let name = invocation.name();
+    let (overflow, operator) = name
+        .as_str()
+        .split_once('_')
+        .and_then(|(overflow, operator)| {
+            let operator = match operator {
+                "add" => BinaryOperator::Add,
+                "sub" => BinaryOperator::Subtract,
+                "mul" => BinaryOperator::Multiply,
+                _ => return None,
+            };
+            Some((Overflow::from_prefix(overflow)?, operator))
+        })
+        .expect("Only arithmetic intrinsics are expanded here");
+    let mut operands = expect_arguments(2, invocation, context)?
+        .into_iter()
+        .map(|argument| {
+            parser::parse_expression_argument(
+                argument,
+                invocation.span(),
+                context.session.features(),
+            )
+            .map_err(|error| error.to_diagnostic(context.file))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let right = operands.pop().expect("There are two operands");
+    let left = operands.pop().expect("There are two operands");
+    Ok(Box::new(
+        BinaryOperation::new(operator, invocation.span(), left, right)
+            .with_overflow(Some(overflow)),
+    ))
+}
+
+fn expand_assert(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_assertion(AssertionKind::True, invocation, context)
+}
+
+fn expand_assert_eq(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_assertion(AssertionKind::Equal, invocation, context)
+}
+
+/// Expands `unwrap!(value)`, which panics if the option or result is `none` or an `err`, and
+/// `unwrap_or!(value, default)`.
+fn expand_unwrapping(
+    with_default: bool,
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let arguments = expect_arguments(if with_default { 2 } else { 1 }, invocation, context)?;
+    let mut operands = arguments
+        .iter()
+        .map(|argument| {
+            parser::parse_expression_argument(
+                argument,
+                invocation.span(),
+                context.session.features(),
+            )
+            .map_err(|error| error.to_diagnostic(context.file))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let default = if with_default { operands.pop() } else { None };
+    let option = operands.pop().expect("The option is always an argument");
+    let message = format!(
+        "`{}` at {}",
+        context.source_text(arguments[0]),
+        context.position(invocation)
+    );
+    Ok(Box::new(Unwrap::new(
+        invocation.span(),
+        option,
+        default,
+        message,
+    )))
+}
+
+/// Expands `len!(string)`, `args!()` and the other macros which are just a call to the runtime library.
+fn expand_runtime_call(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let function = [
+        RuntimeFunction::StringLength,
+        RuntimeFunction::StringSlice,
+        RuntimeFunction::StringChars,
+        RuntimeFunction::StringCompare,
+        RuntimeFunction::StringParseInt,
+        RuntimeFunction::CharToString,
+        RuntimeFunction::CharCode,
+        RuntimeFunction::CharFromCode,
+        RuntimeFunction::Args,
+        RuntimeFunction::EnvVar,
+    ]
+    .into_iter()
+    .find(|function| invocation.name() == function.macro_name())
+    .expect("Only calls to the runtime library are expanded here");
+    let arguments = expect_arguments(function.parameter_types().len(), invocation, context)?
+        .into_iter()
+        .map(|argument| {
+            parser::parse_expression_argument(
+                argument,
+                invocation.span(),
+                context.session.features(),
+            )
+            .map_err(|error| error.to_diagnostic(context.file))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Box::new(RuntimeCall::new(
+        function,
+        invocation.span(),
+        arguments,
+    )))
+}
+
+fn expand_unwrap(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_unwrapping(false, invocation, context)
+}
+
+fn expand_unwrap_or(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    expand_unwrapping(true, invocation, context)
+}
+
+fn format_error(
+    message: impl Into<String>,
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Diagnostic {
+    Diagnostic::error(message)
+        .with_code(error_codes::FORMAT_STRING)
+        .with_location(context.location(invocation))
+}
+
+/// Parses the template and arguments shared by `format!`, `print!`, `println!` and `panic!`.
+///
+/// Arguments are matched to placeholders here, but their types are checked once they have been expanded.
+fn parse_format(
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<Format, Diagnostic> {
+    let mut arguments = split_arguments(invocation).into_iter();
+    let template = match arguments.next() {
+        Some([(Token::StringLiteral(template), _)]) => template,
+        _ => {
+            return Err(format_error(
+                format!(
+                    "'{}!' must be given a string literal as its first argument",
+                    invocation.name()
+                ),
+                invocation,
+                context,
+            ))
+        }
+    };
+    let pieces = format_string::unescape(template)
+        .and_then(|template| format_string::parse(&template))
+        .map_err(|message| {
+            format_error(
+                format!("Invalid format string: {message}"),
+                invocation,
+                context,
+            )
+        })?;
+    let mut values = Vec::new();
+    let mut names = Vec::new();
+    for argument in arguments {
+        let (name, value) = match argument {
+            [(Token::Identifier(name), _), (Token::Equals, _), value @ ..] => (Some(name), value),
+            value => (None, value),
+        };
+        match name {
+            Some(name) => names.push(name.as_str()),
+            None if !names.is_empty() => {
+                return Err(format_error(
+                    "Positional arguments must come before named arguments",
+                    invocation,
+                    context,
+                ))
+            }
+            None => {}
+        }
+        values.push(
+            parser::parse_expression_argument(value, invocation.span(), context.session.features())
+                .map_err(|error| error.to_diagnostic(context.file))?,
+        );
+    }
+    let positional_count = values.len() - names.len();
+    let mut used = vec![false; values.len()];
+    let mut next_positional = 0;
+    let mut segments = Vec::new();
+    for piece in pieces {
+        let (argument, spec) = match piece {
+            Piece::Text(text) => {
+                segments.push(FormatSegment::Text(text));
+                continue;
+            }
+            Piece::Placeholder { argument, spec } => (argument, spec),
+        };
+        let index = match argument {
+            ArgumentReference::Name(name) => names
+                .iter()
+                .position(|known| *known == name)
+                .map(|position| positional_count + position)
+                .ok_or_else(|| {
+                    format_error(
+                        format!("There is no argument named '{name}'"),
+                        invocation,
+                        context,
+                    )
+                })?,
+            positional => {
+                let index = match positional {
+                    ArgumentReference::Index(index) => index,
+                    _ => {
+                        next_positional += 1;
+                        next_positional - 1
+                    }
+                };
+                if index >= positional_count {
+                    return Err(format_error(
+                        format!(
+                            "The format string uses positional argument {index}, but there {}",
+                            match positional_count {
+                                0 => "are none".to_string(),
+                                1 => "is only 1".to_string(),
+                                count => format!("are only {count}"),
+                            }
+                        ),
+                        invocation,
+                        context,
+                    ));
+                }
+                index
+            }
+        };
+        used[index] = true;
+        segments.push(FormatSegment::Argument { index, spec });
+    }
+    if let Some(unused) = used.iter().position(|used| !used) {
+        return Err(format_error(
+            format!("Argument {unused} is never used by the format string"),
+            invocation,
+            context,
+        ));
+    }
+    Ok(Format::new(invocation.span(), segments, values))
+}
+fn expand_format(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    Ok(Box::new(parse_format(invocation, context)?))
+}
+
+fn expand_print(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let format = parse_format(invocation, context)?;
+    Ok(Box::new(RuntimeCall::new(
+        RuntimeFunction::Print,
+        invocation.span(),
+        vec![Box::new(format)],
+    )))
+}
+
+fn expand_println(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let format = parse_format(invocation, context)?;
+    let mut segments = format.segments().to_vec();
+    segments.push(FormatSegment::Text("\n".to_string()));
+    let format = Format::new(format.span(), segments, format.arguments().to_vec());
+    Ok(Box::new(RuntimeCall::new(
+        RuntimeFunction::Print,
+        invocation.span(),
+        vec![Box::new(format)],
+    )))
+}
+
+fn expand_panic(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let format = parse_format(invocation, context)?;
+    let mut segments = vec![FormatSegment::Text(format!(
+        "panicked at {}: ",
+        context.position(invocation)
+    ))];
+    segments.extend_from_slice(format.segments());
+    let format = Format::new(format.span(), segments, format.arguments().to_vec());
+    Ok(Box::new(RuntimeCall::new(
+        RuntimeFunction::Panic,
+        invocation.span(),
+        vec![Box::new(format)],
+    )))
+}
+
+/// Reads the file named by the invocation's single string literal argument, relative to the including file.
+fn load_included_file(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<usize, Diagnostic> {
+    let path = match expect_arguments(1, invocation, context)?.as_slice() {
+        [[(Token::StringLiteral(path), _)]] => {
+            format_string::unescape(path).map_err(|message| {
+                Diagnostic::error(format!("Invalid path: {message}"))
+                    .with_code(error_codes::INCLUDE_FAILED)
+                    .with_location(context.location(invocation))
+            })?
+        }
+        _ => {
+            return Err(Diagnostic::error(format!(
+                "'{}!' must be given the path of the file to include as a string literal",
+                invocation.name()
+            ))
+            .with_code(error_codes::INCLUDE_FAILED)
+            .with_location(context.location(invocation)))
+        }
+    };
+    let resolved_path = context.relative_path(&path);
+    let file = context
+        .session
+        .source_map_mut()
+        .load(&resolved_path)
+        .map_err(|error| {
+            Diagnostic::error(format!(
+                "Couldn't include '{}': {error}",
+                resolved_path.display()
+            ))
+            .with_code(error_codes::INCLUDE_FAILED)
+            .with_location(context.location(invocation))
+        })?;
+    if let Some(warning) = context.session.source_map().invalid_utf8_warning(file) {
+        context.session.emit(warning);
+    }
+    Ok(file)
+}
+
+/// Splices the items of another file into the including module.
+fn expand_include(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    // The included file gets its own index, so that its diagnostics point into it.
+    let file = load_included_file(invocation, context)?;
+    let mut tokens = crate::tokenize_str(context.session.source_map()[file].text()).into_iter();
+    let mut token_stream =
+        parser::TokenStream::new(&mut tokens).with_features(context.session.features().clone());
+    let items = parser::parse(&mut token_stream).map_err(|error| error.to_diagnostic(file))?;
+    Ok(Box::new(Module::new(context.module.clone(), file, items)))
+}
+
+/// Turns a file's contents into a string.
+fn expand_include_str(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let file = load_included_file(invocation, context)?;
+    Ok(Box::new(Format::new(
+        invocation.span(),
+        vec![FormatSegment::Text(
+            context.session.source_map()[file].text().to_string(),
+        )],
+        Vec::new(),
+    )))
+}
+
+/// The text of a macro argument which must be a single string literal.
+fn string_argument(
+    argument: &[(Token, Span)],
+    invocation: &MacroInvocation,
+    context: &ExpansionContext,
+) -> Result<String, Diagnostic> {
+    match argument {
+        [(Token::StringLiteral(text), _)] => format_string::unescape(text),
+        _ => Err("expected a string literal".to_string()),
+    }
+    .map_err(|message| {
+        Diagnostic::error(format!(
+            "Invalid message for '{}!': {message}",
+            invocation.name()
+        ))
+        .with_location(context.location(invocation))
+    })
+}
+
+/// Reports its message as an error as soon as it is expanded.
+fn expand_compile_error(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let arguments = expect_arguments(1, invocation, context)?;
+    let message = string_argument(arguments[0], invocation, context)?;
+    Err(Diagnostic::error(message).with_location(context.location(invocation)))
+}
+
+/// Expands to a condition which is checked once the target is known, as it may depend on layouts.
+fn expand_static_assert(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let arguments = split_arguments(invocation);
+    if !(1..=2).contains(&arguments.len()) {
+        return Err(Diagnostic::error(format!(
+            "'static_assert!' takes a condition and an optional message, but {} arguments were given",
+            arguments.len()
+        ))
+        .with_code(error_codes::MACRO_ARGUMENT_COUNT)
+        .with_location(context.location(invocation)));
+    }
+    let condition = parser::parse_expression_argument(
+        arguments[0],
+        invocation.span(),
+        context.session.features(),
+    )
+    .map_err(|error| error.to_diagnostic(context.file))?;
+    let message = match arguments.get(1) {
+        Some(message) => string_argument(message, invocation, context)?,
+        None => format!(
+            "static assertion failed: `{}`",
+            context.source_text(arguments[0])
+        ),
+    };
+    Ok(Box::new(StaticAssertion::new(
+        invocation.span(),
+        condition,
+        message,
+    )))
+}
+
+/// Turns an environment variable of the compiler's process into a string, for embedding build information.
+fn expand_env(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let arguments = split_arguments(invocation);
+    if !(1..=2).contains(&arguments.len()) {
+        return Err(Diagnostic::error(format!(
+            "'env!' takes a variable name and an optional message, but {} arguments were given",
+            arguments.len()
+        ))
+        .with_code(error_codes::MACRO_ARGUMENT_COUNT)
+        .with_location(context.location(invocation)));
+    }
+    let name = string_argument(arguments[0], invocation, context)?;
+    let value = match std::env::var(&name) {
+        Ok(value) => value,
+        Err(error) => {
+            let message = match arguments.get(1) {
+                Some(message) => string_argument(message, invocation, context)?,
+                None => match error {
+                    VarError::NotPresent => {
+                        format!("The environment variable '{name}' isn't set")
+                    }
+                    VarError::NotUnicode(_) => {
+                        format!("The environment variable '{name}' isn't valid UTF-8")
+                    }
+                },
+            };
+            return Err(Diagnostic::error(message)
+                .with_code(error_codes::ENVIRONMENT_VARIABLE)
+                .with_location(context.location(invocation)));
+        }
+    };
+    Ok(Box::new(Format::new(
+        invocation.span(),
+        vec![FormatSegment::Text(value)],
+        Vec::new(),
+    )))
+}
+
+/// Turns an environment variable of the compiler's process into `some` string, or `none` if it isn't set.
+fn expand_option_env(
+    invocation: &MacroInvocation,
+    context: &mut ExpansionContext,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let arguments = split_arguments(invocation);
+    if arguments.len() != 1 {
+        return Err(Diagnostic::error(format!(
+            "'option_env!' takes a variable name, but {} arguments were given",
+            arguments.len()
+        ))
+        .with_code(error_codes::MACRO_ARGUMENT_COUNT)
+        .with_location(context.location(invocation)));
+    }
+    let name = string_argument(arguments[0], invocation, context)?;
+    let value = match std::env::var(&name) {
+        Ok(value) => Some(Box::new(Format::new(
+            invocation.span(),
+            vec![FormatSegment::Text(value)],
+            Vec::new(),
+        )) as Box<dyn AstNode>),
+        Err(VarError::NotPresent) => None,
+        // A value which isn't a string can't be `some` string, but it is set, so it isn't `none` either.
+        Err(VarError::NotUnicode(_)) => {
+            return Err(Diagnostic::error(format!(
+                "The environment variable '{name}' isn't valid UTF-8"
+            ))
+            .with_code(error_codes::ENVIRONMENT_VARIABLE)
+            .with_location(context.location(invocation)))
+        }
+    };
+    Ok(Box::new(OptionLiteral::new(invocation.span(), value)))
+}
+
+/// Checks that an expanded `format!` argument can be presented as its placeholder asks.
+///
+/// Method calls have no type until method resolution, so the type checker checks them again afterwards.
+pub(crate) fn check_format_argument(
+    argument: &dyn AstNode,
+    index: usize,
+    spec: FormatSpec,
+) -> Result<(), String> {
+    match semantic::expression_type(argument) {
+        // Empty literals, `none`, `ok` and `err` don't say all of their type, but are still printed as what they are.
+        None if matches!(
+            NodeKind::of(argument),
+            NodeKind::ListLiteral
+                | NodeKind::MapLiteral
+                | NodeKind::OptionLiteral
+                | NodeKind::ResultLiteral
+                | NodeKind::MethodCall
+        ) =>
+        {
+            Ok(())
+        }
+        None => Err(format!(
+            "Argument {index} doesn't produce a value which can be formatted"
+        )),
+        Some(argument_type) if spec != FormatSpec::Display && !argument_type.is_integer() => {
+            Err(format!(
+                "`{{:{}}}` can only format integers, but argument {index} is a {}",
+                spec.suffix(),
+                formatter::type_name(&argument_type)
+            ))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+/// Rebuilds a tree with every `MacroInvocation` replaced by its expansion.
+struct MacroExpander<'program> {
+    context: ExpansionContext<'program>,
+    /// How many expansions the node being visited is nested inside.
+    depth: usize,
+    /// The first macro which couldn't be expanded, after which nothing more is.
+    error: Option<Diagnostic>,
+}
+
+impl MacroExpander<'_> {
+    fn expand_module(&mut self, module: &Module) -> Box<dyn AstNode> {
+        // Modules are nested when files are included, so the including file is restored afterwards.
+        let including_module =
+            std::mem::replace(&mut self.context.module, module.name().to_string());
+        let including_file = std::mem::replace(&mut self.context.file, module.file());
+        let module = module.map_children(&mut |items| ast::fold(self, items));
+        self.context.module = including_module;
+        self.context.file = including_file;
+        module
+    }
+
+    fn expand_binary_operation(&mut self, operation: &BinaryOperation) -> Box<dyn AstNode> {
+        let left = ast::fold(self, operation.left());
+        let right = ast::fold(self, operation.right());
+        // Operators follow the build's overflow checks, so that later passes needn't know about them.
+        let overflow = operation
+            .overflow()
+            .unwrap_or(if self.context.session.overflow_checks() {
+                Overflow::Checked
+            } else {
+                Overflow::Wrapping
+            });
+        Box::new(
+            BinaryOperation::new(operation.operator(), operation.span(), left, right)
+                .with_overflow(Some(overflow)),
+        )
+    }
+
+    fn expand_format(&mut self, format: &Format) -> Result<Box<dyn AstNode>, Diagnostic> {
+        let arguments = format
+            .arguments()
+            .iter()
+            .map(|argument| ast::fold(self, argument.as_ref()))
+            .collect::<Vec<_>>();
+        for segment in format.segments() {
+            if let FormatSegment::Argument { index, spec } = segment {
+                check_format_argument(arguments[*index].as_ref(), *index, *spec).map_err(
+                    |message| {
+                        Diagnostic::error(message)
+                            .with_code(error_codes::FORMAT_ARGUMENT_TYPE)
+                            .with_location(Location::new(self.context.file, format.span()))
+                    },
+                )?;
+            }
+        }
+        Ok(Box::new(Format::new(
+            format.span(),
+            format.segments().to_vec(),
+            arguments,
+        )))
+    }
+
+    fn expand_invocation(
+        &mut self,
+        invocation: &MacroInvocation,
+    ) -> Result<Box<dyn AstNode>, Diagnostic> {
+        let location = self.context.location(invocation);
+        if self.depth >= RECURSION_LIMIT {
+            return Err(Diagnostic::error(format!(
+                "Expanding '{}!' exceeded the limit of {RECURSION_LIMIT} nested macro expansions",
+                invocation.name()
+            ))
+            .with_code(error_codes::MACRO_RECURSION_LIMIT)
+            .with_location(location));
+        }
+        if self.context.session.is_freestanding()
+            && RUNTIME_MACROS.contains(&invocation.name().as_str())
+        {
+            return Err(Diagnostic::error(format!(
+                "'{}!' needs the runtime library, which freestanding programs don't have",
+                invocation.name()
+            ))
+            .with_code(error_codes::RUNTIME_UNAVAILABLE)
+            .with_location(location));
+        }
+        let in_scope = |name: &str| self.context.session.has_prelude() || !PRELUDE.contains(&name);
+        let (_, expansion) = BUILTIN_MACROS
+            .iter()
+            .find(|(name, _)| invocation.name() == *name && in_scope(name))
+            .ok_or_else(|| {
+                let diagnostic =
+                    Diagnostic::error(format!("Unknown macro '{}!'", invocation.name()))
+                        .with_code(error_codes::UNKNOWN_MACRO)
+                        .with_location(location);
+                let in_scope_names = BUILTIN_MACROS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .filter(|name| in_scope(name));
+                if PRELUDE.contains(&invocation.name().as_str()) {
+                    diagnostic.with_note(format!(
+                        "'{}!' is in the prelude, which --no-prelude leaves out",
+                        invocation.name()
+                    ))
+                } else if let Some(name) =
+                    suggestions::closest(invocation.name().as_str(), in_scope_names)
+                {
+                    let start = location.span().start();
+                    let name_span = Span::new(start, start + invocation.name().as_str().len());
+                    diagnostic.with_suggestion(Suggestion::new(
+                        format!("did you mean '{name}!'?"),
+                        name_span,
+                        name,
+                        Applicability::MaybeIncorrect,
+                    ))
+                } else {
+                    diagnostic
+                }
+            })?;
+        let expanded = expansion(invocation, &mut self.context)?;
+        // Expansions may use macros themselves.
+        self.depth += 1;
+        let expanded = ast::fold(self, expanded.as_ref());
+        self.depth -= 1;
+        Ok(expanded)
+    }
+}
+
+impl Folder for MacroExpander<'_> {
+    fn fold_node(&mut self, node: &dyn AstNode) -> Option<Box<dyn AstNode>> {
+        // The tree is thrown away once a macro can't be expanded, so the rest is left as it is.
+        if self.error.is_some() {
+            return Some(Box::new(Vec::new()));
+        }
+        let expanded = if let Some(invocation) = node.downcast_ref::<MacroInvocation>() {
+            self.expand_invocation(invocation)
+        } else if let Some(format) = node.downcast_ref::<Format>() {
+            self.expand_format(format)
+        } else if let Some(operation) = node.downcast_ref::<BinaryOperation>() {
+            Ok(self.expand_binary_operation(operation))
+        } else if let Some(module) = node.downcast_ref::<Module>() {
+            Ok(self.expand_module(module))
+        } else {
+            return None;
+        };
+        Some(expanded.unwrap_or_else(|error| {
+            self.error.get_or_insert(error);
+            Box::new(Vec::new())
+        }))
+    }
+}
+
+/// Expands every macro in `program`, reporting the first which can't be expanded.
+///
+/// Some macros quote the files in the session's source map, and files included with `include!` are added to it.
+pub fn expand_macros(
+    program: &dyn AstNode,
+    session: &mut Session,
+) -> Result<Box<dyn AstNode>, Diagnostic> {
+    let mut expander = MacroExpander {
+        context: ExpansionContext {
+            session,
+            module: String::new(),
+            file: 0,
+        },
+        depth: 0,
+        error: None,
+    };
+    let program = ast::fold(&mut expander, program);
+    match expander.error {
+        Some(error) => Err(error),
+        None => Ok(program),
+    }
+}
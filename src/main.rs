@@ -1,37 +1,1418 @@
-use std::{error::Error, fs::File, io::BufReader};
-
-use utf8_chars::BufReadCharsExt;
+use std::{
+    cell::RefCell,
+    env,
+    fs::{self, File},
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, ExitCode},
+    rc::Rc,
+    sync::mpsc,
+    time::Duration,
+};
 
+use backend::BackendKind;
 use clap::Parser;
+use coverage::{Coverage, CoverageFormat};
+use docs::DocFormat;
+use hematite_lang::{
+    ast::{AstNode, Module},
+    database::Database,
+    diagnostics::{
+        self, Applicability, Diagnostic, DiagnosticCounts, DiagnosticEmitter, JsonEmitter,
+        Suggestion, TerminalEmitter,
+    },
+    error_codes,
+    features::{Feature, Features, LanguageVersion},
+    formatter, grammar, highlight, ice,
+    interpreter::{self, EvaluationHook, Frame, RuntimeError},
+    intrinsics,
+    lexer::{self, Span, Token},
+    macros, methods, parser, semantic,
+    session::Session,
+    source::{SourceFile, SourceMap},
+    statistics::{self, Statistics},
+    symbol::Symbol,
+    target::Target,
+};
+use manifest::Manifest;
+use notify::{RecursiveMode, Watcher};
+use profiler::Profiler;
+use rayon::prelude::*;
+
+mod backend;
+mod c_header;
+mod config;
+mod coverage;
+mod debugger;
+mod docs;
+mod lsp;
+mod manifest;
+mod profiler;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum EmitKind {
+    Tokens,
+    Ast,
+    Hir,
+    Ir,
+    /// LLVM bitcode, for linking with other LLVM-compiled code.
+    #[value(name = "llvm-bc")]
+    #[serde(rename = "llvm-bc")]
+    LlvmBc,
+    Asm,
+    Obj,
+    /// C declarations of the `@export`ed functions.
+    #[value(name = "c-header")]
+    #[serde(rename = "c-header")]
+    CHeader,
+    /// The grammar of the language in EBNF, which doesn't need any input files.
+    Grammar,
+    /// The input files as an HTML page, with names highlighted by what they declare.
+    #[value(name = "highlight-html")]
+    #[serde(rename = "highlight-html")]
+    HighlightHtml,
+}
+
+impl EmitKind {
+    fn extension(&self) -> &'static str {
+        match self {
+            EmitKind::Tokens => "tokens",
+            EmitKind::Ast => "ast",
+            EmitKind::Hir => "hir",
+            EmitKind::Ir => "ir",
+            EmitKind::LlvmBc => "bc",
+            EmitKind::Asm => "s",
+            EmitKind::Obj => "o",
+            EmitKind::CHeader => "h",
+            EmitKind::Grammar => "ebnf",
+            EmitKind::HighlightHtml => "html",
+        }
+    }
+
+    /// Whether the front-end writes this output itself, rather than the backend.
+    fn is_front_end(&self) -> bool {
+        matches!(
+            self,
+            EmitKind::Tokens
+                | EmitKind::Ast
+                | EmitKind::Hir
+                | EmitKind::Grammar
+                | EmitKind::HighlightHtml
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn should_color(&self) -> bool {
+        match self {
+            ColorChoice::Auto => io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum ErrorFormat {
+    Human,
+    Json,
+}
+
+/// Extra code compiled into a program to observe it as it runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+enum Instrumentation {
+    /// Count and time each function's calls, printing a profile when the program exits
+    Profile,
+}
+
+#[derive(Clone, Debug, clap::Subcommand)]
+enum Command {
+    /// Run a language server over stdin and stdout for editors to use
+    Lsp,
+    /// Rewrite files in the canonical style
+    Fmt(FormatCommand),
+    /// Report any errors in a program without producing output
+    Check(CheckCommand),
+    /// Check a program again every time one of its files changes
+    Watch(CheckCommand),
+    /// Build the project described by the nearest hematite.toml
+    Build(BuildCommand),
+    /// Generate documentation from the doc comments in a program
+    Doc(DocCommand),
+    /// Run every function marked @test
+    Test(CheckCommand),
+    /// Run a program's main function in the interpreter, stopping at breakpoints to inspect it
+    Debug(DebugCommand),
+    /// Rewrite a program's files, applying the fixes suggested for its errors
+    Fix(FixCommand),
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct FixCommand {
+    /// Also apply fixes which may not be what was meant, such as the closest spelling of an unknown name
+    #[clap(long)]
+    maybe_incorrect: bool,
+
+    #[clap(flatten)]
+    check: CheckCommand,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct DebugCommand {
+    /// Stop when a statement on this line is reached, such as 'main.hematite:3' (may be repeated).
+    /// Without any, the program stops at its first statement.
+    #[clap(long = "break", value_name = "FILE:LINE", value_parser = debugger::Breakpoint::parse)]
+    breakpoints: Vec<debugger::Breakpoint>,
+
+    #[clap(flatten)]
+    check: CheckCommand,
+
+    /// The arguments to give the program, after '--'
+    #[clap(last = true)]
+    arguments: Vec<String>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct DocCommand {
+    /// The format of the documentation
+    #[clap(long, value_enum, default_value = "markdown")]
+    format: DocFormat,
+    /// Where to write the documentation; '-' means stdout.
+    /// Defaults to the first input file with its extension replaced.
+    #[clap(short, long = "output")]
+    output_file: Option<String>,
+
+    /// The files to document; '-' or nothing reads from stdin
+    #[clap(default_value = "-")]
+    input_files: Vec<String>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct BuildCommand {
+    /// The manifest to build, instead of searching from the current directory
+    #[clap(long)]
+    manifest_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct CheckCommand {
+    /// The target triple to check for (defaults to the host)
+    #[clap(long)]
+    target: Option<String>,
 
-mod ast;
-mod lexer;
-mod parser;
+    /// The files to check; '-' or nothing reads from stdin
+    #[clap(default_value = "-")]
+    input_files: Vec<String>,
+}
+
+#[derive(Clone, Debug, clap::Args)]
+struct FormatCommand {
+    /// Don't write anything; fail if any file isn't already formatted
+    #[clap(long)]
+    check: bool,
+    /// The number of spaces to indent each level of nesting by
+    #[clap(long, default_value = "4")]
+    indent: usize,
+    /// The line length to try to keep within
+    #[clap(long, default_value = "100")]
+    max_width: usize,
+
+    /// The files to format; '-' or nothing formats stdin to stdout
+    #[clap(default_value = "-")]
+    input_files: Vec<String>,
+}
 
-#[derive(Debug, clap::Parser)]
+#[derive(Clone, Debug, clap::Parser)]
+// Defaults from the config file are given as arguments, which those on the command line replace.
+#[clap(args_override_self = true)]
 struct CommandLineOptions {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// The amount of optimization to perform on the code;
     /// 0 = no optimizations,
     /// 1 = some optimizations,
     /// 2 = most optimizations,
     /// 3 = aggressive optimizations
-    #[clap(short = 'O', default_value = "2")]
+    #[clap(short = 'O', long, default_value = "2")]
     optimization_level: i32,
-    #[clap(short, long = "output", default_value = "<stdin>")]
-    output_file: String,
-
-    input_file: String,
-}
-
-fn main() -> Result<(), Box<dyn Error>> {
-    let options = CommandLineOptions::parse();
-    let input_file = File::open(options.input_file).unwrap();
-    let mut buffered_file_reader = BufReader::new(input_file);
-    let character_iterator = buffered_file_reader.chars();
-    let mut character_iterator =
-        character_iterator.map(|possibly_char| possibly_char.expect("Failed to read from file"));
-    let token_iterator = lexer::tokenize(&mut character_iterator);
-    let program = parser::parse(&mut token_iterator.peekable())?;
-    println!("{:#?}", program);
+    /// Where to write the output; '-' means stdout.
+    /// Defaults to the input file with its extension replaced.
+    #[clap(short, long = "output")]
+    output_file: Option<String>,
+    /// The target triple to compile for (defaults to the host)
+    #[clap(long)]
+    target: Option<String>,
+    /// The compilation stages to output (may be repeated)
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "ast")]
+    emit: Vec<EmitKind>,
+    /// The code generator to produce the requested outputs with
+    #[clap(long, value_enum, default_value = "c")]
+    backend: BackendKind,
+    /// Compile for bare metal: without the runtime library, starting at --entry instead of main
+    #[clap(long, requires = "entry", global = true)]
+    freestanding: bool,
+    /// The function a freestanding program starts at
+    #[clap(long, value_name = "NAME", requires = "freestanding", global = true)]
+    entry: Option<String>,
+    /// Don't bring the prelude's macros, such as println! and assert!, into scope implicitly
+    #[clap(long, global = true)]
+    no_prelude: bool,
+    /// A linker script laying out a freestanding program's memory
+    #[clap(long, value_name = "PATH", requires = "freestanding")]
+    linker_script: Option<PathBuf>,
+    /// Optimize across all of the program's files at link time
+    #[clap(long)]
+    lto: bool,
+    /// A C library to link against, such as 'm' for libm (may be repeated)
+    #[clap(short = 'l', long = "library", value_name = "NAME")]
+    libraries: Vec<String>,
+    /// A directory to search for libraries given with -l (may be repeated)
+    #[clap(short = 'L', long = "library-path", value_name = "DIRECTORY")]
+    library_paths: Vec<PathBuf>,
+    /// Print a detailed explanation of an error code, such as E0001, and exit
+    #[clap(long, value_name = "CODE")]
+    explain: Option<String>,
+    /// How to print diagnostics; json prints one object per line for tools to consume
+    #[clap(long, value_enum, default_value = "human", global = true)]
+    error_format: ErrorFormat,
+    /// Whether to color diagnostics
+    #[clap(long, value_enum, default_value = "auto", global = true)]
+    color: ColorChoice,
+    /// The version of the language the program is written in
+    #[clap(long, value_enum, default_value = "2023", global = true)]
+    language_version: LanguageVersion,
+    /// Allow an unstable language feature (may be repeated)
+    #[clap(long, value_enum, value_delimiter = ',', global = true)]
+    enable_feature: Vec<Feature>,
+    /// Instrument the programs run by `test` (may be repeated)
+    #[clap(long, value_enum, value_delimiter = ',', global = true)]
+    instrument: Vec<Instrumentation>,
+    /// Record which lines the programs run by `test` execute, writing a report in this format
+    #[clap(long, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "lcov", global = true)]
+    coverage: Option<CoverageFormat>,
+    /// Where to write the coverage report; '-' means stdout. Defaults to 'coverage.info' or 'coverage.html'.
+    #[clap(long, value_name = "PATH", requires = "coverage", global = true)]
+    coverage_output: Option<String>,
+    /// Whether arithmetic operators panic on overflow rather than wrapping; on by default unless optimizing
+    #[clap(long, value_name = "BOOL", num_args = 0..=1, require_equals = true, default_missing_value = "true", global = true)]
+    overflow_checks: Option<bool>,
+    /// Stop showing errors after this many have been reported; 0 shows them all
+    #[clap(long, value_name = "COUNT", default_value = "50", global = true)]
+    error_limit: usize,
+    /// Print how long each compiler pass took and how much it processed
+    #[clap(short = 'v', long, visible_alias = "verbose", global = true)]
+    time_passes: bool,
+
+    /// The files to compile; '-' or nothing reads from stdin
+    #[clap(default_value = "-")]
+    input_files: Vec<String>,
+}
+
+impl CommandLineOptions {
+    fn features(&self) -> Features {
+        Features::new(self.language_version, self.enable_feature.clone())
+    }
+
+    fn emitter(&self) -> Box<dyn DiagnosticEmitter> {
+        match self.error_format {
+            ErrorFormat::Human => Box::new(TerminalEmitter::new(self.color.should_color())),
+            ErrorFormat::Json => Box::new(JsonEmitter),
+        }
+    }
+
+    /// The target the command compiles for, which `check`, `watch` and `test` take as their own option.
+    fn target(&self) -> Option<&str> {
+        match &self.command {
+            Some(Command::Check(command) | Command::Watch(command) | Command::Test(command)) => {
+                command.target.as_deref()
+            }
+            Some(Command::Debug(command)) => command.check.target.as_deref(),
+            Some(Command::Fix(command)) => command.check.target.as_deref(),
+            _ => self.target.as_deref(),
+        }
+    }
+
+    /// Whether arithmetic overflow panics. Like a release build, an optimized compilation wraps instead, but programs
+    /// run by `test` and `debug` are checked unless asked otherwise.
+    fn overflow_checks(&self) -> bool {
+        self.overflow_checks
+            .unwrap_or(self.command.is_some() || self.optimization_level == 0)
+    }
+
+    fn session(&self, target: Target) -> Session {
+        self.session_with_emitter(target, self.emitter())
+    }
+
+    /// A session reporting its diagnostics to `emitter` rather than the one chosen with `--error-format`.
+    fn session_with_emitter(&self, target: Target, emitter: Box<dyn DiagnosticEmitter>) -> Session {
+        let session = Session::new(target, self.features(), emitter)
+            .with_statistics(Statistics::new(self.time_passes))
+            .with_overflow_checks(self.overflow_checks());
+        let session = if self.no_prelude {
+            session.without_prelude()
+        } else {
+            session
+        };
+        let session = match self.error_limit {
+            0 => session,
+            limit => session.with_error_limit(limit),
+        };
+        match &self.entry {
+            Some(entry) if self.freestanding => {
+                session.with_freestanding_entry_point(Symbol::intern(entry))
+            }
+            _ => session,
+        }
+    }
+}
+
+/// A failure which stops the compiler, categorised by how it is reported to the calling process.
+#[derive(Debug)]
+enum DriverError {
+    /// The command line asked for something impossible.
+    Usage(String),
+    /// A file couldn't be read or written.
+    Io { path: String, error: io::Error },
+    /// The program being compiled is invalid.
+    Compile(Diagnostic),
+    /// The program being compiled is invalid, and the errors have already been emitted.
+    Reported,
+    /// `fmt --check` found files which aren't formatted.
+    Unformatted(usize),
+    /// Some of the tests run by `test` failed.
+    TestsFailed(usize),
+    /// A program run by `debug` stopped early, which has already been reported along with where it happened.
+    Panicked,
+    /// A program run by `debug` returned something other than zero from its entry point, which becomes the
+    /// compiler's own exit code.
+    Exited(i32),
+}
+
+impl DriverError {
+    fn exit_code(&self) -> ExitCode {
+        match self {
+            DriverError::Compile(_)
+            | DriverError::Reported
+            | DriverError::Unformatted(_)
+            | DriverError::TestsFailed(_) => ExitCode::from(1),
+            DriverError::Usage(_) => ExitCode::from(2),
+            // Matches the exit code of a compiled program which panics.
+            DriverError::Panicked => ExitCode::from(101),
+            DriverError::Io { .. } => ExitCode::from(3),
+            // `finish` exits with the whole code, but only its low byte fits in an `ExitCode`.
+            DriverError::Exited(code) => ExitCode::from(*code as u8),
+        }
+    }
+
+    fn io(path: &str) -> impl FnOnce(io::Error) -> Self + '_ {
+        move |error| DriverError::Io {
+            path: path.to_string(),
+            error,
+        }
+    }
+
+    /// The diagnostic describing the error, unless it has already been reported.
+    fn to_diagnostic(&self) -> Option<Diagnostic> {
+        Some(match self {
+            DriverError::Usage(message) => Diagnostic::error(message.clone()),
+            DriverError::Io { path, error } => Diagnostic::error(format!("{path}: {error}")),
+            DriverError::Compile(diagnostic) => diagnostic.clone(),
+            DriverError::Reported | DriverError::Panicked | DriverError::Exited(_) => return None,
+            DriverError::Unformatted(count) => {
+                Diagnostic::error(format!("{count} file(s) would be reformatted"))
+            }
+            DriverError::TestsFailed(count) => Diagnostic::error(format!("{count} test(s) failed")),
+        })
+    }
+}
+
+fn main() -> ExitCode {
+    ice::install_handler();
+    let default_arguments = match config::default_arguments() {
+        Ok(default_arguments) => default_arguments,
+        Err(error) => {
+            eprintln!("error: {error}");
+            return ExitCode::from(2);
+        }
+    };
+    let mut arguments = env::args_os();
+    let options = CommandLineOptions::parse_from(
+        arguments
+            .next()
+            .into_iter()
+            .chain(default_arguments.into_iter().map(Into::into))
+            .chain(arguments),
+    );
+    let mut session = match resolve_target(options.target()) {
+        Ok(target) => options.session(target),
+        Err(error) => {
+            // Without a target there is no session, but the error is still reported in the chosen format.
+            let mut emitter = options.emitter();
+            let counts = report(&error, &SourceMap::new(), emitter.as_mut());
+            emitter.finish(&counts);
+            return error.exit_code();
+        }
+    };
+    let result = match (&options.command, &options.explain) {
+        (Some(Command::Lsp), _) => lsp::run().map_err(|error| DriverError::Io {
+            path: "<stdio>".to_string(),
+            error: io::Error::other(error),
+        }),
+        (Some(Command::Fmt(command)), _) => format(command, &mut session),
+        (Some(Command::Check(command)), _) => check(command, &mut session, true).map(|_program| ()),
+        (Some(Command::Watch(command)), _) => watch(command, &options),
+        (Some(Command::Test(command)), _) => test(command, &options, &mut session),
+        (Some(Command::Debug(command)), _) => debug(command, &mut session),
+        (Some(Command::Fix(command)), _) => fix(command, &options, &mut session),
+        (Some(Command::Doc(command)), _) => document(command, &mut session),
+        (Some(Command::Build(command)), _) => build(command, &options, &mut session),
+        (None, Some(code)) => explain(code),
+        (None, None) => compile(&options, &mut session),
+    };
+    finish(session, result)
+}
+
+/// Reports how a command ended, returning the exit code for it.
+fn finish(mut session: Session, result: Result<(), DriverError>) -> ExitCode {
+    if session.statistics().enabled() {
+        eprint!("{}", session.statistics().report());
+    }
+    let exit_code = match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(DriverError::Exited(code)) => {
+            session.finish();
+            // Windows keeps all 32 bits of an exit code, which `ExitCode` can't represent.
+            process::exit(code);
+        }
+        Err(error) => {
+            if let Some(diagnostic) = error.to_diagnostic() {
+                session.emit(diagnostic);
+            }
+            error.exit_code()
+        }
+    };
+    session.finish();
+    exit_code
+}
+
+/// Reports `error` without a session, returning what was reported for the emitter to summarise.
+fn report(
+    error: &DriverError,
+    source_map: &SourceMap,
+    emitter: &mut dyn DiagnosticEmitter,
+) -> DiagnosticCounts {
+    let mut counts = DiagnosticCounts::default();
+    if let Some(diagnostic) = error.to_diagnostic() {
+        counts.record(diagnostic.severity());
+        emitter.emit(diagnostic, source_map);
+    }
+    counts
+}
+
+fn explain(code: &str) -> Result<(), DriverError> {
+    let explanation = error_codes::explain(code)
+        .ok_or_else(|| DriverError::Usage(format!("{code} is not a known error code")))?;
+    println!("{explanation}");
     Ok(())
 }
+
+fn resolve_target(triple: Option<&str>) -> Result<Target, DriverError> {
+    match triple {
+        Some(triple) => {
+            Target::from_triple(triple).map_err(|error| DriverError::Usage(error.to_string()))
+        }
+        None => Ok(Target::host()),
+    }
+}
+
+fn validate_input_files(input_files: &[String]) -> Result<(), DriverError> {
+    if input_files
+        .iter()
+        .filter(|input_file| *input_file == "-")
+        .count()
+        > 1
+    {
+        return Err(DriverError::Usage(
+            "stdin can only be read once".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn tokenize_files(session: &mut Session) -> Vec<Vec<(Token, Span)>> {
+    let token_lists = session.time("lexing", |session| {
+        session
+            .source_map()
+            .files()
+            .par_iter()
+            .map(|source_file| hematite_lang::tokenize_str(source_file.text()))
+            .collect::<Vec<Vec<_>>>()
+    });
+    session
+        .statistics_mut()
+        .count("tokens", token_lists.iter().map(Vec::len).sum());
+    token_lists
+}
+
+/// Parses each file into a module, together making up the program.
+fn parse_files(
+    input_files: &[String],
+    token_lists: Vec<Vec<(Token, Span)>>,
+    session: &mut Session,
+) -> Result<Box<dyn AstNode>, DriverError> {
+    // Files don't depend on each other until names are resolved, so they are parsed in parallel.
+    let features = session.features().clone();
+    let modules = session.time("parsing", |_| {
+        token_lists
+            .into_par_iter()
+            .enumerate()
+            .map(|(file, tokens)| {
+                let mut token_iterator = tokens.into_iter();
+                let mut tokens =
+                    parser::TokenStream::new(&mut token_iterator).with_features(features.clone());
+                let items =
+                    parser::parse(&mut tokens).map_err(|error| error.to_diagnostic(file))?;
+                let name = module_name(&input_files[file]);
+                Ok(Box::new(Module::new(name, file, items)) as Box<dyn AstNode>)
+            })
+            .collect::<Vec<_>>()
+    });
+    // Each file's error is emitted in the order of the files, however the threads ran.
+    let error_count = session.error_count();
+    let modules = modules
+        .into_iter()
+        .filter_map(|module| module.map_err(|diagnostic| session.emit(diagnostic)).ok())
+        .collect::<Vec<_>>();
+    if session.error_count() > error_count {
+        return Err(DriverError::Reported);
+    }
+    let program: Box<dyn AstNode> = Box::new(modules);
+    if session.statistics().enabled() {
+        let node_count = statistics::count_nodes(program.as_ref());
+        session.statistics_mut().count("AST nodes", node_count);
+    }
+    Ok(program)
+}
+
+/// Parses and checks the program made up of `input_files`, returning it ready for code generation.
+///
+/// Both `check` and full compilations go through here, so they report exactly the same errors.
+fn analyse(
+    input_files: &[String],
+    token_lists: Vec<Vec<(Token, Span)>>,
+    require_entry_point: bool,
+    session: &mut Session,
+) -> Result<Box<dyn AstNode>, DriverError> {
+    let mut found_invalid_token = false;
+    for (file, tokens) in token_lists.iter().enumerate() {
+        found_invalid_token |= session.report_invalid_tokens(file, tokens);
+    }
+    if found_invalid_token {
+        return Err(DriverError::Reported);
+    }
+    let program = parse_files(input_files, token_lists, session)?;
+    analyse_program(program, require_entry_point, session)
+}
+
+/// Runs the passes after parsing over the program, returning it ready for code generation.
+fn analyse_program(
+    program: Box<dyn AstNode>,
+    require_entry_point: bool,
+    session: &mut Session,
+) -> Result<Box<dyn AstNode>, DriverError> {
+    let program = session
+        .time("macro expansion", |session| {
+            macros::expand_macros(program.as_ref(), session)
+        })
+        .map_err(|diagnostic| {
+            session.emit(diagnostic);
+            DriverError::Reported
+        })?;
+    session
+        .time("name resolution", |_| {
+            semantic::resolve_names(program.as_ref())
+        })
+        .and_then(|()| {
+            session.time("attribute validation", |_| {
+                semantic::validate_attributes(program.as_ref())
+            })
+        })
+        .and_then(|()| {
+            if !require_entry_point {
+                return Ok(());
+            }
+            session.time("entry point validation", |session| {
+                match session.freestanding_entry_point() {
+                    Some(entry_point) => {
+                        semantic::validate_freestanding_entry_point(program.as_ref(), entry_point)
+                    }
+                    None => semantic::validate_entry_point(program.as_ref()),
+                }
+            })
+        })
+        .map_err(|error| {
+            session.emit(error.to_diagnostic());
+            DriverError::Reported
+        })?;
+    let program = session.time("intrinsic resolution", |session| {
+        intrinsics::resolve_intrinsics(program.as_ref(), session)
+    });
+    // Methods are looked up by their receiver's type, which can depend on intrinsics.
+    let program = session
+        .time("method resolution", |_| {
+            methods::resolve_methods(program.as_ref())
+        })
+        .map_err(|error| {
+            session.emit(error.to_diagnostic());
+            DriverError::Reported
+        })?;
+    // Static assertions may index collections, so their types are checked first.
+    session
+        .time("type checking", |_| semantic::check_types(program.as_ref()))
+        .map_err(|errors| {
+            for error in errors {
+                session.emit(error.to_diagnostic());
+            }
+            DriverError::Reported
+        })?;
+    session
+        .time("constant checking", |_| {
+            semantic::check_constants(program.as_ref())
+        })
+        .map_err(|error| {
+            session.emit(error.to_diagnostic());
+            DriverError::Reported
+        })?;
+    let warnings = session.time("unused value checking", |_| {
+        semantic::find_unused_values(program.as_ref())
+    });
+    for warning in warnings {
+        session.emit(warning);
+    }
+    Ok(program)
+}
+
+/// Runs every pass of the front-end over `command`'s files, stopping before any output is produced.
+fn check(
+    command: &CheckCommand,
+    session: &mut Session,
+    require_entry_point: bool,
+) -> Result<Box<dyn AstNode>, DriverError> {
+    validate_input_files(&command.input_files)?;
+    for input_file in &command.input_files {
+        read_source(session, input_file)?;
+    }
+    let token_lists = tokenize_files(session);
+    analyse(
+        &command.input_files,
+        token_lists,
+        require_entry_point,
+        session,
+    )
+}
+
+/// Like `check`, but reusing the tokens and trees of the files which haven't changed since `database` last saw them.
+fn check_incrementally(
+    command: &CheckCommand,
+    database: &mut Database,
+    session: &mut Session,
+) -> Result<Box<dyn AstNode>, DriverError> {
+    validate_input_files(&command.input_files)?;
+    // Naming a file more than once only loads it once.
+    let mut files = Vec::new();
+    for input_file in &command.input_files {
+        let file = read_source(session, input_file)?;
+        if !files.contains(&file) {
+            files.push(file);
+        }
+    }
+    for &file in &files {
+        let text = session.source_map()[file].text().to_string();
+        if file < database.file_count() {
+            database.set_text(file, text);
+        } else {
+            database.add_file(text);
+        }
+    }
+    let file_count = files.len();
+    let token_lists = session.time("lexing", |_| {
+        files
+            .iter()
+            .map(|&file| (file, database.tokens(file)))
+            .collect::<Vec<_>>()
+    });
+    let mut found_invalid_token = false;
+    for (file, tokens) in &token_lists {
+        found_invalid_token |= session.report_invalid_tokens(*file, tokens);
+    }
+    if found_invalid_token {
+        return Err(DriverError::Reported);
+    }
+    let trees = session.time("parsing", |_| {
+        files
+            .iter()
+            .map(|&file| (file, database.syntax(file)))
+            .collect::<Vec<_>>()
+    });
+    let mut modules = Vec::new();
+    for (file, tree) in trees {
+        match tree.as_ref() {
+            Ok(items) => {
+                let name = module_name(session.source_map()[file].name());
+                modules.push(Box::new(Module::new(name, file, items.clone())) as Box<dyn AstNode>);
+            }
+            Err(error) => session.emit(error.to_diagnostic(file)),
+        }
+    }
+    if modules.len() < file_count {
+        return Err(DriverError::Reported);
+    }
+    analyse_program(Box::new(modules), true, session)
+}
+
+/// How long to wait for more changes after a file changes, so that a burst of writes is checked once.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Checks `command`'s files whenever one of them changes, until the process is interrupted.
+fn watch(command: &CheckCommand, options: &CommandLineOptions) -> Result<(), DriverError> {
+    if command
+        .input_files
+        .iter()
+        .any(|input_file| input_file == "-")
+    {
+        return Err(DriverError::Usage("stdin can't be watched".to_string()));
+    }
+    let target = resolve_target(command.target.as_deref())?;
+    let watch_error = |error: notify::Error| DriverError::Io {
+        path: command.input_files.join(", "),
+        error: io::Error::other(error),
+    };
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    let mut watched_files = Vec::new();
+    for input_file in &command.input_files {
+        let path = fs::canonicalize(input_file).map_err(DriverError::io(input_file))?;
+        // Editors often save by replacing the file, which would end a watch on the file itself.
+        let directory = path.parent().unwrap_or(&path);
+        watcher
+            .watch(directory, RecursiveMode::NonRecursive)
+            .map_err(watch_error)?;
+        watched_files.push(path);
+    }
+    // Files which haven't changed aren't lexed or parsed again.
+    let mut database = Database::new(options.features());
+    loop {
+        // Clear the screen so only the latest diagnostics are visible.
+        print!("\x1b[2J\x1b[H");
+        io::stdout().flush().map_err(DriverError::io("<stdout>"))?;
+        let mut session = options.session(target.clone());
+        let result = check_incrementally(command, &mut database, &mut session).map(|_program| {
+            println!("No errors found.");
+        });
+        finish(session, result);
+        println!("Watching for changes...");
+        loop {
+            let event = receiver
+                .recv()
+                .expect("The watcher should live as long as the loop")
+                .map_err(watch_error)?;
+            if event.paths.iter().any(|path| watched_files.contains(path)) {
+                break;
+            }
+        }
+        while receiver.recv_timeout(WATCH_DEBOUNCE).is_ok() {}
+    }
+}
+
+/// The hooks `test` runs each test with, for the instrumentation which was asked for.
+struct TestHooks<'files> {
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage<'files>>,
+}
+
+impl EvaluationHook for TestHooks<'_> {
+    fn before_statement(&mut self, stack: &[Frame]) -> Result<(), RuntimeError> {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.before_statement(stack)?;
+        }
+        Ok(())
+    }
+
+    fn enter_function(&mut self, function: Symbol) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.enter_function(function);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.enter_function(function);
+        }
+    }
+
+    fn exit_function(&mut self, function: Symbol) {
+        if let Some(profiler) = &mut self.profiler {
+            profiler.exit_function(function);
+        }
+    }
+}
+
+/// Runs every `@test` function in `command`'s files, reporting each one's result.
+fn test(
+    command: &CheckCommand,
+    options: &CommandLineOptions,
+    session: &mut Session,
+) -> Result<(), DriverError> {
+    // Tests are run on their own, so the files don't need a `main`.
+    let program = check(command, session, false)?;
+    let tests = semantic::collect_module_functions(program.as_ref())
+        .into_iter()
+        .filter(|collected| collected.function().has_attribute("test"))
+        .collect::<Vec<_>>();
+    println!("running {} test(s)", tests.len());
+    let mut hooks = TestHooks {
+        profiler: options
+            .instrument
+            .contains(&Instrumentation::Profile)
+            .then(Profiler::default),
+        coverage: options.coverage.map(|_| {
+            Coverage::new(
+                session.source_map(),
+                &semantic::collect_module_functions(program.as_ref()),
+            )
+        }),
+    };
+    let mut failed = 0;
+    for collected in &tests {
+        let name = format!("{}::{}", collected.module(), collected.function().name());
+        match interpreter::call_with_hook(
+            collected.function(),
+            collected.location().file(),
+            &[],
+            &mut hooks,
+        ) {
+            Ok(0) => println!("test {name} ... ok"),
+            Ok(code) => {
+                println!("test {name} ... FAILED (returned {code})");
+                failed += 1;
+            }
+            Err(error) => {
+                println!("test {name} ... FAILED ({error})");
+                print!("{}", backtrace(&error, session.source_map()));
+                failed += 1;
+            }
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {failed} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        tests.len() - failed
+    );
+    if let Some(profiler) = hooks.profiler {
+        eprint!("\n{}", profiler.report());
+    }
+    if let (Some(coverage), Some(format)) = (hooks.coverage, options.coverage) {
+        let output_path = options
+            .coverage_output
+            .clone()
+            .unwrap_or_else(|| format!("coverage.{}", format.extension()));
+        open_output(&output_path)
+            .and_then(|mut output| output.write_all(coverage.render(format).as_bytes()))
+            .map_err(DriverError::io(&output_path))?;
+    }
+    if failed > 0 {
+        return Err(DriverError::TestsFailed(failed));
+    }
+    Ok(())
+}
+
+/// Runs the program in `command`'s files under the debugger, from its entry point.
+fn debug(command: &DebugCommand, session: &mut Session) -> Result<(), DriverError> {
+    if command
+        .check
+        .input_files
+        .iter()
+        .any(|input_file| input_file == "-")
+    {
+        return Err(DriverError::Usage(
+            "the debugger reads its commands from stdin, so the program must be in files"
+                .to_string(),
+        ));
+    }
+    let program = check(&command.check, session, true)?;
+    let entry_point = session
+        .freestanding_entry_point()
+        .unwrap_or_else(|| Symbol::intern("main"));
+    let entry_point = semantic::collect_module_functions(program.as_ref())
+        .into_iter()
+        .find(|collected| collected.function().name() == entry_point)
+        .expect("Analysis requires the entry point");
+    let mut debugger = debugger::Debugger::new(session.source_map(), command.breakpoints.clone());
+    match interpreter::call_with_hook(
+        entry_point.function(),
+        entry_point.location().file(),
+        &command.arguments,
+        &mut debugger,
+    ) {
+        Ok(0) => {
+            println!("The program exited with code 0");
+            Ok(())
+        }
+        Ok(code) => {
+            println!("The program exited with code {code}");
+            // Clamped to what `process::exit` accepts; the platform keeps as much of that as it can.
+            Err(DriverError::Exited(
+                code.clamp(i32::MIN.into(), i32::MAX.into()) as i32,
+            ))
+        }
+        Err(error) => {
+            println!("The program stopped: {error}");
+            print!("{}", backtrace(&error, session.source_map()));
+            Err(DriverError::Panicked)
+        }
+    }
+}
+
+/// How many times `fix` checks a program, as fixing one error can let the compiler find the next.
+const FIX_ROUNDS: usize = 8;
+
+/// Keeps the suggestions `fix` may apply from the diagnostics emitted to it, along with the file each is in.
+struct SuggestionRecorder {
+    maybe_incorrect: bool,
+    suggestions: Rc<RefCell<Vec<(usize, Suggestion)>>>,
+}
+
+impl DiagnosticEmitter for SuggestionRecorder {
+    fn emit(&mut self, diagnostic: Diagnostic, _source_map: &SourceMap) {
+        let Some(location) = diagnostic.location() else {
+            return;
+        };
+        let mut suggestions = self.suggestions.borrow_mut();
+        for suggestion in diagnostic.suggestions() {
+            if self.maybe_incorrect
+                || suggestion.applicability() == Applicability::MachineApplicable
+            {
+                suggestions.push((location.file(), suggestion.clone()));
+            }
+        }
+    }
+}
+
+/// Applies the fixes suggested for `command`'s files until there are none left, then checks them once more to
+/// report anything which couldn't be fixed.
+fn fix(
+    command: &FixCommand,
+    options: &CommandLineOptions,
+    session: &mut Session,
+) -> Result<(), DriverError> {
+    if command
+        .check
+        .input_files
+        .iter()
+        .any(|input_file| input_file == "-")
+    {
+        return Err(DriverError::Usage(
+            "fix rewrites the program's files, so it can't be read from stdin".to_string(),
+        ));
+    }
+    for _ in 0..FIX_ROUNDS {
+        let suggestions = Rc::new(RefCell::new(Vec::new()));
+        let recorder = SuggestionRecorder {
+            maybe_incorrect: command.maybe_incorrect,
+            suggestions: suggestions.clone(),
+        };
+        let mut round = options.session_with_emitter(session.target().clone(), Box::new(recorder));
+        // Only the suggestions matter here; the errors are reported by the final check.
+        let _ = check(&command.check, &mut round, true);
+        let suggestions = suggestions.take();
+        if suggestions.is_empty() {
+            break;
+        }
+        for (file, source_file) in round.source_map().files().iter().enumerate() {
+            let in_file = suggestions
+                .iter()
+                .filter(|(other, _)| *other == file)
+                .map(|(_, suggestion)| suggestion)
+                .collect::<Vec<_>>();
+            if in_file.is_empty() {
+                continue;
+            }
+            refuse_lossy_rewrite(source_file)?;
+            let fixed = diagnostics::apply_suggestions(source_file.text(), in_file.iter().copied());
+            fs::write(source_file.name(), fixed).map_err(DriverError::io(source_file.name()))?;
+            println!(
+                "Fixed {} problem(s) in {}",
+                in_file.len(),
+                source_file.name()
+            );
+        }
+    }
+    check(&command.check, session, true).map(|_program| ())
+}
+
+/// The functions which were running when a program stopped, innermost first, one per line.
+fn backtrace(error: &RuntimeError, source_map: &SourceMap) -> String {
+    error
+        .backtrace()
+        .iter()
+        .map(|frame| match frame.location() {
+            Some(location) => {
+                let source_file = &source_map[location.file()];
+                let (line, column) = source_file.line_and_column(location.span().start());
+                format!(
+                    "    at {} ({}:{line}:{column})\n",
+                    frame.function(),
+                    source_file.name()
+                )
+            }
+            None => format!("    at {}\n", frame.function()),
+        })
+        .collect()
+}
+
+/// Writes documentation for every function in `command`'s files.
+fn document(command: &DocCommand, session: &mut Session) -> Result<(), DriverError> {
+    validate_input_files(&command.input_files)?;
+    for input_file in &command.input_files {
+        read_source(session, input_file)?;
+    }
+    let token_lists = tokenize_files(session);
+    // Libraries have no entry point, so only parsing is needed, not the rest of the front-end.
+    let program = parse_files(&command.input_files, token_lists, session)?;
+    let documentation = docs::generate(program.as_ref(), session.source_map(), command.format);
+    let first_input_file = &command.input_files[0];
+    let output_path = match &command.output_file {
+        Some(output_file) => output_file.clone(),
+        None if first_input_file == "-" => "-".to_string(),
+        None => default_output_file(first_input_file, command.format.extension()),
+    };
+    open_output(&output_path)
+        .and_then(|mut output| output.write_all(documentation.as_bytes()))
+        .map_err(DriverError::io(&output_path))
+}
+
+/// Compiles every source file of a project as described by its manifest.
+fn build(
+    command: &BuildCommand,
+    options: &CommandLineOptions,
+    session: &mut Session,
+) -> Result<(), DriverError> {
+    let manifest = match &command.manifest_path {
+        Some(path) => Manifest::load(path),
+        None => Manifest::find(&env::current_dir().map_err(DriverError::io("."))?),
+    }
+    .map_err(|error| DriverError::Usage(error.to_string()))?;
+    let input_files = manifest
+        .source_files()
+        .map_err(|error| DriverError::Usage(error.to_string()))?;
+    if input_files.is_empty() {
+        return Err(DriverError::Usage(format!(
+            "{} has no source files",
+            manifest.name()
+        )));
+    }
+    let output_directory = manifest.output_directory();
+    fs::create_dir_all(&output_directory)
+        .map_err(DriverError::io(&output_directory.to_string_lossy()))?;
+    let output_file = output_directory
+        .join(manifest.name())
+        .with_extension(manifest.output().extension());
+    // A build is an ordinary compilation, with its inputs and outputs taken from the manifest.
+    let build_options = CommandLineOptions {
+        command: None,
+        output_file: Some(output_file.to_string_lossy().into_owned()),
+        target: manifest
+            .target()
+            .map(str::to_string)
+            .or_else(|| options.target.clone()),
+        emit: vec![manifest.output()],
+        libraries: manifest
+            .libraries()
+            .iter()
+            .chain(&options.libraries)
+            .cloned()
+            .collect(),
+        library_paths: manifest
+            .library_paths()
+            .chain(options.library_paths.iter().cloned())
+            .collect(),
+        input_files: input_files
+            .iter()
+            .map(|input_file| input_file.to_string_lossy().into_owned())
+            .collect(),
+        ..options.clone()
+    };
+    if let Some(triple) = &build_options.target {
+        session.set_target(resolve_target(Some(triple))?);
+    }
+    compile(&build_options, session)
+}
+
+/// An HTML page showing each file with its tokens highlighted.
+///
+/// Files which don't parse are still shown, but only their keywords, literals and macros are highlighted.
+fn highlighted_html(session: &Session, token_lists: &[Vec<(Token, Span)>]) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Source</title>\n<style>\n\
+         .keyword, .macro { color: #a626a4; }\n\
+         .type { color: #c18401; }\n\
+         .function { color: #4078f2; }\n\
+         .parameter, .local { color: #e45649; }\n\
+         .variant, .number { color: #986801; }\n\
+         .attribute { color: #0184bc; }\n\
+         .string { color: #50a14f; }\n\
+         .comment { color: #a0a1a7; font-style: italic; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    for (file, tokens) in token_lists.iter().enumerate() {
+        let mut token_iterator = tokens.iter().cloned();
+        let program = parser::parse(
+            &mut parser::TokenStream::new(&mut token_iterator)
+                .with_features(session.features().clone()),
+        )
+        .ok();
+        let source_file = &session.source_map()[file];
+        let highlights = highlight::highlight(tokens, program.as_deref());
+        output.push_str(&format!(
+            "<h1><code>{}</code></h1>\n<pre><code>{}</code></pre>\n",
+            docs::escape_html(source_file.name()),
+            highlight::to_html(source_file.text(), &highlights)
+        ));
+    }
+    output.push_str("</body>\n</html>\n");
+    output
+}
+
+/// Compiles the program described by `options`, loading its files into the session so that diagnostics can refer to them.
+fn compile(options: &CommandLineOptions, session: &mut Session) -> Result<(), DriverError> {
+    let backend = options.backend.backend();
+    for kind in &options.emit {
+        if *kind == EmitKind::Hir {
+            return Err(DriverError::Usage(format!(
+                "Emitting {kind:?} is not supported yet"
+            )));
+        }
+        if !kind.is_front_end() && !backend.supports(*kind) {
+            return Err(DriverError::Usage(format!(
+                "The {} backend can't emit {kind:?}",
+                backend.name()
+            )));
+        }
+    }
+    if session.statistics().enabled() {
+        eprintln!("backend: {}", backend.describe(session.target()));
+    }
+    // Like linking, link-time optimization needs generated code, which no output supported yet contains.
+    if options.lto {
+        return Err(DriverError::Usage(
+            "--lto only applies to generated code, which can't be emitted yet".to_string(),
+        ));
+    }
+    // The interpreter is the only thing which runs programs, so there is nothing to instrument here.
+    if !options.instrument.is_empty() || options.coverage.is_some() {
+        return Err(DriverError::Usage(
+            "--instrument and --coverage only apply to programs run by `test`, as code can't be generated yet"
+                .to_string(),
+        ));
+    }
+    if options.linker_script.is_some() {
+        return Err(DriverError::Usage(
+            "--linker-script only applies to linked outputs, which can't be emitted yet"
+                .to_string(),
+        ));
+    }
+    // None of the outputs which can be emitted are linked, so libraries would be silently ignored.
+    if !options.libraries.is_empty() || !options.library_paths.is_empty() {
+        return Err(DriverError::Usage(
+            "-l and -L only apply to linked outputs, which can't be emitted yet".to_string(),
+        ));
+    }
+    let output_to_stdout = options.output_file.as_deref() == Some("-");
+    if options.emit.len() > 1 && options.output_file.is_some() && !output_to_stdout {
+        return Err(DriverError::Usage(
+            "-o can only name a file when a single --emit kind is requested".to_string(),
+        ));
+    }
+    validate_input_files(&options.input_files)?;
+    // The output is named after the first input file, as the program is compiled as a whole.
+    let first_input_file = &options.input_files[0];
+    let output_path_for = |kind: EmitKind| match &options.output_file {
+        Some(output_file) => output_file.clone(),
+        // There is no file name to derive the output's from when reading stdin.
+        None if first_input_file == "-" => "-".to_string(),
+        None => default_output_file(first_input_file, kind.extension()),
+    };
+    if options.emit.contains(&EmitKind::Grammar) {
+        let output_path = output_path_for(EmitKind::Grammar);
+        open_output(&output_path)
+            .and_then(|mut output| write!(output, "{}", grammar::grammar()))
+            .map_err(DriverError::io(&output_path))?;
+        if options.emit.iter().all(|kind| *kind == EmitKind::Grammar) {
+            return Ok(());
+        }
+    }
+    for input_file in &options.input_files {
+        read_source(session, input_file)?;
+    }
+    let token_lists = tokenize_files(session);
+    if options.emit.contains(&EmitKind::Tokens) {
+        let output_path = output_path_for(EmitKind::Tokens);
+        let mut output = open_output(&output_path).map_err(DriverError::io(&output_path))?;
+        for (token, span) in token_lists.iter().flatten() {
+            writeln!(output, "{span} {token:?}").map_err(DriverError::io(&output_path))?;
+        }
+    }
+    if options.emit.contains(&EmitKind::HighlightHtml) {
+        let output_path = output_path_for(EmitKind::HighlightHtml);
+        let html = highlighted_html(session, &token_lists);
+        open_output(&output_path)
+            .and_then(|mut output| write!(output, "{html}"))
+            .map_err(DriverError::io(&output_path))?;
+    }
+    // Neither of these needs the program to be valid, or even to parse.
+    if options
+        .emit
+        .iter()
+        .all(|kind| matches!(kind, EmitKind::Tokens | EmitKind::HighlightHtml))
+    {
+        return Ok(());
+    }
+    let program = analyse(&options.input_files, token_lists, true, session)?;
+    if options.emit.contains(&EmitKind::Ast) {
+        let output_path = output_path_for(EmitKind::Ast);
+        session
+            .time("output", |_| {
+                open_output(&output_path).and_then(|mut output| writeln!(output, "{:#?}", program))
+            })
+            .map_err(DriverError::io(&output_path))?;
+    }
+    let backend_outputs = options
+        .emit
+        .iter()
+        .filter(|kind| !kind.is_front_end())
+        .collect::<Vec<_>>();
+    if backend_outputs.is_empty() {
+        return Ok(());
+    }
+    let lowered = session
+        .time("lowering", |session| {
+            backend.lower(program.as_ref(), session)
+        })
+        .map_err(DriverError::Compile)?;
+    for &kind in backend_outputs {
+        let output_path = output_path_for(kind);
+        // Outputs are named after their file, or the program when they have no file name.
+        let name = Path::new(if output_path == "-" {
+            first_input_file
+        } else {
+            &output_path
+        })
+        .file_stem()
+        .map_or_else(|| "program".into(), |stem| stem.to_string_lossy());
+        session
+            .time("output", |_| {
+                open_output(&output_path)
+                    .and_then(|mut output| lowered.emit(kind, &name, &mut output))
+            })
+            .map_err(DriverError::io(&output_path))?;
+    }
+    Ok(())
+}
+
+/// Formats each of `command`'s files in place, or just checks them with `--check`.
+fn format(command: &FormatCommand, session: &mut Session) -> Result<(), DriverError> {
+    let style = formatter::Style::new(command.indent, command.max_width);
+    let mut unformatted = 0;
+    for input_file in &command.input_files {
+        let file = read_source(session, input_file)?;
+        let text = session.source_map()[file].text();
+        let mut characters = text.chars();
+        let mut tokens = lexer::tokenize(&mut characters);
+        let program = parser::parse(
+            // Formatting shouldn't depend on which features the program is compiled with.
+            &mut parser::TokenStream::new(&mut tokens).with_features(Features::all()),
+        )
+        .map_err(|error| DriverError::Compile(error.to_diagnostic(file)))?;
+        let formatted = formatter::format(program.as_ref(), &style);
+        if command.check {
+            if formatted != text {
+                println!("Would reformat {input_file}");
+                unformatted += 1;
+            }
+        } else if input_file == "-" {
+            print!("{formatted}");
+        } else if formatted != text {
+            refuse_lossy_rewrite(&session.source_map()[file])?;
+            fs::write(input_file, formatted).map_err(DriverError::io(input_file))?;
+        }
+    }
+    if unformatted > 0 {
+        return Err(DriverError::Unformatted(unformatted));
+    }
+    Ok(())
+}
+
+/// Fails if `source_file` had invalid UTF-8 replaced when it was read, as writing its text back would lose the bytes.
+fn refuse_lossy_rewrite(source_file: &SourceFile) -> Result<(), DriverError> {
+    match source_file.invalid_utf8() {
+        Some(_) => Err(DriverError::Usage(format!(
+            "'{}' isn't valid UTF-8, so it can't be rewritten without losing the invalid bytes",
+            source_file.name()
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Loads a file, or standard input for `-`, returning its index in the session's source map.
+fn read_source(session: &mut Session, input_file: &str) -> Result<usize, DriverError> {
+    let file = if input_file == "-" {
+        let mut bytes = Vec::new();
+        io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(DriverError::io("<stdin>"))?;
+        session
+            .source_map_mut()
+            .add(SourceFile::from_bytes("<stdin>".to_string(), bytes))
+    } else {
+        session
+            .source_map_mut()
+            .load(Path::new(input_file))
+            .map_err(DriverError::io(input_file))?
+    };
+    if let Some(warning) = session.source_map().invalid_utf8_warning(file) {
+        session.emit(warning);
+    }
+    Ok(file)
+}
+
+fn module_name(input_file: &str) -> String {
+    if input_file == "-" {
+        "<stdin>".to_string()
+    } else {
+        Path::new(input_file).file_stem().map_or_else(
+            || input_file.to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        )
+    }
+}
+
+fn open_output(output_file: &str) -> io::Result<Box<dyn Write>> {
+    if output_file == "-" {
+        Ok(Box::new(io::stdout()))
+    } else {
+        Ok(Box::new(File::create(output_file)?))
+    }
+}
+
+fn default_output_file(input_file: &str, extension: &str) -> String {
+    Path::new(input_file)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned()
+}
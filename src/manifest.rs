@@ -0,0 +1,174 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::EmitKind;
+
+pub const MANIFEST_FILE_NAME: &str = "hematite.toml";
+pub const SOURCE_EXTENSION: &str = "hematite";
+
+#[derive(Debug)]
+pub enum ManifestError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    NotFound(PathBuf),
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::Io { path, error } => write!(f, "{}: {error}", path.display()),
+            ManifestError::Invalid { path, error } => {
+                // TOML errors end with a blank line which would separate them from the rest of the diagnostic.
+                write!(
+                    f,
+                    "Invalid manifest {}: {}",
+                    path.display(),
+                    error.to_string().trim_end()
+                )
+            }
+            ManifestError::NotFound(directory) => write!(
+                f,
+                "Couldn't find {MANIFEST_FILE_NAME} in {} or any of its parents",
+                directory.display()
+            ),
+        }
+    }
+}
+
+impl Error for ManifestError {}
+
+fn default_source_roots() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+fn default_output() -> EmitKind {
+    EmitKind::Ast
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Package {
+    name: String,
+    /// Directories, relative to the manifest, which are searched for source files.
+    #[serde(default = "default_source_roots")]
+    source_roots: Vec<PathBuf>,
+    /// What the build produces.
+    #[serde(default = "default_output")]
+    output: EmitKind,
+    /// The target triple to build for, if not the host.
+    target: Option<String>,
+    /// C libraries to link against, as given to `-l`.
+    #[serde(default)]
+    libraries: Vec<String>,
+    /// Directories, relative to the manifest, which are searched for `libraries`.
+    #[serde(default)]
+    library_paths: Vec<PathBuf>,
+}
+
+/// The contents of a project's `hematite.toml`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    package: Package,
+    /// The directory containing the manifest, which its paths are relative to.
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let text = fs::read_to_string(path).map_err(|error| ManifestError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        let mut manifest: Manifest =
+            toml::from_str(&text).map_err(|error| ManifestError::Invalid {
+                path: path.to_path_buf(),
+                error,
+            })?;
+        manifest.root = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+        Ok(manifest)
+    }
+
+    /// Loads the manifest in `directory` or the closest of its ancestors.
+    pub fn find(directory: &Path) -> Result<Self, ManifestError> {
+        directory
+            .ancestors()
+            .map(|ancestor| ancestor.join(MANIFEST_FILE_NAME))
+            .find(|path| path.is_file())
+            .ok_or_else(|| ManifestError::NotFound(directory.to_path_buf()))
+            .and_then(|path| Self::load(&path))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.package.name
+    }
+
+    pub fn output(&self) -> EmitKind {
+        self.package.output
+    }
+
+    pub fn target(&self) -> Option<&str> {
+        self.package.target.as_deref()
+    }
+
+    pub fn libraries(&self) -> &[String] {
+        &self.package.libraries
+    }
+
+    pub fn library_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.package
+            .library_paths
+            .iter()
+            .map(|library_path| self.root.join(library_path))
+    }
+
+    /// Where build outputs are written.
+    pub fn output_directory(&self) -> PathBuf {
+        self.root.join("build")
+    }
+
+    /// Every source file under the package's source roots, in a stable order.
+    pub fn source_files(&self) -> Result<Vec<PathBuf>, ManifestError> {
+        let mut source_files = Vec::new();
+        for source_root in &self.package.source_roots {
+            collect_source_files(&self.root.join(source_root), &mut source_files)?;
+        }
+        source_files.sort();
+        Ok(source_files)
+    }
+}
+
+fn collect_source_files(
+    directory: &Path,
+    source_files: &mut Vec<PathBuf>,
+) -> Result<(), ManifestError> {
+    let io_error = |error| ManifestError::Io {
+        path: directory.to_path_buf(),
+        error,
+    };
+    for entry in fs::read_dir(directory).map_err(io_error)? {
+        let path = entry.map_err(io_error)?.path();
+        if path.is_dir() {
+            collect_source_files(&path, source_files)?;
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == SOURCE_EXTENSION)
+        {
+            source_files.push(path);
+        }
+    }
+    Ok(())
+}
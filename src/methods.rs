@@ -0,0 +1,201 @@
+use crate::{
+    ast::{self, AstNode, BuiltinMethod, Folder, MethodCall, Module, RuntimeFunction, Type},
+    diagnostics::{Applicability, Location, Suggestion},
+    error_codes,
+    formatter::type_name,
+    query::NodeKind,
+    semantic::{self, SemanticError},
+    suggestions,
+};
+
+const STRING_METHODS: &[(&str, BuiltinMethod)] = &[
+    (
+        "length",
+        BuiltinMethod::Runtime(RuntimeFunction::StringLength),
+    ),
+    (
+        "slice",
+        BuiltinMethod::Runtime(RuntimeFunction::StringSlice),
+    ),
+    (
+        "chars",
+        BuiltinMethod::Runtime(RuntimeFunction::StringChars),
+    ),
+    (
+        "compare",
+        BuiltinMethod::Runtime(RuntimeFunction::StringCompare),
+    ),
+    (
+        "parse_int",
+        BuiltinMethod::Runtime(RuntimeFunction::StringParseInt),
+    ),
+];
+const CHAR_METHODS: &[(&str, BuiltinMethod)] = &[
+    ("code", BuiltinMethod::Runtime(RuntimeFunction::CharCode)),
+    (
+        "to_string",
+        BuiltinMethod::Runtime(RuntimeFunction::CharToString),
+    ),
+];
+const LIST_METHODS: &[(&str, BuiltinMethod)] = &[("length", BuiltinMethod::ListLength)];
+const MAP_METHODS: &[(&str, BuiltinMethod)] = &[("length", BuiltinMethod::MapLength)];
+const SIGNED_INTEGER_METHODS: &[(&str, BuiltinMethod)] = &[("abs", BuiltinMethod::Abs)];
+
+/// Every builtin method's name along with the kind of value it is called on, for editors to offer.
+pub fn all_methods() -> impl Iterator<Item = (&'static str, &'static str)> {
+    [
+        ("string", STRING_METHODS),
+        ("char", CHAR_METHODS),
+        ("list", LIST_METHODS),
+        ("map", MAP_METHODS),
+        ("signed integer", SIGNED_INTEGER_METHODS),
+    ]
+    .into_iter()
+    .flat_map(|(receiver, methods)| methods.iter().map(move |(name, _)| (*name, receiver)))
+}
+
+/// The methods a value of some type has, by name.
+fn methods_of(receiver: &Type) -> &'static [(&'static str, BuiltinMethod)] {
+    match receiver {
+        Type::String => STRING_METHODS,
+        Type::Char => CHAR_METHODS,
+        Type::List(_) => LIST_METHODS,
+        Type::Map(_, _) => MAP_METHODS,
+        Type::I8 | Type::I16 | Type::I32 | Type::I64 | Type::Iptr => SIGNED_INTEGER_METHODS,
+        _ => &[],
+    }
+}
+
+/// Rebuilds a tree with the builtin method each `MethodCall` refers to filled in, from the type of its receiver.
+///
+/// Receivers are resolved before the calls on them, so that chained calls such as `s.chars().length()` know the
+/// type the inner call produces.
+struct MethodResolver {
+    current_file: usize,
+    error: Option<SemanticError>,
+}
+
+impl MethodResolver {
+    fn fail(&mut self, error: SemanticError) {
+        self.error.get_or_insert(error);
+    }
+
+    /// Looks up the method a call refers to, reporting an error if its receiver doesn't have it.
+    fn lookup(&mut self, call: &MethodCall, receiver: &dyn AstNode) -> Option<BuiltinMethod> {
+        let location = Location::new(self.current_file, call.span());
+        let name = call.name();
+        let receiver_type = semantic::expression_type(receiver);
+        let (methods, receiver_name) = match (&receiver_type, NodeKind::of(receiver)) {
+            (Some(receiver_type), _) => (methods_of(receiver_type), type_name(receiver_type)),
+            // Empty literals have no type of their own, but their methods don't depend on it.
+            (None, NodeKind::ListLiteral) => (LIST_METHODS, "empty list".to_string()),
+            (None, NodeKind::MapLiteral) => (MAP_METHODS, "empty map".to_string()),
+            (None, _) => {
+                self.fail(SemanticError::at(
+                    error_codes::UNKNOWN_METHOD,
+                    format!("Can't tell the type of the value '{name}' is called on"),
+                    location,
+                ));
+                return None;
+            }
+        };
+        let Some(&(_, method)) = methods.iter().find(|(method, _)| *method == name.as_str()) else {
+            let error = SemanticError::at(
+                error_codes::UNKNOWN_METHOD,
+                format!("No method '{name}' on a {receiver_name}"),
+                location,
+            );
+            let suggestion =
+                suggestions::closest(name.as_str(), methods.iter().map(|(name, _)| *name));
+            self.fail(match suggestion {
+                Some(method) => error.with_suggestion(Suggestion::new(
+                    format!("did you mean '{method}'?"),
+                    call.span(),
+                    method,
+                    Applicability::MaybeIncorrect,
+                )),
+                None => match methods_help(receiver_type.as_ref(), &receiver_name, methods) {
+                    Some(help) => error.with_help(help),
+                    None => error,
+                },
+            });
+            return None;
+        };
+        let expected = method.parameter_types().len();
+        if call.arguments().len() != expected {
+            self.fail(SemanticError::at(
+                error_codes::METHOD_ARGUMENT_COUNT,
+                format!(
+                    "'{name}' takes {expected} argument{}, but it was given {}",
+                    if expected == 1 { "" } else { "s" },
+                    call.arguments().len()
+                ),
+                location,
+            ));
+            return None;
+        }
+        Some(method)
+    }
+}
+
+/// Suggests what can be called on a receiver instead, if anything.
+fn methods_help(
+    receiver_type: Option<&Type>,
+    receiver_name: &str,
+    methods: &[(&str, BuiltinMethod)],
+) -> Option<String> {
+    match receiver_type {
+        Some(Type::Option(_)) => {
+            Some("options have no methods; unwrap them first with 'unwrap!'".into())
+        }
+        Some(Type::Result(_, _)) => {
+            Some("results have no methods; unwrap them first with 'unwrap!' or '?'".into())
+        }
+        _ if methods.is_empty() => None,
+        _ => Some(format!(
+            "a {receiver_name} has {}",
+            methods
+                .iter()
+                .map(|(name, _)| format!("'{name}'"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+impl Folder for MethodResolver {
+    fn fold_node(&mut self, node: &dyn AstNode) -> Option<Box<dyn AstNode>> {
+        if let Some(module) = node.downcast_ref::<Module>() {
+            let including_file = std::mem::replace(&mut self.current_file, module.file());
+            let module = module.map_children(&mut |items| ast::fold(self, items));
+            self.current_file = including_file;
+            return Some(module);
+        }
+        let call = node.downcast_ref::<MethodCall>()?;
+        let receiver = ast::fold(self, call.receiver());
+        let arguments = call
+            .arguments()
+            .iter()
+            .map(|argument| ast::fold(self, argument.as_ref()))
+            .collect();
+        let method = self.lookup(call, receiver.as_ref());
+        Some(Box::new(
+            MethodCall::new(call.span(), receiver, call.name(), arguments).with_method(method),
+        ))
+    }
+}
+
+/// Works out which builtin method every method call in the program refers to.
+///
+/// Methods are compiler-provided for the builtin types; programs can't define their own yet.
+pub fn resolve_methods(program: &dyn AstNode) -> Result<Box<dyn AstNode>, SemanticError> {
+    let mut resolver = MethodResolver {
+        current_file: 0,
+        error: None,
+    };
+    let program = ast::fold(&mut resolver, program);
+    match resolver.error {
+        Some(error) => Err(error),
+        None => Ok(program),
+    }
+}
@@ -1,23 +1,270 @@
-use std::{error::Error, fmt::Display, iter::Peekable};
+use alloc::{
+    boxed::Box,
+    collections::VecDeque,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{error::Error, fmt::Display};
 
 use crate::{
     ast::{
-        AstNode, FunctionDefinition, IgnoreValue, ParameterDeclaration, Type, VariableDefinition,
+        AstNode, BinaryOperation, BinaryOperator, EnumDefinition, EnumVariant, FunctionDefinition,
+        IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral, MethodCall, OptionLiteral,
+        ParameterDeclaration, Propagate, ResultLiteral, ResultVariant, Type, VariableDefinition,
     },
-    lexer::{self, Token},
+    diagnostics::{Applicability, Diagnostic, Location, Suggestion},
+    error_codes,
+    features::{Feature, Features},
+    lexer::{Span, Token},
+    suggestions,
+    symbol::Symbol,
 };
 
-type TokenIterator<'lifetime> = Peekable<lexer::TokenIterator<'lifetime>>;
+/// The tokens being parsed, keeping track of where each one came from.
+///
+/// Any number of tokens can be looked ahead at with `peek_nth`. The tokens checked for at the current position are
+/// recorded, so that an error there can say what was expected instead.
+///
+/// A parse can be tried speculatively from a `Checkpoint` and rewound if it doesn't work out, so that a construct
+/// which can't be told apart from another by its first token is resolved by trying one and then the other.
+pub struct TokenStream<'lifetime> {
+    tokens: &'lifetime mut dyn Iterator<Item = (Token, Span)>,
+    /// Tokens taken from `tokens` which may be returned again: the one peeked at, and while there are checkpoints,
+    /// every token since the earliest of them.
+    buffer: VecDeque<(Token, Span)>,
+    /// The index in `buffer` of the next token.
+    position: usize,
+    /// How many checkpoints haven't been rewound to or committed yet.
+    checkpoints: usize,
+    /// The tokens checked for since the last one was consumed.
+    expected: Vec<Token>,
+    previous_span: Span,
+    /// The unstable syntax which may be parsed.
+    features: Features,
+    /// How many parenthesised expressions we are inside.
+    nesting_depth: usize,
+}
+
+/// A position in the tokens which a speculative parse can be rewound to.
+#[must_use = "a checkpoint keeps tokens buffered until it is rewound to or committed"]
+pub struct Checkpoint {
+    position: usize,
+    previous_span: Span,
+    nesting_depth: usize,
+}
+
+/// How deeply expressions may be nested in parentheses, so that deeply nested input fails instead of overflowing the stack.
+const NESTING_LIMIT: usize = 256;
+
+impl<'lifetime> TokenStream<'lifetime> {
+    pub fn new(tokens: &'lifetime mut dyn Iterator<Item = (Token, Span)>) -> Self {
+        Self {
+            tokens,
+            buffer: VecDeque::new(),
+            position: 0,
+            checkpoints: 0,
+            expected: Vec::new(),
+            previous_span: Span::default(),
+            features: Features::default(),
+            nesting_depth: 0,
+        }
+    }
+
+    pub fn with_features(mut self, features: Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Fails if `feature` isn't enabled, blaming the token most recently returned from `next`.
+    fn require_feature(&self, feature: Feature, construct: &str) -> Result<(), SyntaxError> {
+        if self.features.is_enabled(feature) {
+            Ok(())
+        } else {
+            Err(SyntaxError::feature_disabled(
+                feature,
+                construct,
+                self.previous_span,
+            ))
+        }
+    }
+
+    /// Makes sure the token `n` after the next is in the buffer, unless the input runs out first.
+    fn fill(&mut self, n: usize) {
+        while self.buffer.len() <= self.position + n {
+            match self.tokens.next() {
+                Some(token) => self.buffer.push_back(token),
+                None => break,
+            }
+        }
+    }
+
+    /// The token `n` after the next one, so `peek_nth(0)` is the next token.
+    pub fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        self.fill(n);
+        self.buffer.get(self.position + n).map(|(token, _)| token)
+    }
+
+    /// The span of the token `n` after the next one, or the end of the input if there aren't that many.
+    pub fn peek_nth_span(&mut self, n: usize) -> Span {
+        self.fill(n);
+        match self.buffer.get(self.position + n) {
+            Some((_, span)) => *span,
+            None => match self.buffer.back() {
+                Some((_, span)) if self.buffer.len() > self.position => span.following(),
+                _ => self.previous_span.following(),
+            },
+        }
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    /// The span of the next token, or the end of the input if there isn't one.
+    fn peek_span(&mut self) -> Span {
+        self.peek_nth_span(0)
+    }
+
+    /// Whether the next token is `token`, which is recorded as one of the tokens that could have come next.
+    fn check(&mut self, token: &Token) -> bool {
+        if !self.expected.contains(token) {
+            self.expected.push(token.clone());
+        }
+        self.peek() == Some(token)
+    }
+
+    /// Consumes the next token if it is `token`, returning whether it was.
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.check(token);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    /// Consumes the next token, failing unless it is `token`.
+    fn expect(&mut self, token: Token) -> Result<(), SyntaxError> {
+        if self.eat(&token) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// The tokens checked for since the last one was consumed, which the next token wasn't.
+    pub fn expected(&self) -> &[Token] {
+        &self.expected
+    }
+
+    /// An error for the next token, listing the tokens which were checked for in its place.
+    fn unexpected(&mut self) -> SyntaxError {
+        let span = self.peek_span();
+        let error = SyntaxError::unexpected(self.peek(), span);
+        let expected = self
+            .expected
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        match expected.as_slice() {
+            // Invalid tokens are reported as themselves, as nothing could have been expected in their place.
+            _ if error.code == error_codes::INVALID_TOKEN => error,
+            [] => error,
+            [token] => error.with_help(format!("expected {token}")),
+            tokens => error.with_help(format!("expected one of {}", tokens.join(", "))),
+        }
+    }
+
+    /// The span of the token most recently returned from `next`.
+    fn previous_span(&self) -> Span {
+        self.previous_span
+    }
+
+    /// Marks the current position, keeping every token from here on until the checkpoint is rewound to or
+    /// committed.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints += 1;
+        Checkpoint {
+            position: self.position,
+            previous_span: self.previous_span,
+            nesting_depth: self.nesting_depth,
+        }
+    }
+
+    /// Goes back to `checkpoint`, so that the tokens read since are read again.
+    pub fn rewind(&mut self, checkpoint: Checkpoint) {
+        self.expected.clear();
+        self.position = checkpoint.position;
+        self.previous_span = checkpoint.previous_span;
+        self.nesting_depth = checkpoint.nesting_depth;
+        self.release_checkpoint();
+    }
+
+    /// Keeps what was parsed since `checkpoint`, which can no longer be rewound to.
+    pub fn commit(&mut self, _checkpoint: Checkpoint) {
+        self.release_checkpoint();
+    }
+
+    fn release_checkpoint(&mut self) {
+        self.checkpoints -= 1;
+        // Once nothing can be rewound, the tokens already read are no longer needed.
+        if self.checkpoints == 0 {
+            self.buffer.drain(..self.position);
+            self.position = 0;
+        }
+    }
+
+    /// Runs `parse`, rewinding to where it started if it fails so that something else can be tried in its place.
+    pub fn speculate<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, SyntaxError>,
+    ) -> Result<T, SyntaxError> {
+        let checkpoint = self.checkpoint();
+        let result = parse(self);
+        match result {
+            Ok(_) => self.commit(checkpoint),
+            Err(_) => self.rewind(checkpoint),
+        }
+        result
+    }
+}
+
+impl Iterator for TokenStream<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.expected.clear();
+        let (token, span) = if self.checkpoints == 0 {
+            match self.buffer.pop_front() {
+                Some(token) => token,
+                None => self.tokens.next()?,
+            }
+        } else {
+            self.fill(0);
+            let token = self.buffer.get(self.position)?.clone();
+            self.position += 1;
+            token
+        };
+        self.previous_span = span;
+        Some(token)
+    }
+}
 
 use Token::*;
 
 #[derive(Clone, Debug)]
 pub struct SyntaxError {
+    code: &'static str,
     message: String,
+    span: Span,
+    help: Option<String>,
+    /// Boxed, as most errors don't have one and errors are returned from nearly every function.
+    suggestion: Option<Box<Suggestion>>,
 }
 
 impl Display for SyntaxError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Syntax error: {}", self.message)
     }
 }
@@ -25,206 +272,743 @@ impl Display for SyntaxError {
 impl Error for SyntaxError {}
 
 impl SyntaxError {
-    fn unexpected_token(token: &Token) -> Self {
-        Self {
-            message: format!("Unexpected token: {token}"),
+    fn unexpected_token(token: &Token, span: Span) -> Self {
+        match token {
+            // The lexer reports invalid input as an error token, which we present as its own error.
+            Error(message) => Self {
+                code: error_codes::INVALID_TOKEN,
+                message: message.clone(),
+                span,
+                help: None,
+                suggestion: None,
+            },
+            _ => Self {
+                code: error_codes::UNEXPECTED_TOKEN,
+                message: format!("Unexpected token: {token}"),
+                span,
+                help: None,
+                suggestion: None,
+            },
         }
     }
-    fn unexpected_end() -> Self {
+    fn unexpected_end(span: Span) -> Self {
         Self {
+            code: error_codes::UNEXPECTED_END,
             message: "Unexpected end of input".to_string(),
+            span,
+            help: None,
+            suggestion: None,
+        }
+    }
+    fn nesting_limit(span: Span) -> Self {
+        Self {
+            code: error_codes::NESTING_LIMIT,
+            message: format!("Expressions can't be nested more than {NESTING_LIMIT} deep"),
+            span,
+            help: None,
+            suggestion: None,
+        }
+    }
+    fn feature_disabled(feature: Feature, construct: &str, span: Span) -> Self {
+        Self {
+            code: error_codes::UNSTABLE_FEATURE,
+            message: format!("{construct} is unstable"),
+            span,
+            help: Some(format!(
+                "enable it with `--enable-feature {}`",
+                feature.name()
+            )),
+            suggestion: None,
         }
     }
-    fn unexpected(token: Option<&Token>) -> Self {
+    fn with_help(mut self, help: String) -> Self {
+        self.help = Some(help);
+        self
+    }
+    /// Suggests one of `keywords` if the unexpected token was an identifier spelled like one, such as `fucntion`.
+    fn suggesting_keyword(mut self, token: &Token, keywords: &[Token]) -> Self {
+        let Identifier(name) = token else {
+            return self;
+        };
+        let keywords = keywords
+            .iter()
+            .filter_map(Token::exact_match_text)
+            .collect::<Vec<_>>();
+        if let Some(keyword) = suggestions::closest(name.as_str(), keywords) {
+            self.suggestion = Some(Box::new(Suggestion::new(
+                format!("did you mean '{keyword}'?"),
+                self.span,
+                keyword,
+                Applicability::MaybeIncorrect,
+            )));
+        }
+        self
+    }
+    fn unexpected(token: Option<&Token>, span: Span) -> Self {
         if let Some(token) = token {
-            Self::unexpected_token(token)
+            Self::unexpected_token(token, span)
         } else {
-            Self::unexpected_end()
+            Self::unexpected_end(span)
         }
     }
-}
 
-macro_rules! next_must_be {
-    ($token_iterator:ident, $expected:tt) => {
-        match $token_iterator.next() {
-            Some(token) => match token {
-                $expected => {}
-                _ => return Err(SyntaxError::unexpected_token(&token)),
-            },
-            _ => return Err(SyntaxError::unexpected_end()),
+    pub fn to_diagnostic(&self, file: usize) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(self.message.clone())
+            .with_code(self.code)
+            .with_location(Location::new(file, self.span));
+        if let Some(help) = &self.help {
+            diagnostic = diagnostic.with_help(help.clone());
         }
-    };
+        if let Some(suggestion) = &self.suggestion {
+            diagnostic = diagnostic.with_suggestion(Suggestion::clone(suggestion));
+        }
+        diagnostic
+    }
 }
 
 type ParsedItem = Result<Box<dyn AstNode>, SyntaxError>;
+type SpannedItems = Vec<(Span, Box<dyn AstNode>)>;
+
+/// Expects the `;` ending a statement or item, suggesting that it be added straight after the previous token if it
+/// is missing.
+fn parse_semicolon(token_stream: &mut TokenStream) -> Result<(), SyntaxError> {
+    let end = token_stream.previous_span().following();
+    let span = token_stream.peek_span();
+    if token_stream.eat(&Semicolon) {
+        return Ok(());
+    }
+    // The suggestion says what was expected, so the error doesn't list it as well.
+    let mut error = SyntaxError::unexpected(token_stream.peek(), span);
+    error.suggestion = Some(Box::new(Suggestion::new(
+        "add the missing ';'",
+        end,
+        ";",
+        Applicability::MachineApplicable,
+    )));
+    Err(error)
+}
 
 fn parse_repeated_item(
-    token_iterator: &mut TokenIterator,
-    parser_function: impl Fn(&mut TokenIterator) -> ParsedItem,
+    token_stream: &mut TokenStream,
+    parser_function: impl Fn(&mut TokenStream) -> ParsedItem,
     end: Option<Token>,
 ) -> Result<Vec<Box<dyn AstNode>>, SyntaxError> {
     let mut items = Vec::new();
 
     loop {
-        let token = token_iterator.peek();
-        if token == end.as_ref() {
-            if end != None {
-                token_iterator.next().unwrap();
-            }
+        let finished = match &end {
+            Some(end) => token_stream.eat(end),
+            None => token_stream.peek().is_none(),
+        };
+        if finished {
             return Ok(items);
-        } else if token.is_none() {
-            return Err(SyntaxError::unexpected_end());
+        } else if token_stream.peek().is_none() {
+            return Err(token_stream.unexpected());
         } else {
-            let item = parser_function(token_iterator)?;
+            let item = parser_function(token_stream)?;
             items.push(item);
         }
     }
 }
 
-fn parse_global_item(token_iterator: &mut TokenIterator) -> ParsedItem {
-    match token_iterator.peek() {
+fn parse_documentation(token_stream: &mut TokenStream) -> Vec<String> {
+    let mut documentation = Vec::new();
+    while let Some(DocComment(line)) = token_stream.peek() {
+        documentation.push(line.clone());
+        token_stream.next().unwrap();
+    }
+    documentation
+}
+
+/// Parses any number of `@name` attributes.
+fn parse_attributes(token_stream: &mut TokenStream) -> Result<Vec<(Symbol, Span)>, SyntaxError> {
+    let mut attributes = Vec::new();
+    while token_stream.eat(&At) {
+        match token_stream.next() {
+            Some(Identifier(name)) => attributes.push((name, token_stream.previous_span())),
+            token => {
+                return Err(SyntaxError::unexpected(
+                    token.as_ref(),
+                    token_stream.previous_span(),
+                ))
+            }
+        }
+    }
+    Ok(attributes)
+}
+
+fn parse_global_item(token_stream: &mut TokenStream) -> ParsedItem {
+    let documentation = parse_documentation(token_stream);
+    let attributes = parse_attributes(token_stream)?;
+    let span = token_stream.peek_span();
+    match token_stream.peek() {
         Some(token) => match token {
-            Function => parse_function(token_iterator),
-            _ => Err(SyntaxError::unexpected_token(
-                token_iterator.peek().unwrap(),
-            )),
+            Function => parse_function(token_stream, documentation, attributes),
+            Enum if attributes.is_empty() => parse_enum(token_stream, documentation),
+            // Macros used as items, such as `include!`, can't be documented or given attributes.
+            MacroCall(name) if documentation.is_empty() && attributes.is_empty() => {
+                let name = *name;
+                token_stream.next().unwrap();
+                let invocation = parse_macro_invocation(token_stream, name)?;
+                parse_semicolon(token_stream)?;
+                Ok(invocation)
+            }
+            _ => Err(SyntaxError::unexpected_token(token, span)
+                .suggesting_keyword(token, &[Function, Enum])),
         },
-        None => Err(SyntaxError::unexpected_end()),
+        None => Err(SyntaxError::unexpected_end(span)),
     }
 }
 
-fn parse_variable_definition(token_iterator: &mut TokenIterator) -> ParsedItem {
-    next_must_be!(token_iterator, Let);
-    let mutable = if token_iterator.peek() == Some(&Mut) {
-        token_iterator.next();
-        true
-    } else {
-        false
-    };
-    let name = match token_iterator.next() {
+fn parse_variable_definition(token_stream: &mut TokenStream) -> ParsedItem {
+    let start = token_stream.peek_span().start();
+    token_stream.expect(Let)?;
+    let mutable = token_stream.eat(&Mut);
+    let name = match token_stream.next() {
         Some(token) => match token {
             Identifier(name) => name,
-            _ => return Err(SyntaxError::unexpected_token(&token)),
+            _ => {
+                return Err(SyntaxError::unexpected_token(
+                    &token,
+                    token_stream.previous_span(),
+                ))
+            }
         },
-        None => return Err(SyntaxError::unexpected_end()),
+        None => return Err(SyntaxError::unexpected_end(token_stream.peek_span())),
     };
-    next_must_be!(token_iterator, Colon);
-    let variable_type = parse_type(token_iterator)?;
-    next_must_be!(token_iterator, Equals);
-    let value = parse_expression(token_iterator)?;
-    next_must_be!(token_iterator, Semicolon);
+    token_stream.expect(Colon)?;
+    let type_start = token_stream.peek_span().start();
+    let variable_type = parse_type(token_stream)?;
+    let type_span = Span::new(type_start, token_stream.previous_span().end());
+    token_stream.expect(Equals)?;
+    let value = parse_expression(token_stream)?;
+    parse_semicolon(token_stream)?;
     Ok(Box::new(VariableDefinition::new(
         mutable,
         name,
+        Span::new(start, token_stream.previous_span().end()),
+        type_span,
         variable_type,
         value,
     )))
 }
 
-fn parse_expression(token_iterator: &mut TokenIterator) -> ParsedItem {
-    match token_iterator.next() {
+/// Parses `name!(...)`, keeping the tokens between the parentheses for the macro to interpret when it is expanded.
+fn parse_macro_invocation(token_stream: &mut TokenStream, name: Symbol) -> ParsedItem {
+    let start = token_stream.previous_span().start();
+    token_stream.expect(LeftParen)?;
+    let mut arguments = Vec::new();
+    let mut depth = 0;
+    loop {
+        let token = match token_stream.next() {
+            Some(token) => token,
+            None => return Err(SyntaxError::unexpected_end(token_stream.peek_span())),
+        };
+        match token {
+            LeftParen => depth += 1,
+            RightParen if depth == 0 => break,
+            RightParen => depth -= 1,
+            _ => {}
+        }
+        arguments.push((token, token_stream.previous_span()));
+    }
+    let span = Span::new(start, token_stream.previous_span().end());
+    Ok(Box::new(MacroInvocation::new(name, span, arguments)))
+}
+
+/// Runs `parser_function` on something nested in brackets, which counts towards the nesting limit.
+fn parse_nested(
+    token_stream: &mut TokenStream,
+    parser_function: impl Fn(&mut TokenStream) -> ParsedItem,
+) -> ParsedItem {
+    if token_stream.nesting_depth >= NESTING_LIMIT {
+        return Err(SyntaxError::nesting_limit(token_stream.previous_span()));
+    }
+    token_stream.nesting_depth += 1;
+    let item = parser_function(token_stream);
+    token_stream.nesting_depth -= 1;
+    item
+}
+
+/// Parses the rest of a list literal such as `[1, 2]` or a map literal such as `[1: 2]`, after the opening bracket.
+///
+/// The first entry decides which it is; `[]` is an empty list and `[:]` an empty map.
+fn parse_collection_literal(token_stream: &mut TokenStream) -> ParsedItem {
+    let start = token_stream.previous_span().start();
+    let span = |token_stream: &TokenStream| Span::new(start, token_stream.previous_span().end());
+    match token_stream.peek() {
+        Some(RightBracket) => {
+            token_stream.next().unwrap();
+            return Ok(Box::new(ListLiteral::new(span(token_stream), Vec::new())));
+        }
+        Some(Colon) => {
+            token_stream.next().unwrap();
+            token_stream.expect(RightBracket)?;
+            return Ok(Box::new(MapLiteral::new(
+                span(token_stream),
+                Vec::new(),
+                Vec::new(),
+            )));
+        }
+        _ => {}
+    }
+    let first = parse_expression(token_stream)?;
+    if !token_stream.check(&Colon) {
+        let mut elements = vec![first];
+        while parse_separator(token_stream)? {
+            elements.push(parse_expression(token_stream)?);
+        }
+        return Ok(Box::new(ListLiteral::new(span(token_stream), elements)));
+    }
+    let (mut keys, mut values) = (vec![first], Vec::new());
+    loop {
+        token_stream.expect(Colon)?;
+        values.push(parse_expression(token_stream)?);
+        if !parse_separator(token_stream)? {
+            return Ok(Box::new(MapLiteral::new(span(token_stream), keys, values)));
+        }
+        keys.push(parse_expression(token_stream)?);
+    }
+}
+
+/// Parses what follows an entry of a collection literal, returning whether another entry comes next.
+///
+/// Entries are separated by commas, and a trailing comma is allowed before the closing bracket.
+fn parse_separator(token_stream: &mut TokenStream) -> Result<bool, SyntaxError> {
+    if token_stream.eat(&RightBracket) {
+        Ok(false)
+    } else if token_stream.eat(&Comma) {
+        Ok(!token_stream.eat(&RightBracket))
+    } else {
+        Err(token_stream.unexpected())
+    }
+}
+
+/// Parses the parenthesised value after `some`, `ok` or `err`, along with the span from that keyword onwards.
+fn parse_wrapped_value(
+    token_stream: &mut TokenStream,
+) -> Result<(Span, Box<dyn AstNode>), SyntaxError> {
+    let start = token_stream.previous_span().start();
+    token_stream.expect(LeftParen)?;
+    let value = parse_nested(token_stream, parse_expression)?;
+    token_stream.expect(RightParen)?;
+    Ok((Span::new(start, token_stream.previous_span().end()), value))
+}
+
+/// Parses an expression which isn't a binary operation, unless it is in parentheses.
+fn parse_primary_expression(token_stream: &mut TokenStream) -> ParsedItem {
+    match token_stream.next() {
         Some(token) => match token {
             Integer(value) => Ok(Box::new(value)),
-            _ => Err(SyntaxError::unexpected_token(&token)),
+            MacroCall(name) => {
+                if name == "sizeof" || name == "alignof" {
+                    token_stream.require_feature(Feature::TypeIntrinsics, &format!("`{name}!`"))?;
+                }
+                parse_macro_invocation(token_stream, name)
+            }
+            LeftParen => {
+                let expression = parse_nested(token_stream, parse_expression)?;
+                token_stream.expect(RightParen)?;
+                Ok(expression)
+            }
+            LeftBracket => parse_nested(token_stream, parse_collection_literal),
+            NoneKeyword => Ok(Box::new(OptionLiteral::new(
+                token_stream.previous_span(),
+                None,
+            ))),
+            SomeKeyword => {
+                let (span, value) = parse_wrapped_value(token_stream)?;
+                Ok(Box::new(OptionLiteral::new(span, Some(value))))
+            }
+            OkKeyword | ErrKeyword => {
+                let variant = if token == OkKeyword {
+                    ResultVariant::Ok
+                } else {
+                    ResultVariant::Err
+                };
+                let (span, value) = parse_wrapped_value(token_stream)?;
+                Ok(Box::new(ResultLiteral::new(span, variant, value)))
+            }
+            _ => Err(SyntaxError::unexpected_token(
+                &token,
+                token_stream.previous_span(),
+            )),
         },
-        None => Err(SyntaxError::unexpected_end()),
+        None => Err(SyntaxError::unexpected_end(token_stream.peek_span())),
     }
 }
 
-fn parse_statement(token_iterator: &mut TokenIterator) -> ParsedItem {
-    match token_iterator.peek() {
+/// Parses an argument of a method call, along with the comma after it.
+fn parse_argument(token_stream: &mut TokenStream) -> ParsedItem {
+    let argument = parse_nested(token_stream, parse_expression)?;
+    token_stream.eat(&Comma);
+    Ok(argument)
+}
+
+/// Parses a primary expression followed by any number of indices, `?`s and method calls, such as `[[1, 2]][0][1]`,
+/// `[1: ok(2)][1]?` or `format!("hi").length()`.
+fn parse_postfix_expression(token_stream: &mut TokenStream) -> ParsedItem {
+    let mut expression = parse_primary_expression(token_stream)?;
+    loop {
+        match token_stream.peek() {
+            Some(LeftBracket) => {
+                token_stream.next().unwrap();
+                let start = token_stream.previous_span().start();
+                let index = parse_nested(token_stream, parse_expression)?;
+                token_stream.expect(RightBracket)?;
+                let span = Span::new(start, token_stream.previous_span().end());
+                expression = Box::new(Index::new(span, expression, index));
+            }
+            Some(Question) => {
+                token_stream.next().unwrap();
+                expression = Box::new(Propagate::new(token_stream.previous_span(), expression));
+            }
+            Some(Dot) => {
+                token_stream.next().unwrap();
+                let name = match token_stream.next() {
+                    Some(Identifier(name)) => name,
+                    token => {
+                        return Err(SyntaxError::unexpected(
+                            token.as_ref(),
+                            token_stream.previous_span(),
+                        ))
+                    }
+                };
+                let span = token_stream.previous_span();
+                token_stream.expect(LeftParen)?;
+                let arguments =
+                    parse_repeated_item(token_stream, parse_argument, Some(RightParen))?;
+                expression = Box::new(MethodCall::new(span, expression, name, arguments));
+            }
+            _ => return Ok(expression),
+        }
+    }
+}
+
+/// Which side of a chain of operators with the same precedence is grouped first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    Left,
+    /// `a = b = c` is `a = (b = c)`.
+    Right,
+}
+
+/// How tightly a binary operator binds to its operands. Higher precedences bind more tightly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinaryPrecedence {
+    pub precedence: u8,
+    pub associativity: Associativity,
+}
+
+/// Every binary operator with its precedence and associativity, from the loosest binding to the tightest.
+///
+/// This is the only place operator precedence is written down: the parser climbs it, the formatter decides where
+/// parentheses are needed from it and `--emit grammar` documents it.
+pub const BINARY_OPERATORS: &[(BinaryOperator, BinaryPrecedence)] = &[
+    (
+        BinaryOperator::Add,
+        BinaryPrecedence {
+            precedence: 1,
+            associativity: Associativity::Left,
+        },
+    ),
+    (
+        BinaryOperator::Subtract,
+        BinaryPrecedence {
+            precedence: 1,
+            associativity: Associativity::Left,
+        },
+    ),
+    (
+        BinaryOperator::Multiply,
+        BinaryPrecedence {
+            precedence: 2,
+            associativity: Associativity::Left,
+        },
+    ),
+    (
+        BinaryOperator::Divide,
+        BinaryPrecedence {
+            precedence: 2,
+            associativity: Associativity::Left,
+        },
+    ),
+    (
+        BinaryOperator::Remainder,
+        BinaryPrecedence {
+            precedence: 2,
+            associativity: Associativity::Left,
+        },
+    ),
+];
+
+/// How tightly a binary operator binds, from `BINARY_OPERATORS`.
+pub fn binary_precedence(operator: BinaryOperator) -> BinaryPrecedence {
+    BINARY_OPERATORS
+        .iter()
+        .find(|(entry, _)| *entry == operator)
+        .map(|(_, precedence)| *precedence)
+        .expect("Every binary operator is in the precedence table")
+}
+
+/// Parses an expression whose binary operators all have at least `minimum_precedence`, by precedence climbing.
+fn parse_binary_expression(token_stream: &mut TokenStream, minimum_precedence: u8) -> ParsedItem {
+    let mut left = parse_postfix_expression(token_stream)?;
+    while let Some(operator) = token_stream.peek().and_then(BinaryOperator::from_token) {
+        let BinaryPrecedence {
+            precedence,
+            associativity,
+        } = binary_precedence(operator);
+        if precedence < minimum_precedence {
+            break;
+        }
+        token_stream.next();
+        let span = token_stream.previous_span();
+        // Operators of the same precedence are only grouped into the right operand if they are right associative.
+        let right_precedence = match associativity {
+            Associativity::Left => precedence + 1,
+            Associativity::Right => precedence,
+        };
+        let right = parse_binary_expression(token_stream, right_precedence)?;
+        left = Box::new(BinaryOperation::new(operator, span, left, right));
+    }
+    Ok(left)
+}
+
+fn parse_expression(token_stream: &mut TokenStream) -> ParsedItem {
+    parse_binary_expression(token_stream, 0)
+}
+
+fn parse_statement(token_stream: &mut TokenStream) -> ParsedItem {
+    let span = token_stream.peek_span();
+    match token_stream.peek() {
         Some(token) => match token {
-            Let => parse_variable_definition(token_iterator),
-            _ => {
-                let expression = parse_expression(token_iterator)?;
-                if token_iterator.peek() == Some(&Semicolon) {
-                    token_iterator.next().unwrap();
-                    Ok(Box::new(IgnoreValue::new(expression)))
+            Let => parse_variable_definition(token_stream),
+            token if token.can_start_expression() => {
+                let first_token = token.clone();
+                let expression = parse_expression(token_stream).map_err(|error| {
+                    // A misspelled `let` is read as the start of an expression, which fails at the same token.
+                    if error.span == span {
+                        error.suggesting_keyword(&first_token, &[Let])
+                    } else {
+                        error
+                    }
+                })?;
+                if token_stream.eat(&Semicolon) {
+                    Ok(Box::new(IgnoreValue::new(
+                        Span::new(span.start(), token_stream.previous_span().end()),
+                        expression,
+                    )))
                 } else {
                     Ok(expression)
                 }
             }
+            token => Err(SyntaxError::unexpected_token(token, span)),
         },
-        None => Err(SyntaxError::unexpected_end()),
+        None => Err(SyntaxError::unexpected_end(span)),
     }
 }
 
-fn parse_block(token_iterator: &mut TokenIterator) -> ParsedItem {
-    next_must_be!(token_iterator, LeftBrace);
-    let statements = parse_repeated_item(token_iterator, parse_statement, Some(RightBrace))?;
-    Ok(Box::new(statements))
+/// Parses a block, along with the span of its last expression if it ends with one rather than a statement.
+fn parse_block(
+    token_stream: &mut TokenStream,
+) -> Result<(Box<dyn AstNode>, Option<Span>), SyntaxError> {
+    token_stream.expect(LeftBrace)?;
+    let mut statements = Vec::new();
+    let mut tail_span = None;
+    while !token_stream.eat(&RightBrace) {
+        if token_stream.peek().is_none() {
+            return Err(token_stream.unexpected());
+        }
+        let start = token_stream.peek_span().start();
+        let statement = parse_statement(token_stream)?;
+        let is_expression = statement.downcast_ref::<IgnoreValue>().is_none()
+            && statement.downcast_ref::<VariableDefinition>().is_none();
+        tail_span = is_expression.then(|| Span::new(start, token_stream.previous_span().end()));
+        statements.push(statement);
+    }
+    Ok((Box::new(statements), tail_span))
 }
 
-fn parse_type(token_iterator: &mut TokenIterator) -> ParsedItem {
-    match token_iterator.next() {
-        Some(token) => match token {
-            I8 => Ok(Box::new(Type::I8)),
-            I16 => Ok(Box::new(Type::I16)),
-            I32 => Ok(Box::new(Type::I32)),
-            I64 => Ok(Box::new(Type::I64)),
-            Iptr => Ok(Box::new(Type::Iptr)),
-            U8 => Ok(Box::new(Type::U8)),
-            U16 => Ok(Box::new(Type::U16)),
-            U32 => Ok(Box::new(Type::U32)),
-            U64 => Ok(Box::new(Type::U64)),
-            Uptr => Ok(Box::new(Type::Uptr)),
-            F32 => Ok(Box::new(Type::F32)),
-            F64 => Ok(Box::new(Type::F64)),
-            Bool => Ok(Box::new(Type::Bool)),
-            CharType => Ok(Box::new(Type::Char)),
-            StringType => Ok(Box::new(Type::String)),
-            _ => Err(SyntaxError::unexpected_token(&token)),
-        },
-        _ => Err(SyntaxError::unexpected_end()),
+/// Parses a type, including the parameters of collection types such as `map<u8, list<i32>>`.
+fn parse_type_value(token_stream: &mut TokenStream) -> Result<Type, SyntaxError> {
+    match token_stream.next() {
+        Some(List) => {
+            token_stream.expect(Less)?;
+            let element_type = parse_type_value(token_stream)?;
+            token_stream.expect(Greater)?;
+            Ok(Type::List(Box::new(element_type)))
+        }
+        Some(Map) => {
+            token_stream.expect(Less)?;
+            let key_type = parse_type_value(token_stream)?;
+            token_stream.expect(Comma)?;
+            let value_type = parse_type_value(token_stream)?;
+            token_stream.expect(Greater)?;
+            Ok(Type::Map(Box::new(key_type), Box::new(value_type)))
+        }
+        Some(OptionType) => {
+            token_stream.expect(Less)?;
+            let value_type = parse_type_value(token_stream)?;
+            token_stream.expect(Greater)?;
+            Ok(Type::Option(Box::new(value_type)))
+        }
+        Some(ResultType) => {
+            token_stream.expect(Less)?;
+            let value_type = parse_type_value(token_stream)?;
+            token_stream.expect(Comma)?;
+            let error_type = parse_type_value(token_stream)?;
+            token_stream.expect(Greater)?;
+            Ok(Type::Result(Box::new(value_type), Box::new(error_type)))
+        }
+        Some(token) => token
+            .type_keyword()
+            .ok_or_else(|| SyntaxError::unexpected_token(&token, token_stream.previous_span())),
+        _ => Err(SyntaxError::unexpected_end(token_stream.peek_span())),
     }
 }
 
-fn parse_parameter_declaration(token_iterator: &mut TokenIterator) -> ParsedItem {
-    let name = match token_iterator.next() {
+fn parse_type(token_stream: &mut TokenStream) -> ParsedItem {
+    Ok(Box::new(parse_type_value(token_stream)?))
+}
+
+fn parse_parameter_declaration(token_stream: &mut TokenStream) -> ParsedItem {
+    let name = match token_stream.next() {
         Some(token) => match token {
             Identifier(name) => name,
-            _ => return Err(SyntaxError::unexpected_token(&token)),
+            _ => {
+                return Err(SyntaxError::unexpected_token(
+                    &token,
+                    token_stream.previous_span(),
+                ))
+            }
         },
-        _ => return Err(SyntaxError::unexpected_end()),
+        _ => return Err(SyntaxError::unexpected_end(token_stream.peek_span())),
     };
-    next_must_be!(token_iterator, Colon);
-    let parameter_type = parse_type(token_iterator)?;
-    if token_iterator.peek() == Some(&Comma) {
-        token_iterator.next().unwrap();
-    }
+    token_stream.expect(Colon)?;
+    let parameter_type = parse_type(token_stream)?;
+    token_stream.eat(&Comma);
     Ok(Box::new(ParameterDeclaration::new(name, parameter_type)))
 }
 
-fn parse_function(token_iterator: &mut TokenIterator) -> ParsedItem {
-    assert!(token_iterator.next() == Some(Token::Function));
-    let name = if let Some(Identifier(name)) = token_iterator.peek() {
-        Ok(name.clone())
+fn parse_function(
+    token_stream: &mut TokenStream,
+    documentation: Vec<String>,
+    attributes: Vec<(Symbol, Span)>,
+) -> ParsedItem {
+    assert!(token_stream.next() == Some(Token::Function));
+    let name_span = token_stream.peek_span();
+    let name = if let Some(Identifier(name)) = token_stream.peek() {
+        Ok(*name)
     } else {
-        Err(SyntaxError::unexpected(token_iterator.peek()))
+        Err(SyntaxError::unexpected(token_stream.peek(), name_span))
     }?;
-    token_iterator.next().unwrap();
-    next_must_be!(token_iterator, LeftParen);
-    let parameters = parse_repeated_item(
-        token_iterator,
-        parse_parameter_declaration,
-        Some(RightParen),
-    )?;
-    next_must_be!(token_iterator, Arrow);
-    let return_type = parse_type(token_iterator)?;
-    let body = parse_block(token_iterator)?;
-    Ok(Box::new(FunctionDefinition::new(
-        name,
-        parameters,
-        return_type,
-        body,
-    )))
+    token_stream.next().unwrap();
+    token_stream.expect(LeftParen)?;
+    let parameters =
+        parse_repeated_item(token_stream, parse_parameter_declaration, Some(RightParen))?;
+    token_stream.expect(Arrow)?;
+    let return_type = parse_type(token_stream)?;
+    let (body, tail_span) = parse_block(token_stream)?;
+    Ok(Box::new(
+        FunctionDefinition::new(name, name_span, parameters, return_type, body)
+            .with_tail_span(tail_span)
+            .with_documentation(documentation)
+            .with_attributes(attributes),
+    ))
 }
 
-fn parse_program(token_iterator: &mut TokenIterator) -> ParsedItem {
-    let children = parse_repeated_item(token_iterator, parse_global_item, None)?;
+/// Parses a variant of an enum, such as the `A = 1` in `enum Flags: u8 { A = 1 }`, along with the comma after it.
+fn parse_enum_variant(token_stream: &mut TokenStream) -> ParsedItem {
+    let span = token_stream.peek_span();
+    let name = match token_stream.next() {
+        Some(Identifier(name)) => name,
+        token => return Err(SyntaxError::unexpected(token.as_ref(), span)),
+    };
+    let discriminant = if token_stream.eat(&Equals) {
+        Some(parse_expression(token_stream)?)
+    } else {
+        None
+    };
+    if !token_stream.check(&RightBrace) {
+        token_stream.expect(Comma)?;
+    }
+    Ok(Box::new(EnumVariant::new(name, span, discriminant)))
+}
+
+fn parse_enum(token_stream: &mut TokenStream, documentation: Vec<String>) -> ParsedItem {
+    assert!(token_stream.next() == Some(Token::Enum));
+    let name_span = token_stream.peek_span();
+    let name = if let Some(Identifier(name)) = token_stream.peek() {
+        Ok(*name)
+    } else {
+        Err(SyntaxError::unexpected(token_stream.peek(), name_span))
+    }?;
+    token_stream.next().unwrap();
+    let representation = if token_stream.eat(&Colon) {
+        Some(parse_type(token_stream)?)
+    } else {
+        None
+    };
+    token_stream.expect(LeftBrace)?;
+    let variants = parse_repeated_item(token_stream, parse_enum_variant, Some(RightBrace))?;
+    Ok(Box::new(
+        EnumDefinition::new(name, name_span, representation, variants)
+            .with_documentation(documentation),
+    ))
+}
+
+fn parse_program(token_stream: &mut TokenStream) -> ParsedItem {
+    let children = parse_repeated_item(token_stream, parse_global_item, None)?;
     Ok(Box::new(children))
 }
 
-pub fn parse(token_iterator: &mut TokenIterator) -> Result<Box<dyn AstNode>, SyntaxError> {
-    parse_program(token_iterator)
+/// Parses the whole of a macro's arguments with `parser_function`, failing if any tokens are left over.
+fn parse_arguments(
+    arguments: &[(Token, Span)],
+    invocation_span: Span,
+    features: &Features,
+    parser_function: impl Fn(&mut TokenStream) -> ParsedItem,
+) -> ParsedItem {
+    let mut tokens = arguments.iter().cloned();
+    let mut token_stream = TokenStream::new(&mut tokens).with_features(features.clone());
+    // Running out of arguments is reported at the end of the invocation.
+    token_stream.previous_span = Span::new(invocation_span.start(), invocation_span.end() - 1);
+    let item = parser_function(&mut token_stream)?;
+    let span = token_stream.peek_span();
+    match token_stream.peek() {
+        Some(token) => Err(SyntaxError::unexpected_token(token, span)),
+        None => Ok(item),
+    }
+}
+
+/// Parses a macro argument which is a type, such as the `i32` in `sizeof!(i32)`.
+pub fn parse_type_argument(arguments: &[(Token, Span)], invocation_span: Span) -> ParsedItem {
+    parse_arguments(arguments, invocation_span, &Features::default(), parse_type)
+}
+
+/// Parses a macro argument which is an expression, such as the `1` in `assert!(1)`.
+pub fn parse_expression_argument(
+    arguments: &[(Token, Span)],
+    invocation_span: Span,
+    features: &Features,
+) -> ParsedItem {
+    parse_arguments(arguments, invocation_span, features, parse_expression)
+}
+
+/// Parses top-level items until the tokens run out, along with the span each one covers from its first doc comment
+/// or attribute to its last token, so that an edit can be matched to the items it touches.
+pub fn parse_items_with_spans(token_stream: &mut TokenStream) -> Result<SpannedItems, SyntaxError> {
+    let mut items = Vec::new();
+    while token_stream.peek().is_some() {
+        let start = token_stream.peek_span().start();
+        let item = parse_global_item(token_stream)?;
+        items.push((Span::new(start, token_stream.previous_span().end()), item));
+    }
+    Ok(items)
+}
+
+pub fn parse(token_stream: &mut TokenStream) -> Result<Box<dyn AstNode>, SyntaxError> {
+    parse_program(token_stream)
 }
@@ -0,0 +1,106 @@
+//! Timing of the functions a program runs, for `--instrument profile`.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use hematite_lang::{interpreter::EvaluationHook, symbol::Symbol};
+
+/// How often, and for how long, the functions along one call path ran.
+#[derive(Debug, Default)]
+struct CallRecord {
+    calls: usize,
+    total: Duration,
+    /// The part of `total` spent in functions called from this one.
+    in_callees: Duration,
+}
+
+/// Counts each function's calls as the interpreter enters and leaves it, building a call tree.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    /// The functions being run, outermost first, with when each started.
+    stack: Vec<(Symbol, Instant)>,
+    /// Keyed by call path, outermost function first, so that a path's callees sort straight after it.
+    records: BTreeMap<Vec<Symbol>, CallRecord>,
+}
+
+impl Profiler {
+    /// How many calls were made, with the total and self time of each function, busiest first.
+    fn flat(&self) -> Vec<(Symbol, CallRecord)> {
+        let mut functions = BTreeMap::<Symbol, CallRecord>::new();
+        for (path, record) in &self.records {
+            let (&function, callers) = path.split_last().expect("Paths are never empty");
+            let flat = functions.entry(function).or_default();
+            flat.calls += record.calls;
+            // A recursive call's time is already part of its outermost call's.
+            if !callers.contains(&function) {
+                flat.total += record.total;
+            }
+            flat.in_callees += record.in_callees;
+        }
+        let mut functions = functions.into_iter().collect::<Vec<_>>();
+        functions.sort_by_key(|(_, record)| {
+            std::cmp::Reverse(record.total.saturating_sub(record.in_callees))
+        });
+        functions
+    }
+
+    /// A flat profile followed by the call tree.
+    pub fn report(&self) -> String {
+        let milliseconds = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        let mut report =
+            String::from("flat profile:\n     calls     self (ms)    total (ms)  function\n");
+        for (function, record) in self.flat() {
+            writeln!(
+                report,
+                "{:>10} {:>13.3} {:>13.3}  {function}",
+                record.calls,
+                milliseconds(record.total.saturating_sub(record.in_callees)),
+                milliseconds(record.total)
+            )
+            .unwrap();
+        }
+        report += "\ncall tree:\n     calls    total (ms)  function\n";
+        for (path, record) in &self.records {
+            writeln!(
+                report,
+                "{:>10} {:>13.3}  {}{}",
+                record.calls,
+                milliseconds(record.total),
+                "  ".repeat(path.len() - 1),
+                path.last().expect("Paths are never empty")
+            )
+            .unwrap();
+        }
+        report
+    }
+}
+
+impl EvaluationHook for Profiler {
+    fn enter_function(&mut self, function: Symbol) {
+        self.stack.push((function, Instant::now()));
+    }
+
+    fn exit_function(&mut self, function: Symbol) {
+        let (entered, start) = self
+            .stack
+            .pop()
+            .expect("Functions are exited in the order they were entered");
+        debug_assert_eq!(entered, function);
+        let elapsed = start.elapsed();
+        let mut path = self
+            .stack
+            .iter()
+            .map(|(caller, _)| *caller)
+            .collect::<Vec<_>>();
+        if !path.is_empty() {
+            self.records.entry(path.clone()).or_default().in_callees += elapsed;
+        }
+        path.push(function);
+        let record = self.records.entry(path).or_default();
+        record.calls += 1;
+        record.total += elapsed;
+    }
+}
@@ -0,0 +1,246 @@
+//! Looking things up in a tree, for tools such as the language server and refactorings.
+
+use alloc::{boxed::Box, vec, vec::Vec};
+
+use crate::{
+    ast::{
+        Assertion, AstNode, AstVisitor, BinaryOperation, EnumDefinition, EnumVariant, Format,
+        FunctionDefinition, IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral,
+        MethodCall, Module, OptionLiteral, ParameterDeclaration, Propagate, ResultLiteral,
+        RuntimeCall, StaticAssertion, Type, TypeIntrinsic, Unwrap, VariableDefinition,
+    },
+    lexer::Span,
+};
+
+/// Which type a node is, with one variant for each of the visitor's methods.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    List,
+    VariableDefinition,
+    Type,
+    ParameterDeclaration,
+    FunctionDefinition,
+    IgnoreValue,
+    IntegerLiteral,
+    BinaryOperation,
+    TypeIntrinsic,
+    Module,
+    MacroInvocation,
+    Assertion,
+    Format,
+    RuntimeCall,
+    StaticAssertion,
+    ListLiteral,
+    MapLiteral,
+    Index,
+    OptionLiteral,
+    Unwrap,
+    ResultLiteral,
+    Propagate,
+    MethodCall,
+    EnumDefinition,
+    EnumVariant,
+}
+
+impl NodeKind {
+    pub fn of(node: &dyn AstNode) -> Self {
+        let mut finder = KindFinder(NodeKind::List);
+        node.apply(&mut finder);
+        finder.0
+    }
+}
+
+struct KindFinder(NodeKind);
+
+impl AstVisitor for KindFinder {
+    fn visit_list(&mut self, _list: &[Box<dyn AstNode>]) {
+        self.0 = NodeKind::List;
+    }
+    fn visit_variable_definition(&mut self, _variable: &VariableDefinition) {
+        self.0 = NodeKind::VariableDefinition;
+    }
+    fn visit_type(&mut self, _type_value: &Type) {
+        self.0 = NodeKind::Type;
+    }
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {
+        self.0 = NodeKind::ParameterDeclaration;
+    }
+    fn visit_function_definition(&mut self, _function: &FunctionDefinition) {
+        self.0 = NodeKind::FunctionDefinition;
+    }
+    fn visit_ignore_value(&mut self, _ignore_value: &IgnoreValue) {
+        self.0 = NodeKind::IgnoreValue;
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {
+        self.0 = NodeKind::IntegerLiteral;
+    }
+    fn visit_binary_operation(&mut self, _operation: &BinaryOperation) {
+        self.0 = NodeKind::BinaryOperation;
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {
+        self.0 = NodeKind::TypeIntrinsic;
+    }
+    fn visit_module(&mut self, _module: &Module) {
+        self.0 = NodeKind::Module;
+    }
+    fn visit_macro_invocation(&mut self, _invocation: &MacroInvocation) {
+        self.0 = NodeKind::MacroInvocation;
+    }
+    fn visit_assertion(&mut self, _assertion: &Assertion) {
+        self.0 = NodeKind::Assertion;
+    }
+    fn visit_format(&mut self, _format: &Format) {
+        self.0 = NodeKind::Format;
+    }
+    fn visit_runtime_call(&mut self, _call: &RuntimeCall) {
+        self.0 = NodeKind::RuntimeCall;
+    }
+    fn visit_static_assertion(&mut self, _assertion: &StaticAssertion) {
+        self.0 = NodeKind::StaticAssertion;
+    }
+    fn visit_list_literal(&mut self, _literal: &ListLiteral) {
+        self.0 = NodeKind::ListLiteral;
+    }
+    fn visit_map_literal(&mut self, _literal: &MapLiteral) {
+        self.0 = NodeKind::MapLiteral;
+    }
+    fn visit_index(&mut self, _index: &Index) {
+        self.0 = NodeKind::Index;
+    }
+    fn visit_option_literal(&mut self, _literal: &OptionLiteral) {
+        self.0 = NodeKind::OptionLiteral;
+    }
+    fn visit_unwrap(&mut self, _unwrap: &Unwrap) {
+        self.0 = NodeKind::Unwrap;
+    }
+    fn visit_result_literal(&mut self, _literal: &ResultLiteral) {
+        self.0 = NodeKind::ResultLiteral;
+    }
+    fn visit_propagate(&mut self, _propagate: &Propagate) {
+        self.0 = NodeKind::Propagate;
+    }
+    fn visit_method_call(&mut self, _call: &MethodCall) {
+        self.0 = NodeKind::MethodCall;
+    }
+    fn visit_enum_definition(&mut self, _definition: &EnumDefinition) {
+        self.0 = NodeKind::EnumDefinition;
+    }
+    fn visit_enum_variant(&mut self, _variant: &EnumVariant) {
+        self.0 = NodeKind::EnumVariant;
+    }
+}
+
+/// Finds the span of the nodes which record one. For a function or an enum, that is the span of its name.
+#[derive(Default)]
+struct SpanFinder(Option<Span>);
+
+impl AstVisitor for SpanFinder {
+    fn visit_list(&mut self, _list: &[Box<dyn AstNode>]) {}
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.0 = Some(variable.span());
+    }
+    fn visit_type(&mut self, _type_value: &Type) {}
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {}
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.0 = Some(function.name_span());
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.0 = Some(ignore_value.span());
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {}
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.0 = Some(operation.span());
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {}
+    fn visit_module(&mut self, _module: &Module) {}
+    fn visit_macro_invocation(&mut self, invocation: &MacroInvocation) {
+        self.0 = Some(invocation.span());
+    }
+    fn visit_assertion(&mut self, _assertion: &Assertion) {}
+    fn visit_format(&mut self, format: &Format) {
+        self.0 = Some(format.span());
+    }
+    fn visit_runtime_call(&mut self, _call: &RuntimeCall) {}
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        self.0 = Some(assertion.span());
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.0 = Some(literal.span());
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.0 = Some(literal.span());
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.0 = Some(index.span());
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.0 = Some(literal.span());
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.0 = Some(unwrap.span());
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.0 = Some(literal.span());
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.0 = Some(propagate.span());
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.0 = Some(call.span());
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        self.0 = Some(definition.name_span());
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.0 = Some(variant.span());
+    }
+}
+
+/// The span a node records, if it records one.
+pub fn span_of(node: &dyn AstNode) -> Option<Span> {
+    let mut finder = SpanFinder::default();
+    node.apply(&mut finder);
+    finder.0
+}
+
+/// The definition of the function called `name`, wherever it is in the tree.
+pub fn find_function<'tree>(
+    program: &'tree dyn AstNode,
+    name: &str,
+) -> Option<&'tree FunctionDefinition> {
+    items_of_kind(program, NodeKind::FunctionDefinition)
+        .into_iter()
+        .filter_map(|node| node.downcast_ref::<FunctionDefinition>())
+        .find(|function| function.name() == name)
+}
+
+/// Every node of a kind in the tree, including the root, in the order a visitor would reach them.
+pub fn items_of_kind(program: &dyn AstNode, kind: NodeKind) -> Vec<&dyn AstNode> {
+    let mut items = Vec::new();
+    let mut pending = vec![program];
+    while let Some(node) = pending.pop() {
+        if NodeKind::of(node) == kind {
+            items.push(node);
+        }
+        // Pushed in reverse so that the first child is popped first.
+        pending.extend(node.children().into_iter().rev());
+    }
+    items
+}
+
+/// The innermost node whose span contains `offset`, for a tree parsed from a single file.
+///
+/// Nodes without a span of their own, such as literals, are never found, so this is the nearest node around the
+/// offset which records where it is.
+pub fn node_at_offset(program: &dyn AstNode, offset: usize) -> Option<&dyn AstNode> {
+    // Spans don't nest reliably, as a function's only covers its name, so every child is searched.
+    program
+        .children()
+        .into_iter()
+        .find_map(|child| node_at_offset(child, offset))
+        .or_else(|| {
+            span_of(program)
+                .filter(|span| span.start() <= offset && offset <= span.end())
+                .map(|_| program)
+        })
+}
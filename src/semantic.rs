@@ -0,0 +1,1443 @@
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use rayon::prelude::*;
+
+use crate::{
+    ast::{
+        Assertion, AstNode, AstVisitor, BinaryOperation, BinaryOperator, EnumDefinition,
+        EnumVariant, Format, FormatSegment, FunctionDefinition, IgnoreValue, Index, ListLiteral,
+        MacroInvocation, MapLiteral, MethodCall, Module, OptionLiteral, ParameterDeclaration,
+        Propagate, ResultLiteral, ResultVariant, RuntimeCall, StaticAssertion, Type, TypeIntrinsic,
+        Unwrap, VariableDefinition,
+    },
+    diagnostics::{Applicability, Diagnostic, Label, Location, Suggestion},
+    error_codes,
+    formatter::type_name,
+    interpreter,
+    lexer::Span,
+    macros,
+    query::{self, NodeKind},
+    suggestions,
+    symbol::Symbol,
+};
+
+#[derive(Clone, Debug)]
+pub struct SemanticError {
+    code: &'static str,
+    message: String,
+    location: Option<Location>,
+    help: Option<String>,
+    suggestion: Option<Box<Suggestion>>,
+    label: Option<Box<Label>>,
+}
+
+impl Display for SemanticError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Semantic error: {}", self.message)
+    }
+}
+
+impl Error for SemanticError {}
+
+impl SemanticError {
+    fn new(code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            location: None,
+            help: None,
+            suggestion: None,
+            label: None,
+        }
+    }
+
+    pub(crate) fn at(code: &'static str, message: impl Into<String>, location: Location) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            location: Some(location),
+            help: None,
+            suggestion: None,
+            label: None,
+        }
+    }
+
+    pub(crate) fn with_help(mut self, help: String) -> Self {
+        self.help = Some(help);
+        self
+    }
+
+    pub(crate) fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestion = Some(Box::new(suggestion));
+        self
+    }
+
+    /// Points at another place in the program which explains the error.
+    pub(crate) fn with_label(mut self, label: Label) -> Self {
+        self.label = Some(Box::new(label));
+        self
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let mut diagnostic = Diagnostic::error(self.message.clone()).with_code(self.code);
+        if let Some(help) = &self.help {
+            diagnostic = diagnostic.with_help(help.clone());
+        }
+        if let Some(suggestion) = &self.suggestion {
+            diagnostic = diagnostic.with_suggestion(Suggestion::clone(suggestion));
+        }
+        if let Some(label) = &self.label {
+            diagnostic = diagnostic.with_label(Label::clone(label));
+        }
+        match self.location {
+            Some(location) => diagnostic.with_location(location),
+            None => diagnostic,
+        }
+    }
+}
+
+/// A top-level function along with the module it is defined in.
+pub struct CollectedFunction {
+    module: String,
+    file: usize,
+    function: FunctionDefinition,
+}
+
+impl CollectedFunction {
+    pub fn module(&self) -> &str {
+        &self.module
+    }
+
+    pub fn function(&self) -> &FunctionDefinition {
+        &self.function
+    }
+
+    pub fn location(&self) -> Location {
+        Location::new(self.file, self.function.name_span())
+    }
+}
+
+/// Collects the top-level function definitions of a program.
+struct FunctionCollector {
+    current_module: String,
+    current_file: usize,
+    functions: Vec<CollectedFunction>,
+}
+
+impl FunctionCollector {
+    fn collect(program: &dyn AstNode) -> Vec<CollectedFunction> {
+        let mut collector = Self {
+            current_module: String::new(),
+            current_file: 0,
+            functions: Vec::new(),
+        };
+        program.apply(&mut collector);
+        collector.functions
+    }
+}
+
+impl AstVisitor for FunctionCollector {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        for item in list {
+            item.apply(self);
+        }
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.functions.push(CollectedFunction {
+            module: self.current_module.clone(),
+            file: self.current_file,
+            function: function.clone(),
+        });
+    }
+    fn visit_module(&mut self, module: &Module) {
+        // Included files are nested modules, after which the including file's items continue.
+        let including_module =
+            std::mem::replace(&mut self.current_module, module.name().to_string());
+        let including_file = std::mem::replace(&mut self.current_file, module.file());
+        module.items().apply(self);
+        self.current_module = including_module;
+        self.current_file = including_file;
+    }
+}
+
+/// Works out the type of an expression's value, for the expressions whose type is known without type checking.
+struct ExpressionTyper {
+    expression_type: Option<Type>,
+}
+
+impl AstVisitor for ExpressionTyper {
+    fn visit_list(&mut self, _list: &[Box<dyn AstNode>]) {}
+    fn visit_variable_definition(&mut self, _variable: &VariableDefinition) {}
+    fn visit_type(&mut self, _type_value: &Type) {}
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {}
+    fn visit_function_definition(&mut self, _function: &FunctionDefinition) {}
+    fn visit_ignore_value(&mut self, _ignore_value: &IgnoreValue) {}
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {
+        // Like `main`'s return value, integers are `i32` unless something says otherwise.
+        self.expression_type = Some(Type::I32);
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        // Both operands have the same type, so the left one decides.
+        self.expression_type = expression_type(operation.left());
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {
+        self.expression_type = Some(Type::Uptr);
+    }
+    fn visit_module(&mut self, _module: &Module) {}
+    fn visit_macro_invocation(&mut self, _invocation: &MacroInvocation) {}
+    fn visit_assertion(&mut self, _assertion: &Assertion) {}
+    fn visit_format(&mut self, _format: &Format) {
+        self.expression_type = Some(Type::String);
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        self.expression_type = call.function().return_type();
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.expression_type = call
+            .method()
+            .and_then(|method| method.return_type(expression_type(call.receiver()).as_ref()));
+    }
+    fn visit_enum_definition(&mut self, _definition: &EnumDefinition) {}
+    fn visit_enum_variant(&mut self, _variant: &EnumVariant) {}
+    fn visit_static_assertion(&mut self, _assertion: &StaticAssertion) {}
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        // The elements all have the same type, so the first decides; an empty list could hold anything.
+        self.expression_type = literal
+            .elements()
+            .first()
+            .and_then(|element| expression_type(element.as_ref()))
+            .map(|element_type| Type::List(Box::new(element_type)));
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.expression_type = literal
+            .keys()
+            .first()
+            .zip(literal.values().first())
+            .and_then(|(key, value)| {
+                Some(Type::Map(
+                    Box::new(expression_type(key.as_ref())?),
+                    Box::new(expression_type(value.as_ref())?),
+                ))
+            });
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.expression_type = match expression_type(index.collection()) {
+            Some(Type::List(element_type)) => Some(*element_type),
+            Some(Type::Map(_, value_type)) => Some(*value_type),
+            // Strings are indexed by byte.
+            Some(Type::String) => Some(Type::U8),
+            _ => None,
+        };
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        // Like an empty list, `none` could be an option of anything.
+        self.expression_type = literal
+            .value()
+            .and_then(expression_type)
+            .map(|value_type| Type::Option(Box::new(value_type)));
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.expression_type = match expression_type(unwrap.option()) {
+            Some(Type::Option(value_type) | Type::Result(value_type, _)) => Some(*value_type),
+            // Unwrapping `ok` gives its value, and `none` or `err` the default, if there is one.
+            None => literal_type(unwrap.option(), ResultVariant::Ok)
+                .or_else(|| unwrap.default().and_then(expression_type)),
+            Some(_) => None,
+        };
+    }
+    fn visit_result_literal(&mut self, _literal: &ResultLiteral) {
+        // Only one of a result's types is known from `ok` or `err`.
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.expression_type = match expression_type(propagate.value()) {
+            Some(Type::Result(value_type, _)) => Some(*value_type),
+            Some(_) => None,
+            None => literal_type(propagate.value(), ResultVariant::Ok),
+        };
+    }
+}
+
+/// The type of the value in `node`, if it is an `ok` or `err` literal of the given variant.
+fn literal_type(node: &dyn AstNode, variant: ResultVariant) -> Option<Type> {
+    node.downcast_ref::<ResultLiteral>()
+        .filter(|literal| literal.variant() == variant)
+        .and_then(|literal| expression_type(literal.value()))
+}
+
+/// The type of `node`'s value, or `None` if it doesn't produce one or its type isn't known yet.
+pub fn expression_type(node: &dyn AstNode) -> Option<Type> {
+    let mut typer = ExpressionTyper {
+        expression_type: None,
+    };
+    node.apply(&mut typer);
+    typer.expression_type
+}
+
+/// Checks that the program has exactly one `main` function, that it takes no parameters and that it returns `i32`.
+/// The value returned from `main` becomes the process's exit code.
+pub fn validate_entry_point(program: &dyn AstNode) -> Result<(), SemanticError> {
+    let functions = FunctionCollector::collect(program);
+    let mut main_functions = functions
+        .iter()
+        .filter(|collected| collected.function.name() == "main");
+    let main = main_functions
+        .next()
+        .ok_or_else(|| SemanticError::new(error_codes::MISSING_MAIN, "No main function found"))?;
+    if let Some(duplicate) = main_functions.next() {
+        return Err(SemanticError::at(
+            error_codes::DUPLICATE_FUNCTION,
+            "The main function is defined more than once",
+            duplicate.location(),
+        ));
+    }
+    if !main.function.parameters().is_empty() {
+        return Err(SemanticError::at(
+            error_codes::MAIN_HAS_PARAMETERS,
+            "The main function must not take any parameters",
+            main.location(),
+        ));
+    }
+    if !matches!(
+        main.function.return_type().downcast_ref::<Type>(),
+        Some(Type::I32)
+    ) {
+        return Err(SemanticError::at(
+            error_codes::MAIN_RETURN_TYPE,
+            "The main function must return i32",
+            main.location(),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a freestanding program defines its entry point exactly once, and that it takes no parameters.
+///
+/// Unlike `main`, the entry point may return anything, as what happens next is up to the program.
+pub fn validate_freestanding_entry_point(
+    program: &dyn AstNode,
+    entry_point: Symbol,
+) -> Result<(), SemanticError> {
+    let functions = FunctionCollector::collect(program);
+    let mut entry_functions = functions
+        .iter()
+        .filter(|collected| collected.function.name() == entry_point);
+    let entry = entry_functions.next().ok_or_else(|| {
+        SemanticError::new(
+            error_codes::MISSING_ENTRY_POINT,
+            format!("No entry point '{entry_point}' found"),
+        )
+    })?;
+    if let Some(duplicate) = entry_functions.next() {
+        return Err(SemanticError::at(
+            error_codes::DUPLICATE_FUNCTION,
+            format!("The entry point '{entry_point}' is defined more than once"),
+            duplicate.location(),
+        ));
+    }
+    if !entry.function.parameters().is_empty() {
+        return Err(SemanticError::at(
+            error_codes::ENTRY_POINT_HAS_PARAMETERS,
+            format!("The entry point '{entry_point}' must not take any parameters"),
+            entry.location(),
+        ));
+    }
+    Ok(())
+}
+
+/// The attributes functions may have.
+pub(crate) const KNOWN_ATTRIBUTES: &[&str] = &["export", "test"];
+
+/// Checks that every attribute is known, and that test functions can be run like `main`.
+pub fn validate_attributes(program: &dyn AstNode) -> Result<(), SemanticError> {
+    for collected in FunctionCollector::collect(program) {
+        let function = &collected.function;
+        if let Some((unknown, span)) = function
+            .attributes()
+            .iter()
+            .find(|(attribute, _)| !KNOWN_ATTRIBUTES.contains(&attribute.as_str()))
+        {
+            let error = SemanticError::at(
+                error_codes::UNKNOWN_ATTRIBUTE,
+                format!(
+                    "Unknown attribute '@{unknown}' on function '{}'",
+                    function.name()
+                ),
+                Location::new(collected.file, *span),
+            );
+            return Err(
+                match suggestions::closest(unknown.as_str(), KNOWN_ATTRIBUTES.iter().copied()) {
+                    Some(attribute) => error.with_suggestion(Suggestion::new(
+                        format!("did you mean '@{attribute}'?"),
+                        *span,
+                        attribute,
+                        Applicability::MaybeIncorrect,
+                    )),
+                    None => error,
+                },
+            );
+        }
+        if function.has_attribute("test")
+            && (!function.parameters().is_empty()
+                || !matches!(
+                    function.return_type().downcast_ref::<Type>(),
+                    Some(Type::I32)
+                ))
+        {
+            return Err(SemanticError::at(
+                error_codes::TEST_SIGNATURE,
+                format!(
+                    "The test function '{}' must take no parameters and return i32",
+                    function.name()
+                ),
+                collected.location(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// All of the program's top-level functions, in the order they are defined.
+pub fn collect_functions(program: &dyn AstNode) -> Vec<FunctionDefinition> {
+    collect_module_functions(program)
+        .into_iter()
+        .map(|collected| collected.function)
+        .collect()
+}
+
+/// Like `collect_functions`, but keeping track of which module defines each function.
+pub fn collect_module_functions(program: &dyn AstNode) -> Vec<CollectedFunction> {
+    FunctionCollector::collect(program)
+}
+
+/// Checks that every function name refers to exactly one function across all of the program's modules.
+pub fn resolve_names(program: &dyn AstNode) -> Result<(), SemanticError> {
+    let functions = FunctionCollector::collect(program);
+    let mut defining_modules = HashMap::new();
+    for collected in &functions {
+        let name = collected.function.name();
+        let module = &collected.module;
+        if let Some(previous_module) = defining_modules.insert(name, module) {
+            let message = if previous_module == module {
+                format!("Function '{name}' is defined more than once in {module}")
+            } else {
+                format!("Function '{name}' is defined in both {previous_module} and {module}")
+            };
+            return Err(SemanticError::at(
+                error_codes::DUPLICATE_FUNCTION,
+                message,
+                collected.location(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates every `static_assert!` and enum discriminant, stopping at the first which fails.
+struct ConstantChecker {
+    current_file: usize,
+    result: Result<(), SemanticError>,
+}
+
+impl ConstantChecker {
+    fn check(&mut self, node: &dyn AstNode) {
+        if self.result.is_ok() {
+            node.apply(self);
+        }
+    }
+
+    /// Works out each variant's discriminant, checking that they are distinct and fit the enum's representation.
+    fn check_discriminants(&self, definition: &EnumDefinition) -> Result<(), SemanticError> {
+        let representation = representation_of(definition);
+        // The type checker has already reported representations which aren't integers.
+        if !representation.is_integer() {
+            return Ok(());
+        }
+        let (min, max) = interpreter::integer_range(&representation);
+        let mut names = Vec::new();
+        let mut variants_by_discriminant = HashMap::new();
+        let mut previous = None;
+        for variant in definition.variants() {
+            let Some(variant) = variant.downcast_ref::<EnumVariant>() else {
+                continue;
+            };
+            let name = variant.name();
+            let location = Location::new(self.current_file, variant.span());
+            if names.contains(&name) {
+                return Err(SemanticError::at(
+                    error_codes::DUPLICATE_VARIANT,
+                    format!(
+                        "Enum '{}' has more than one variant called '{name}'",
+                        definition.name()
+                    ),
+                    location,
+                ));
+            }
+            names.push(name);
+            let discriminant = match variant.discriminant() {
+                Some(discriminant) => {
+                    interpreter::evaluate_constant_as(discriminant, &representation).map_err(
+                        |error| {
+                            SemanticError::at(
+                        error_codes::NOT_CONSTANT,
+                        format!("The discriminant of '{name}' isn't a constant integer: {error}"),
+                        location,
+                    )
+                        },
+                    )?
+                }
+                None => previous.map_or(0, |previous| previous + 1),
+            };
+            if !(min..=max).contains(&discriminant) {
+                let error = SemanticError::at(
+                    error_codes::DISCRIMINANT_RANGE,
+                    format!(
+                        "The discriminant of '{name}' is {discriminant}, which doesn't fit in {}",
+                        type_name(&representation)
+                    ),
+                    location,
+                );
+                return Err(if variant.discriminant().is_none() {
+                    error.with_help(
+                        "a variant without a discriminant is one more than the variant before it"
+                            .to_string(),
+                    )
+                } else {
+                    error
+                });
+            }
+            if let Some(other) = variants_by_discriminant.insert(discriminant, name) {
+                return Err(SemanticError::at(
+                    error_codes::DUPLICATE_DISCRIMINANT,
+                    format!(
+                        "'{name}' has the same discriminant as '{other}', which is {discriminant}"
+                    ),
+                    location,
+                ));
+            }
+            previous = Some(discriminant);
+        }
+        Ok(())
+    }
+}
+
+impl AstVisitor for ConstantChecker {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        for item in list {
+            self.check(item.as_ref());
+        }
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.check(variable.value());
+    }
+    fn visit_type(&mut self, _type_value: &Type) {}
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {}
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.check(function.body());
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.check(ignore_value.value());
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {}
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.check(operation.left());
+        self.check(operation.right());
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {}
+    fn visit_module(&mut self, module: &Module) {
+        let including_file = std::mem::replace(&mut self.current_file, module.file());
+        self.check(module.items());
+        self.current_file = including_file;
+    }
+    fn visit_macro_invocation(&mut self, _invocation: &MacroInvocation) {}
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        for operand in assertion.operands() {
+            self.check(operand.as_ref());
+        }
+    }
+    fn visit_format(&mut self, format: &Format) {
+        for argument in format.arguments() {
+            self.check(argument.as_ref());
+        }
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        for argument in call.arguments() {
+            self.check(argument.as_ref());
+        }
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.check(call.receiver());
+        for argument in call.arguments() {
+            self.check(argument.as_ref());
+        }
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        self.result = self.check_discriminants(definition);
+    }
+    fn visit_enum_variant(&mut self, _variant: &EnumVariant) {}
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        let location = Location::new(self.current_file, assertion.span());
+        self.result = match interpreter::evaluate_constant(assertion.condition()) {
+            Ok(0) => Err(SemanticError::at(
+                error_codes::STATIC_ASSERTION_FAILED,
+                assertion.message(),
+                location,
+            )),
+            Ok(_) => Ok(()),
+            Err(error) => Err(SemanticError::at(
+                error_codes::NOT_CONSTANT,
+                format!("The condition of 'static_assert!' isn't a constant integer: {error}"),
+                location,
+            )),
+        };
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        for element in literal.elements() {
+            self.check(element.as_ref());
+        }
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        for (key, value) in literal.keys().iter().zip(literal.values()) {
+            self.check(key.as_ref());
+            self.check(value.as_ref());
+        }
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.check(index.collection());
+        self.check(index.index());
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        if let Some(value) = literal.value() {
+            self.check(value);
+        }
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.check(unwrap.option());
+        if let Some(default) = unwrap.default() {
+            self.check(default);
+        }
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.check(literal.value());
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.check(propagate.value());
+    }
+}
+
+/// Checks that the condition of every `static_assert!` is a non-zero constant, and that every enum's discriminants
+/// are distinct constants which fit its representation.
+/// Intrinsics must already have been resolved, as constants may depend on the target.
+pub fn check_constants(program: &dyn AstNode) -> Result<(), SemanticError> {
+    let mut checker = ConstantChecker {
+        current_file: 0,
+        result: Ok(()),
+    };
+    checker.check(program);
+    checker.result
+}
+
+/// The integer type an enum's discriminants are stored as.
+pub fn representation_of(definition: &EnumDefinition) -> Type {
+    definition
+        .representation()
+        .and_then(|representation| representation.downcast_ref::<Type>().cloned())
+        .unwrap_or(Type::I32)
+}
+
+/// Whether a value of type `actual` can be stored where `expected` is wanted.
+///
+/// Integer literals are `i32` until there is type inference, so any integer type fits any other.
+fn is_compatible(expected: &Type, actual: &Type) -> bool {
+    match (expected, actual) {
+        (Type::List(expected), Type::List(actual)) => is_compatible(expected, actual),
+        (Type::Map(expected_key, expected_value), Type::Map(actual_key, actual_value)) => {
+            is_compatible(expected_key, actual_key) && is_compatible(expected_value, actual_value)
+        }
+        (Type::Option(expected), Type::Option(actual)) => is_compatible(expected, actual),
+        (
+            Type::Result(expected_value, expected_error),
+            Type::Result(actual_value, actual_error),
+        ) => {
+            is_compatible(expected_value, actual_value)
+                && is_compatible(expected_error, actual_error)
+        }
+        _ => expected == actual || (expected.is_integer() && actual.is_integer()),
+    }
+}
+
+/// The first key type within `type_value` which maps can't use, such as the `string` in `list<map<string, u8>>`.
+fn invalid_key_type(type_value: &Type) -> Option<&Type> {
+    match type_value {
+        Type::List(element_type) | Type::Option(element_type) => invalid_key_type(element_type),
+        Type::Map(key_type, _) if !key_type.is_map_key() => Some(key_type),
+        Type::Map(key_type, value_type) => {
+            invalid_key_type(key_type).or_else(|| invalid_key_type(value_type))
+        }
+        Type::Result(value_type, error_type) => {
+            invalid_key_type(value_type).or_else(|| invalid_key_type(error_type))
+        }
+        _ => None,
+    }
+}
+
+/// The error type of a result, if it is known: either from its type, or from the value in an `err`.
+fn error_type(result: &dyn AstNode) -> Option<Type> {
+    match expression_type(result) {
+        Some(Type::Result(_, error_type)) => Some(*error_type),
+        Some(_) => None,
+        None => literal_type(result, ResultVariant::Err),
+    }
+}
+
+/// How `value` doesn't fit where `declared` is wanted, if it has a different type.
+fn value_mismatch(declared: &Type, value: &dyn AstNode) -> Option<String> {
+    let (fits, value_description) = match expression_type(value) {
+        Some(value_type) => (
+            is_compatible(declared, &value_type),
+            format!("has type {}", type_name(&value_type)),
+        ),
+        // Empty literals, `none`, `ok` and `err` don't say all of their type, but do say what kind of type it is.
+        None => match NodeKind::of(value) {
+            NodeKind::ListLiteral => (matches!(declared, Type::List(_)), "is a list".to_string()),
+            NodeKind::MapLiteral => (matches!(declared, Type::Map(_, _)), "is a map".to_string()),
+            NodeKind::OptionLiteral => (
+                matches!(declared, Type::Option(_)),
+                "is an option".to_string(),
+            ),
+            NodeKind::ResultLiteral => {
+                let literal = value
+                    .downcast_ref::<ResultLiteral>()
+                    .expect("The node kind says what it is");
+                let (keyword, expected) = match (declared, literal.variant()) {
+                    (Type::Result(value_type, _), ResultVariant::Ok) => ("ok", value_type),
+                    (Type::Result(_, error_type), ResultVariant::Err) => ("err", error_type),
+                    _ => return Some("is a result".to_string()),
+                };
+                match expression_type(literal.value()) {
+                    Some(inner) if !is_compatible(expected, &inner) => (
+                        false,
+                        format!("is an `{keyword}` holding a {}", type_name(&inner)),
+                    ),
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        },
+    };
+    (!fits).then_some(value_description)
+}
+
+/// The error code for `value` not fitting where `declared` is wanted.
+///
+/// Options and results are named by the error when they are involved, as the fix is often to unwrap or wrap the
+/// value rather than to change the collection.
+fn mismatch_code(declared: &Type, value: &dyn AstNode) -> &'static str {
+    let value_type = expression_type(value);
+    let kinds = [Some(declared), value_type.as_ref()];
+    let involves = |matches: fn(&Type) -> bool, kind: NodeKind| {
+        kinds.iter().flatten().any(|type_value| matches(type_value)) || NodeKind::of(value) == kind
+    };
+    if involves(
+        |type_value| matches!(type_value, Type::Result(_, _)),
+        NodeKind::ResultLiteral,
+    ) {
+        error_codes::RESULT_TYPE
+    } else if involves(
+        |type_value| matches!(type_value, Type::Option(_)),
+        NodeKind::OptionLiteral,
+    ) {
+        error_codes::OPTION_TYPE
+    } else if kinds.contains(&Some(&Type::Char)) {
+        error_codes::CHAR_CONVERSION
+    } else {
+        error_codes::COLLECTION_TYPE
+    }
+}
+
+/// Checks the types of collections, options and results, stopping at the first which is wrong.
+struct TypeChecker {
+    current_file: usize,
+    /// The function being checked and its return type, which `?` returns errors from.
+    function: Option<(Symbol, Type)>,
+    /// Whether to check the bodies of functions as they are reached, rather than leaving them to be checked on their
+    /// own.
+    check_bodies: bool,
+    result: Result<(), SemanticError>,
+}
+
+impl TypeChecker {
+    fn new(current_file: usize, check_bodies: bool) -> Self {
+        Self {
+            current_file,
+            function: None,
+            check_bodies,
+            result: Ok(()),
+        }
+    }
+
+    fn check(&mut self, node: &dyn AstNode) {
+        if self.result.is_ok() {
+            node.apply(self);
+        }
+    }
+
+    fn check_body(&mut self, function: &FunctionDefinition) {
+        let enclosing = self.function.take();
+        self.function = function
+            .return_type()
+            .downcast_ref::<Type>()
+            .cloned()
+            .map(|return_type| (function.name(), return_type));
+        self.check(function.body());
+        self.check_returned_value(function);
+        self.function = enclosing;
+    }
+
+    /// Checks the arguments of a runtime function or method against its parameters.
+    fn check_arguments(
+        &mut self,
+        arguments: &[Box<dyn AstNode>],
+        parameter_types: &[Type],
+        callee: &str,
+        span: Span,
+    ) {
+        for (position, (argument, parameter_type)) in
+            arguments.iter().zip(parameter_types).enumerate()
+        {
+            let Some(argument_type) = expression_type(argument.as_ref()) else {
+                continue;
+            };
+            // Offsets can be any integer type, as the integer types don't convert to each other yet.
+            let (fits, expected) = if parameter_type.is_integer() {
+                (argument_type.is_integer(), "an integer".to_string())
+            } else {
+                (
+                    is_compatible(parameter_type, &argument_type),
+                    format!("a {}", type_name(parameter_type)),
+                )
+            };
+            if !fits {
+                let code = if *parameter_type == Type::Char {
+                    error_codes::CHAR_CONVERSION
+                } else {
+                    error_codes::STRING_OPERATION
+                };
+                self.fail(
+                    code,
+                    format!(
+                        "Argument {} of {callee} must be {expected}, not a {}",
+                        position + 1,
+                        type_name(&argument_type)
+                    ),
+                    span,
+                );
+            }
+            self.check(argument.as_ref());
+        }
+    }
+
+    /// Records an error, unless an earlier one has already been found.
+    fn fail(&mut self, code: &'static str, message: String, span: Span) {
+        self.fail_with_help(code, message, None, span);
+    }
+
+    fn fail_with_help(
+        &mut self,
+        code: &'static str,
+        message: String,
+        help: Option<String>,
+        span: Span,
+    ) {
+        if self.result.is_ok() {
+            let error = SemanticError::at(code, message, Location::new(self.current_file, span));
+            self.result = Err(match help {
+                Some(help) => error.with_help(help),
+                None => error,
+            });
+        }
+    }
+
+    /// Fails if a type written at `span` is a map with keys which can't be compared.
+    fn check_key_types(&mut self, type_node: &dyn AstNode, span: Span) {
+        if let Some(key_type) = type_node.downcast_ref::<Type>().and_then(invalid_key_type) {
+            self.fail(
+                error_codes::MAP_KEY_TYPE,
+                format!("Maps can't have {} keys", type_name(key_type)),
+                span,
+            );
+        }
+    }
+
+    /// Fails if a variable is initialised with a collection, option or result of the wrong type, or with one when
+    /// it isn't one at all.
+    fn check_initialiser(&mut self, variable: &VariableDefinition) {
+        let Some(declared) = variable.variable_type().downcast_ref::<Type>() else {
+            return;
+        };
+        if let Some(value_description) = value_mismatch(declared, variable.value()) {
+            self.fail_initialiser(variable, declared, &value_description);
+        }
+    }
+
+    /// Fails like `check_initialiser` if the last expression in a function's body, which is what it returns, doesn't
+    /// fit its return type.
+    fn check_returned_value(&mut self, function: &FunctionDefinition) {
+        let (Some(return_type), Some(body)) = (
+            function.return_type().downcast_ref::<Type>(),
+            function.body().downcast_ref::<Vec<Box<dyn AstNode>>>(),
+        ) else {
+            return;
+        };
+        let Some(value) = body.last() else {
+            return;
+        };
+        if let Some(value_description) = value_mismatch(return_type, value.as_ref()) {
+            self.fail(
+                mismatch_code(return_type, value.as_ref()),
+                format!(
+                    "'{}' returns {}, but its last expression {value_description}",
+                    function.name(),
+                    type_name(return_type)
+                ),
+                query::span_of(value.as_ref()).unwrap_or(function.name_span()),
+            );
+        }
+    }
+
+    /// Fails if a variable is initialised with a char where it is declared as a string or an integer, or the other
+    /// way around, as chars are only converted explicitly.
+    fn check_char_conversion(&mut self, variable: &VariableDefinition) {
+        let (Some(declared), Some(value_type)) = (
+            variable.variable_type().downcast_ref::<Type>().cloned(),
+            expression_type(variable.value()),
+        ) else {
+            return;
+        };
+        let conversion = match (&declared, &value_type) {
+            (Type::String, Type::Char) => "convert it with 'to_string!'",
+            (Type::Char, Type::String) => "take a character from it with 'chars!'",
+            (Type::Char, value_type) if value_type.is_integer() => {
+                "convert it with 'from_char_code!'"
+            }
+            (declared, Type::Char) if declared.is_integer() => "get its code with 'char_code!'",
+            _ => return,
+        };
+        self.fail(
+            error_codes::CHAR_CONVERSION,
+            format!(
+                "'{}' has type {}, but its value is a {}; {conversion}",
+                variable.name(),
+                type_name(&declared),
+                type_name(&value_type)
+            ),
+            variable.span(),
+        );
+    }
+
+    /// Reports that `variable`'s value doesn't fit its declared type.
+    fn fail_initialiser(
+        &mut self,
+        variable: &VariableDefinition,
+        declared: &Type,
+        value_description: &str,
+    ) {
+        let value = variable.value();
+        let code = mismatch_code(declared, value);
+        if self.result.is_ok() {
+            // The value is underlined where it records a span, with the annotation it was checked against beside it.
+            let span = query::span_of(value).unwrap_or(variable.span());
+            let message = format!(
+                "'{}' has type {}, but its value {value_description}",
+                variable.name(),
+                type_name(declared)
+            );
+            let annotation = Label::new(
+                Location::new(self.current_file, variable.type_span()),
+                "expected because of this annotation",
+            );
+            self.result =
+                Err(
+                    SemanticError::at(code, message, Location::new(self.current_file, span))
+                        .with_label(annotation),
+                );
+        }
+    }
+
+    /// Fails if `operand` is an option or a result, which has to be unwrapped before it can be used as `what`.
+    fn check_unwrapped(&mut self, operand: &dyn AstNode, what: &str, span: Span) {
+        let kind = match expression_type(operand) {
+            Some(Type::Option(_)) => NodeKind::OptionLiteral,
+            Some(Type::Result(_, _)) => NodeKind::ResultLiteral,
+            Some(_) => return,
+            None => NodeKind::of(operand),
+        };
+        match kind {
+            NodeKind::OptionLiteral => self.fail(
+                error_codes::OPTION_TYPE,
+                format!(
+                    "An option can't be used as {what} until it is unwrapped with 'unwrap!' or 'unwrap_or!'"
+                ),
+                span,
+            ),
+            NodeKind::ResultLiteral => self.fail(
+                error_codes::RESULT_TYPE,
+                format!(
+                    "A result can't be used as {what} until it is unwrapped with 'unwrap!', 'unwrap_or!' or '?'"
+                ),
+                span,
+            ),
+            _ => {}
+        }
+    }
+
+    /// Fails unless every entry in `entries` has a type compatible with the first's, naming them as the `entry`s of
+    /// a `collection`.
+    fn check_entries(
+        &mut self,
+        entries: &[Box<dyn AstNode>],
+        entry: &str,
+        collection: &str,
+        span: Span,
+    ) {
+        let mut types = entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| Some((index, expression_type(entry.as_ref())?)));
+        let Some((first_index, first_type)) = types.next() else {
+            return;
+        };
+        if let Some((index, entry_type)) =
+            types.find(|(_, entry_type)| !is_compatible(&first_type, entry_type))
+        {
+            self.fail(
+                error_codes::COLLECTION_TYPE,
+                format!(
+                    "The {entry}s of a {collection} must all have the same type, but {entry} {} is a {} and \
+                     {entry} {} is a {}",
+                    first_index + 1,
+                    type_name(&first_type),
+                    index + 1,
+                    type_name(&entry_type)
+                ),
+                span,
+            );
+        }
+    }
+}
+
+impl AstVisitor for TypeChecker {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        for item in list {
+            self.check(item.as_ref());
+        }
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.check_key_types(variable.variable_type(), variable.span());
+        // Char conversions are checked first, as their errors say how to convert the value.
+        self.check_char_conversion(variable);
+        self.check_initialiser(variable);
+        self.check(variable.value());
+    }
+    fn visit_type(&mut self, _type_value: &Type) {}
+    fn visit_parameter_declaration(&mut self, _parameter: &ParameterDeclaration) {}
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        for parameter in function.parameters() {
+            if let Some(parameter) = parameter.downcast_ref::<ParameterDeclaration>() {
+                self.check_key_types(parameter.parameter_type(), function.name_span());
+            }
+        }
+        self.check_key_types(function.return_type(), function.name_span());
+        if self.check_bodies {
+            self.check_body(function);
+        }
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.check(ignore_value.value());
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {}
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        let operator = operation.operator().token().source_text();
+        let what = format!("an operand of '{operator}'");
+        self.check_unwrapped(operation.left(), &what, operation.span());
+        self.check_unwrapped(operation.right(), &what, operation.span());
+        let operand_types = [operation.left(), operation.right()].map(expression_type);
+        if operand_types.contains(&Some(Type::Char)) {
+            self.fail(
+                error_codes::CHAR_CONVERSION,
+                format!(
+                    "'{operator}' can't be used on chars, which aren't numbers; get a char's code with 'char_code!'"
+                ),
+                operation.span(),
+            );
+        } else if operand_types.contains(&Some(Type::String)) {
+            if operation.operator() != BinaryOperator::Add {
+                self.fail(
+                    error_codes::STRING_OPERATION,
+                    format!(
+                        "'{operator}' can't be used on strings, which can only be joined with '+'"
+                    ),
+                    operation.span(),
+                );
+            } else if let Some(other) = operand_types
+                .iter()
+                .flatten()
+                .find(|operand_type| **operand_type != Type::String)
+            {
+                self.fail(
+                    error_codes::STRING_OPERATION,
+                    format!(
+                        "Strings can only be joined to strings, not a {}",
+                        type_name(other)
+                    ),
+                    operation.span(),
+                );
+            }
+        }
+        self.check(operation.left());
+        self.check(operation.right());
+    }
+    fn visit_type_intrinsic(&mut self, _intrinsic: &TypeIntrinsic) {}
+    fn visit_module(&mut self, module: &Module) {
+        let including_file = std::mem::replace(&mut self.current_file, module.file());
+        self.check(module.items());
+        self.current_file = including_file;
+    }
+    fn visit_macro_invocation(&mut self, _invocation: &MacroInvocation) {}
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        for operand in assertion.operands() {
+            // Assertions don't record where they are, but any option in one does.
+            if let Some(span) = query::span_of(operand.as_ref()) {
+                self.check_unwrapped(operand.as_ref(), "an assertion's operand", span);
+            }
+            self.check(operand.as_ref());
+        }
+    }
+    fn visit_format(&mut self, format: &Format) {
+        for segment in format.segments() {
+            let FormatSegment::Argument { index, spec } = segment else {
+                continue;
+            };
+            let argument = format.arguments()[*index].as_ref();
+            if NodeKind::of(argument) != NodeKind::MethodCall {
+                continue;
+            }
+            if let Err(message) = macros::check_format_argument(argument, *index, *spec) {
+                self.fail(error_codes::FORMAT_ARGUMENT_TYPE, message, format.span());
+            }
+        }
+        for argument in format.arguments() {
+            self.check(argument.as_ref());
+        }
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        let function = call.function();
+        self.check_arguments(
+            call.arguments(),
+            &function.parameter_types(),
+            &format!("'{}!'", function.macro_name()),
+            call.span(),
+        );
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        let representation = representation_of(definition);
+        if !representation.is_integer() {
+            self.fail(
+                error_codes::ENUM_REPRESENTATION,
+                format!(
+                    "Enum '{}' can't be represented by a {}, as discriminants are integers",
+                    definition.name(),
+                    type_name(&representation)
+                ),
+                definition.name_span(),
+            );
+        }
+        for variant in definition.variants() {
+            self.check(variant.as_ref());
+        }
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        if let Some(discriminant) = variant.discriminant() {
+            self.check(discriminant);
+        }
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        if let Some(method) = call.method() {
+            self.check_arguments(
+                call.arguments(),
+                &method.parameter_types(),
+                &format!("'{}'", call.name()),
+                call.span(),
+            );
+        }
+        self.check(call.receiver());
+    }
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        self.check(assertion.condition());
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.check_entries(literal.elements(), "element", "list", literal.span());
+        for element in literal.elements() {
+            self.check(element.as_ref());
+        }
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.check_entries(literal.keys(), "key", "map", literal.span());
+        self.check_entries(literal.values(), "value", "map", literal.span());
+        if let Some(key_type) = literal
+            .keys()
+            .first()
+            .and_then(|key| expression_type(key.as_ref()))
+            .filter(|key_type| !key_type.is_map_key())
+        {
+            self.fail(
+                error_codes::MAP_KEY_TYPE,
+                format!("Maps can't have {} keys", type_name(&key_type)),
+                literal.span(),
+            );
+        }
+        for (key, value) in literal.keys().iter().zip(literal.values()) {
+            self.check(key.as_ref());
+            self.check(value.as_ref());
+        }
+    }
+    fn visit_index(&mut self, index: &Index) {
+        let index_type = expression_type(index.index());
+        let error = match (expression_type(index.collection()), index_type) {
+            (Some(Type::List(_)), Some(index_type)) if !index_type.is_integer() => Some(format!(
+                "Lists are indexed with integers, not a {}",
+                type_name(&index_type)
+            )),
+            (Some(Type::String), Some(index_type)) if !index_type.is_integer() => Some(format!(
+                "Strings are indexed by byte with integers, not a {}",
+                type_name(&index_type)
+            )),
+            (Some(Type::Map(key_type, _)), Some(index_type))
+                if !is_compatible(&key_type, &index_type) =>
+            {
+                Some(format!(
+                    "A map with {} keys can't be indexed with a {}",
+                    type_name(&key_type),
+                    type_name(&index_type)
+                ))
+            }
+            (Some(Type::List(_) | Type::Map(_, _) | Type::String) | None, _) => None,
+            (Some(collection_type), _) => Some(format!(
+                "Only lists, maps and strings can be indexed, not a {}",
+                type_name(&collection_type)
+            )),
+        };
+        self.check_unwrapped(index.collection(), "a collection", index.span());
+        self.check_unwrapped(index.index(), "an index", index.span());
+        if let Some(message) = error {
+            self.fail(error_codes::NOT_INDEXABLE, message, index.span());
+        }
+        self.check(index.collection());
+        self.check(index.index());
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        if let Some(value) = literal.value() {
+            self.check(value);
+        }
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        match expression_type(unwrap.option()) {
+            Some(Type::Option(value_type) | Type::Result(value_type, _)) => {
+                if let Some(default_type) = unwrap.default().and_then(expression_type) {
+                    if !is_compatible(&value_type, &default_type) {
+                        self.fail(
+                            error_codes::OPTION_TYPE,
+                            format!(
+                                "'unwrap_or!' needs a default of type {}, but it was given a {}",
+                                type_name(&value_type),
+                                type_name(&default_type)
+                            ),
+                            unwrap.span(),
+                        );
+                    }
+                }
+            }
+            Some(option_type) => self.fail(
+                error_codes::OPTION_TYPE,
+                format!(
+                    "Only options and results can be unwrapped, not a {}",
+                    type_name(&option_type)
+                ),
+                unwrap.span(),
+            ),
+            None => {}
+        }
+        self.check(unwrap.option());
+        if let Some(default) = unwrap.default() {
+            self.check(default);
+        }
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.check(literal.value());
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        let value = propagate.value();
+        match expression_type(value) {
+            Some(Type::Result(_, _)) | None => {}
+            Some(value_type) => self.fail(
+                error_codes::RESULT_TYPE,
+                format!(
+                    "Only results can be propagated with '?', not a {}",
+                    type_name(&value_type)
+                ),
+                propagate.span(),
+            ),
+        }
+        if let Some((function, return_type)) = self.function.clone() {
+            let error_type = error_type(value);
+            match (&return_type, error_type) {
+                (Type::Result(_, function_error), Some(error_type))
+                    if !is_compatible(function_error, &error_type) =>
+                {
+                    self.fail(
+                        error_codes::ERROR_PROPAGATION,
+                        format!(
+                            "This can fail with a {}, but '{function}' can only fail with a {}",
+                            type_name(&error_type),
+                            type_name(function_error)
+                        ),
+                        propagate.span(),
+                    )
+                }
+                (Type::Result(_, _), _) => {}
+                (_, error_type) => self.fail_with_help(
+                    error_codes::ERROR_PROPAGATION,
+                    format!(
+                        "'?' can only be used in a function which returns a result, but '{function}' returns {}",
+                        type_name(&return_type)
+                    ),
+                    error_type.map(|error_type| {
+                        format!(
+                            "This can fail with a {}, so '{function}' would need to return {}",
+                            type_name(&error_type),
+                            type_name(&Type::Result(
+                                Box::new(return_type.clone()),
+                                Box::new(error_type.clone())
+                            ))
+                        )
+                    }),
+                    propagate.span(),
+                ),
+            }
+        }
+        self.check(value);
+    }
+}
+
+/// Checks that the entries of each list and map literal have the same type, that variables are initialised with
+/// the collections, options and results they are declared as, and that only lists and maps are indexed, with the
+/// right type of index.
+///
+/// Options and results are never used as their values implicitly: they have to be unwrapped before they are
+/// operated on or indexed, and only they can be unwrapped. `?` can only propagate an error out of a function which
+/// returns a result that can hold it.
+///
+/// A function's body only depends on the signatures around it, so once everything else has been checked, the bodies
+/// are checked in parallel. The first error in each body is reported, along with the first outside of them, sorted
+/// by where they are so that the order doesn't depend on which thread finished first.
+///
+/// Later errors in a body are dropped because they could follow from the first. Bodies can't refer to each other,
+/// as there are no function calls, so one body's error can't cause errors in another. Once there are calls, a
+/// function whose body failed will need an error type, so that its callers' uses of it are skipped.
+pub fn check_types(program: &dyn AstNode) -> Result<(), Vec<SemanticError>> {
+    let mut checker = TypeChecker::new(0, false);
+    checker.check(program);
+    let mut bodies = Vec::new();
+    find_function_bodies(program, 0, &mut bodies);
+    let mut errors = checker
+        .result
+        .err()
+        .into_iter()
+        .chain(
+            bodies
+                .into_par_iter()
+                .filter_map(|(file, function)| {
+                    let mut checker = TypeChecker::new(file, true);
+                    checker.check_body(function);
+                    checker.result.err()
+                })
+                .collect::<Vec<_>>(),
+        )
+        .collect::<Vec<_>>();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    errors.sort_by_key(|error| {
+        error
+            .location
+            .map(|location| (location.file(), location.span().start()))
+    });
+    Err(errors)
+}
+
+/// Finds each top-level function along with the file it is in, without copying them as `collect_module_functions`
+/// does.
+fn find_function_bodies<'program>(
+    node: &'program dyn AstNode,
+    file: usize,
+    bodies: &mut Vec<(usize, &'program FunctionDefinition)>,
+) {
+    if let Some(items) = node.downcast_ref::<Vec<Box<dyn AstNode>>>() {
+        for item in items {
+            find_function_bodies(item.as_ref(), file, bodies);
+        }
+    } else if let Some(module) = node.downcast_ref::<Module>() {
+        find_function_bodies(module.items(), module.file(), bodies);
+    } else if let Some(function) = node.downcast_ref::<FunctionDefinition>() {
+        bodies.push((file, function));
+    }
+}
+
+/// Collects the warnings for `find_unused_values`.
+struct UnusedValueFinder {
+    current_file: usize,
+    warnings: Vec<Diagnostic>,
+}
+
+impl AstVisitor for UnusedValueFinder {
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        function.body().apply(self);
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        // Statements such as `print!` don't produce a value, so there is nothing to lose.
+        let Some(value_type) = expression_type(ignore_value.value()) else {
+            return;
+        };
+        let span = ignore_value.span();
+        let value_type = type_name(&value_type);
+        let suggestion = Suggestion::new(
+            "assign it to '_' to show that it is ignored on purpose",
+            Span::new(span.start(), span.start()),
+            format!("let _: {value_type} = "),
+            Applicability::MachineApplicable,
+        );
+        self.warnings.push(
+            Diagnostic::warning(format!("This statement's {value_type} value is never used"))
+                .with_code(error_codes::UNUSED_VALUE)
+                .with_location(Location::new(self.current_file, span))
+                .with_suggestion(suggestion),
+        );
+    }
+    fn visit_module(&mut self, module: &Module) {
+        let including_file = std::mem::replace(&mut self.current_file, module.file());
+        module.items().apply(self);
+        self.current_file = including_file;
+    }
+}
+
+/// Warns about each statement which ends in a semicolon but has a value, which is then silently discarded.
+///
+/// Assigning the value to `_` says that it is meant to be ignored. Types must already have been checked, so that
+/// each value's type is known.
+pub fn find_unused_values(program: &dyn AstNode) -> Vec<Diagnostic> {
+    let mut finder = UnusedValueFinder {
+        current_file: 0,
+        warnings: Vec::new(),
+    };
+    program.apply(&mut finder);
+    finder.warnings
+}
@@ -0,0 +1,220 @@
+//! Serde support for `Box<dyn AstNode>`, which can't derive it as the concrete type of each node is hidden.
+//!
+//! Nodes are written as an enum whose variant names the kind of node, so that they can be read back as the same
+//! type.
+
+use alloc::{boxed::Box, vec::Vec};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::ast::{
+    Assertion, AstNode, AstVisitor, BinaryOperation, EnumDefinition, EnumVariant, Format,
+    FunctionDefinition, IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral, MethodCall,
+    Module, OptionLiteral, ParameterDeclaration, Propagate, ResultLiteral, RuntimeCall,
+    StaticAssertion, Type, TypeIntrinsic, Unwrap, VariableDefinition,
+};
+
+/// A node being written, borrowed from the tree.
+#[derive(Serialize)]
+enum NodeRef<'node> {
+    List(&'node [Box<dyn AstNode>]),
+    VariableDefinition(&'node VariableDefinition),
+    Type(&'node Type),
+    ParameterDeclaration(&'node ParameterDeclaration),
+    FunctionDefinition(&'node FunctionDefinition),
+    IgnoreValue(&'node IgnoreValue),
+    IntegerLiteral(i128),
+    BinaryOperation(&'node BinaryOperation),
+    TypeIntrinsic(&'node TypeIntrinsic),
+    Module(&'node Module),
+    MacroInvocation(&'node MacroInvocation),
+    Assertion(&'node Assertion),
+    Format(&'node Format),
+    RuntimeCall(&'node RuntimeCall),
+    StaticAssertion(&'node StaticAssertion),
+    ListLiteral(&'node ListLiteral),
+    MapLiteral(&'node MapLiteral),
+    Index(&'node Index),
+    OptionLiteral(&'node OptionLiteral),
+    Unwrap(&'node Unwrap),
+    ResultLiteral(&'node ResultLiteral),
+    Propagate(&'node Propagate),
+    MethodCall(&'node MethodCall),
+    EnumDefinition(&'node EnumDefinition),
+    EnumVariant(&'node EnumVariant),
+}
+
+/// A node being read, with the same variants as `NodeRef`.
+#[derive(Deserialize)]
+enum Node {
+    List(Vec<Box<dyn AstNode>>),
+    VariableDefinition(VariableDefinition),
+    Type(Type),
+    ParameterDeclaration(ParameterDeclaration),
+    FunctionDefinition(FunctionDefinition),
+    IgnoreValue(IgnoreValue),
+    IntegerLiteral(i128),
+    BinaryOperation(BinaryOperation),
+    TypeIntrinsic(TypeIntrinsic),
+    Module(Module),
+    MacroInvocation(MacroInvocation),
+    Assertion(Assertion),
+    Format(Format),
+    RuntimeCall(RuntimeCall),
+    StaticAssertion(StaticAssertion),
+    ListLiteral(ListLiteral),
+    MapLiteral(MapLiteral),
+    Index(Index),
+    OptionLiteral(OptionLiteral),
+    Unwrap(Unwrap),
+    ResultLiteral(ResultLiteral),
+    Propagate(Propagate),
+    MethodCall(MethodCall),
+    EnumDefinition(EnumDefinition),
+    EnumVariant(EnumVariant),
+}
+
+impl Node {
+    fn into_boxed(self) -> Box<dyn AstNode> {
+        match self {
+            Node::List(list) => Box::new(list),
+            Node::VariableDefinition(variable) => Box::new(variable),
+            Node::Type(type_value) => Box::new(type_value),
+            Node::ParameterDeclaration(parameter) => Box::new(parameter),
+            Node::FunctionDefinition(function) => Box::new(function),
+            Node::IgnoreValue(ignore_value) => Box::new(ignore_value),
+            Node::IntegerLiteral(integer_literal) => Box::new(integer_literal),
+            Node::BinaryOperation(operation) => Box::new(operation),
+            Node::TypeIntrinsic(intrinsic) => Box::new(intrinsic),
+            Node::Module(module) => Box::new(module),
+            Node::MacroInvocation(invocation) => Box::new(invocation),
+            Node::Assertion(assertion) => Box::new(assertion),
+            Node::Format(format) => Box::new(format),
+            Node::RuntimeCall(call) => Box::new(call),
+            Node::StaticAssertion(assertion) => Box::new(assertion),
+            Node::ListLiteral(literal) => Box::new(literal),
+            Node::MapLiteral(literal) => Box::new(literal),
+            Node::Index(index) => Box::new(index),
+            Node::OptionLiteral(literal) => Box::new(literal),
+            Node::Unwrap(unwrap) => Box::new(unwrap),
+            Node::ResultLiteral(literal) => Box::new(literal),
+            Node::Propagate(propagate) => Box::new(propagate),
+            Node::MethodCall(call) => Box::new(call),
+            Node::EnumDefinition(definition) => Box::new(definition),
+            Node::EnumVariant(variant) => Box::new(variant),
+        }
+    }
+}
+
+/// Writes whichever node it visits, since the node can only be borrowed for the duration of the visit.
+struct NodeSerializer<S: Serializer> {
+    serializer: Option<S>,
+    result: Option<Result<S::Ok, S::Error>>,
+}
+
+impl<S: Serializer> NodeSerializer<S> {
+    fn write(&mut self, node: NodeRef) {
+        let serializer = self
+            .serializer
+            .take()
+            .expect("Each node is only visited once");
+        self.result = Some(node.serialize(serializer));
+    }
+}
+
+impl<S: Serializer> AstVisitor for NodeSerializer<S> {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        self.write(NodeRef::List(list));
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.write(NodeRef::VariableDefinition(variable));
+    }
+    fn visit_type(&mut self, type_value: &Type) {
+        self.write(NodeRef::Type(type_value));
+    }
+    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration) {
+        self.write(NodeRef::ParameterDeclaration(parameter));
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.write(NodeRef::FunctionDefinition(function));
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.write(NodeRef::IgnoreValue(ignore_value));
+    }
+    fn visit_integer_literal(&mut self, integer_literal: &i128) {
+        self.write(NodeRef::IntegerLiteral(*integer_literal));
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.write(NodeRef::BinaryOperation(operation));
+    }
+    fn visit_type_intrinsic(&mut self, intrinsic: &TypeIntrinsic) {
+        self.write(NodeRef::TypeIntrinsic(intrinsic));
+    }
+    fn visit_module(&mut self, module: &Module) {
+        self.write(NodeRef::Module(module));
+    }
+    fn visit_macro_invocation(&mut self, invocation: &MacroInvocation) {
+        self.write(NodeRef::MacroInvocation(invocation));
+    }
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        self.write(NodeRef::Assertion(assertion));
+    }
+    fn visit_format(&mut self, format: &Format) {
+        self.write(NodeRef::Format(format));
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        self.write(NodeRef::RuntimeCall(call));
+    }
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        self.write(NodeRef::StaticAssertion(assertion));
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.write(NodeRef::ListLiteral(literal));
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.write(NodeRef::MapLiteral(literal));
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.write(NodeRef::Index(index));
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.write(NodeRef::OptionLiteral(literal));
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.write(NodeRef::Unwrap(unwrap));
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.write(NodeRef::ResultLiteral(literal));
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.write(NodeRef::Propagate(propagate));
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.write(NodeRef::MethodCall(call));
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        self.write(NodeRef::EnumDefinition(definition));
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.write(NodeRef::EnumVariant(variant));
+    }
+}
+
+impl Serialize for dyn AstNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut node_serializer = NodeSerializer {
+            serializer: Some(serializer),
+            result: None,
+        };
+        self.apply(&mut node_serializer);
+        node_serializer
+            .result
+            .expect("Every kind of node has a visit method")
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn AstNode> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Node::deserialize(deserializer).map(Node::into_boxed)
+    }
+}
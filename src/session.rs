@@ -0,0 +1,184 @@
+//! The state shared by every pass of a single compilation.
+
+use crate::{
+    diagnostics::{Diagnostic, DiagnosticCounts, DiagnosticEmitter, Severity},
+    features::Features,
+    lexer::{self, Span, Token},
+    source::SourceMap,
+    statistics::Statistics,
+    symbol::Symbol,
+    target::Target,
+};
+
+/// A compilation in progress, which each pass is given rather than reaching for global state.
+pub struct Session {
+    source_map: SourceMap,
+    emitter: Box<dyn DiagnosticEmitter>,
+    target: Target,
+    features: Features,
+    statistics: Statistics,
+    /// The entry point of a freestanding program, which has no runtime library to call `main` for it.
+    freestanding_entry_point: Option<Symbol>,
+    /// Whether arithmetic operators panic when they overflow, rather than wrapping.
+    overflow_checks: bool,
+    /// Whether the prelude's names are in scope without being asked for.
+    prelude: bool,
+    /// How many errors are emitted before the rest are only counted, if there is a limit.
+    error_limit: Option<usize>,
+    counts: DiagnosticCounts,
+    /// Every diagnostic emitted so far, so that a pass which finds the same problem twice only reports it once.
+    emitted: Vec<Diagnostic>,
+}
+
+impl Session {
+    pub fn new(target: Target, features: Features, emitter: Box<dyn DiagnosticEmitter>) -> Self {
+        Self {
+            source_map: SourceMap::new(),
+            emitter,
+            target,
+            features,
+            statistics: Statistics::default(),
+            freestanding_entry_point: None,
+            overflow_checks: true,
+            prelude: true,
+            error_limit: None,
+            counts: DiagnosticCounts::default(),
+            emitted: Vec::new(),
+        }
+    }
+
+    pub fn with_statistics(mut self, statistics: Statistics) -> Self {
+        self.statistics = statistics;
+        self
+    }
+
+    /// Compiles for bare metal, without the runtime library, starting at `entry_point` rather than `main`.
+    pub fn with_freestanding_entry_point(mut self, entry_point: Symbol) -> Self {
+        self.freestanding_entry_point = Some(entry_point);
+        self
+    }
+
+    /// Makes arithmetic operators wrap when they overflow, as optimized builds do, instead of panicking.
+    pub fn with_overflow_checks(mut self, overflow_checks: bool) -> Self {
+        self.overflow_checks = overflow_checks;
+        self
+    }
+
+    /// Leaves out the prelude, for programs such as freestanding ones which can't use what it provides.
+    pub fn without_prelude(mut self) -> Self {
+        self.prelude = false;
+        self
+    }
+
+    /// Stops reporting errors once `limit` of them have been, so that one mistake can't bury the output.
+    pub fn with_error_limit(mut self, limit: usize) -> Self {
+        self.error_limit = Some(limit);
+        self
+    }
+
+    pub fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    pub fn source_map_mut(&mut self) -> &mut SourceMap {
+        &mut self.source_map
+    }
+
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    /// Compiles for a different target, such as one named by a project's manifest.
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
+
+    pub fn features(&self) -> &Features {
+        &self.features
+    }
+
+    pub fn is_freestanding(&self) -> bool {
+        self.freestanding_entry_point.is_some()
+    }
+
+    pub fn freestanding_entry_point(&self) -> Option<Symbol> {
+        self.freestanding_entry_point
+    }
+
+    pub fn overflow_checks(&self) -> bool {
+        self.overflow_checks
+    }
+
+    pub fn has_prelude(&self) -> bool {
+        self.prelude
+    }
+
+    pub fn statistics(&self) -> &Statistics {
+        &self.statistics
+    }
+
+    pub fn statistics_mut(&mut self) -> &mut Statistics {
+        &mut self.statistics
+    }
+
+    /// The symbol for `name`.
+    ///
+    /// Symbols can be compared and printed without the session, so the interner behind them is shared by every
+    /// session in the process.
+    pub fn intern(&self, name: &str) -> Symbol {
+        Symbol::intern(name)
+    }
+
+    /// Reports a diagnostic, whose location refers to one of the session's files.
+    ///
+    /// A diagnostic identical to one already reported is dropped, and errors past the limit are counted but not
+    /// shown.
+    pub fn emit(&mut self, diagnostic: Diagnostic) {
+        if self.emitted.contains(&diagnostic) {
+            return;
+        }
+        if diagnostic.severity() == Severity::Error
+            && self
+                .error_limit
+                .is_some_and(|limit| self.counts.errors() >= limit)
+        {
+            self.counts.suppress();
+            return;
+        }
+        self.counts.record(diagnostic.severity());
+        self.emitted.push(diagnostic.clone());
+        self.emitter.emit(diagnostic, &self.source_map);
+    }
+
+    /// The number of errors found so far, including any past the limit.
+    pub fn error_count(&self) -> usize {
+        self.counts.errors() + self.counts.suppressed_errors()
+    }
+
+    /// How many diagnostics of each kind have been reported so far.
+    pub fn counts(&self) -> &DiagnosticCounts {
+        &self.counts
+    }
+
+    /// Reports each `Token::Error` in a file's tokens, returning whether there were any.
+    pub fn report_invalid_tokens(&mut self, file: usize, tokens: &[(Token, Span)]) -> bool {
+        let error_count = self.error_count();
+        for diagnostic in lexer::invalid_token_diagnostics(file, tokens) {
+            self.emit(diagnostic);
+        }
+        self.error_count() > error_count
+    }
+
+    /// Runs `pass` with the session, recording how long it took under `name`.
+    pub fn time<T>(&mut self, name: &'static str, pass: impl FnOnce(&mut Self) -> T) -> T {
+        let mut statistics = std::mem::take(&mut self.statistics);
+        let result = statistics.time(name, || pass(self));
+        self.statistics = statistics;
+        result
+    }
+
+    /// Tells the emitter that the compilation is over, so that it can summarise what it reported.
+    pub fn finish(&mut self) {
+        self.emitter.finish(&self.counts);
+    }
+}
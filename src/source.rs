@@ -0,0 +1,201 @@
+//! The source files a compilation has loaded, which every later stage refers to by index.
+//!
+//! Each file is read once, however many times it is included, and knows where its lines start so that offsets can
+//! be turned into lines and columns cheaply. The files are also laid out one after another in a single global
+//! offset space, so that a span can identify a position in any of them without carrying a file index alongside.
+
+use alloc::{format, string::String, vec::Vec};
+use core::ops::Index;
+#[cfg(feature = "std")]
+use std::{fs, io, path::Path};
+
+use crate::{
+    diagnostics::{Diagnostic, Location},
+    error_codes,
+    lexer::Span,
+};
+
+/// A file which has been loaded for compilation.
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    /// The byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// The global offset of the start of the file.
+    start: usize,
+    /// Where the first bytes which weren't valid UTF-8 were, before they were replaced with U+FFFD.
+    invalid_utf8: Option<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: String, text: String) -> Self {
+        let line_starts = core::iter::once(0)
+            .chain(text.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self {
+            name,
+            text,
+            line_starts,
+            start: 0,
+            invalid_utf8: None,
+        }
+    }
+
+    /// Decodes a file which was read as bytes, replacing anything which isn't valid UTF-8 rather than refusing to
+    /// compile it.
+    pub fn from_bytes(name: String, bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(text) => Self::new(name, text),
+            Err(error) => {
+                let invalid_utf8 = error.utf8_error().valid_up_to();
+                let text = String::from_utf8_lossy(error.as_bytes()).into_owned();
+                Self {
+                    invalid_utf8: Some(invalid_utf8),
+                    ..Self::new(name, text)
+                }
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The offset of the first replacement character put in place of bytes which weren't valid UTF-8, if there were
+    /// any.
+    pub fn invalid_utf8(&self) -> Option<usize> {
+        self.invalid_utf8
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The zero-based index of the line containing `offset`.
+    pub fn line_index(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// The byte offset of the start of a line, or the end of the file if there aren't that many lines.
+    pub fn line_start(&self, line_index: usize) -> usize {
+        self.line_starts
+            .get(line_index)
+            .copied()
+            .unwrap_or(self.text.len())
+    }
+
+    /// The text of a line, without its line ending.
+    pub fn line(&self, line_index: usize) -> &str {
+        let start = self.line_starts[line_index];
+        let end = self.line_start(line_index + 1);
+        self.text[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    /// The one-based line and column (in characters) of `offset`.
+    pub fn line_and_column(&self, offset: usize) -> (usize, usize) {
+        let line_index = self.line_index(offset);
+        let line_start = self.line_starts[line_index];
+        let column = self.text[line_start..offset.min(self.text.len())]
+            .chars()
+            .count();
+        (line_index + 1, column + 1)
+    }
+}
+
+/// Every file loaded for a compilation.
+#[derive(Clone, Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file which has already been read, returning its index.
+    pub fn add(&mut self, mut file: SourceFile) -> usize {
+        // Leaving a gap after each file means the end of one file isn't also the start of the next.
+        file.start = self
+            .files
+            .last()
+            .map_or(0, |previous| previous.start + previous.text.len() + 1);
+        self.files.push(file);
+        self.files.len() - 1
+    }
+
+    /// Reads a file from disk, returning its index. Files which have already been loaded aren't read again.
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, path: &Path) -> io::Result<usize> {
+        let name = path.display().to_string();
+        if let Some(file) = self.find(&name) {
+            return Ok(file);
+        }
+        let bytes = fs::read(path)?;
+        Ok(self.add(SourceFile::from_bytes(name, bytes)))
+    }
+
+    /// A warning that a file had to have invalid UTF-8 replaced, if it did.
+    pub fn invalid_utf8_warning(&self, file: usize) -> Option<Diagnostic> {
+        let offset = self.files[file].invalid_utf8?;
+        let span = Span::new(offset, offset + char::REPLACEMENT_CHARACTER.len_utf8());
+        Some(
+            Diagnostic::warning(format!(
+                "'{}' isn't valid UTF-8, so the invalid bytes have been replaced",
+                self.files[file].name
+            ))
+            .with_code(error_codes::INVALID_UTF8)
+            .with_location(Location::new(file, span)),
+        )
+    }
+
+    /// The index of the file with the given name, if it has been loaded.
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.files.iter().position(|file| file.name == name)
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The span covering `location` in the global offset space shared by every file.
+    pub fn global_span(&self, location: Location) -> Span {
+        let start = self.files[location.file()].start;
+        Span::new(
+            start + location.span().start(),
+            start + location.span().end(),
+        )
+    }
+
+    /// The file and local span a global span refers to, if it is within one of the files.
+    pub fn locate(&self, span: Span) -> Option<Location> {
+        let file = self
+            .files
+            .partition_point(|file| file.start <= span.start())
+            .checked_sub(1)?;
+        let start = self.files[file].start;
+        let local = Span::new(span.start() - start, span.end() - start);
+        (local.end() <= self.files[file].text.len()).then(|| Location::new(file, local))
+    }
+}
+
+impl Index<usize> for SourceMap {
+    type Output = SourceFile;
+
+    fn index(&self, file: usize) -> &SourceFile {
+        &self.files[file]
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ast::{
+        Assertion, AstNode, AstVisitor, BinaryOperation, EnumDefinition, EnumVariant, Format,
+        FunctionDefinition, IgnoreValue, Index, ListLiteral, MacroInvocation, MapLiteral,
+        MethodCall, Module, OptionLiteral, ParameterDeclaration, Propagate, ResultLiteral,
+        RuntimeCall, StaticAssertion, Type, TypeIntrinsic, Unwrap, VariableDefinition,
+    },
+    ice::PassGuard,
+};
+
+/// Measurements of how much work each pass of the compiler did, for `--time-passes`.
+#[derive(Debug, Default)]
+pub struct Statistics {
+    enabled: bool,
+    passes: Vec<(&'static str, Duration)>,
+    counts: Vec<(&'static str, usize)>,
+}
+
+impl Statistics {
+    /// Statistics which are only gathered if `enabled`, so that normal compilations pay nothing for them.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Runs `pass`, recording how long it took under `name`.
+    pub fn time<T>(&mut self, name: &'static str, pass: impl FnOnce() -> T) -> T {
+        // Every pass is timed, so this is also where the pass is recorded for bug reports.
+        let _pass = PassGuard::enter(name);
+        if !self.enabled {
+            return pass();
+        }
+        let start = Instant::now();
+        let result = pass();
+        self.passes.push((name, start.elapsed()));
+        result
+    }
+
+    pub fn count(&mut self, name: &'static str, count: usize) {
+        if self.enabled {
+            self.counts.push((name, count));
+        }
+    }
+
+    pub fn report(&self) -> String {
+        let mut report = String::new();
+        for (name, duration) in &self.passes {
+            report += &format!(
+                "time: {:>10.3}ms  {name}\n",
+                duration.as_secs_f64() * 1000.0
+            );
+        }
+        for (name, count) in &self.counts {
+            report += &format!("count: {count:>9}  {name}\n");
+        }
+        if let Some(peak_memory) = peak_memory() {
+            report += &format!("peak memory: {}KiB\n", peak_memory / 1024);
+        }
+        report
+    }
+}
+
+/// The most memory the process has had resident at once, in bytes, where the OS tells us.
+fn peak_memory() -> Option<usize> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kibibytes = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+    Some(kibibytes * 1024)
+}
+
+struct NodeCounter {
+    count: usize,
+}
+
+impl AstVisitor for NodeCounter {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        self.count += 1;
+        for item in list {
+            item.apply(self);
+        }
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.count += 1;
+        variable.variable_type().apply(self);
+        variable.value().apply(self);
+    }
+    fn visit_type(&mut self, _type_value: &Type) {
+        self.count += 1;
+    }
+    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration) {
+        self.count += 1;
+        parameter.parameter_type().apply(self);
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.count += 1;
+        for parameter in function.parameters() {
+            parameter.apply(self);
+        }
+        function.return_type().apply(self);
+        function.body().apply(self);
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.count += 1;
+        ignore_value.value().apply(self);
+    }
+    fn visit_integer_literal(&mut self, _integer_literal: &i128) {
+        self.count += 1;
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.count += 1;
+        operation.left().apply(self);
+        operation.right().apply(self);
+    }
+    fn visit_type_intrinsic(&mut self, intrinsic: &TypeIntrinsic) {
+        self.count += 1;
+        intrinsic.type_value().apply(self);
+    }
+    fn visit_module(&mut self, module: &Module) {
+        self.count += 1;
+        module.items().apply(self);
+    }
+    fn visit_macro_invocation(&mut self, _invocation: &MacroInvocation) {
+        self.count += 1;
+    }
+    fn visit_format(&mut self, format: &Format) {
+        self.count += 1;
+        for argument in format.arguments() {
+            argument.apply(self);
+        }
+    }
+    fn visit_runtime_call(&mut self, call: &RuntimeCall) {
+        self.count += 1;
+        for argument in call.arguments() {
+            argument.apply(self);
+        }
+    }
+    fn visit_static_assertion(&mut self, assertion: &StaticAssertion) {
+        self.count += 1;
+        assertion.condition().apply(self);
+    }
+    fn visit_assertion(&mut self, assertion: &Assertion) {
+        self.count += 1;
+        for operand in assertion.operands() {
+            operand.apply(self);
+        }
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.count += 1;
+        for element in literal.elements() {
+            element.apply(self);
+        }
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.count += 1;
+        for (key, value) in literal.keys().iter().zip(literal.values()) {
+            key.apply(self);
+            value.apply(self);
+        }
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.count += 1;
+        index.collection().apply(self);
+        index.index().apply(self);
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.count += 1;
+        if let Some(value) = literal.value() {
+            value.apply(self);
+        }
+    }
+    fn visit_unwrap(&mut self, unwrap: &Unwrap) {
+        self.count += 1;
+        unwrap.option().apply(self);
+        if let Some(default) = unwrap.default() {
+            default.apply(self);
+        }
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.count += 1;
+        literal.value().apply(self);
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.count += 1;
+        propagate.value().apply(self);
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.count += 1;
+        call.receiver().apply(self);
+        for argument in call.arguments() {
+            argument.apply(self);
+        }
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        self.count += 1;
+        if let Some(representation) = definition.representation() {
+            representation.apply(self);
+        }
+        for variant in definition.variants() {
+            variant.apply(self);
+        }
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.count += 1;
+        if let Some(discriminant) = variant.discriminant() {
+            discriminant.apply(self);
+        }
+    }
+}
+
+/// The number of nodes in the tree rooted at `node`, including itself.
+pub fn count_nodes(node: &dyn AstNode) -> usize {
+    let mut counter = NodeCounter { count: 0 };
+    node.apply(&mut counter);
+    counter.count
+}
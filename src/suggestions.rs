@@ -0,0 +1,47 @@
+//! "Did you mean" suggestions for names which don't refer to anything, found by edit distance.
+
+use alloc::vec::Vec;
+
+/// How many single character insertions, deletions, substitutions or swaps of adjacent characters turn `from` into
+/// `to`, so that `fucntion` is one edit from `function`.
+pub fn edit_distance(from: &str, to: &str) -> usize {
+    let from = from.chars().collect::<Vec<_>>();
+    let to = to.chars().collect::<Vec<_>>();
+    // The distances between prefixes of `from` and `to`, two rows back, one row back and in the current row.
+    let mut before_previous = Vec::new();
+    let mut previous = (0..=to.len()).collect::<Vec<_>>();
+    for i in 1..=from.len() {
+        let mut current = Vec::with_capacity(to.len() + 1);
+        current.push(i);
+        for j in 1..=to.len() {
+            let substitution = usize::from(from[i - 1] != to[j - 1]);
+            let mut distance = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + substitution);
+            if i > 1 && j > 1 && from[i - 1] == to[j - 2] && from[i - 2] == to[j - 1] {
+                distance = distance.min(before_previous[j - 2] + 1);
+            }
+            current.push(distance);
+        }
+        before_previous = core::mem::replace(&mut previous, current);
+    }
+    previous[to.len()]
+}
+
+/// The candidate closest to `name`, if any is close enough to be what was meant.
+///
+/// A third of the name's length may be wrong, but always at least one character, so short names can still be
+/// corrected. Ties go to the earliest candidate.
+pub fn closest<'candidate>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'candidate str>,
+) -> Option<&'candidate str> {
+    let limit = name.chars().count().max(3) / 3;
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
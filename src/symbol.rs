@@ -0,0 +1,132 @@
+//! Interned identifier names.
+//!
+//! Names are stored once in a global table and referred to by index, so tokens and AST nodes can be copied freely
+//! and names compared without looking at their text.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec::Vec};
+use core::fmt::{self, Debug, Display, Formatter};
+
+/// An interned name, which is equal to another symbol exactly when their text is the same.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The table of names; a `BTreeMap` rather than a `HashMap` so that it can be built in a `static` without `std`.
+struct Interner {
+    indices: BTreeMap<&'static str, Symbol>,
+    names: Vec<&'static str>,
+}
+
+impl Interner {
+    const fn new() -> Self {
+        Self {
+            indices: BTreeMap::new(),
+            names: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn with_interner<T>(use_interner: impl FnOnce(&mut Interner) -> T) -> T {
+    static INTERNER: std::sync::Mutex<Interner> = std::sync::Mutex::new(Interner::new());
+    use_interner(&mut INTERNER.lock().unwrap())
+}
+
+/// Without `std` there is no mutex, so the table is guarded by a spin lock instead.
+#[cfg(not(feature = "std"))]
+fn with_interner<T>(use_interner: impl FnOnce(&mut Interner) -> T) -> T {
+    use core::{
+        cell::UnsafeCell,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    struct SpinLock {
+        locked: AtomicBool,
+        interner: UnsafeCell<Interner>,
+    }
+
+    // The interner is only reached while `locked` is held.
+    unsafe impl Sync for SpinLock {}
+
+    static INTERNER: SpinLock = SpinLock {
+        locked: AtomicBool::new(false),
+        interner: UnsafeCell::new(Interner::new()),
+    };
+    while INTERNER
+        .locked
+        .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_err()
+    {
+        core::hint::spin_loop();
+    }
+    // SAFETY: the lock was just taken, so nothing else has a reference to the interner.
+    let result = use_interner(unsafe { &mut *INTERNER.interner.get() });
+    INTERNER.locked.store(false, Ordering::Release);
+    result
+}
+
+impl Symbol {
+    /// The symbol for `name`, adding it to the table if it hasn't been seen before.
+    pub fn intern(name: &str) -> Self {
+        with_interner(|interner| {
+            if let Some(&symbol) = interner.indices.get(name) {
+                return symbol;
+            }
+            // Names live as long as the table, which lives as long as the program.
+            let name: &'static str = Box::leak(name.into());
+            let symbol = Symbol(interner.names.len() as u32);
+            interner.names.push(name);
+            interner.indices.insert(name, symbol);
+            symbol
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        with_interner(|interner| interner.names[self.0 as usize])
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Symbols are written as their text, since their indices mean nothing to another process.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = alloc::string::String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&name))
+    }
+}
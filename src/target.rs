@@ -0,0 +1,151 @@
+use std::{error::Error, fmt::Display};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    Big,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallingConvention {
+    /// The System V AMD64 ABI used by x86_64 Unix-likes.
+    SysV64,
+    /// The Microsoft x64 calling convention.
+    Win64,
+    /// The 32-bit x86 C calling convention.
+    Cdecl,
+    /// The 32-bit ARM procedure call standard.
+    Aapcs,
+    /// The 64-bit ARM procedure call standard.
+    Aapcs64,
+    RiscV,
+    PowerPc,
+    Mips,
+    Wasm,
+    /// The C calling convention of a host whose architecture the compiler doesn't know.
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct UnknownTargetError {
+    triple: String,
+}
+
+impl Display for UnknownTargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown target: {}", self.triple)
+    }
+}
+
+impl Error for UnknownTargetError {}
+
+/// A description of the machine we are generating code for.
+#[derive(Clone, Debug)]
+pub struct Target {
+    triple: String,
+    /// The width of a pointer (and so of `iptr` and `uptr`) in bits.
+    pointer_width: u32,
+    endianness: Endianness,
+    calling_convention: CallingConvention,
+}
+
+impl Target {
+    /// Parses a target triple of the form `<arch>-<vendor>-<os>[-<environment>]`.
+    /// Only the architecture and operating system are significant.
+    pub fn from_triple(triple: &str) -> Result<Self, UnknownTargetError> {
+        let unknown_target = || UnknownTargetError {
+            triple: triple.to_string(),
+        };
+        let mut components = triple.split('-');
+        let architecture = components.next().ok_or_else(unknown_target)?;
+        let windows = triple.contains("windows");
+        let (pointer_width, endianness, calling_convention) = match architecture {
+            "x86_64" | "amd64" => {
+                if windows {
+                    (64, Endianness::Little, CallingConvention::Win64)
+                } else {
+                    (64, Endianness::Little, CallingConvention::SysV64)
+                }
+            }
+            "i386" | "i486" | "i586" | "i686" | "x86" => {
+                (32, Endianness::Little, CallingConvention::Cdecl)
+            }
+            "aarch64" | "arm64" => (64, Endianness::Little, CallingConvention::Aapcs64),
+            "aarch64_be" => (64, Endianness::Big, CallingConvention::Aapcs64),
+            "riscv64" | "riscv64gc" | "riscv64imac" => {
+                (64, Endianness::Little, CallingConvention::RiscV)
+            }
+            "riscv32" | "riscv32i" | "riscv32imc" | "riscv32imac" => {
+                (32, Endianness::Little, CallingConvention::RiscV)
+            }
+            "powerpc" => (32, Endianness::Big, CallingConvention::PowerPc),
+            "powerpc64" => (64, Endianness::Big, CallingConvention::PowerPc),
+            "powerpc64le" => (64, Endianness::Little, CallingConvention::PowerPc),
+            "mips" => (32, Endianness::Big, CallingConvention::Mips),
+            "mipsel" => (32, Endianness::Little, CallingConvention::Mips),
+            "wasm32" => (32, Endianness::Little, CallingConvention::Wasm),
+            "wasm64" => (64, Endianness::Little, CallingConvention::Wasm),
+            _ if architecture.starts_with("armeb") || architecture.starts_with("thumbeb") => {
+                (32, Endianness::Big, CallingConvention::Aapcs)
+            }
+            _ if architecture.starts_with("arm") || architecture.starts_with("thumb") => {
+                (32, Endianness::Little, CallingConvention::Aapcs)
+            }
+            _ => return Err(unknown_target()),
+        };
+        // Anything with an architecture but nothing else isn't a triple.
+        if components.next().is_none() {
+            return Err(unknown_target());
+        }
+        Ok(Self {
+            triple: triple.to_string(),
+            pointer_width,
+            endianness,
+            calling_convention,
+        })
+    }
+
+    /// The target matching the machine the compiler is running on.
+    ///
+    /// A host whose architecture isn't known is described by its pointer width and endianness alone, so that the
+    /// compiler still runs there.
+    pub fn host() -> Self {
+        let rest_of_triple = match std::env::consts::OS {
+            "linux" => "unknown-linux-gnu",
+            "macos" => "apple-darwin",
+            "windows" => "pc-windows-msvc",
+            "freebsd" => "unknown-freebsd",
+            _ => "unknown-unknown",
+        };
+        let triple = format!("{}-{}", std::env::consts::ARCH, rest_of_triple);
+        match Self::from_triple(&triple) {
+            Ok(target) => target,
+            Err(_) => Self {
+                triple,
+                pointer_width: usize::BITS,
+                endianness: if cfg!(target_endian = "big") {
+                    Endianness::Big
+                } else {
+                    Endianness::Little
+                },
+                calling_convention: CallingConvention::Unknown,
+            },
+        }
+    }
+
+    pub fn triple(&self) -> &str {
+        &self.triple
+    }
+
+    pub fn pointer_width(&self) -> u32 {
+        self.pointer_width
+    }
+
+    pub fn endianness(&self) -> Endianness {
+        self.endianness
+    }
+
+    pub fn calling_convention(&self) -> CallingConvention {
+        self.calling_convention
+    }
+}
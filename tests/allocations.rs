@@ -0,0 +1,47 @@
+//! Checks that lexing doesn't allocate for each token, using an allocator which counts allocations.
+//!
+//! The count is shared by every thread, so this file must only ever hold one test.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use hematite_lang::lexer;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(pointer, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// How many allocations lexing `source` takes, without keeping the tokens.
+fn allocations_while_lexing(source: &str) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let token_count = lexer::tokenize(&mut source.chars()).count();
+    assert!(token_count > 0);
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+#[test]
+fn lexing_allocates_the_same_however_many_tokens_there_are() {
+    // Only tokens which don't own any text, so that the tokens themselves don't allocate.
+    let function = "function main() -> i32 {\n    let x: i32 = (1 + 2) * 3;\n    x % 4\n}\n";
+    // Interns `main` and `x`, and lets the lexer's buffers grow to their final sizes.
+    allocations_while_lexing(function);
+    let once = allocations_while_lexing(function);
+    let many_times = allocations_while_lexing(&function.repeat(1000));
+    assert_eq!(once, many_times);
+}
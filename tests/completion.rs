@@ -0,0 +1,84 @@
+//! Checks what is offered for completion at different points in a file, marked with `|`.
+
+use hematite_lang::completion::{self, CompletionKind};
+
+/// The labels of the completions at the `|` in `source`, with their kinds.
+fn completions_at(source: &str) -> Vec<(String, CompletionKind)> {
+    let offset = source.find('|').expect("The source should mark the cursor");
+    let source = source.replace('|', "");
+    let tokens = hematite_lang::tokenize_str(&source);
+    completion::completions(&tokens, offset, true)
+        .into_iter()
+        .map(|completion| (completion.label().to_string(), completion.kind()))
+        .collect()
+}
+
+fn labels_at(source: &str) -> Vec<String> {
+    completions_at(source)
+        .into_iter()
+        .map(|(label, _)| label)
+        .collect()
+}
+
+#[test]
+fn items_start_with_a_keyword() {
+    assert_eq!(labels_at("|"), ["function", "enum"]);
+    assert_eq!(
+        labels_at("function main() -> i32 { 0 }\nfu|"),
+        ["function", "enum"]
+    );
+    assert_eq!(labels_at("@test\n|"), ["function", "enum"]);
+    assert!(labels_at("function |").is_empty());
+}
+
+#[test]
+fn types_come_after_colons_and_arrows() {
+    let labels = labels_at("function main(x: |");
+    assert!(labels.iter().any(|label| label == "i32"));
+    assert!(labels.iter().any(|label| label == "option"));
+    assert_eq!(labels_at("function main() -> st|"), labels);
+    assert_eq!(labels_at("function main() -> map<string, |"), labels);
+    assert!(completions_at("function main() { let x: list<|")
+        .iter()
+        .all(|(_, kind)| *kind == CompletionKind::Type));
+}
+
+#[test]
+fn statements_and_expressions_are_offered_in_function_bodies() {
+    let statement = labels_at("function main() -> i32 {\n    |");
+    assert_eq!(statement[0], "let");
+    assert!(statement.iter().any(|label| label == "some"));
+    assert!(statement.iter().any(|label| label == "format"));
+    let expression = labels_at("function main() -> i32 {\n    let x: i32 = 1 + |");
+    assert_eq!(expression, statement[1..]);
+    // A map's value is an expression, not a type.
+    assert_eq!(labels_at("function main() { [1: |"), expression);
+    assert!(labels_at("function main() -> i32 { 1 |").is_empty());
+}
+
+#[test]
+fn methods_come_after_a_dot_with_what_they_can_be_called_on() {
+    let tokens = hematite_lang::tokenize_str("function main() { format!(\"hi\").len");
+    let offset = tokens.last().unwrap().1.end();
+    let completions = completion::completions(&tokens, offset, true);
+    assert!(completions
+        .iter()
+        .all(|completion| completion.kind() == CompletionKind::Method));
+    let length = completions
+        .iter()
+        .find(|completion| completion.label() == "length")
+        .expect("Strings and lists have a length");
+    assert!(length.detail().unwrap().contains("string"));
+    assert!(length.detail().unwrap().contains("list"));
+}
+
+#[test]
+fn attributes_come_after_an_at_sign() {
+    assert_eq!(labels_at("@|"), ["export", "test"]);
+    assert_eq!(completions_at("@te|")[0].1, CompletionKind::Attribute);
+}
+
+#[test]
+fn nothing_is_offered_for_enum_variants() {
+    assert!(labels_at("enum Colour { Red, |").is_empty());
+}
@@ -0,0 +1,44 @@
+//! Checks that the query database only runs queries again when what they depend on has changed.
+
+use hematite_lang::{
+    database::{Database, Query},
+    Features,
+};
+
+const FIRST: &str = "function first() -> i32 { 1 }\n";
+const SECOND: &str = "function second() -> i32 { 2 }\n";
+
+fn runs(database: &Database) -> (usize, usize) {
+    (database.runs(Query::Tokens), database.runs(Query::Syntax))
+}
+
+#[test]
+fn only_changed_files_are_lexed_and_parsed_again() {
+    let mut database = Database::new(Features::default());
+    let first = database.add_file(FIRST.to_string());
+    let second = database.add_file(SECOND.to_string());
+    assert!(database.syntax(first).is_ok());
+    assert!(database.syntax(second).is_ok());
+    assert_eq!(runs(&database), (2, 2));
+    database.set_text(second, SECOND.to_string());
+    database.syntax(first);
+    database.syntax(second);
+    assert_eq!(runs(&database), (2, 2));
+    database.set_text(second, SECOND.replace('2', "3"));
+    database.syntax(first);
+    database.syntax(second);
+    assert_eq!(runs(&database), (3, 3));
+}
+
+#[test]
+fn a_change_which_leaves_the_tokens_alone_isnt_parsed_again() {
+    let mut database = Database::new(Features::default());
+    let file = database.add_file(FIRST.to_string());
+    database.syntax(file);
+    database.set_text(file, format!("{FIRST}\n\n"));
+    assert!(database.syntax(file).is_ok());
+    assert_eq!(runs(&database), (2, 1));
+    database.set_text(file, "function".to_string());
+    assert!(database.syntax(file).is_err());
+    assert_eq!(runs(&database), (3, 2));
+}
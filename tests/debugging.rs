@@ -0,0 +1,81 @@
+//! Checks that `debug` runs programs as they would run on their own: with their arguments and environment, and
+//! exiting with whatever they returned.
+
+use std::{
+    fs,
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// Runs `source` under the debugger with `arguments`, giving it `command` whenever it stops, and returns the
+/// compiler's exit code and what was printed.
+fn debug(name: &str, source: &str, arguments: &[&str], command: &str) -> (Option<i32>, String) {
+    let path = std::env::temp_dir().join(format!("hematite-debugging-{name}.hmt"));
+    fs::write(&path, source).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hematite-lang"))
+        .arg("debug")
+        .arg(&path)
+        .arg("--")
+        .args(arguments)
+        .env("HEMATITE_DEBUGGING_TEST", "set")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(format!("{command}\n").repeat(4).as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(path).unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    assert!(stdout.contains("The program exited with code"));
+    (output.status.code(), stdout)
+}
+
+#[test]
+fn a_program_returning_zero_succeeds() {
+    let (code, _) = debug(
+        "zero",
+        "function main() -> i32 {\n    0\n}\n",
+        &[],
+        "continue",
+    );
+    assert_eq!(code, Some(0));
+}
+
+#[test]
+fn a_program_returning_nonzero_exits_with_its_value() {
+    let (code, _) = debug(
+        "three",
+        "function main() -> i32 {\n    3\n}\n",
+        &[],
+        "continue",
+    );
+    assert_eq!(code, Some(3));
+}
+
+#[test]
+fn the_program_sees_its_arguments_and_environment() {
+    let source = r#"function main() -> i32 {
+    println!("{}", args!());
+    println!("{}", env_var!(format!("HEMATITE_DEBUGGING_TEST")));
+    println!("{}", env_var!(format!("HEMATITE_DEBUGGING_UNSET")));
+    0
+}
+"#;
+    let (code, stdout) = debug("arguments", source, &["one", "two three"], "continue");
+    assert_eq!(code, Some(0));
+    assert!(stdout.contains("[\"one\", \"two three\"]\nsome(\"set\")\nnone\n"));
+}
+
+#[test]
+fn stepping_stops_at_a_literal_ending_the_function() {
+    let source = "function main() -> i32 {\n    let x: i32 = 1;\n    2\n}\n";
+    let (code, stdout) = debug("tail", source, &[], "step");
+    assert_eq!(code, Some(2));
+    assert!(stdout.contains(".hmt:3\n    3 |     2\n"));
+    assert!(!stdout.contains("unknown location"));
+}
@@ -0,0 +1,112 @@
+//! Checks that the precedence table, the parser and the generated grammar agree.
+
+use hematite_lang::{
+    ast::{BinaryOperation, BinaryOperator, FunctionDefinition},
+    grammar,
+    lexer::Token,
+    parser::{binary_precedence, Associativity, BINARY_OPERATORS},
+    NodeKind,
+};
+
+#[test]
+fn the_table_runs_from_loosest_to_tightest() {
+    for pair in BINARY_OPERATORS.windows(2) {
+        assert!(pair[0].1.precedence <= pair[1].1.precedence);
+    }
+}
+
+#[test]
+fn every_operator_is_in_the_grammar() {
+    let grammar = grammar::grammar();
+    for (operator, _) in BINARY_OPERATORS {
+        let text = format!("\"{}\"", operator.token().source_text());
+        assert!(
+            grammar.contains(&text),
+            "{text} is missing from:\n{grammar}"
+        );
+    }
+}
+
+#[test]
+fn every_exact_match_token_is_in_the_grammar() {
+    let grammar = grammar::grammar();
+    for token in Token::EXACT_MATCH_TOKENS {
+        let text = format!("\"{}\"", token.source_text());
+        assert!(
+            grammar.contains(&text),
+            "{text} is missing from:\n{grammar}"
+        );
+    }
+}
+
+/// The names on the left of each rule, and every name used on the right of one.
+fn defined_and_used_rules(grammar: &str) -> (Vec<&str>, Vec<String>) {
+    let mut defined = Vec::new();
+    let mut used = Vec::new();
+    for rule in grammar.split(" ;\n") {
+        // Skip the comment before the rule, if there is one.
+        let rule = rule.rsplit("*)").next().unwrap();
+        let Some((name, body)) = rule.split_once(" = ") else {
+            continue;
+        };
+        defined.push(name.trim());
+        // Quoted terminals and special sequences aren't rule names, so they are left out of the words.
+        let mut words = String::new();
+        let mut delimiter = None;
+        for character in body.chars() {
+            match delimiter {
+                Some(end) if character == end => delimiter = None,
+                Some(_) => {}
+                None if matches!(character, '"' | '\'' | '?') => delimiter = Some(character),
+                None => words.push(character),
+            }
+        }
+        assert!(delimiter.is_none(), "unterminated terminal in {rule}");
+        used.extend(
+            words
+                .split(|character: char| !(character.is_alphanumeric() || character == '_'))
+                .filter(|word| !word.is_empty())
+                .map(String::from),
+        );
+    }
+    (defined, used)
+}
+
+#[test]
+fn every_rule_used_is_defined_once() {
+    let grammar = grammar::grammar();
+    let (defined, used) = defined_and_used_rules(&grammar);
+    for (index, name) in defined.iter().enumerate() {
+        assert!(
+            !defined[..index].contains(name),
+            "{name} is defined twice in:\n{grammar}"
+        );
+    }
+    for name in used {
+        assert!(
+            defined.contains(&name.as_str()),
+            "{name} is used but not defined in:\n{grammar}"
+        );
+    }
+}
+
+#[test]
+fn operators_group_as_the_table_says() {
+    let program = hematite_lang::parse_str("function f() -> i32 { 1 - 2 * 3 - 4 }").unwrap();
+    let function = hematite_lang::items_of_kind(program.as_ref(), NodeKind::FunctionDefinition)[0]
+        .downcast_ref::<FunctionDefinition>()
+        .unwrap();
+    let root = hematite_lang::items_of_kind(function, NodeKind::BinaryOperation)[0]
+        .downcast_ref::<BinaryOperation>()
+        .unwrap();
+    // Subtraction is left associative, so the last one is at the root, and multiplication binds more tightly.
+    assert_eq!(root.operator(), BinaryOperator::Subtract);
+    assert_eq!(
+        binary_precedence(BinaryOperator::Subtract).associativity,
+        Associativity::Left
+    );
+    let left = root.left().downcast_ref::<BinaryOperation>().unwrap();
+    assert_eq!(left.operator(), BinaryOperator::Subtract);
+    let product = left.right().downcast_ref::<BinaryOperation>().unwrap();
+    assert_eq!(product.operator(), BinaryOperator::Multiply);
+}
@@ -0,0 +1,85 @@
+//! Checks how tokens are classified for highlighting, with and without a tree to find declarations in.
+
+use hematite_lang::highlight::{self, HighlightKind};
+
+const SOURCE: &str = "\
+/// Doubles.
+@export
+function double(x: i32, y: list<i32>) -> i32 {
+    let mut x: i32 = 2 * 21;
+    format!(\"<b>\").length();
+    42
+}
+
+enum Colour: u8 { Red = 1, Green }
+";
+
+/// The text of each highlighted token along with its kind, from a parsed tree if `parse` is set.
+fn highlights(parse: bool) -> Vec<(&'static str, HighlightKind)> {
+    let tokens = hematite_lang::tokenize_str(SOURCE);
+    let program = parse.then(|| hematite_lang::parse_str(SOURCE).unwrap());
+    highlight::highlight(&tokens, program.as_deref())
+        .into_iter()
+        .map(|(span, kind)| (&SOURCE[span.start()..span.end()], kind))
+        .collect()
+}
+
+/// The kinds of the highlighted tokens with this text, in order.
+fn kinds_of(highlights: &[(&str, HighlightKind)], text: &str) -> Vec<HighlightKind> {
+    highlights
+        .iter()
+        .filter(|(highlighted, _)| *highlighted == text)
+        .map(|(_, kind)| *kind)
+        .collect()
+}
+
+#[test]
+fn names_are_classified_by_what_declares_them() {
+    let highlights = highlights(true);
+    assert_eq!(kinds_of(&highlights, "double"), [HighlightKind::Function]);
+    // The variable shadows the parameter.
+    assert_eq!(
+        kinds_of(&highlights, "x"),
+        [HighlightKind::Parameter, HighlightKind::Local]
+    );
+    assert_eq!(kinds_of(&highlights, "y"), [HighlightKind::Parameter]);
+    assert_eq!(kinds_of(&highlights, "Colour"), [HighlightKind::Type]);
+    assert_eq!(kinds_of(&highlights, "Red"), [HighlightKind::Variant]);
+    assert_eq!(kinds_of(&highlights, "export"), [HighlightKind::Attribute]);
+}
+
+#[test]
+fn keywords_types_and_literals_are_classified_by_their_token() {
+    let highlights = highlights(true);
+    assert_eq!(kinds_of(&highlights, "mut"), [HighlightKind::Keyword]);
+    assert_eq!(kinds_of(&highlights, "list"), [HighlightKind::Type]);
+    assert_eq!(kinds_of(&highlights, "u8"), [HighlightKind::Type]);
+    assert_eq!(kinds_of(&highlights, "format!"), [HighlightKind::Macro]);
+    assert_eq!(kinds_of(&highlights, "42"), [HighlightKind::Number]);
+    assert_eq!(kinds_of(&highlights, "\"<b>\""), [HighlightKind::String]);
+    assert_eq!(
+        kinds_of(&highlights, "/// Doubles."),
+        [HighlightKind::Comment]
+    );
+    assert!(kinds_of(&highlights, "length").is_empty());
+}
+
+#[test]
+fn only_attributes_among_names_are_classified_without_a_tree() {
+    let highlights = highlights(false);
+    assert!(kinds_of(&highlights, "double").is_empty());
+    assert!(kinds_of(&highlights, "x").is_empty());
+    assert_eq!(kinds_of(&highlights, "export"), [HighlightKind::Attribute]);
+    assert_eq!(kinds_of(&highlights, "function"), [HighlightKind::Keyword]);
+}
+
+#[test]
+fn html_escapes_text_and_wraps_highlighted_tokens() {
+    let text = "1 < 2";
+    let tokens = hematite_lang::tokenize_str(text);
+    let highlights = highlight::highlight(&tokens, None);
+    assert_eq!(
+        highlight::to_html(text, &highlights),
+        "<span class=\"number\">1</span> &lt; <span class=\"number\">2</span>"
+    );
+}
@@ -0,0 +1,76 @@
+//! Checks what hovering over different parts of a program describes.
+
+use hematite_lang::{
+    ast::Module, diagnostics::DiagnosticCollector, hover, intrinsics, macros, methods, AstNode,
+    Features, Session, SourceFile, Target,
+};
+
+const SOURCE: &str = "\
+/// Doubles a number.
+/// Twice.
+function double(x: i32) -> i32 {
+    let mut y: list<i32> = [1];
+    format!(\"hi\").length();
+    21 * 2
+}
+
+/// The primary colours.
+enum Colour { Red, Green, Blue }
+";
+
+/// The program with its macros expanded and methods resolved, as the language server hovers over it.
+fn analysed() -> Box<dyn AstNode> {
+    let target = Target::from_triple("x86_64-unknown-linux-gnu").unwrap();
+    let mut session = Session::new(
+        target,
+        Features::default(),
+        Box::new(DiagnosticCollector::new()),
+    );
+    session
+        .source_map_mut()
+        .add(SourceFile::new("hover".to_string(), SOURCE.to_string()));
+    let items = hematite_lang::parse_str(SOURCE).unwrap();
+    let module = Module::new("hover".to_string(), 0, items);
+    let expanded = macros::expand_macros(&module, &mut session).unwrap();
+    let resolved = intrinsics::resolve_intrinsics(expanded.as_ref(), &session);
+    methods::resolve_methods(resolved.as_ref()).unwrap()
+}
+
+/// The code and documentation shown when hovering just after the first occurrence of `text`.
+fn hover_after(text: &str) -> Option<(String, Vec<String>)> {
+    let offset = SOURCE.find(text).expect("The text should be in the source") + text.len();
+    hover::hover(analysed().as_ref(), offset)
+        .map(|hover| (hover.code().to_string(), hover.documentation().to_vec()))
+}
+
+#[test]
+fn functions_show_their_signature_and_doc_comment() {
+    let (code, documentation) = hover_after("function dou").unwrap();
+    assert_eq!(code, "function double(x: i32) -> i32");
+    assert_eq!(documentation, ["Doubles a number.", "Twice."]);
+}
+
+#[test]
+fn enums_show_their_name_and_doc_comment() {
+    let (code, documentation) = hover_after("enum Col").unwrap();
+    assert_eq!(code, "enum Colour");
+    assert_eq!(documentation, ["The primary colours."]);
+}
+
+#[test]
+fn expressions_show_their_type() {
+    assert_eq!(hover_after("21 *").unwrap().0, "i32");
+    assert_eq!(hover_after("[").unwrap().0, "list<i32>");
+    assert_eq!(hover_after("format!(").unwrap().0, "string");
+    assert_eq!(hover_after(").len").unwrap().0, "uptr");
+}
+
+#[test]
+fn variables_show_their_declared_type() {
+    assert_eq!(hover_after("let m").unwrap().0, "let mut y: list<i32>");
+}
+
+#[test]
+fn nothing_is_shown_between_items() {
+    assert_eq!(hover_after("}\n\n"), None);
+}
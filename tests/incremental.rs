@@ -0,0 +1,85 @@
+//! Checks that editing a file and reparsing incrementally gives the same tokens and tree as parsing the edited text
+//! from scratch.
+
+use hematite_lang::{incremental::IncrementalParse, Features, Span};
+
+const SOURCE: &str = "\
+/// Adds one.
+function increment(x: i32) -> i32 {
+    1 + 1
+}
+
+/// Returns zero.
+function main() -> i32 {
+    let y: i32 = 41 * 2;
+    format!(\"hé\").length();
+    42 - 42
+}
+
+function unused() -> i32 { 0 }
+";
+
+/// Applies each edit in turn, checking the incremental parse against a full parse after every one.
+fn check_edits(edits: &[(&str, &str)]) {
+    let mut parse = IncrementalParse::new(SOURCE.to_string(), Features::default());
+    assert!(parse.program().is_ok());
+    for (old, new) in edits {
+        let start = parse
+            .text()
+            .find(old)
+            .expect("Edits should replace existing text");
+        parse.edit(Span::new(start, start + old.len()), new);
+        let text = parse.text();
+        assert_eq!(parse.tokens(), hematite_lang::tokenize_str(text), "{text}");
+        let expected = hematite_lang::parse_str(text).ok();
+        assert_eq!(
+            format!("{:?}", parse.program().ok()),
+            format!("{expected:?}"),
+            "{text}"
+        );
+    }
+}
+
+#[test]
+fn an_edit_inside_a_function_moves_the_items_after_it() {
+    check_edits(&[("1 + 1", "1 + 100"), ("41 * 2", "4"), ("42 - 42", "7")]);
+}
+
+#[test]
+fn items_can_be_added_and_removed_between_others() {
+    check_edits(&[
+        (
+            "\n\n/// Returns",
+            "\n\nfunction added() -> i32 { 1 }\n\n/// Returns",
+        ),
+        ("function unused() -> i32 { 0 }\n", ""),
+    ]);
+}
+
+#[test]
+fn a_broken_item_is_reported_until_it_is_fixed() {
+    check_edits(&[
+        ("42 - 42", "42 -"),
+        ("42 -", "42 - 4"),
+        ("increment(x", "increment("),
+    ]);
+}
+
+#[test]
+fn tokens_after_an_unterminated_string_are_found_once_it_is_closed() {
+    check_edits(&[("\"hé\"", "\"hé"), ("\"hé", "\"hé\""), ("\"hé\"", "\"h\"")]);
+}
+
+#[test]
+fn doc_comments_can_be_changed_added_and_removed() {
+    check_edits(&[
+        ("Returns zero", "Returns nothing useful"),
+        ("/// Adds one.\n", ""),
+        (
+            "function increment",
+            "/// Adds one.\n/// Twice.\nfunction increment",
+        ),
+        ("    let y", "    /// let y"),
+        ("/// let y", "let y"),
+    ]);
+}
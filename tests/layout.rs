@@ -0,0 +1,90 @@
+//! Checks how values are laid out in memory: padding between fields, where enum payloads start, and which types
+//! depend on the target's pointer width.
+
+use hematite_lang::{ast::Type, layout, layout::Layout, Target};
+
+#[test]
+fn struct_fields_are_padded_to_their_alignment() {
+    let byte = Layout::new(1, 1);
+    let int = Layout::new(4, 4);
+    let long = Layout::new(8, 8);
+    let struct_layout = Layout::of_struct(&[byte, int, byte, long]);
+    assert_eq!(struct_layout.field_offsets(), [0, 4, 8, 16]);
+    assert_eq!(struct_layout.layout(), Layout::new(24, 8));
+    // Trailing padding keeps the size a multiple of the alignment.
+    let struct_layout = Layout::of_struct(&[int, byte]);
+    assert_eq!(struct_layout.field_offsets(), [0, 4]);
+    assert_eq!(struct_layout.layout(), Layout::new(8, 4));
+}
+
+#[test]
+fn an_empty_struct_takes_no_space() {
+    let struct_layout = Layout::of_struct(&[]);
+    assert!(struct_layout.field_offsets().is_empty());
+    assert_eq!(struct_layout.layout(), Layout::new(0, 1));
+}
+
+#[test]
+fn enum_payloads_follow_the_discriminant_at_the_largest_alignment() {
+    let flag = Layout::new(1, 1);
+    let enum_layout = Layout::of_enum(flag, &[Layout::new(2, 2), Layout::new(8, 8)]);
+    assert_eq!(enum_layout.discriminant(), flag);
+    assert_eq!(enum_layout.payload_offset(), 8);
+    assert_eq!(enum_layout.layout(), Layout::new(16, 8));
+    // Without payloads, an enum is just its discriminant.
+    let enum_layout = Layout::of_enum(Layout::new(4, 4), &[]);
+    assert_eq!(enum_layout.payload_offset(), 4);
+    assert_eq!(enum_layout.layout(), Layout::new(4, 4));
+}
+
+#[test]
+fn array_elements_are_each_padded_to_their_alignment() {
+    assert_eq!(Layout::of_array(Layout::new(4, 4), 3), Layout::new(12, 4));
+    assert_eq!(Layout::of_array(Layout::new(5, 4), 2), Layout::new(16, 4));
+    assert_eq!(Layout::of_array(Layout::new(8, 8), 0), Layout::new(0, 8));
+}
+
+#[test]
+fn pointer_sized_types_follow_the_target() {
+    let wide = Target::from_triple("x86_64-unknown-linux-gnu").unwrap();
+    let narrow = Target::from_triple("i686-unknown-linux-gnu").unwrap();
+    let cases = [
+        (Type::Uptr, Layout::new(8, 8), Layout::new(4, 4)),
+        (Type::String, Layout::new(16, 8), Layout::new(8, 4)),
+        (
+            Type::List(Box::new(Type::U8)),
+            Layout::new(24, 8),
+            Layout::new(12, 4),
+        ),
+        (
+            Type::Map(Box::new(Type::String), Box::new(Type::I32)),
+            Layout::new(8, 8),
+            Layout::new(4, 4),
+        ),
+        (
+            Type::Option(Box::new(Type::String)),
+            Layout::new(24, 8),
+            Layout::new(12, 4),
+        ),
+    ];
+    for (type_value, on_wide, on_narrow) in cases {
+        assert_eq!(layout::layout_of(&type_value, &wide), on_wide);
+        assert_eq!(layout::layout_of(&type_value, &narrow), on_narrow);
+    }
+}
+
+#[test]
+fn fixed_size_types_are_the_same_on_every_target() {
+    let wide = Target::from_triple("x86_64-unknown-linux-gnu").unwrap();
+    let narrow = Target::from_triple("i686-unknown-linux-gnu").unwrap();
+    let result = Type::Result(Box::new(Type::I64), Box::new(Type::U8));
+    for (type_value, expected) in [
+        (Type::U8, Layout::new(1, 1)),
+        (Type::Char, Layout::new(4, 4)),
+        (Type::I64, Layout::new(8, 8)),
+        (result, Layout::new(16, 8)),
+    ] {
+        assert_eq!(layout::layout_of(&type_value, &wide), expected);
+        assert_eq!(layout::layout_of(&type_value, &narrow), expected);
+    }
+}
@@ -0,0 +1,55 @@
+//! Checks the lexer on inputs which are unusual in shape rather than in content.
+
+use hematite_lang::{Span, Symbol, Token};
+
+#[test]
+fn long_tokens_are_lexed_whole() {
+    let identifier = "a".repeat(100_000);
+    let text = "é".repeat(100_000);
+    let source = format!("{identifier} \"{text}\" /// {text}\n");
+    let tokens = hematite_lang::tokenize_str(&source);
+    let string_start = identifier.len() + 1;
+    let comment_start = string_start + text.len() + 3;
+    assert_eq!(
+        tokens,
+        [
+            (
+                Token::Identifier(Symbol::intern(&identifier)),
+                Span::new(0, identifier.len())
+            ),
+            (
+                Token::StringLiteral(text.clone()),
+                Span::new(string_start, comment_start - 1)
+            ),
+            (
+                Token::DocComment(text.clone()),
+                Span::new(comment_start, source.len() - 1)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn words_are_keywords_only_when_they_match_exactly() {
+    let tokens = hematite_lang::tokenize_str("function functional i32 i32x _let format! é2²!")
+        .into_iter()
+        .map(|(token, _)| token)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            Token::Function,
+            Token::Identifier(Symbol::intern("functional")),
+            Token::I32,
+            Token::Identifier(Symbol::intern("i32x")),
+            Token::Identifier(Symbol::intern("_let")),
+            Token::MacroCall(Symbol::intern("format")),
+            Token::MacroCall(Symbol::intern("é2²")),
+        ]
+    );
+    let tokens = hematite_lang::tokenize_str("a² b");
+    assert_eq!(
+        tokens,
+        [(Token::Error("Invalid token: a² ".into()), Span::new(0, 4))]
+    );
+}
@@ -0,0 +1,49 @@
+//! Checks the token stream the parser reads from: looking ahead, and rewinding speculative parses.
+
+use hematite_lang::{parser, Span, SyntaxError, Token, TokenStream};
+
+#[test]
+fn rewinding_reads_the_same_tokens_again() {
+    let mut tokens = hematite_lang::tokenize_str("a b c").into_iter();
+    let mut token_stream = TokenStream::new(&mut tokens);
+    let checkpoint = token_stream.checkpoint();
+    let first = token_stream.next();
+    let inner = token_stream.checkpoint();
+    assert_eq!(token_stream.next(), Some(Token::Identifier("b".into())));
+    token_stream.commit(inner);
+    token_stream.rewind(checkpoint);
+    assert_eq!(token_stream.next(), first);
+    assert_eq!(
+        token_stream.collect::<Vec<_>>(),
+        [Token::Identifier("b".into()), Token::Identifier("c".into())]
+    );
+}
+
+#[test]
+fn a_failed_speculation_consumes_nothing() {
+    let mut tokens = hematite_lang::tokenize_str("a b").into_iter();
+    let mut token_stream = TokenStream::new(&mut tokens);
+    let failed = token_stream.speculate(|token_stream| {
+        token_stream.next();
+        parser::parse(token_stream)
+    });
+    assert!(failed.is_err());
+    let succeeded =
+        token_stream.speculate(|token_stream| Ok::<_, SyntaxError>(token_stream.next()));
+    assert_eq!(succeeded.unwrap(), Some(Token::Identifier("a".into())));
+    assert_eq!(token_stream.next(), Some(Token::Identifier("b".into())));
+}
+
+#[test]
+fn tokens_can_be_looked_ahead_at_without_consuming_them() {
+    let mut tokens = hematite_lang::tokenize_str("a b").into_iter();
+    let mut token_stream = TokenStream::new(&mut tokens);
+    assert_eq!(
+        token_stream.peek_nth(1),
+        Some(&Token::Identifier("b".into()))
+    );
+    assert_eq!(token_stream.peek_nth(2), None);
+    assert_eq!(token_stream.peek_nth_span(2), Span::new(3, 3));
+    assert_eq!(token_stream.next(), Some(Token::Identifier("a".into())));
+    assert_eq!(token_stream.peek_nth_span(0), Span::new(2, 3));
+}
@@ -0,0 +1,45 @@
+//! Checks the tree queries against a parsed program.
+
+use hematite_lang::{ast::FunctionDefinition, NodeKind};
+
+const SOURCE: &str = "function first() -> i32 {
+    1 + 2
+}
+
+function second() -> i32 {
+    let value: i32 = 3 * 4;
+    5
+}
+";
+
+#[test]
+fn functions_are_found_by_name() {
+    let program = hematite_lang::parse_str(SOURCE).unwrap();
+    let function = hematite_lang::find_function(program.as_ref(), "second").unwrap();
+    assert_eq!(function.name(), "second");
+    assert!(hematite_lang::find_function(program.as_ref(), "third").is_none());
+}
+
+#[test]
+fn items_are_listed_in_order() {
+    let program = hematite_lang::parse_str(SOURCE).unwrap();
+    let names = hematite_lang::items_of_kind(program.as_ref(), NodeKind::FunctionDefinition)
+        .into_iter()
+        .map(|node| node.downcast_ref::<FunctionDefinition>().unwrap().name())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["first", "second"]);
+    let operations = hematite_lang::items_of_kind(program.as_ref(), NodeKind::BinaryOperation);
+    assert_eq!(operations.len(), 2);
+}
+
+#[test]
+fn the_innermost_node_is_found_at_an_offset() {
+    let program = hematite_lang::parse_str(SOURCE).unwrap();
+    let plus = SOURCE.find('+').unwrap();
+    let node = hematite_lang::node_at_offset(program.as_ref(), plus).unwrap();
+    assert_eq!(NodeKind::of(node), NodeKind::BinaryOperation);
+    let name = SOURCE.find("second").unwrap();
+    let node = hematite_lang::node_at_offset(program.as_ref(), name + 2).unwrap();
+    assert_eq!(NodeKind::of(node), NodeKind::FunctionDefinition);
+    assert!(hematite_lang::node_at_offset(program.as_ref(), 0).is_none());
+}
@@ -0,0 +1,48 @@
+//! Checks that the commands which rewrite files in place leave files with invalid UTF-8 alone.
+
+use std::{fs, path::PathBuf, process::Command};
+
+/// A misspelled attribute for `fix` to correct, badly formatted for `fmt`, with a Latin-1 `é` in a doc comment.
+const SOURCE: &[u8] = b"/// caf\xe9\n@tset\nfunction main() -> i32 {   0 }\n";
+
+/// Writes `SOURCE` to a file of its own for one test.
+fn write_source(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("hematite-rewriting-{name}.hmt"));
+    fs::write(&path, SOURCE).unwrap();
+    path
+}
+
+/// Runs the compiler with `arguments` followed by `path`, returning its exit code and standard error.
+fn run(arguments: &[&str], path: &PathBuf) -> (Option<i32>, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_hematite-lang"))
+        .args(arguments)
+        .arg(path)
+        .output()
+        .unwrap();
+    (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn fmt_refuses_to_rewrite_invalid_utf8() {
+    let path = write_source("fmt");
+    let (code, stderr) = run(&["fmt"], &path);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("isn't valid UTF-8, so it can't be rewritten"));
+    assert!(stderr.contains(&path.display().to_string()));
+    assert_eq!(fs::read(&path).unwrap(), SOURCE);
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn fix_refuses_to_rewrite_invalid_utf8() {
+    let path = write_source("fix");
+    let (code, stderr) = run(&["fix", "--maybe-incorrect"], &path);
+    assert_eq!(code, Some(2));
+    assert!(stderr.contains("isn't valid UTF-8, so it can't be rewritten"));
+    assert!(stderr.contains(&path.display().to_string()));
+    assert_eq!(fs::read(&path).unwrap(), SOURCE);
+    fs::remove_file(path).unwrap();
+}
@@ -0,0 +1,536 @@
+//! Checks that printing a tree and parsing the result gives back the same tree, for randomly generated programs.
+//!
+//! Spans can't survive a round trip, so trees are compared as `Shape`s, which keep everything else.
+
+use hematite_lang::{
+    ast::{
+        AstNode, AstVisitor, BinaryOperation, BinaryOperator, EnumDefinition, EnumVariant,
+        FunctionDefinition, IgnoreValue, Index, ListLiteral, MapLiteral, MethodCall, OptionLiteral,
+        ParameterDeclaration, Propagate, ResultLiteral, ResultVariant, Type, VariableDefinition,
+    },
+    formatter::{self, Style},
+    parser::BINARY_OPERATORS,
+    Span, Symbol,
+};
+
+/// A deterministic source of randomness, so that a failing case can be reproduced from its seed.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift gets stuck at zero, so every seed is offset away from it.
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, bound: usize) -> usize {
+        (self.next() % bound as u64) as usize
+    }
+
+    fn chance(&mut self, numerator: usize, denominator: usize) -> bool {
+        self.below(denominator) < numerator
+    }
+
+    fn choose<T: Copy>(&mut self, choices: &[T]) -> T {
+        choices[self.below(choices.len())]
+    }
+}
+
+const TYPES: &[Type] = &[
+    Type::I8,
+    Type::I16,
+    Type::I32,
+    Type::I64,
+    Type::Iptr,
+    Type::U8,
+    Type::U16,
+    Type::U32,
+    Type::U64,
+    Type::Uptr,
+    Type::F32,
+    Type::F64,
+    Type::Bool,
+    Type::Char,
+    Type::String,
+];
+
+/// A tree without its spans.
+#[derive(Debug, PartialEq)]
+enum Shape {
+    List(Vec<Shape>),
+    Function {
+        name: String,
+        documentation: Vec<String>,
+        attributes: Vec<String>,
+        parameters: Vec<Shape>,
+        return_type: Box<Shape>,
+        body: Box<Shape>,
+    },
+    Parameter {
+        name: String,
+        parameter_type: Box<Shape>,
+    },
+    Enum {
+        name: String,
+        documentation: Vec<String>,
+        representation: Option<Box<Shape>>,
+        variants: Vec<Shape>,
+    },
+    Variant(String, Option<Box<Shape>>),
+    Variable {
+        mutable: bool,
+        name: String,
+        variable_type: Box<Shape>,
+        value: Box<Shape>,
+    },
+    IgnoreValue(Box<Shape>),
+    Integer(i128),
+    Binary(BinaryOperator, Box<Shape>, Box<Shape>),
+    ListLiteral(Vec<Shape>),
+    MapLiteral(Vec<(Shape, Shape)>),
+    Index(Box<Shape>, Box<Shape>),
+    OptionLiteral(Option<Box<Shape>>),
+    ResultLiteral(ResultVariant, Box<Shape>),
+    Propagate(Box<Shape>),
+    MethodCall(Box<Shape>, String, Vec<Shape>),
+    Type(Type),
+}
+
+fn generate_name(rng: &mut Rng) -> String {
+    // The prefixes aren't keywords, so no suffix can turn a name into one.
+    let prefix = rng.choose(&["x", "value", "count", "_temporary", "total"]);
+    format!("{prefix}{}", rng.below(100))
+}
+
+fn generate_type_value(rng: &mut Rng, depth: usize) -> Type {
+    if depth == 0 || rng.chance(3, 4) {
+        return TYPES[rng.below(TYPES.len())].clone();
+    }
+    match rng.below(4) {
+        0 => Type::List(Box::new(generate_type_value(rng, depth - 1))),
+        1 => Type::Map(
+            Box::new(generate_type_value(rng, depth - 1)),
+            Box::new(generate_type_value(rng, depth - 1)),
+        ),
+        2 => Type::Result(
+            Box::new(generate_type_value(rng, depth - 1)),
+            Box::new(generate_type_value(rng, depth - 1)),
+        ),
+        _ => Type::Option(Box::new(generate_type_value(rng, depth - 1))),
+    }
+}
+
+fn generate_type(rng: &mut Rng) -> Shape {
+    Shape::Type(generate_type_value(rng, 2))
+}
+
+fn generate_expression(rng: &mut Rng, depth: usize) -> Shape {
+    if depth == 0 || rng.chance(1, 3) {
+        // Literals can't be negative, as there is no unary minus.
+        return Shape::Integer(rng.choose(&[0, 1, 7, 42, i128::from(u64::MAX), i128::MAX]));
+    }
+    match rng.below(10) {
+        0 => Shape::ListLiteral(
+            (0..rng.below(3))
+                .map(|_| generate_expression(rng, depth - 1))
+                .collect(),
+        ),
+        1 => Shape::MapLiteral(
+            (0..rng.below(3))
+                .map(|_| {
+                    (
+                        generate_expression(rng, depth - 1),
+                        generate_expression(rng, depth - 1),
+                    )
+                })
+                .collect(),
+        ),
+        2 => Shape::Index(
+            Box::new(generate_expression(rng, depth - 1)),
+            Box::new(generate_expression(rng, depth - 1)),
+        ),
+        3 => Shape::OptionLiteral(
+            rng.chance(1, 2)
+                .then(|| Box::new(generate_expression(rng, depth - 1))),
+        ),
+        4 => Shape::ResultLiteral(
+            rng.choose(&[ResultVariant::Ok, ResultVariant::Err]),
+            Box::new(generate_expression(rng, depth - 1)),
+        ),
+        5 => Shape::Propagate(Box::new(generate_expression(rng, depth - 1))),
+        6 => Shape::MethodCall(
+            Box::new(generate_expression(rng, depth - 1)),
+            generate_name(rng),
+            (0..rng.below(3))
+                .map(|_| generate_expression(rng, depth - 1))
+                .collect(),
+        ),
+        _ => Shape::Binary(
+            rng.choose(BINARY_OPERATORS).0,
+            Box::new(generate_expression(rng, depth - 1)),
+            Box::new(generate_expression(rng, depth - 1)),
+        ),
+    }
+}
+
+/// Whether an expression is printed starting with `[`, which at the start of a statement would index the value of
+/// the statement before it.
+fn starts_with_bracket(shape: &Shape) -> bool {
+    match shape {
+        Shape::ListLiteral(_) | Shape::MapLiteral(_) => true,
+        Shape::Index(collection, _)
+        | Shape::Propagate(collection)
+        | Shape::MethodCall(collection, _, _) => starts_with_bracket(collection),
+        // A binary operation is only parenthesised inside another, so its left operand is printed first.
+        Shape::Binary(_, left, _) => starts_with_bracket(left),
+        _ => false,
+    }
+}
+
+/// An expression to start a statement with.
+fn generate_leading_expression(rng: &mut Rng) -> Shape {
+    loop {
+        let expression = generate_expression(rng, 4);
+        if !starts_with_bracket(&expression) {
+            return expression;
+        }
+    }
+}
+
+fn generate_statement(rng: &mut Rng) -> Shape {
+    match rng.below(3) {
+        0 => Shape::Variable {
+            mutable: rng.chance(1, 2),
+            name: generate_name(rng),
+            variable_type: Box::new(generate_type(rng)),
+            value: Box::new(generate_expression(rng, 4)),
+        },
+        1 => Shape::IgnoreValue(Box::new(generate_leading_expression(rng))),
+        _ => generate_leading_expression(rng),
+    }
+}
+
+fn generate_documentation_line(rng: &mut Rng) -> String {
+    // Doc comments lose leading and trailing whitespace, so lines are words separated by single spaces.
+    let words = (0..rng.below(5))
+        .map(|_| rng.choose(&["adds", "the", "numbers", "{braces}", "`code`", "ünïcödé"]))
+        .collect::<Vec<_>>();
+    words.join(" ")
+}
+
+fn generate_function(rng: &mut Rng) -> Shape {
+    Shape::Function {
+        name: generate_name(rng),
+        documentation: (0..rng.below(3))
+            .map(|_| generate_documentation_line(rng))
+            .collect(),
+        attributes: (0..rng.below(2)).map(|_| generate_name(rng)).collect(),
+        parameters: (0..rng.below(4))
+            .map(|_| Shape::Parameter {
+                name: generate_name(rng),
+                parameter_type: Box::new(generate_type(rng)),
+            })
+            .collect(),
+        return_type: Box::new(generate_type(rng)),
+        body: Box::new(Shape::List(
+            (0..rng.below(5)).map(|_| generate_statement(rng)).collect(),
+        )),
+    }
+}
+
+fn generate_enum(rng: &mut Rng) -> Shape {
+    Shape::Enum {
+        name: generate_name(rng),
+        documentation: (0..rng.below(3))
+            .map(|_| generate_documentation_line(rng))
+            .collect(),
+        representation: rng.chance(1, 2).then(|| Box::new(generate_type(rng))),
+        variants: (0..rng.below(4))
+            .map(|_| {
+                Shape::Variant(
+                    generate_name(rng),
+                    rng.chance(1, 2)
+                        .then(|| Box::new(generate_expression(rng, 2))),
+                )
+            })
+            .collect(),
+    }
+}
+
+fn generate_program(rng: &mut Rng) -> Shape {
+    Shape::List(
+        (0..rng.below(4))
+            .map(|_| {
+                if rng.chance(1, 4) {
+                    generate_enum(rng)
+                } else {
+                    generate_function(rng)
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Builds the tree a shape describes, with empty spans.
+fn build(shape: &Shape) -> Box<dyn AstNode> {
+    let build_all = |shapes: &[Shape]| shapes.iter().map(build).collect::<Vec<_>>();
+    match shape {
+        Shape::List(items) => Box::new(build_all(items)),
+        Shape::Function {
+            name,
+            documentation,
+            attributes,
+            parameters,
+            return_type,
+            body,
+        } => Box::new(
+            FunctionDefinition::new(
+                Symbol::intern(name),
+                Span::default(),
+                build_all(parameters),
+                build(return_type),
+                build(body),
+            )
+            .with_documentation(documentation.clone())
+            .with_attributes(
+                attributes
+                    .iter()
+                    .map(|name| (Symbol::intern(name), Span::default()))
+                    .collect(),
+            ),
+        ),
+        Shape::Parameter {
+            name,
+            parameter_type,
+        } => Box::new(ParameterDeclaration::new(
+            Symbol::intern(name),
+            build(parameter_type),
+        )),
+        Shape::Enum {
+            name,
+            documentation,
+            representation,
+            variants,
+        } => Box::new(
+            EnumDefinition::new(
+                Symbol::intern(name),
+                Span::default(),
+                representation.as_deref().map(build),
+                build_all(variants),
+            )
+            .with_documentation(documentation.clone()),
+        ),
+        Shape::Variant(name, discriminant) => Box::new(EnumVariant::new(
+            Symbol::intern(name),
+            Span::default(),
+            discriminant.as_deref().map(build),
+        )),
+        Shape::Variable {
+            mutable,
+            name,
+            variable_type,
+            value,
+        } => Box::new(VariableDefinition::new(
+            *mutable,
+            Symbol::intern(name),
+            Span::default(),
+            Span::default(),
+            build(variable_type),
+            build(value),
+        )),
+        Shape::IgnoreValue(value) => Box::new(IgnoreValue::new(Span::default(), build(value))),
+        Shape::Integer(value) => Box::new(*value),
+        Shape::Binary(operator, left, right) => Box::new(BinaryOperation::new(
+            *operator,
+            Span::default(),
+            build(left),
+            build(right),
+        )),
+        Shape::ListLiteral(elements) => {
+            Box::new(ListLiteral::new(Span::default(), build_all(elements)))
+        }
+        Shape::MapLiteral(entries) => Box::new(MapLiteral::new(
+            Span::default(),
+            entries.iter().map(|(key, _)| build(key)).collect(),
+            entries.iter().map(|(_, value)| build(value)).collect(),
+        )),
+        Shape::Index(collection, index) => {
+            Box::new(Index::new(Span::default(), build(collection), build(index)))
+        }
+        Shape::OptionLiteral(value) => Box::new(OptionLiteral::new(
+            Span::default(),
+            value.as_deref().map(build),
+        )),
+        Shape::ResultLiteral(variant, value) => {
+            Box::new(ResultLiteral::new(Span::default(), *variant, build(value)))
+        }
+        Shape::Propagate(value) => Box::new(Propagate::new(Span::default(), build(value))),
+        Shape::MethodCall(receiver, name, arguments) => Box::new(MethodCall::new(
+            Span::default(),
+            build(receiver),
+            Symbol::intern(name),
+            build_all(arguments),
+        )),
+        Shape::Type(type_value) => Box::new(type_value.clone()),
+    }
+}
+
+/// Recovers the shape of a parsed tree.
+#[derive(Default)]
+struct ShapeExtractor {
+    shape: Option<Shape>,
+}
+
+impl ShapeExtractor {
+    fn extract(node: &dyn AstNode) -> Shape {
+        let mut extractor = Self::default();
+        node.apply(&mut extractor);
+        extractor
+            .shape
+            .expect("The parser only produces nodes which the generator can")
+    }
+
+    fn extract_all(nodes: &[Box<dyn AstNode>]) -> Vec<Shape> {
+        nodes
+            .iter()
+            .map(|node| Self::extract(node.as_ref()))
+            .collect()
+    }
+}
+
+impl AstVisitor for ShapeExtractor {
+    fn visit_list(&mut self, list: &[Box<dyn AstNode>]) {
+        self.shape = Some(Shape::List(Self::extract_all(list)));
+    }
+    fn visit_variable_definition(&mut self, variable: &VariableDefinition) {
+        self.shape = Some(Shape::Variable {
+            mutable: variable.mutable(),
+            name: variable.name().to_string(),
+            variable_type: Box::new(Self::extract(variable.variable_type())),
+            value: Box::new(Self::extract(variable.value())),
+        });
+    }
+    fn visit_type(&mut self, type_value: &Type) {
+        self.shape = Some(Shape::Type(type_value.clone()));
+    }
+    fn visit_parameter_declaration(&mut self, parameter: &ParameterDeclaration) {
+        self.shape = Some(Shape::Parameter {
+            name: parameter.name().to_string(),
+            parameter_type: Box::new(Self::extract(parameter.parameter_type())),
+        });
+    }
+    fn visit_function_definition(&mut self, function: &FunctionDefinition) {
+        self.shape = Some(Shape::Function {
+            name: function.name().to_string(),
+            documentation: function.documentation().to_vec(),
+            attributes: function
+                .attributes()
+                .iter()
+                .map(|(attribute, _)| attribute.to_string())
+                .collect(),
+            parameters: Self::extract_all(function.parameters()),
+            return_type: Box::new(Self::extract(function.return_type())),
+            body: Box::new(Self::extract(function.body())),
+        });
+    }
+    fn visit_ignore_value(&mut self, ignore_value: &IgnoreValue) {
+        self.shape = Some(Shape::IgnoreValue(Box::new(Self::extract(
+            ignore_value.value(),
+        ))));
+    }
+    fn visit_integer_literal(&mut self, integer_literal: &i128) {
+        self.shape = Some(Shape::Integer(*integer_literal));
+    }
+    fn visit_binary_operation(&mut self, operation: &BinaryOperation) {
+        self.shape = Some(Shape::Binary(
+            operation.operator(),
+            Box::new(Self::extract(operation.left())),
+            Box::new(Self::extract(operation.right())),
+        ));
+    }
+    fn visit_list_literal(&mut self, literal: &ListLiteral) {
+        self.shape = Some(Shape::ListLiteral(Self::extract_all(literal.elements())));
+    }
+    fn visit_map_literal(&mut self, literal: &MapLiteral) {
+        self.shape = Some(Shape::MapLiteral(
+            Self::extract_all(literal.keys())
+                .into_iter()
+                .zip(Self::extract_all(literal.values()))
+                .collect(),
+        ));
+    }
+    fn visit_index(&mut self, index: &Index) {
+        self.shape = Some(Shape::Index(
+            Box::new(Self::extract(index.collection())),
+            Box::new(Self::extract(index.index())),
+        ));
+    }
+    fn visit_option_literal(&mut self, literal: &OptionLiteral) {
+        self.shape = Some(Shape::OptionLiteral(
+            literal.value().map(|value| Box::new(Self::extract(value))),
+        ));
+    }
+    fn visit_result_literal(&mut self, literal: &ResultLiteral) {
+        self.shape = Some(Shape::ResultLiteral(
+            literal.variant(),
+            Box::new(Self::extract(literal.value())),
+        ));
+    }
+    fn visit_propagate(&mut self, propagate: &Propagate) {
+        self.shape = Some(Shape::Propagate(Box::new(Self::extract(propagate.value()))));
+    }
+    fn visit_enum_definition(&mut self, definition: &EnumDefinition) {
+        self.shape = Some(Shape::Enum {
+            name: definition.name().to_string(),
+            documentation: definition.documentation().to_vec(),
+            representation: definition
+                .representation()
+                .map(|representation| Box::new(Self::extract(representation))),
+            variants: Self::extract_all(definition.variants()),
+        });
+    }
+    fn visit_enum_variant(&mut self, variant: &EnumVariant) {
+        self.shape = Some(Shape::Variant(
+            variant.name().to_string(),
+            variant
+                .discriminant()
+                .map(|discriminant| Box::new(Self::extract(discriminant))),
+        ));
+    }
+    fn visit_method_call(&mut self, call: &MethodCall) {
+        self.shape = Some(Shape::MethodCall(
+            Box::new(Self::extract(call.receiver())),
+            call.name().to_string(),
+            Self::extract_all(call.arguments()),
+        ));
+    }
+}
+
+/// How many programs are generated; each is checked with both the default style and a narrow one.
+const CASES: u64 = 512;
+
+#[test]
+fn printed_programs_parse_to_the_same_tree() {
+    let styles = [Style::default(), Style::new(2, 20)];
+    for seed in 0..CASES {
+        let shape = generate_program(&mut Rng::new(seed));
+        let program = build(&shape);
+        for style in &styles {
+            let source = formatter::format(program.as_ref(), style);
+            let parsed = hematite_lang::parse_str(&source).unwrap_or_else(|diagnostics| {
+                panic!("Seed {seed} printed code which doesn't parse: {diagnostics:?}\n{source}")
+            });
+            assert_eq!(
+                ShapeExtractor::extract(parsed.as_ref()),
+                shape,
+                "Seed {seed} changed when printed as:\n{source}"
+            );
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Checks that trees survive being written and read back with serde.
+
+use hematite_lang::{ast::AstNode, Span, Token};
+
+const SOURCE: &str = "/// Adds things.
+@test
+function add(x: i32, y: i64) -> i32 {
+    let mut total: i32 = (1 + 2) * 3;
+    4 % 5;
+    10 - 4
+}
+";
+
+#[test]
+fn trees_survive_a_round_trip() {
+    let program = hematite_lang::parse_str(SOURCE).unwrap();
+    let json = serde_json::to_string(&program).unwrap();
+    let read: Box<dyn AstNode> = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{read:?}"), format!("{program:?}"));
+}
+
+#[test]
+fn tokens_survive_a_round_trip() {
+    let tokens = hematite_lang::tokenize_str(SOURCE);
+    let json = serde_json::to_string(&tokens).unwrap();
+    let read: Vec<(Token, Span)> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, tokens);
+}
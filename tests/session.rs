@@ -0,0 +1,66 @@
+//! Checks how the session filters the diagnostics passes emit before they reach the emitter.
+
+use std::{cell::RefCell, rc::Rc};
+
+use hematite_lang::{
+    diagnostics::{DiagnosticCounts, DiagnosticEmitter},
+    Diagnostic, Features, Location, Session, SourceMap, Span, Target,
+};
+
+/// Keeps the messages it is sent and the counts it is finished with, shared with the test.
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<(Vec<String>, Option<DiagnosticCounts>)>>);
+
+impl DiagnosticEmitter for Recorder {
+    fn emit(&mut self, diagnostic: Diagnostic, _source_map: &SourceMap) {
+        self.0.borrow_mut().0.push(diagnostic.message().to_string());
+    }
+
+    fn finish(&mut self, counts: &DiagnosticCounts) {
+        self.0.borrow_mut().1 = Some(*counts);
+    }
+}
+
+fn session(recorder: &Recorder) -> Session {
+    let target = Target::from_triple("x86_64-unknown-linux-gnu").unwrap();
+    Session::new(target, Features::default(), Box::new(recorder.clone()))
+}
+
+fn error_at(message: &str, start: usize) -> Diagnostic {
+    Diagnostic::error(message).with_location(Location::new(0, Span::new(start, start + 1)))
+}
+
+#[test]
+fn identical_diagnostics_are_emitted_once() {
+    let recorder = Recorder::default();
+    let mut session = session(&recorder);
+    session.emit(error_at("first", 0));
+    session.emit(error_at("first", 0));
+    session.emit(error_at("first", 4));
+    session.emit(Diagnostic::warning("unused"));
+    session.emit(Diagnostic::warning("unused"));
+    session.finish();
+    let (messages, counts) = recorder.0.take();
+    assert_eq!(messages, ["first", "first", "unused"]);
+    let counts = counts.unwrap();
+    assert_eq!(counts.errors(), 2);
+    assert_eq!(counts.warnings(), 1);
+}
+
+#[test]
+fn errors_past_the_limit_are_only_counted() {
+    let recorder = Recorder::default();
+    let mut session = session(&recorder).with_error_limit(2);
+    for start in 0..5 {
+        session.emit(error_at("error", start));
+    }
+    session.emit(Diagnostic::warning("still shown"));
+    session.finish();
+    assert_eq!(session.error_count(), 5);
+    let (messages, counts) = recorder.0.take();
+    assert_eq!(messages, ["error", "error", "still shown"]);
+    let counts = counts.unwrap();
+    assert_eq!(counts.errors(), 2);
+    assert_eq!(counts.suppressed_errors(), 3);
+    assert_eq!(counts.warnings(), 1);
+}
@@ -0,0 +1,182 @@
+//! Runs the front-end over every `.hmt` file in `tests/snapshots`, comparing what it produces with checked-in files.
+//!
+//! Each file says what to compare with `// EXPECT:` lines listing any of `tokens`, `ast` and `diagnostics`. The
+//! snapshot for `name.hmt` is kept in `name.tokens`, `name.ast` or `name.diagnostics` alongside it. The language has
+//! no plain comments, so `// EXPECT:` lines are blanked out before the file is compiled, keeping line numbers intact.
+//!
+//! Run with `UPDATE_SNAPSHOTS=1` to write the current output as the new snapshots.
+
+use std::{
+    cell::RefCell,
+    env, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use hematite_lang::{
+    ast::{AstNode, Module},
+    diagnostics::{DiagnosticEmitter, Renderer},
+    intrinsics, macros, methods, parser, semantic, Diagnostic, Features, Session, SourceFile,
+    SourceMap, Span, Target, Token, TokenStream,
+};
+
+const EXPECT_PREFIX: &str = "// EXPECT:";
+
+/// The target snapshots are compiled for, so that they don't depend on the machine running the tests.
+const TARGET: &str = "x86_64-unknown-linux-gnu";
+
+/// Renders diagnostics as the terminal would, without colour, into a buffer shared with the test.
+#[derive(Clone, Default)]
+struct Recorder(Rc<RefCell<String>>);
+
+impl DiagnosticEmitter for Recorder {
+    fn emit(&mut self, diagnostic: Diagnostic, source_map: &SourceMap) {
+        let mut output = self.0.borrow_mut();
+        if !output.is_empty() {
+            output.push('\n');
+        }
+        *output += &Renderer::new(source_map, false).render(&diagnostic);
+    }
+}
+
+/// What the front-end produced for one file.
+struct Output {
+    tokens: String,
+    ast: String,
+    diagnostics: String,
+}
+
+/// Runs each pass in turn, as `check` does, stopping at the first which reports an error.
+fn compile(name: &str, source: &str) -> Output {
+    let recorder = Recorder::default();
+    let target = Target::from_triple(TARGET).unwrap();
+    let mut session = Session::new(target, Features::default(), Box::new(recorder.clone()));
+    let file = session
+        .source_map_mut()
+        .add(SourceFile::new(name.to_string(), source.to_string()));
+    let tokens = hematite_lang::tokenize_str(source);
+    let token_dump = tokens
+        .iter()
+        .map(|(token, span)| format!("{span} {token:?}\n"))
+        .collect();
+    let ast = analyse(name, file, tokens, &mut session);
+    Output {
+        tokens: token_dump,
+        ast: ast.map_or_else(String::new, |ast| format!("{ast:#?}\n")),
+        diagnostics: recorder.0.take(),
+    }
+}
+
+fn analyse(
+    name: &str,
+    file: usize,
+    tokens: Vec<(Token, Span)>,
+    session: &mut Session,
+) -> Option<Box<dyn AstNode>> {
+    if session.report_invalid_tokens(file, &tokens) {
+        return None;
+    }
+    let mut tokens = tokens.into_iter();
+    let mut token_stream = TokenStream::new(&mut tokens).with_features(session.features().clone());
+    let items = parser::parse(&mut token_stream)
+        .map_err(|error| session.emit(error.to_diagnostic(file)))
+        .ok()?;
+    let module_name = name.trim_end_matches(".hmt").to_string();
+    let program: Box<dyn AstNode> = Box::new(vec![
+        Box::new(Module::new(module_name, file, items)) as Box<dyn AstNode>
+    ]);
+    let program = macros::expand_macros(program.as_ref(), session)
+        .map_err(|diagnostic| session.emit(diagnostic))
+        .ok()?;
+    // Snapshots needn't be whole programs, so they aren't required to have a `main`.
+    semantic::resolve_names(program.as_ref())
+        .and_then(|()| semantic::validate_attributes(program.as_ref()))
+        .map_err(|error| session.emit(error.to_diagnostic()))
+        .ok()?;
+    let program = intrinsics::resolve_intrinsics(program.as_ref(), session);
+    let program = methods::resolve_methods(program.as_ref())
+        .map_err(|error| session.emit(error.to_diagnostic()))
+        .ok()?;
+    semantic::check_types(program.as_ref())
+        .map_err(|errors| {
+            for error in errors {
+                session.emit(error.to_diagnostic());
+            }
+        })
+        .ok()?;
+    semantic::check_constants(program.as_ref())
+        .map_err(|error| session.emit(error.to_diagnostic()))
+        .ok()?;
+    for warning in semantic::find_unused_values(program.as_ref()) {
+        session.emit(warning);
+    }
+    Some(program)
+}
+
+/// Which snapshots a file asks for, and its source with the `// EXPECT:` lines blanked out.
+fn read_expectations(path: &Path) -> (Vec<String>, String) {
+    let text = fs::read_to_string(path).unwrap();
+    let mut kinds = Vec::new();
+    let mut source = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        match line.trim().strip_prefix(EXPECT_PREFIX) {
+            Some(list) => {
+                kinds.extend(list.split(',').map(|kind| kind.trim().to_string()));
+                if line.ends_with('\n') {
+                    source.push('\n');
+                }
+            }
+            None => source += line,
+        }
+    }
+    (kinds, source)
+}
+
+fn snapshot_files() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots");
+    let mut files = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "hmt"))
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn snapshots_match() {
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some();
+    let mut failures = Vec::new();
+    for path in snapshot_files() {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let (kinds, source) = read_expectations(&path);
+        assert!(!kinds.is_empty(), "{name} has no `{EXPECT_PREFIX}` line");
+        let output = compile(&name, &source);
+        for kind in kinds {
+            let actual = match kind.as_str() {
+                "tokens" => &output.tokens,
+                "ast" => &output.ast,
+                "diagnostics" => &output.diagnostics,
+                _ => panic!("{name} expects an unknown kind of output: '{kind}'"),
+            };
+            let snapshot_path = path.with_extension(&kind);
+            if update {
+                fs::write(&snapshot_path, actual).unwrap();
+                continue;
+            }
+            match fs::read_to_string(&snapshot_path) {
+                Ok(expected) if expected == *actual => {}
+                Ok(expected) => failures.push(format!(
+                    "{} doesn't match.\n--- expected\n{expected}--- actual\n{actual}",
+                    snapshot_path.display()
+                )),
+                Err(_) => failures.push(format!("{} is missing", snapshot_path.display())),
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "{}\nRun with UPDATE_SNAPSHOTS=1 to accept the new output.",
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,21 @@
+//! Checks how source files are decoded and where their offsets fall.
+
+use hematite_lang::{error_codes, SourceFile, SourceMap, Span};
+
+#[test]
+fn invalid_utf8_is_replaced_and_warned_about() {
+    let bytes = b"/// caf\xe9\nfunction main() -> i32 { 0 }\n".to_vec();
+    let mut source_map = SourceMap::new();
+    source_map.add(SourceFile::new("first".to_string(), String::new()));
+    let file = source_map.add(SourceFile::from_bytes("latin1".to_string(), bytes));
+    assert_eq!(
+        source_map[file].text(),
+        "/// caf\u{fffd}\nfunction main() -> i32 { 0 }\n"
+    );
+    let warning = source_map.invalid_utf8_warning(file).unwrap();
+    assert_eq!(warning.code(), Some(error_codes::INVALID_UTF8));
+    let location = warning.location().unwrap();
+    assert_eq!((location.file(), location.span()), (file, Span::new(7, 10)));
+    assert!(hematite_lang::parse_str(source_map[file].text()).is_ok());
+    assert!(source_map.invalid_utf8_warning(0).is_none());
+}
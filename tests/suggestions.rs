@@ -0,0 +1,75 @@
+//! Checks that suggested fixes are found for common mistakes and that applying them gives working code.
+
+use hematite_lang::{
+    diagnostics::{self, Applicability, Suggestion},
+    semantic, suggestions, Span,
+};
+
+#[test]
+fn swapped_characters_are_one_edit() {
+    assert_eq!(suggestions::edit_distance("fucntion", "function"), 1);
+    assert_eq!(suggestions::edit_distance("lenght", "length"), 1);
+    assert_eq!(suggestions::edit_distance("", "let"), 3);
+    assert_eq!(suggestions::edit_distance("size", "slice"), 2);
+}
+
+#[test]
+fn only_close_names_are_suggested() {
+    let candidates = ["length", "slice", "chars"];
+    assert_eq!(suggestions::closest("lenght", candidates), Some("length"));
+    assert_eq!(suggestions::closest("size", candidates), None);
+    assert_eq!(suggestions::closest("x", ["if"]), None);
+}
+
+#[test]
+fn a_missing_semicolon_is_fixed_automatically() {
+    let source = "function main() -> i32 {\n    let x: i32 = 1\n    0\n}\n";
+    let diagnostics = hematite_lang::parse_str(source).unwrap_err();
+    let suggestions = diagnostics[0].suggestions();
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(
+        suggestions[0].applicability(),
+        Applicability::MachineApplicable
+    );
+    let fixed = diagnostics::apply_suggestions(source, suggestions);
+    assert_eq!(
+        fixed,
+        "function main() -> i32 {\n    let x: i32 = 1;\n    0\n}\n"
+    );
+    assert!(hematite_lang::parse_str(&fixed).is_ok());
+}
+
+#[test]
+fn a_misspelled_keyword_may_be_corrected() {
+    let source = "fucntion main() -> i32 { 0 }";
+    let diagnostics = hematite_lang::parse_str(source).unwrap_err();
+    let suggestion = &diagnostics[0].suggestions()[0];
+    assert_eq!(suggestion.applicability(), Applicability::MaybeIncorrect);
+    assert_eq!(suggestion.replacement(), "function");
+    let fixed = diagnostics::apply_suggestions(source, [suggestion]);
+    assert_eq!(fixed, "function main() -> i32 { 0 }");
+}
+
+#[test]
+fn overlapping_suggestions_are_applied_once() {
+    let first = Suggestion::new("", Span::new(0, 3), "abc", Applicability::MaybeIncorrect);
+    let second = Suggestion::new("", Span::new(2, 5), "xyz", Applicability::MaybeIncorrect);
+    let insertion = Suggestion::new("", Span::new(5, 5), ";", Applicability::MachineApplicable);
+    let fixed = diagnostics::apply_suggestions("01234", [&first, &second, &insertion, &insertion]);
+    assert_eq!(fixed, "abc34;");
+}
+
+#[test]
+fn an_unused_value_is_assigned_to_underscore() {
+    let source = "function main() -> i32 {\n    1 + 2;\n    0\n}\n";
+    let program = hematite_lang::parse_str(source).unwrap();
+    let warnings = semantic::find_unused_values(program.as_ref());
+    assert_eq!(warnings.len(), 1);
+    let fixed = diagnostics::apply_suggestions(source, warnings[0].suggestions());
+    assert_eq!(
+        fixed,
+        "function main() -> i32 {\n    let _: i32 = 1 + 2;\n    0\n}\n"
+    );
+    let program = hematite_lang::parse_str(&fixed).unwrap();
+    assert!(semantic::find_unused_values(program.as_ref()).is_empty());
+}
@@ -0,0 +1,118 @@
+//! Checks what target triples describe, and which aren't understood.
+
+use hematite_lang::{
+    target::{CallingConvention, Endianness},
+    Target,
+};
+
+/// The pointer width, endianness and calling convention `triple` describes.
+fn describe(triple: &str) -> (u32, Endianness, CallingConvention) {
+    let target = Target::from_triple(triple).unwrap();
+    assert_eq!(target.triple(), triple);
+    (
+        target.pointer_width(),
+        target.endianness(),
+        target.calling_convention(),
+    )
+}
+
+#[test]
+fn x86_64_uses_the_windows_convention_only_on_windows() {
+    assert_eq!(
+        describe("x86_64-unknown-linux-gnu"),
+        (64, Endianness::Little, CallingConvention::SysV64)
+    );
+    assert_eq!(
+        describe("x86_64-pc-windows-msvc"),
+        (64, Endianness::Little, CallingConvention::Win64)
+    );
+    assert_eq!(
+        describe("amd64-unknown-freebsd"),
+        (64, Endianness::Little, CallingConvention::SysV64)
+    );
+}
+
+#[test]
+fn pointer_width_follows_the_architecture() {
+    assert_eq!(describe("i686-unknown-linux-gnu").0, 32);
+    assert_eq!(describe("aarch64-apple-darwin").0, 64);
+    assert_eq!(describe("riscv32imac-unknown-none-elf").0, 32);
+    assert_eq!(describe("riscv64gc-unknown-linux-gnu").0, 64);
+    assert_eq!(describe("wasm32-unknown-unknown").0, 32);
+    assert_eq!(describe("wasm64-unknown-unknown").0, 64);
+    assert_eq!(describe("thumbv7em-none-eabihf").0, 32);
+}
+
+#[test]
+fn endianness_follows_the_architecture() {
+    assert_eq!(describe("powerpc64-unknown-linux-gnu").1, Endianness::Big);
+    assert_eq!(
+        describe("powerpc64le-unknown-linux-gnu").1,
+        Endianness::Little
+    );
+    assert_eq!(describe("mips-unknown-linux-gnu").1, Endianness::Big);
+    assert_eq!(describe("mipsel-unknown-linux-gnu").1, Endianness::Little);
+    assert_eq!(describe("armebv7r-none-eabi").1, Endianness::Big);
+    assert_eq!(
+        describe("armv7-unknown-linux-gnueabihf").1,
+        Endianness::Little
+    );
+    assert_eq!(describe("aarch64_be-unknown-linux-gnu").1, Endianness::Big);
+}
+
+#[test]
+fn calling_convention_follows_the_architecture() {
+    assert_eq!(
+        describe("i386-unknown-linux-gnu").2,
+        CallingConvention::Cdecl
+    );
+    assert_eq!(
+        describe("arm-unknown-linux-gnueabi").2,
+        CallingConvention::Aapcs
+    );
+    assert_eq!(
+        describe("aarch64-unknown-linux-gnu").2,
+        CallingConvention::Aapcs64
+    );
+    assert_eq!(
+        describe("riscv64gc-unknown-linux-gnu").2,
+        CallingConvention::RiscV
+    );
+    assert_eq!(
+        describe("powerpc-unknown-linux-gnu").2,
+        CallingConvention::PowerPc
+    );
+    assert_eq!(
+        describe("mips-unknown-linux-gnu").2,
+        CallingConvention::Mips
+    );
+    assert_eq!(
+        describe("wasm32-unknown-unknown").2,
+        CallingConvention::Wasm
+    );
+}
+
+#[test]
+fn unknown_architectures_and_incomplete_triples_are_rejected() {
+    for triple in [
+        "",
+        "x86_64",
+        "s390x-unknown-linux-gnu",
+        "sparc64-sun-solaris",
+    ] {
+        let error = Target::from_triple(triple).unwrap_err();
+        assert_eq!(error.to_string(), format!("Unknown target: {triple}"));
+    }
+}
+
+#[test]
+fn the_host_is_always_described() {
+    let host = Target::host();
+    assert_eq!(host.pointer_width(), usize::BITS);
+    let endianness = if cfg!(target_endian = "big") {
+        Endianness::Big
+    } else {
+        Endianness::Little
+    };
+    assert_eq!(host.endianness(), endianness);
+}